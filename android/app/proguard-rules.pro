@@ -1,4 +1,6 @@
 -keepnames class dev.janm.pinger.** { *; }
 -keep public class dev.janm.pinger.PingInfo
 -keep public class dev.janm.pinger.KeyExchange
+-keep public class dev.janm.pinger.PingerException { *; }
+-keep public class dev.janm.pinger.PingerException$* { *; }
 -printmapping classmapping.txt
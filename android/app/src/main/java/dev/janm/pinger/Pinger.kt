@@ -118,9 +118,11 @@ public class Pinger private constructor(
 		connection.cancel()
 		state = State.Connecting
 		sendingTo = null
+		keyExchange?.close()
 		keyExchange = null
 		infoToSend = null
 		timeoutTimer = Timer("PingerTimeouts", true)
+		exchanges.values.forEach { it.keyExchange?.close() }
 		exchanges.clear()
 
 		connection = connect()
@@ -144,12 +146,14 @@ public class Pinger private constructor(
 				state = State.Ready
 				sendingTo = null
 				infoToSend = null
+				keyExchange?.close()
 				keyExchange = null
 				onResponseTimeout.forEach { it.accept(id) }
 			}
 		}
 
 		logger.info("sending ping request to $to")
+		keyExchange?.close()
 		keyExchange = KeyExchange()
 		state = State.AwaitingAccept
 		infoToSend = info
@@ -219,13 +223,14 @@ public class Pinger private constructor(
 				is MessageFromServer.NoSuchId -> if (message.id == sendingTo) {
 					state.assertIs(State.AwaitingAccept, State.AwaitingAck)
 					state = State.Ready
+					keyExchange?.close()
 					keyExchange = null
 					infoToSend = null
 					logger.warning("id not found: ${message.id}")
 					onIdNotFound.forEach { it.accept(message.id) }
 				} else {
 					logger.warning("incoming ping from ${message.id} can't be replied to")
-					exchanges.remove(message.id)
+					exchanges.remove(message.id)?.keyExchange?.close()
 					onIdNotFound.forEach { it.accept(message.id) }
 				}
 				is MessageFromServer.Ping -> if (exchanges.containsKey(message.from)) {
@@ -235,7 +240,7 @@ public class Pinger private constructor(
 					exchange.pingTimeout.cancel()
 
 					try {
-						val pingInfo = message.getInfo(exchange.keyExchange!!.diffieHellman(exchange.theirPublicKey))
+						val pingInfo = exchange.keyExchange!!.diffieHellman(exchange.theirPublicKey).use { message.getInfo(it) }
 						onPing.forEach { it.accept(message.from to pingInfo) }
 						exchanges.remove(message.from)
 						connection.send(MessageToServer.PingAck(message.from).toString())
@@ -280,7 +285,9 @@ public class Pinger private constructor(
 
 					logger.info("ping accepted by ${message.from}")
 					onAccepted.forEach { it.accept(message.from) }
-					connection.send(MessageToServer.Ping(message.from, infoToSend!!, keyExchange!!.diffieHellman(message.key)).toString())
+					val ping = keyExchange!!.diffieHellman(message.key).use { MessageToServer.Ping(message.from, infoToSend!!, it) }
+					connection.send(ping.toString())
+					keyExchange = null
 					infoToSend = null
 					timeoutTimer.schedule(ackTimeout, BASE_TIMEOUT)
 				} else {
@@ -291,6 +298,8 @@ public class Pinger private constructor(
 					state = State.Ready
 					sendingTo = null
 					infoToSend = null
+					keyExchange?.close()
+					keyExchange = null
 					responseTimeout?.cancel()
 					responseTimeout = null
 					logger.info("ping rejected by ${message.from}")
@@ -320,13 +329,13 @@ public class Pinger private constructor(
 								logger.warning("ping timeout triggered while not awaiting ping or without exchange (awaiting ping from ${message.from})")
 							}
 
-							exchanges.remove(message.from)
+							exchanges.remove(message.from)?.keyExchange?.close()
 							onPingTimeout.forEach { it.accept(message.from) }
 						}
 					}
 
 					timeoutTimer.schedule(decisionTimeout, DECISION_TIMEOUT)
-					exchanges[message.from] = Exchange(message.key, decisionTimeout, pingTimeout)
+					exchanges.put(message.from, Exchange(message.key, decisionTimeout, pingTimeout))?.keyExchange?.close()
 					logger.info("new ping request from ${message.from}")
 					onRequest.forEach { it.accept(message.from) }
 				}
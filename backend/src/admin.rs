@@ -0,0 +1,166 @@
+//! The admin API, for operating the server while it's running
+//!
+//! The API is served on the `admin.bind` addresses if there are any (which
+//! shouldn't be publicly reachable), or on the main addresses otherwise.
+//! If `admin.token` is set, requests need an `Authorization: Bearer <token>`
+//! header, which is required unless the API is only served on loopback
+//! addresses.
+//!
+//! - `GET /admin/connections` gets the number of connections, their IDs and
+//!   ages, and whether new connections are accepted
+//! - `DELETE /admin/connections/{id}` disconnects the client with that ID
+//! - `POST /admin/notice` with `{ "details": "..." }` sends a `notice` message
+//!   to all clients
+//! - `PUT /admin/accepting` with `{ "accepting": false }` stops accepting new
+//!   connections, and `true` starts accepting them again
+//!
+//! Nothing about the clients themselves or their messages is exposed.
+
+use std::sync::{Arc, atomic::Ordering};
+
+use axum::{
+	Json, Router,
+	extract::{Path, Request, State},
+	http::{
+		StatusCode,
+		header::{AUTHORIZATION, WWW_AUTHENTICATE},
+	},
+	middleware::{Next, from_fn_with_state},
+	response::{IntoResponse, Response},
+	routing::{delete, get, post, put},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{Ctx, Id};
+
+/// The connections to the server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConnectionList {
+	/// The number of connections
+	pub count: usize,
+	/// Whether new connections are accepted
+	pub accepting: bool,
+	/// The connections, oldest first
+	pub connections: Vec<ConnectionInfo>,
+}
+
+/// A connection to the server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+	/// The client's ID
+	pub id: Id,
+	/// The number of seconds since the client connected
+	pub age: u64,
+}
+
+/// A notice to send to all clients
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Notice {
+	/// The text of the notice
+	pub details: String,
+}
+
+/// The result of sending a notice
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Delivered {
+	/// The number of clients which received the notice
+	pub delivered: usize,
+}
+
+/// Whether new connections are accepted
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Accepting {
+	/// Whether new connections are accepted
+	pub accepting: bool,
+}
+
+/// Get the router for the admin API
+pub fn router(ctx: Arc<Ctx>) -> Router {
+	Router::new()
+		.route("/admin/connections", get(connections))
+		.route("/admin/connections/{id}", delete(disconnect))
+		.route("/admin/notice", post(notice))
+		.route("/admin/accepting", put(accepting))
+		.layer(from_fn_with_state(Arc::clone(&ctx), authorize))
+		.with_state(ctx)
+}
+
+/// Reject admin requests without the right bearer token, if one is required
+async fn authorize(State(ctx): State<Arc<Ctx>>, req: Request, next: Next) -> Response {
+	if let Some(token) = &ctx.admin_token {
+		let given = req
+			.headers()
+			.get(AUTHORIZATION)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.strip_prefix("Bearer "));
+
+		if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes())) {
+			warn!(
+				"Rejecting unauthorized admin request to {}",
+				req.uri().path()
+			);
+			return (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response();
+		}
+	}
+
+	next.run(req).await
+}
+
+/// Check if `a` and `b` are equal, taking the same time for all inputs of the
+/// same length
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// List the connections
+async fn connections(State(ctx): State<Arc<Ctx>>) -> Json<ConnectionList> {
+	let connections = ctx
+		.connection_ages()
+		.into_iter()
+		.map(|(id, age)| ConnectionInfo {
+			id,
+			age: age.as_secs(),
+		})
+		.collect::<Vec<_>>();
+
+	Json(ConnectionList {
+		count: connections.len(),
+		accepting: ctx.accepting.load(Ordering::Relaxed),
+		connections,
+	})
+}
+
+/// Disconnect a client
+async fn disconnect(State(ctx): State<Arc<Ctx>>, Path(id): Path<Id>) -> StatusCode {
+	if ctx.disconnect(id) {
+		info!("Disconnecting {id} on behalf of an admin");
+		StatusCode::NO_CONTENT
+	} else {
+		StatusCode::NOT_FOUND
+	}
+}
+
+/// Send a notice to all clients
+async fn notice(State(ctx): State<Arc<Ctx>>, Json(notice): Json<Notice>) -> Json<Delivered> {
+	let delivered = ctx.send_notice(&notice.details).await;
+	info!("Sent a notice to {delivered} clients: {}", notice.details);
+
+	Json(Delivered { delivered })
+}
+
+/// Start or stop accepting new connections
+async fn accepting(
+	State(ctx): State<Arc<Ctx>>,
+	Json(Accepting { accepting }): Json<Accepting>,
+) -> Json<Accepting> {
+	ctx.set_accepting(accepting);
+
+	if accepting {
+		info!("Accepting new connections");
+	} else {
+		info!("No longer accepting new connections");
+	}
+
+	Json(Accepting { accepting })
+}
@@ -0,0 +1,669 @@
+//! The server configuration, from command-line flags, environment variables,
+//! and an optional configuration file
+//!
+//! Every setting can be given as a command-line flag, an environment variable
+//! (see `--help`), or in a TOML configuration file (given with `--config` or
+//! `PINGER_CONFIG`), in that order of precedence, e.g.
+//!
+//! ```toml
+//! bind = ["0.0.0.0:443", "[::]:443"]
+//! id-range = "100-999"
+//! log = "pinger_backend=info"
+//! log-format = "compact"
+//! allowed-origins = ["https://pinger.example.com"]
+//!
+//! [tls]
+//! cert = "/etc/pinger/cert.pem"
+//! key = "/etc/pinger/key.pem"
+//! redirect-port = 80
+//!
+//! [admin]
+//! bind = ["127.0.0.1:8001"]
+//! ```
+//!
+//! The effective settings can be printed in the same format with
+//! `--print-config`, except for the admin token.
+
+use std::{
+	fmt::{Display, Formatter, Result as FmtResult},
+	fs, io,
+	net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+	ops::RangeInclusive,
+	path::PathBuf,
+	str::FromStr,
+	time::Duration,
+};
+
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+use thiserror::Error;
+use tracing_subscriber::EnvFilter;
+
+use crate::security::{OriginPolicy, normalize_origin};
+
+/// The port to listen on if no addresses are given
+pub const DEFAULT_PORT: u16 = 8000;
+
+/// All IDs which can be given out, 2- or 3-digit numbers
+const ID_LIMITS: RangeInclusive<u16> = 10..=999;
+
+/// The smallest allowed maximum message size, which fits all valid messages
+const MIN_MESSAGE_SIZE: usize = 256;
+
+/// The shortest allowed admin token
+const MIN_TOKEN_LENGTH: usize = 16;
+
+/// The longest allowed interval or timeout in seconds (one day), so that
+/// deadlines calculated from them can't overflow
+const MAX_TIMEOUT: u64 = 24 * 60 * 60;
+
+/// The Pinger backend server
+#[derive(Debug, Default, Parser)]
+#[command(version, about)]
+pub struct Args {
+	/// The TOML configuration file to use
+	#[arg(short, long, value_name = "FILE", env = "PINGER_CONFIG")]
+	config: Option<PathBuf>,
+	/// The comma-separated addresses to listen on [default: `[::]:PORT` and
+	/// `0.0.0.0:PORT`]
+	#[arg(
+		short,
+		long,
+		value_name = "ADDR",
+		value_delimiter = ',',
+		env = "PINGER_BIND"
+	)]
+	bind: Option<Vec<SocketAddr>>,
+	/// The port to listen on if no addresses are given [default: 8000]
+	#[arg(short, long, env = "PORT")]
+	port: Option<u16>,
+	/// The range of IDs given out to clients [default: 10-999]
+	#[arg(long, value_name = "MIN-MAX", env = "PINGER_ID_RANGE")]
+	id_range: Option<IdRange>,
+	/// The number of messages buffered for each client [default: 2]
+	#[arg(long, value_name = "MESSAGES", env = "PINGER_BUFFER_SIZE")]
+	buffer_size: Option<usize>,
+	/// The number of seconds to wait for a client to take a relayed message
+	/// before giving up [default: 10]
+	#[arg(long, value_name = "SECS", env = "PINGER_RELAY_TIMEOUT")]
+	relay_timeout: Option<u64>,
+	/// The number of seconds between websocket pings to each client
+	/// [default: 30]
+	#[arg(long, value_name = "SECS", env = "PINGER_PING_INTERVAL")]
+	ping_interval: Option<u64>,
+	/// The number of seconds a client may take to answer a websocket ping
+	/// before it's disconnected [default: 10]
+	#[arg(long, value_name = "SECS", env = "PINGER_PONG_TIMEOUT")]
+	pong_timeout: Option<u64>,
+	/// The number of seconds after which clients which didn't send any
+	/// messages are disconnected, or 0 to never disconnect them [default:
+	/// 3600]
+	#[arg(long, value_name = "SECS", env = "PINGER_IDLE_TIMEOUT")]
+	idle_timeout: Option<u64>,
+	/// The maximum size of websocket messages from clients, in bytes
+	/// [default: 4096]
+	#[arg(long, value_name = "BYTES", env = "PINGER_MAX_MESSAGE_SIZE")]
+	max_message_size: Option<usize>,
+	/// The log filter, e.g. `info` or `pinger_backend=debug` [default: error]
+	#[arg(long, value_name = "FILTER", env = "PINGER_LOG")]
+	log: Option<String>,
+	/// The format of log messages [default: full]
+	#[arg(long, value_name = "FORMAT", env = "PINGER_LOG_FORMAT")]
+	log_format: Option<LogFormat>,
+	/// Whether to include clients' IP addresses in logs [default: false]
+	#[arg(long, value_name = "BOOL", env = "PINGER_LOG_IPS")]
+	log_ips: Option<bool>,
+	/// The comma-separated origins (`scheme://host[:port]`, or `*` for any)
+	/// of web pages allowed to use the API, in addition to the server's own
+	#[arg(
+		long,
+		value_name = "ORIGINS",
+		value_delimiter = ',',
+		env = "PINGER_ALLOWED_ORIGINS"
+	)]
+	allowed_origins: Option<Vec<String>>,
+	/// Whether to allow API requests without an `Origin`, which native clients
+	/// don't send [default: true]
+	#[arg(long, value_name = "BOOL", env = "PINGER_ALLOW_MISSING_ORIGIN")]
+	allow_missing_origin: Option<bool>,
+	/// Serve HTTPS with the PEM certificate chain in this file
+	#[arg(long, value_name = "FILE", env = "PINGER_TLS_CERT")]
+	tls_cert: Option<PathBuf>,
+	/// The PEM private key for the TLS certificate
+	#[arg(long, value_name = "FILE", env = "PINGER_TLS_KEY")]
+	tls_key: Option<PathBuf>,
+	/// The number of seconds a client may take to complete the TLS handshake
+	/// [default: 10]
+	#[arg(long, value_name = "SECS", env = "PINGER_TLS_HANDSHAKE_TIMEOUT")]
+	tls_handshake_timeout: Option<u64>,
+	/// Redirect plain HTTP requests on this port to HTTPS
+	#[arg(long, value_name = "PORT", env = "PINGER_REDIRECT_PORT")]
+	redirect_port: Option<u16>,
+	/// The comma-separated addresses to serve the admin API on, instead of the
+	/// main addresses, which must be loopback addresses unless a token is given
+	#[arg(
+		long,
+		value_name = "ADDR",
+		value_delimiter = ',',
+		env = "PINGER_ADMIN_BIND"
+	)]
+	admin_bind: Option<Vec<SocketAddr>>,
+	/// The bearer token required for the admin API, which is served on the
+	/// main addresses if no admin addresses are given
+	#[arg(
+		long,
+		value_name = "TOKEN",
+		env = "PINGER_ADMIN_TOKEN",
+		hide_env_values = true
+	)]
+	admin_token: Option<String>,
+	/// Print the effective settings and exit
+	#[arg(long)]
+	pub print_config: bool,
+}
+
+/// The contents of a configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+	bind: Option<Vec<SocketAddr>>,
+	port: Option<u16>,
+	id_range: Option<IdRange>,
+	buffer_size: Option<usize>,
+	relay_timeout: Option<u64>,
+	ping_interval: Option<u64>,
+	pong_timeout: Option<u64>,
+	idle_timeout: Option<u64>,
+	max_message_size: Option<usize>,
+	log: Option<String>,
+	log_format: Option<LogFormat>,
+	log_ips: Option<bool>,
+	allowed_origins: Option<Vec<String>>,
+	allow_missing_origin: Option<bool>,
+	tls: TlsFile,
+	admin: AdminFile,
+}
+
+/// The TLS settings in a configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct TlsFile {
+	cert: Option<PathBuf>,
+	key: Option<PathBuf>,
+	handshake_timeout: Option<u64>,
+	redirect_port: Option<u16>,
+}
+
+/// The admin API settings in a configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct AdminFile {
+	bind: Option<Vec<SocketAddr>>,
+	token: Option<String>,
+}
+
+/// An error in the configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+	#[error("can't read {}: {source}", path.display())]
+	Read {
+		path: PathBuf,
+		#[source]
+		source: io::Error,
+	},
+	#[error("invalid configuration in {}: {source}", path.display())]
+	Parse {
+		path: PathBuf,
+		#[source]
+		source: toml::de::Error,
+	},
+	#[error("{}", .0.join(", "))]
+	Invalid(Vec<String>),
+}
+
+/// The format of log messages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+	/// One line per message, with all fields
+	#[default]
+	Full,
+	/// One shorter line per message
+	Compact,
+	/// Multiple lines per message, for reading during development
+	Pretty,
+	/// One JSON object per line, for log collectors
+	Json,
+}
+
+/// A range of Ping IDs, written as `MIN-MAX`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdRange(pub RangeInclusive<u16>);
+
+impl Default for IdRange {
+	fn default() -> Self {
+		Self(ID_LIMITS)
+	}
+}
+
+impl Display for IdRange {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "{}-{}", self.0.start(), self.0.end())
+	}
+}
+
+impl FromStr for IdRange {
+	type Err = String;
+
+	/// Parse `MIN-MAX`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("invalid ID range `{s}` (expected MIN-MAX)");
+		let (min, max) = s.split_once('-').ok_or_else(invalid)?;
+		let min = min.trim().parse().map_err(|_| invalid())?;
+		let max = max.trim().parse().map_err(|_| invalid())?;
+
+		Ok(Self(min..=max))
+	}
+}
+
+impl Serialize for IdRange {
+	fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+		ser.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for IdRange {
+	fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+		String::deserialize(de)?.parse().map_err(D::Error::custom)
+	}
+}
+
+/// The effective server settings
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+	/// The addresses to listen on
+	pub bind: Vec<SocketAddr>,
+	/// Whether the addresses are the default ones, in which case it's enough
+	/// to listen on any of them (e.g. on hosts without IPv6)
+	#[serde(skip)]
+	pub default_bind: bool,
+	/// The range of IDs given out to clients
+	pub id_range: IdRange,
+	/// The number of messages buffered for each client
+	pub buffer_size: usize,
+	/// The number of seconds to wait for a client to take a relayed message
+	pub relay_timeout: u64,
+	/// The number of seconds between websocket pings to each client
+	pub ping_interval: u64,
+	/// The number of seconds a client may take to answer a websocket ping
+	pub pong_timeout: u64,
+	/// The number of seconds after which clients which didn't send any
+	/// messages are disconnected, or 0 to never disconnect them
+	pub idle_timeout: u64,
+	/// The maximum size of websocket messages from clients, in bytes
+	pub max_message_size: usize,
+	/// The log filter
+	pub log: String,
+	/// The format of log messages
+	pub log_format: LogFormat,
+	/// Whether to include clients' IP addresses in logs
+	pub log_ips: bool,
+	/// The origins of web pages allowed to use the API, in addition to the
+	/// server's own
+	pub allowed_origins: Vec<String>,
+	/// Whether to allow API requests without an `Origin`
+	pub allow_missing_origin: bool,
+	/// The TLS settings, if the server terminates TLS itself
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tls: Option<TlsSettings>,
+	/// The admin API settings, if it's enabled
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub admin: Option<AdminSettings>,
+}
+
+/// The TLS settings
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsSettings {
+	/// The PEM certificate chain file
+	pub cert: PathBuf,
+	/// The PEM private key file
+	pub key: PathBuf,
+	/// The number of seconds a client may take to complete the TLS handshake
+	pub handshake_timeout: u64,
+	/// The port on which plain HTTP requests are redirected to HTTPS
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub redirect_port: Option<u16>,
+}
+
+/// The admin API settings
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AdminSettings {
+	/// The addresses to serve the admin API on, or none for the main addresses,
+	/// which must all be loopback addresses if there is no token
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub bind: Vec<SocketAddr>,
+	/// The bearer token required for the admin API, which isn't printed
+	#[serde(skip)]
+	pub token: Option<String>,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self {
+			bind: default_bind(DEFAULT_PORT),
+			default_bind: true,
+			id_range: IdRange::default(),
+			buffer_size: 2,
+			relay_timeout: 10,
+			ping_interval: 30,
+			pong_timeout: 10,
+			idle_timeout: 60 * 60,
+			max_message_size: 4096,
+			log: "error".to_string(),
+			log_format: LogFormat::default(),
+			log_ips: false,
+			allowed_origins: Vec::new(),
+			allow_missing_origin: true,
+			tls: None,
+			admin: None,
+		}
+	}
+}
+
+impl Settings {
+	/// Combine the command-line arguments (and environment variables) in
+	/// `args` with the configuration file, if any, and validate the result
+	///
+	/// # Errors
+	/// Returns an error if the configuration file can't be read or parsed, or
+	/// if the settings are invalid
+	pub fn load(args: Args) -> Result<Self, ConfigError> {
+		let file = match &args.config {
+			Some(path) => {
+				let toml = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+					path: path.clone(),
+					source,
+				})?;
+
+				toml::from_str(&toml).map_err(|source| ConfigError::Parse {
+					path: path.clone(),
+					source,
+				})?
+			}
+			None => ConfigFile::default(),
+		};
+
+		let defaults = Self::default();
+		let bind = args.bind.or(file.bind);
+		let port = args.port.or(file.port);
+		let cert = args.tls_cert.or(file.tls.cert);
+		let key = args.tls_key.or(file.tls.key);
+		let handshake_timeout = args.tls_handshake_timeout.or(file.tls.handshake_timeout);
+		let redirect_port = args.redirect_port.or(file.tls.redirect_port);
+		let admin_bind = args.admin_bind.or(file.admin.bind);
+		let admin_token = args.admin_token.or(file.admin.token);
+
+		let mut errors = Vec::new();
+
+		if bind.is_some() && port.is_some() {
+			errors.push("the port can't be given together with addresses".to_string());
+		}
+
+		let tls = match (cert, key) {
+			(Some(cert), Some(key)) => Some(TlsSettings {
+				cert,
+				key,
+				handshake_timeout: handshake_timeout.unwrap_or(10),
+				redirect_port,
+			}),
+			(None, None) => {
+				if handshake_timeout.is_some() || redirect_port.is_some() {
+					errors.push("TLS settings require a TLS certificate and key".to_string());
+				}
+
+				None
+			}
+			_ => {
+				errors.push("the TLS certificate and key must be given together".to_string());
+				None
+			}
+		};
+
+		let admin = (admin_bind.is_some() || admin_token.is_some()).then(|| AdminSettings {
+			bind: admin_bind.unwrap_or_default(),
+			token: admin_token,
+		});
+
+		let settings = Self {
+			default_bind: bind.is_none(),
+			bind: bind.unwrap_or_else(|| default_bind(port.unwrap_or(DEFAULT_PORT))),
+			id_range: args.id_range.or(file.id_range).unwrap_or(defaults.id_range),
+			buffer_size: args
+				.buffer_size
+				.or(file.buffer_size)
+				.unwrap_or(defaults.buffer_size),
+			relay_timeout: args
+				.relay_timeout
+				.or(file.relay_timeout)
+				.unwrap_or(defaults.relay_timeout),
+			ping_interval: args
+				.ping_interval
+				.or(file.ping_interval)
+				.unwrap_or(defaults.ping_interval),
+			pong_timeout: args
+				.pong_timeout
+				.or(file.pong_timeout)
+				.unwrap_or(defaults.pong_timeout),
+			idle_timeout: args
+				.idle_timeout
+				.or(file.idle_timeout)
+				.unwrap_or(defaults.idle_timeout),
+			max_message_size: args
+				.max_message_size
+				.or(file.max_message_size)
+				.unwrap_or(defaults.max_message_size),
+			log: args.log.or(file.log).unwrap_or(defaults.log),
+			log_format: args
+				.log_format
+				.or(file.log_format)
+				.unwrap_or(defaults.log_format),
+			log_ips: args.log_ips.or(file.log_ips).unwrap_or(defaults.log_ips),
+			allowed_origins: args
+				.allowed_origins
+				.or(file.allowed_origins)
+				.unwrap_or(defaults.allowed_origins),
+			allow_missing_origin: args
+				.allow_missing_origin
+				.or(file.allow_missing_origin)
+				.unwrap_or(defaults.allow_missing_origin),
+			tls,
+			admin,
+		};
+
+		errors.extend(settings.errors());
+
+		if errors.is_empty() {
+			Ok(settings)
+		} else {
+			Err(ConfigError::Invalid(errors))
+		}
+	}
+
+	/// Get a description of each invalid setting
+	fn errors(&self) -> Vec<String> {
+		let mut errors = Vec::new();
+
+		if self.bind.is_empty() {
+			errors.push("there must be at least one address to listen on".to_string());
+		}
+
+		for (i, addr) in self.bind.iter().enumerate() {
+			if self.bind[..i].contains(addr) {
+				errors.push(format!("the address {addr} is given more than once"));
+			}
+		}
+
+		let ids = &self.id_range.0;
+		if ids.is_empty() || !ID_LIMITS.contains(ids.start()) || !ID_LIMITS.contains(ids.end()) {
+			errors.push(format!(
+				"the ID range must be a non-empty range within {}",
+				IdRange(ID_LIMITS)
+			));
+		}
+
+		// the `connected` message must always fit
+		if self.buffer_size == 0 {
+			errors.push("the buffer size must be at least 1".to_string());
+		}
+
+		if self.relay_timeout == 0 {
+			errors.push("the relay timeout must be at least 1 second".to_string());
+		}
+
+		if self.ping_interval == 0 {
+			errors.push("the ping interval must be at least 1 second".to_string());
+		}
+
+		if self.pong_timeout == 0 {
+			errors.push("the pong timeout must be at least 1 second".to_string());
+		}
+
+		for (name, secs) in [
+			("relay timeout", self.relay_timeout),
+			("ping interval", self.ping_interval),
+			("pong timeout", self.pong_timeout),
+			("idle timeout", self.idle_timeout),
+		] {
+			if secs > MAX_TIMEOUT {
+				errors.push(format!(
+					"the {name} must be at most {MAX_TIMEOUT} seconds (one day)"
+				));
+			}
+		}
+
+		// every valid message (e.g. a Ping) must fit
+		if self.max_message_size < MIN_MESSAGE_SIZE {
+			errors.push(format!(
+				"the maximum message size must be at least {MIN_MESSAGE_SIZE} bytes"
+			));
+		}
+
+		for origin in &self.allowed_origins {
+			if origin != "*" && normalize_origin(origin).is_none() {
+				errors.push(format!(
+					"invalid origin `{origin}` (expected scheme://host[:port] or *)"
+				));
+			}
+		}
+
+		if let Err(e) = EnvFilter::try_new(&self.log) {
+			errors.push(format!("invalid log filter `{}`: {e}", self.log));
+		}
+
+		if let Some(tls) = &self.tls {
+			if tls.handshake_timeout == 0 {
+				errors.push("the TLS handshake timeout must be at least 1 second".to_string());
+			}
+
+			if let Some(port) = tls.redirect_port
+				&& self.bind.iter().any(|addr| addr.port() == port)
+			{
+				errors.push(format!("the redirect port {port} is also used for HTTPS"));
+			}
+		}
+
+		if let Some(admin) = &self.admin {
+			if admin.bind.is_empty() && admin.token.is_none() {
+				errors.push(
+					"the admin API needs its own addresses or a token to be served on the main \
+					 addresses"
+						.to_string(),
+				);
+			}
+
+			if admin.token.is_none()
+				&& let Some(addr) = admin.bind.iter().find(|addr| !addr.ip().is_loopback())
+			{
+				errors.push(format!(
+					"the admin API needs a token to be served on the non-loopback address {addr}"
+				));
+			}
+
+			for (i, addr) in admin.bind.iter().enumerate() {
+				if admin.bind[..i].contains(addr) || self.bind.contains(addr) {
+					errors.push(format!("the address {addr} is given more than once"));
+				}
+			}
+
+			if admin
+				.token
+				.as_ref()
+				.is_some_and(|token| token.chars().count() < MIN_TOKEN_LENGTH)
+			{
+				errors.push(format!(
+					"the admin token must be at least {MIN_TOKEN_LENGTH} characters long"
+				));
+			}
+		}
+
+		errors
+	}
+
+	/// Get the IDs given out to clients
+	pub fn id_range(&self) -> RangeInclusive<u16> {
+		self.id_range.0.clone()
+	}
+
+	/// Get the policy for the origins allowed to use the API
+	pub fn origin_policy(&self) -> OriginPolicy {
+		OriginPolicy {
+			allowed: self
+				.allowed_origins
+				.iter()
+				.map(|origin| normalize_origin(origin).unwrap_or_else(|| origin.clone()))
+				.collect(),
+			allow_missing: self.allow_missing_origin,
+		}
+	}
+
+	/// Get the time to wait for a client to take a relayed message
+	pub const fn relay_timeout(&self) -> Duration {
+		Duration::from_secs(self.relay_timeout)
+	}
+
+	/// Get the time between websocket pings to each client
+	pub const fn ping_interval(&self) -> Duration {
+		Duration::from_secs(self.ping_interval)
+	}
+
+	/// Get the time a client may take to answer a websocket ping
+	pub const fn pong_timeout(&self) -> Duration {
+		Duration::from_secs(self.pong_timeout)
+	}
+
+	/// Get the time after which clients which didn't send any messages are
+	/// disconnected, if they are
+	pub const fn idle_timeout(&self) -> Option<Duration> {
+		if self.idle_timeout == 0 {
+			None
+		} else {
+			Some(Duration::from_secs(self.idle_timeout))
+		}
+	}
+
+	/// Get the settings in the configuration file format
+	pub fn to_toml(&self) -> String {
+		toml::to_string(self).expect("settings can't be serialized")
+	}
+}
+
+/// Get the addresses to listen on by default, on all IPv6 and IPv4 interfaces
+fn default_bind(port: u16) -> Vec<SocketAddr> {
+	vec![
+		SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port),
+		SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+	]
+}
@@ -0,0 +1,105 @@
+//! Logging, without anything that could identify or locate users
+//!
+//! Logs are written as text or as JSON (`log-format = "json"`), and all events
+//! of a websocket connection are in a `connection` span with the client's
+//! Ping `id` and a random `conn` ID, which (unlike Ping IDs) isn't reused.
+//!
+//! What is logged is restricted as follows:
+//!
+//! - IP addresses are only logged if `log-ips` is enabled, otherwise
+//!   third-party events which contain them (from `axum::serve`) are dropped
+//! - Keys and Ping info are never logged, because the `Debug` output of
+//!   messages between clients only contains their type
+//! - The contents of messages from clients are never logged, only their type
+//!   and destination
+
+use std::{
+	fmt::{Debug, Display, Formatter, Result as FmtResult},
+	net::SocketAddr,
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+use axum::{extract::connect_info::Connected, serve::IncomingStream};
+use tokio::net::TcpListener;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{
+	EnvFilter, Layer,
+	filter::filter_fn,
+	fmt::{self, MakeWriter},
+	prelude::*,
+};
+
+use crate::{
+	config::{LogFormat, Settings},
+	tls::TlsListener,
+};
+
+/// Whether IP addresses are logged
+static LOG_IPS: AtomicBool = AtomicBool::new(false);
+
+/// Set up logging to stdout for the whole program
+pub fn init(settings: &Settings) {
+	LOG_IPS.store(settings.log_ips, Ordering::Relaxed);
+	subscriber(settings, std::io::stdout).init();
+}
+
+/// Get a subscriber which writes logs to `writer` with the configured filter
+/// and format
+pub fn subscriber<W>(settings: &Settings, writer: W) -> impl Subscriber + Send + Sync + use<W>
+where
+	W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+	let layer = match settings.log_format {
+		LogFormat::Full => fmt::layer().with_writer(writer).boxed(),
+		LogFormat::Compact => fmt::layer().compact().with_writer(writer).boxed(),
+		LogFormat::Pretty => fmt::layer().pretty().with_writer(writer).boxed(),
+		LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+	};
+
+	let log_ips = settings.log_ips;
+	let ips = filter_fn(move |meta| {
+		log_ips || meta.target() != "axum::serve" || *meta.level() != Level::TRACE
+	});
+
+	tracing_subscriber::registry()
+		.with(layer)
+		.with(EnvFilter::new(&settings.log))
+		.with(ips)
+}
+
+/// Check if IP addresses are logged
+pub fn log_ips() -> bool {
+	LOG_IPS.load(Ordering::Relaxed)
+}
+
+/// A client's address, which is only shown in logs if IP addresses are logged
+#[derive(Clone, Copy)]
+pub struct Ip(pub SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>> for Ip {
+	fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+		Self(*stream.remote_addr())
+	}
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for Ip {
+	fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+		Self(*stream.remote_addr())
+	}
+}
+
+impl Display for Ip {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		if log_ips() {
+			write!(f, "{}", self.0)
+		} else {
+			f.write_str("<redacted>")
+		}
+	}
+}
+
+impl Debug for Ip {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		Display::fmt(self, f)
+	}
+}
@@ -1,35 +1,75 @@
 //! The Pinger backend server
+//!
+//! The server listens on port 8000 (or `--port`/`PORT`) on all interfaces by
+//! default, or on the addresses given with `--bind`.
+//! If `--tls-cert` and `--tls-key` are given the paths of a PEM certificate
+//! chain and private key, it serves HTTPS and `wss://` itself, reloading the
+//! certificate on `SIGHUP` or when the files change.
+//! With `--redirect-port`, plain HTTP requests on that port are then
+//! redirected to HTTPS.
+//! With `--admin-bind` or `--admin-token`, an API for operating the server is
+//! also served, see the [`admin`] module.
+//! See `--help` and the [`config`] module for all settings.
 
 use std::{
 	collections::HashMap,
-	env,
-	fmt::{Display, Formatter, Result as FmtResult},
-	net::{Ipv6Addr, SocketAddrV6},
+	fmt::Debug,
+	future, io,
+	net::SocketAddr,
 	ops::RangeInclusive,
-	sync::{Arc, RwLock},
+	process,
+	sync::{
+		Arc, RwLock,
+		atomic::{AtomicBool, Ordering},
+	},
+	time::Duration,
 };
 
 use axum::{
-	Router,
-	extract::{State, WebSocketUpgrade, ws::Message as WsMessage},
+	Extension, Router,
+	body::Bytes,
+	extract::{
+		ConnectInfo, State, WebSocketUpgrade,
+		ws::{CloseFrame, Message as WsMessage, WebSocket, close_code},
+	},
 	http::{HeaderName, HeaderValue, StatusCode},
+	middleware::from_fn_with_state,
 	response::{IntoResponse, Response},
-	routing::get,
+	routing::{any, get},
 };
-use pinger::EncryptedPingInfo;
+use clap::Parser;
+// the tests refer to the key type of the messages as `crate::PublicKey`
+#[cfg(test)]
+use messages::PublicKey;
 use rand::Rng;
-use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use thiserror::Error;
 use tokio::{
 	net::TcpListener,
 	select,
-	sync::mpsc::{self, Sender, error::SendError as ChannelSendError},
+	sync::{
+		Notify,
+		mpsc::{self, Sender, error::SendError as ChannelSendError},
+	},
+	task::JoinSet,
+	time::{self, Instant},
+};
+use tracing::{Instrument, debug, error, field, info, info_span, instrument, warn};
+
+use crate::{
+	config::{Args, Settings},
+	messages::{ClientClientMessage, ClientDownMessage, ClientUpMessage, Id, ServerClientMessage},
+	security::OriginPolicy,
 };
-use tracing::{debug, error, info, instrument};
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
+mod admin;
+mod config;
+mod logging;
+mod messages;
+mod security;
 mod serde_support;
 mod tests;
+mod tls;
 
 /// Serve a static asset named `name` with a `Content-Type` of `type` over HTTP
 macro_rules! serve_asset {
@@ -52,11 +92,12 @@ macro_rules! serve_asset {
 	}};
 }
 
-/// Serve a minified HTML file named `name`
+/// Serve a minified HTML file named `name` with the content security policy
+/// `csp`
 ///
 /// This file must have been minified in the build script
 macro_rules! serve_html {
-	($name:literal) => {{
+	($name:literal, $csp:expr) => {{
 		#[instrument(name = $name)]
 		async fn serve_html() -> impl IntoResponse {
 			(
@@ -65,6 +106,10 @@ macro_rules! serve_html {
 						HeaderName::from_static("content-type"),
 						HeaderValue::from_static("text/html"),
 					)
+					.header(
+						HeaderName::from_static("content-security-policy"),
+						HeaderValue::from_static($csp),
+					)
 					.body(())
 					.unwrap(),
 				include_str!(concat!(env!("OUT_DIR"), concat!("/", $name, ".html"))),
@@ -75,80 +120,84 @@ macro_rules! serve_html {
 	}};
 }
 
-/// A Ping ID, a 2- or 3-digit number
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
-struct Id(pub u16);
-
-impl Display for Id {
-	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-		write!(f, "{}", self.0)
-	}
-}
-
-/// A Pinger public key
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-struct PublicKey(#[serde(with = "serde_support::public_key")] pinger::PublicKey);
-
-/// A websocket message sent by the server to a client
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case", tag = "msg")]
-enum ServerClientMessage {
-	Connected { id: Id },
-	NoSuchId { id: Id },
-	Error { details: String },
-}
-
-/// A websocket message sent a client to another
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case", tag = "msg")]
-enum ClientClientMessage {
-	PingRequest { key: PublicKey },
-	AcceptPing { key: PublicKey },
-	RejectPing,
-	Ping { info: EncryptedPingInfo },
-	PingAck,
-}
-
-/// A message sent by a client to the server or via the server to another client
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct ClientUpMessage {
-	to: Id,
-	#[serde(flatten)]
-	msg: ClientClientMessage,
-}
-
-/// A message sent by the server to a client, possibly on behalf of another
-/// client
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-enum ClientDownMessage {
-	FromClient {
-		from: Id,
-		#[serde(flatten)]
-		msg: ClientClientMessage,
-	},
-	FromServer {
-		#[serde(flatten)]
-		msg: ServerClientMessage,
-	},
-}
-
 /// An error when sending a Pinger websocket message
 #[derive(Debug, Clone, Error)]
 enum SendError {
 	#[error("id {0} not found")]
 	NoSuchId(Id),
+	#[error("id {0} is not receiving messages")]
+	TimedOut(Id),
 	#[error(transparent)]
 	ChannelError(#[from] ChannelSendError<ClientDownMessage>),
 }
 
+/// A client connected to the server
+#[derive(Debug)]
+struct Connection {
+	/// The sender for messages to the client
+	sender: Sender<ClientDownMessage>,
+	/// When the client connected
+	connected: Instant,
+	/// Notified to disconnect the client
+	disconnect: Arc<Notify>,
+}
+
 /// The server context containing a map of all connections
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct Ctx {
-	connections: RwLock<HashMap<Id, Sender<ClientDownMessage>>>,
+	connections: RwLock<HashMap<Id, Connection>>,
+	/// Whether new connections are accepted
+	accepting: AtomicBool,
+	/// The IDs given out to clients
+	id_range: RangeInclusive<u16>,
+	/// The number of messages buffered for each client
+	buffer_size: usize,
+	/// How long to wait for a client to take a relayed message
+	relay_timeout: Duration,
+	/// The time between websocket pings to each client
+	ping_interval: Duration,
+	/// How long a client may take to answer a websocket ping
+	pong_timeout: Duration,
+	/// How long a client may go without sending any messages, if limited
+	idle_timeout: Option<Duration>,
+	/// The maximum size of websocket messages from clients
+	max_message_size: usize,
+	/// The origins allowed to use the API
+	origins: OriginPolicy,
+	/// Whether to tell browsers to only use HTTPS
+	hsts: bool,
+	/// The bearer token required for the admin API, if any
+	admin_token: Option<String>,
+}
+
+impl Default for Ctx {
+	fn default() -> Self {
+		Self::new(&Settings::default())
+	}
 }
 
 impl Ctx {
+	/// Create a new server context with no connections
+	fn new(settings: &Settings) -> Self {
+		Self {
+			connections: RwLock::default(),
+			accepting: AtomicBool::new(true),
+			id_range: settings.id_range(),
+			buffer_size: settings.buffer_size,
+			relay_timeout: settings.relay_timeout(),
+			ping_interval: settings.ping_interval(),
+			pong_timeout: settings.pong_timeout(),
+			idle_timeout: settings.idle_timeout(),
+			max_message_size: settings.max_message_size,
+			origins: settings.origin_policy(),
+			hsts: settings.tls.is_some(),
+			admin_token: settings
+				.admin
+				.as_ref()
+				.and_then(|admin| admin.token.clone()),
+		}
+	}
+
 	/// Relay a client-client message
 	async fn send(&self, to: Id, from: Id, msg: ClientClientMessage) -> Result<(), SendError> {
 		let Some(dest) = self
@@ -156,31 +205,44 @@ impl Ctx {
 			.read()
 			.expect("lock poisoned")
 			.get(&to)
-			.cloned()
+			.map(|conn| conn.sender.clone())
 		else {
 			return Err(SendError::NoSuchId(to));
 		};
 
-		dest.send(ClientDownMessage::FromClient { from, msg })
-			.await?;
+		time::timeout(
+			self.relay_timeout,
+			dest.send(ClientDownMessage::FromClient { from, msg }),
+		)
+		.await
+		.map_err(|_| SendError::TimedOut(to))??;
 
 		Ok(())
 	}
 
-	/// Add a new connection to the map
-	fn add_connection(&self, sender: Sender<ClientDownMessage>) -> Result<Id, impl IntoResponse> {
+	/// Add a new connection to the map, and get its ID and the notification
+	/// for disconnecting it
+	fn add_connection(
+		&self,
+		sender: Sender<ClientDownMessage>,
+	) -> Result<(Id, Arc<Notify>), impl IntoResponse> {
 		let mut conns = self.connections.write().expect("lock poisoned");
 
 		#[expect(clippy::question_mark, reason = "type inference")]
-		let id = match Self::gen_id(&*conns) {
+		let id = match Self::gen_id(&*conns, self.id_range.clone()) {
 			Ok(id) => id,
 			Err(err) => return Err(err),
 		};
 
-		conns.insert(id, sender);
+		let disconnect = Arc::new(Notify::new());
+		conns.insert(id, Connection {
+			sender,
+			connected: Instant::now(),
+			disconnect: Arc::clone(&disconnect),
+		});
 
 		drop(conns);
-		Ok(id)
+		Ok((id, disconnect))
 	}
 
 	/// Drop a connection from the map
@@ -188,17 +250,81 @@ impl Ctx {
 		drop(self.connections.write().expect("lock poisoned").remove(&id));
 	}
 
+	/// Get the ID of each connection and how long ago it was made, oldest
+	/// first
+	fn connection_ages(&self) -> Vec<(Id, Duration)> {
+		let mut ages = self
+			.connections
+			.read()
+			.expect("lock poisoned")
+			.iter()
+			.map(|(id, conn)| (*id, conn.connected.elapsed()))
+			.collect::<Vec<_>>();
+
+		ages.sort_by(|(_, a), (_, b)| b.cmp(a));
+		ages
+	}
+
+	/// Disconnect the client with `id`, returning whether it was connected
+	fn disconnect(&self, id: Id) -> bool {
+		self.connections
+			.read()
+			.expect("lock poisoned")
+			.get(&id)
+			.map(|conn| conn.disconnect.notify_one())
+			.is_some()
+	}
+
+	/// Send a notice to all clients, returning the number of clients which
+	/// received it in time
+	async fn send_notice(&self, details: &str) -> usize {
+		let senders = self
+			.connections
+			.read()
+			.expect("lock poisoned")
+			.values()
+			.map(|conn| conn.sender.clone())
+			.collect::<Vec<_>>();
+
+		let mut sends = JoinSet::new();
+		for sender in senders {
+			let msg = ClientDownMessage::FromServer {
+				msg: ServerClientMessage::Notice {
+					details: details.to_string(),
+				},
+			};
+
+			sends.spawn(time::timeout(self.relay_timeout, async move {
+				sender.send(msg).await
+			}));
+		}
+
+		sends
+			.join_all()
+			.await
+			.into_iter()
+			.filter(|res| matches!(res, Ok(Ok(()))))
+			.count()
+	}
+
+	/// Start or stop accepting new connections
+	fn set_accepting(&self, accepting: bool) {
+		self.accepting.store(accepting, Ordering::Relaxed);
+	}
+
 	/// Generate a random, unused Ping ID
-	fn gen_id<T>(connections: &HashMap<Id, T>) -> Result<Id, impl IntoResponse + use<T>> {
+	fn gen_id<T>(
+		connections: &HashMap<Id, T>,
+		range: RangeInclusive<u16>,
+	) -> Result<Id, impl IntoResponse + use<T>> {
 		const MAX_RETRIES: usize = 100;
-		const ID_RANGE: RangeInclusive<u16> = 10..=999;
 
 		let mut rng = rand::rng();
 		let mut i = 0;
-		let mut id = rng.random_range(ID_RANGE);
+		let mut id = rng.random_range(range.clone());
 
 		while connections.contains_key(&Id(id)) {
-			id = rng.random_range(ID_RANGE);
+			id = rng.random_range(range.clone());
 			i += 1;
 
 			if i > MAX_RETRIES {
@@ -212,17 +338,156 @@ impl Ctx {
 
 #[tokio::main]
 async fn main() {
-	tracing_subscriber::registry()
-		.with(fmt::layer())
-		.with(EnvFilter::from_env("PINGER_LOG"))
-		.init();
+	let args = Args::parse();
+	let print_config = args.print_config;
+
+	let settings = match Settings::load(args) {
+		Ok(settings) => settings,
+		Err(e) => {
+			eprintln!("Invalid configuration: {e}");
+			process::exit(1);
+		}
+	};
+
+	if print_config {
+		print!("{}", settings.to_toml());
+		return;
+	}
+
+	logging::init(&settings);
 
-	let ctx = Arc::default();
+	let certs = settings.tls.as_ref().map(|tls| {
+		match tls::Certificates::load(tls.cert.clone(), tls.key.clone()) {
+			Ok(certs) => Arc::new(certs),
+			Err(e) => {
+				error!("Error loading TLS certificate: {e}");
+				process::exit(1);
+			}
+		}
+	});
+
+	let listeners = bind_all(settings.bind.iter().copied(), settings.default_bind);
+	let ctx = Arc::new(Ctx::new(&settings));
+	let mut app = app(Arc::clone(&ctx));
+	let mut servers = JoinSet::new();
+
+	info!("Pinger backend starting");
 
-	let app = Router::new()
-		.route("/", serve_html!("index"))
-		.route("/api", get(pinger))
-		.route("/bug", serve_html!("bug"))
+	if let Some(admin) = &settings.admin {
+		if admin.bind.is_empty() {
+			// the admin API requires a token in this case
+			app = app.merge(admin::router(ctx));
+		} else {
+			for listener in bind_all(admin.bind.iter().copied(), false) {
+				let router = admin::router(Arc::clone(&ctx));
+				servers.spawn(async move { axum::serve(listener, router).await });
+			}
+		}
+	}
+
+	match (&settings.tls, certs) {
+		(Some(tls), Some(certs)) => {
+			let config = certs.server_config();
+			tokio::spawn(certs.watch());
+
+			if let Some(redirect_port) = tls.redirect_port {
+				// redirect to the first HTTPS port if there are several
+				let https_port = settings.bind[0].port();
+				let addrs = listeners
+					.iter()
+					.filter_map(|listener| listener.local_addr().ok())
+					.map(|addr| SocketAddr::new(addr.ip(), redirect_port));
+
+				for listener in bind_all(addrs, settings.default_bind) {
+					servers.spawn(
+						async move { axum::serve(listener, tls::redirect(https_port)).await },
+					);
+				}
+			}
+
+			let timeout = Duration::from_secs(tls.handshake_timeout);
+			for listener in listeners {
+				let listener =
+					tls::TlsListener::new(listener, Arc::clone(&config), timeout).unwrap();
+				let app = app
+					.clone()
+					.into_make_service_with_connect_info::<logging::Ip>();
+				servers.spawn(async move { axum::serve(listener, app).await });
+			}
+		}
+		_ => {
+			for listener in listeners {
+				let app = app
+					.clone()
+					.into_make_service_with_connect_info::<logging::Ip>();
+				servers.spawn(async move { axum::serve(listener, app).await });
+			}
+		}
+	}
+
+	while let Some(res) = servers.join_next().await {
+		res.unwrap().unwrap();
+	}
+}
+
+/// Listen on each of `addrs`, exiting if that's not possible
+///
+/// If `any` is true, it's enough to listen on any of the addresses.
+fn bind_all(addrs: impl Iterator<Item = SocketAddr>, any: bool) -> Vec<TcpListener> {
+	let mut listeners = Vec::new();
+
+	for addr in addrs {
+		match bind(addr) {
+			Ok(listener) => {
+				info!("Listening on {addr}");
+				listeners.push(listener);
+			}
+			Err(e) if any => warn!("Can't listen on {addr}: {e}"),
+			Err(e) => {
+				error!("Can't listen on {addr}: {e}");
+				process::exit(1);
+			}
+		}
+	}
+
+	if listeners.is_empty() {
+		error!("Can't listen on any address");
+		process::exit(1);
+	}
+
+	listeners
+}
+
+/// Listen on `addr`
+///
+/// IPv6 addresses only accept IPv6 connections, so that the same port can be
+/// used for IPv4 separately.
+fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+	let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+	if addr.is_ipv6() {
+		socket.set_only_v6(true)?;
+	}
+
+	#[cfg(not(windows))]
+	socket.set_reuse_address(true)?;
+	socket.set_nonblocking(true)?;
+	socket.bind(&addr.into())?;
+	socket.listen(1024)?;
+
+	TcpListener::from_std(socket.into())
+}
+
+/// Get the router for the Pinger website and API
+fn app(ctx: Arc<Ctx>) -> Router {
+	Router::new()
+		.route("/", serve_html!("index", security::INDEX_CSP))
+		// websockets over HTTP/2 use `CONNECT` instead of `GET`
+		.route(
+			"/api",
+			any(pinger).layer(from_fn_with_state(Arc::clone(&ctx), security::check_origin)),
+		)
+		.route("/bug", serve_html!("bug", security::BUG_CSP))
 		.route("/favicon.ico", serve_asset!("favicon.ico", "image/x-icon"))
 		.route("/icon.svg", serve_asset!("icon.svg", "image/svg+xml"))
 		.route("/icon.png", serve_asset!("icon.png", "image/png"))
@@ -242,32 +507,28 @@ async fn main() {
 			"/pinger.webmanifest",
 			serve_asset!("pinger.webmanifest", "application/manifest+json"),
 		)
-		.with_state(ctx);
-
-	let listener = TcpListener::bind(SocketAddrV6::new(
-		Ipv6Addr::UNSPECIFIED,
-		env::var("PORT")
-			.ok()
-			.and_then(|v| v.parse().ok())
-			.unwrap_or(8000),
-		0,
-		0,
-	))
-	.await
-	.unwrap();
-
-	info!("Pinger backend starting");
-	axum::serve(listener, app).await.unwrap();
+		.layer(from_fn_with_state(Arc::clone(&ctx), security::add_headers))
+		.with_state(ctx)
 }
 
 /// The Pinger API server
-#[instrument]
-async fn pinger(State(ctx): State<Arc<Ctx>>, upgrade: WebSocketUpgrade) -> Response {
-	const BUFFER_SIZE: usize = 2;
+#[instrument(skip_all)]
+async fn pinger(
+	State(ctx): State<Arc<Ctx>>,
+	peer: Option<Extension<ConnectInfo<logging::Ip>>>,
+	upgrade: WebSocketUpgrade,
+) -> Response {
+	if !ctx.accepting.load(Ordering::Relaxed) {
+		return (
+			StatusCode::SERVICE_UNAVAILABLE,
+			"not accepting new connections",
+		)
+			.into_response();
+	}
 
-	let (sender, mut receiver) = mpsc::channel(BUFFER_SIZE);
-	let id = match ctx.add_connection(sender.clone()) {
-		Ok(id) => id,
+	let (sender, receiver) = mpsc::channel(ctx.buffer_size);
+	let (id, disconnect) = match ctx.add_connection(sender.clone()) {
+		Ok(conn) => conn,
 		Err(e) => return e.into_response(),
 	};
 
@@ -277,64 +538,169 @@ async fn pinger(State(ctx): State<Arc<Ctx>>, upgrade: WebSocketUpgrade) -> Respo
 		})
 		.expect("empty buffer is full");
 
-	upgrade.on_upgrade(move |mut ws| async move {
-		loop {
-			select! {
-				opt_msg = ws.recv() => {
-					let Some(msg) = opt_msg else {
-						break;
-					};
+	// the connection outlives the upgrade request
+	let span = info_span!(
+		parent: None,
+		"connection",
+		%id,
+		conn = %format_args!("{:016x}", rand::random::<u64>()),
+		peer = field::Empty,
+	);
+
+	if let Some(Extension(ConnectInfo(ip))) = peer
+		&& logging::log_ips()
+	{
+		span.record("peer", field::display(ip));
+	}
 
-					let Ok(msg) = msg else {
-						let _ = sender.send(ClientDownMessage::FromServer {
-							msg: ServerClientMessage::Error {
-								details: "error while receiving websocket message".to_string()
-							}
-						}).await;
-						continue;
-					};
+	upgrade
+		.max_message_size(ctx.max_message_size)
+		.max_frame_size(ctx.max_message_size)
+		.on_upgrade(move |ws| {
+			async move {
+				debug!("Connection {id} opened");
+				let (code, reason) =
+					serve_connection(&ctx, id, ws, &sender, receiver, &disconnect).await;
+				debug!("Connection {id} closed ({code}: {reason})");
+
+				ctx.drop_connection(id);
+			}
+			.instrument(span)
+		})
+}
 
-					let WsMessage::Text(msg) = msg else {
+/// Relay messages to and from the client with `id` on `ws` until the
+/// connection is closed or `disconnect` is notified, and get the close code
+/// and reason
+async fn serve_connection(
+	ctx: &Ctx,
+	id: Id,
+	mut ws: WebSocket,
+	sender: &Sender<ClientDownMessage>,
+	mut receiver: mpsc::Receiver<ClientDownMessage>,
+	disconnect: &Notify,
+) -> (u16, &'static str) {
+	let mut ping_interval =
+		time::interval_at(Instant::now() + ctx.ping_interval, ctx.ping_interval);
+	let mut pong_deadline = None;
+	let idle = time::sleep(ctx.idle_timeout.unwrap_or(Duration::MAX));
+	tokio::pin!(idle);
+
+	let (code, reason) = loop {
+		select! {
+			opt_msg = ws.recv() => {
+				let Some(msg) = opt_msg else {
+					// the client closed the connection or is already gone
+					return (close_code::NORMAL, "client disconnected");
+				};
+
+				let msg = match msg {
+					Ok(msg) => msg,
+					Err(e) => match receive_error(e) {
+						Some(close) => break close,
+						None => return (close_code::ABNORMAL, "connection lost"),
+					},
+				};
+
+				// any message shows that the client is still there
+				pong_deadline = None;
+
+				let msg = match msg {
+					WsMessage::Text(msg) => msg,
+					// the reply to a close is sent automatically
+					WsMessage::Close(_) | WsMessage::Ping(_) | WsMessage::Pong(_) => continue,
+					WsMessage::Binary(_) => {
 						let _ = sender.send(ClientDownMessage::FromServer {
 							msg: ServerClientMessage::Error {
 								details: "unsupported message type, only text messages are supported".to_string()
 							}
 						}).await;
 						continue;
-					};
+					}
+				};
 
-					let Ok(msg) = serde_json::from_str::<ClientUpMessage>(&msg) else {
-						let _ = sender.send(ClientDownMessage::FromServer {
-							msg: ServerClientMessage::Error {
-								details: "could not deserialize message".to_string()
-							}
-						}).await;
-						continue;
-					};
-
-					if let Err(e) = ctx.send(msg.to, id, msg.msg).await {
-						match e {
-							SendError::NoSuchId(id) => {
-								let _ = sender.send(ClientDownMessage::FromServer {
-									msg: ServerClientMessage::NoSuchId { id }
-								}).await;
-							},
-							SendError::ChannelError(err) => error!("Error sending websocket message: {err}")
+				if let Some(idle_timeout) = ctx.idle_timeout {
+					idle.as_mut().reset(Instant::now() + idle_timeout);
+				}
+
+				let Ok(msg) = serde_json::from_str::<ClientUpMessage>(&msg) else {
+					let _ = sender.send(ClientDownMessage::FromServer {
+						msg: ServerClientMessage::Error {
+							details: "could not deserialize message".to_string()
 						}
+					}).await;
+					continue;
+				};
+
+				debug!(to = %msg.to, msg = ?msg.msg, "Relaying message");
+
+				if let Err(e) = ctx.send(msg.to, id, msg.msg).await {
+					match e {
+						SendError::NoSuchId(id) => {
+							let _ = sender.send(ClientDownMessage::FromServer {
+								msg: ServerClientMessage::NoSuchId { id }
+							}).await;
+						},
+						SendError::TimedOut(id) => {
+							let _ = sender.send(ClientDownMessage::FromServer {
+								msg: ServerClientMessage::Error {
+									details: format!("id {id} is not receiving messages")
+								}
+							}).await;
+						},
+						SendError::ChannelError(err) => error!("Error sending websocket message: {err}")
 					}
-				},
-				opt_msg = receiver.recv() => {
-					let Some(msg) = opt_msg else {
-						break;
-					};
-
-					if let Err(e) = ws.send(WsMessage::Text(serde_json::to_string(&msg).expect("failed to serialize message").into())).await {
-						debug!("error sending websocket message: {e}");
+				}
+			},
+			opt_msg = receiver.recv() => {
+				let Some(msg) = opt_msg else {
+					break (close_code::ERROR, "server error");
+				};
+
+				if let Err(e) = ws.send(WsMessage::Text(serde_json::to_string(&msg).expect("failed to serialize message").into())).await {
+					debug!("error sending websocket message: {e}");
+				}
+			},
+			_ = ping_interval.tick() => {
+				if pong_deadline.is_none() {
+					pong_deadline = Some(Instant::now() + ctx.pong_timeout);
+
+					if let Err(e) = ws.send(WsMessage::Ping(Bytes::new())).await {
+						debug!("error sending websocket ping: {e}");
 					}
-				},
-			}
+				}
+			},
+			() = sleep_until(pong_deadline) => break (close_code::AWAY, "ping timeout"),
+			() = &mut idle => break (close_code::NORMAL, "idle timeout"),
+			() = disconnect.notified() => break (close_code::POLICY, "disconnected by the operator"),
 		}
+	};
 
-		ctx.drop_connection(id);
-	})
+	let _ = ws
+		.send(WsMessage::Close(Some(CloseFrame {
+			code,
+			reason: reason.into(),
+		})))
+		.await;
+
+	(code, reason)
+}
+
+/// Get the close code and reason for an error receiving a websocket message,
+/// unless the connection is already lost
+fn receive_error(e: axum::Error) -> Option<(u16, &'static str)> {
+	match *e.into_inner().downcast::<tungstenite::Error>().ok()? {
+		tungstenite::Error::Capacity(_) => Some((close_code::SIZE, "message too big")),
+		tungstenite::Error::Utf8(_) => Some((close_code::INVALID, "invalid UTF-8")),
+		tungstenite::Error::Protocol(_) => Some((close_code::PROTOCOL, "protocol error")),
+		_ => None,
+	}
+}
+
+/// Wait until `deadline`, or forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => time::sleep_until(deadline).await,
+		None => future::pending().await,
+	}
 }
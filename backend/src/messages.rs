@@ -0,0 +1,107 @@
+//! The messages of the Pinger websocket protocol, as described in the README
+//!
+//! These types don't depend on the rest of the server, so that they can also
+//! be used by the fuzz targets and the load generator (in `fuzz/` and
+//! `loadtest/` in the repository root).
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use pinger::EncryptedPingInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::serde_support;
+
+/// A Ping ID, a 2- or 3-digit number
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Id(pub u16);
+
+impl Display for Id {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "{}", self.0)
+	}
+}
+
+/// A Pinger public key
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PublicKey(#[serde(with = "serde_support::public_key")] pub pinger::PublicKey);
+
+/// A websocket message sent by the server to a client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "msg")]
+pub enum ServerClientMessage {
+	Connected { id: Id },
+	NoSuchId { id: Id },
+	Error { details: String },
+	Notice { details: String },
+}
+
+/// A websocket message sent a client to another
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "msg")]
+pub enum ClientClientMessage {
+	PingRequest {
+		key: PublicKey,
+		/// Whether `key` is the message of a key exchange authenticated with a
+		/// short code instead of a public key
+		#[serde(default, skip_serializing_if = "is_false")]
+		pake: bool,
+	},
+	AcceptPing {
+		key: PublicKey,
+		/// Whether `key` is the message of a key exchange authenticated with a
+		/// short code instead of a public key
+		#[serde(default, skip_serializing_if = "is_false")]
+		pake: bool,
+	},
+	RejectPing,
+	Ping {
+		info: EncryptedPingInfo,
+	},
+	PingAck,
+}
+
+/// Only the message type is shown, to keep keys and Ping info out of logs
+impl Debug for ClientClientMessage {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::PingRequest { .. } => "ping_request",
+			Self::AcceptPing { .. } => "accept_ping",
+			Self::RejectPing => "reject_ping",
+			Self::Ping { .. } => "ping",
+			Self::PingAck => "ping_ack",
+		})
+	}
+}
+
+/// A message sent by a client to the server or via the server to another client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientUpMessage {
+	pub to: Id,
+	#[serde(flatten)]
+	pub msg: ClientClientMessage,
+}
+
+/// A message sent by the server to a client, possibly on behalf of another
+/// client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClientDownMessage {
+	FromClient {
+		from: Id,
+		#[serde(flatten)]
+		msg: ClientClientMessage,
+	},
+	FromServer {
+		#[serde(flatten)]
+		msg: ServerClientMessage,
+	},
+}
+
+/// Check if `b` is `false`, to leave out the default `pake` field
+#[expect(
+	clippy::trivially_copy_pass_by_ref,
+	reason = "serde's skip_serializing_if passes a reference"
+)]
+const fn is_false(b: &bool) -> bool {
+	!*b
+}
@@ -0,0 +1,157 @@
+//! Request validation and security headers
+//!
+//! Browsers let any page open websocket connections to any server, so the
+//! `Origin` of API requests is checked against an allow-list (in addition to
+//! the server's own origin), to keep third-party pages from using visitors'
+//! browsers to talk to the API.
+//! Native clients don't send an `Origin`, and are allowed unless
+//! `allow-missing-origin` is disabled.
+
+use std::sync::Arc;
+
+use axum::{
+	extract::{Request, State},
+	http::{
+		HeaderMap, HeaderValue, StatusCode, Uri,
+		header::{
+			CACHE_CONTROL, CONTENT_SECURITY_POLICY, HOST, ORIGIN, REFERRER_POLICY,
+			STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+		},
+		uri::Authority,
+	},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use tracing::debug;
+
+use crate::Ctx;
+
+/// The content security policy of the main page, which has no scripts
+pub const INDEX_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; img-src 'self'; \
+                             manifest-src 'self'; base-uri 'none'; form-action 'none'; \
+                             frame-ancestors 'none'";
+
+/// The content security policy of the bug report page, which has inline
+/// scripts and event handlers
+pub const BUG_CSP: &str = "default-src 'none'; script-src 'unsafe-inline'; style-src \
+                           'unsafe-inline'; img-src 'self'; manifest-src 'self'; base-uri 'none'; \
+                           form-action 'none'; frame-ancestors 'none'";
+
+/// The value of `Strict-Transport-Security`, if the server terminates TLS
+const HSTS: &str = "max-age=31536000";
+
+/// The origins allowed to use the API
+#[derive(Debug, Clone, Default)]
+pub struct OriginPolicy {
+	/// The allowed origins, as lowercase `scheme://host[:port]`, or `*` for
+	/// any origin
+	pub allowed: Vec<String>,
+	/// Whether requests without an `Origin` (from native clients) are allowed
+	pub allow_missing: bool,
+}
+
+impl OriginPolicy {
+	/// Check if a request with `headers` to `uri` may use the API
+	///
+	/// # Errors
+	/// Returns why the request isn't allowed
+	pub fn check(&self, headers: &HeaderMap, uri: &Uri) -> Result<(), &'static str> {
+		let Some(origin) = headers.get(ORIGIN) else {
+			return if self.allow_missing {
+				Ok(())
+			} else {
+				Err("missing origin")
+			};
+		};
+
+		let Some(origin) = origin.to_str().ok().and_then(normalize_origin) else {
+			return Err("invalid origin");
+		};
+
+		if self
+			.allowed
+			.iter()
+			.any(|allowed| allowed == "*" || *allowed == origin)
+		{
+			return Ok(());
+		}
+
+		// the server's own origin is always allowed
+		let host = headers
+			.get(HOST)
+			.and_then(|host| host.to_str().ok())
+			.or_else(|| uri.authority().map(Authority::as_str));
+
+		match (host, origin.split_once("://")) {
+			(Some(host), Some((_, origin_host))) if host.eq_ignore_ascii_case(origin_host) => {
+				Ok(())
+			}
+			_ => Err("origin not allowed"),
+		}
+	}
+}
+
+/// Normalize `origin` into lowercase `scheme://host[:port]`, if it's a valid
+/// origin
+///
+/// Opaque origins (`null`) are never valid.
+pub fn normalize_origin(origin: &str) -> Option<String> {
+	let uri = origin.trim().parse::<Uri>().ok()?;
+	let scheme = uri.scheme_str()?;
+	let authority = uri.authority()?;
+
+	if !matches!(uri.path(), "" | "/") || uri.query().is_some() || authority.as_str().contains('@')
+	{
+		return None;
+	}
+
+	Some(format!("{scheme}://{authority}").to_ascii_lowercase())
+}
+
+/// Reject API requests from origins which aren't allowed
+pub async fn check_origin(State(ctx): State<Arc<Ctx>>, req: Request, next: Next) -> Response {
+	if let Err(reason) = ctx.origins.check(req.headers(), req.uri()) {
+		debug!(
+			"Rejecting API request from {:?}: {reason}",
+			req.headers().get(ORIGIN)
+		);
+		return (StatusCode::FORBIDDEN, reason).into_response();
+	}
+
+	let mut res = next.run(req).await;
+	res.headers_mut()
+		.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+	res
+}
+
+/// Add the security headers common to all responses
+pub async fn add_headers(State(ctx): State<Arc<Ctx>>, req: Request, next: Next) -> Response {
+	let mut res = next.run(req).await;
+	let headers = res.headers_mut();
+
+	headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+	headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+	headers.insert(REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+	headers.insert(
+		"cross-origin-opener-policy",
+		HeaderValue::from_static("same-origin"),
+	);
+	headers.insert(
+		"cross-origin-resource-policy",
+		HeaderValue::from_static("same-origin"),
+	);
+
+	if !headers.contains_key(CONTENT_SECURITY_POLICY) {
+		headers.insert(
+			CONTENT_SECURITY_POLICY,
+			HeaderValue::from_static("default-src 'none'; frame-ancestors 'none'"),
+		);
+	}
+
+	if ctx.hsts {
+		headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(HSTS));
+	}
+
+	res
+}
@@ -1,11 +1,48 @@
 #![cfg(test)]
 
-use std::error::Error;
+use std::{
+	env,
+	error::Error,
+	fs, io,
+	net::SocketAddr,
+	path::PathBuf,
+	process,
+	sync::{Arc, Mutex},
+};
 
+use axum::{body::Body, extract::Request, serve::Listener};
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
 use pinger::{Degrees, EphemeralSecret, Meters, PingInfo, PublicKey, Timestamp};
 use regex::Regex;
+use rustls::{
+	ClientConfig, RootCertStore,
+	pki_types::{CertificateDer, ServerName},
+};
+use tokio::{
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	net::{TcpListener, TcpStream},
+	time::{self, Instant},
+};
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::{
+	WebSocketStream, connect_async,
+	tungstenite::{
+		Message as TMessage,
+		protocol::frame::{
+			Frame,
+			coding::{Data, OpCode},
+		},
+	},
+};
+use tower::ServiceExt;
 
-use crate::*;
+use crate::{
+	config::{AdminSettings, ConfigError, LogFormat},
+	*,
+};
+
+mod e2e;
 
 #[test]
 fn ser_down() -> Result<(), Box<dyn Error>> {
@@ -23,13 +60,13 @@ fn ser_down() -> Result<(), Box<dyn Error>> {
 		bobs_shared_secret.as_bytes()
 	);
 
-	let ping_info = PingInfo {
-		ts: Timestamp(0x1234567890),
-		lat: Degrees(1.2),
-		lon: Degrees(3.4),
-		alt: Meters(5.6),
-		err: Meters(7.8),
-	};
+	let ping_info = PingInfo::new(
+		Timestamp(0x0012_3456_7890),
+		Degrees(1.2),
+		Degrees(3.4),
+		Meters(5.6),
+		Meters(7.8),
+	)?;
 
 	let info = ping_info.encrypt(alices_shared_secret).unwrap();
 	let info_str = serde_json::to_string(&info)?;
@@ -66,6 +103,15 @@ fn ser_down() -> Result<(), Box<dyn Error>> {
 		r#"{"msg":"no_such_id","id":42}"#
 	);
 
+	assert_eq!(
+		serde_json::to_string(&ClientDownMessage::FromServer {
+			msg: ServerClientMessage::Notice {
+				details: "maintenance soon".to_string()
+			}
+		})?,
+		r#"{"msg":"notice","details":"maintenance soon"}"#
+	);
+
 	assert_eq!(
 		serde_json::to_string(&ClientDownMessage::FromClient {
 			from: Id(42),
@@ -79,14 +125,15 @@ fn ser_down() -> Result<(), Box<dyn Error>> {
 			from: Id(42),
 			msg: ClientClientMessage::Ping { info }
 		})?,
-		format!(r#"{{"from":42,"msg":"ping","info":{}}}"#, info_str)
+		format!(r#"{{"from":42,"msg":"ping","info":{info_str}}}"#)
 	);
 
 	assert_eq!(
 		serde_json::to_string(&ClientDownMessage::FromClient {
 			from: Id(42),
 			msg: ClientClientMessage::AcceptPing {
-				key: PublicKey(alices_public_key)
+				key: PublicKey(alices_public_key),
+				pake: false
 			}
 		})?,
 		format!(r#"{{"from":42,"msg":"accept_ping","key":{apk_str}}}"#)
@@ -96,7 +143,8 @@ fn ser_down() -> Result<(), Box<dyn Error>> {
 		serde_json::to_string(&ClientDownMessage::FromClient {
 			from: Id(42),
 			msg: ClientClientMessage::PingRequest {
-				key: PublicKey(bobs_public_key)
+				key: PublicKey(bobs_public_key),
+				pake: false
 			}
 		})?,
 		format!(r#"{{"from":42,"msg":"ping_request","key":{bpk_str}}}"#)
@@ -129,13 +177,13 @@ fn ser_up() -> Result<(), Box<dyn Error>> {
 		bobs_shared_secret.as_bytes()
 	);
 
-	let ping_info = PingInfo {
-		ts: Timestamp(0x1234567890),
-		lat: Degrees(1.2),
-		lon: Degrees(3.4),
-		alt: Meters(5.6),
-		err: Meters(7.8),
-	};
+	let ping_info = PingInfo::new(
+		Timestamp(0x0012_3456_7890),
+		Degrees(1.2),
+		Degrees(3.4),
+		Meters(5.6),
+		Meters(7.8),
+	)?;
 
 	let info = ping_info.encrypt(alices_shared_secret).unwrap();
 	let info_str = serde_json::to_string(&info)?;
@@ -159,7 +207,8 @@ fn ser_up() -> Result<(), Box<dyn Error>> {
 		serde_json::to_string(&ClientUpMessage {
 			to: Id(42),
 			msg: ClientClientMessage::AcceptPing {
-				key: PublicKey(alices_public_key)
+				key: PublicKey(alices_public_key),
+				pake: false
 			}
 		})?,
 		format!(r#"{{"to":42,"msg":"accept_ping","key":{apk_str}}}"#)
@@ -185,11 +234,876 @@ fn ser_up() -> Result<(), Box<dyn Error>> {
 		serde_json::to_string(&ClientUpMessage {
 			to: Id(42),
 			msg: ClientClientMessage::PingRequest {
-				key: PublicKey(bobs_public_key)
+				key: PublicKey(bobs_public_key),
+				pake: false
 			}
 		})?,
 		format!(r#"{{"to":42,"msg":"ping_request","key":{bpk_str}}}"#)
 	);
 
+	assert_eq!(
+		serde_json::to_string(&ClientUpMessage {
+			to: Id(42),
+			msg: ClientClientMessage::PingRequest {
+				key: PublicKey(bobs_public_key),
+				pake: true
+			}
+		})?,
+		format!(r#"{{"to":42,"msg":"ping_request","key":{bpk_str},"pake":true}}"#)
+	);
+
 	Ok(())
 }
+
+/// Generate a self-signed certificate for `localhost`, and write it and its
+/// private key to temporary files named after `name`
+fn self_signed(name: &str) -> (Vec<u8>, PathBuf, PathBuf) {
+	let key = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+	let dir = env::temp_dir();
+	let cert_path = dir.join(format!("pinger-backend-test-{}-{name}.crt", process::id()));
+	let key_path = dir.join(format!("pinger-backend-test-{}-{name}.key", process::id()));
+
+	fs::write(&cert_path, key.cert.pem()).unwrap();
+	fs::write(&key_path, key.signing_key.serialize_pem()).unwrap();
+
+	(key.cert.der().to_vec(), cert_path, key_path)
+}
+
+/// Connect to `addr` over TLS, trusting only `cert` and offering the ALPN
+/// `protocols`
+async fn tls_connect(
+	addr: SocketAddr,
+	cert: &[u8],
+	protocols: &[&[u8]],
+) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+	let mut roots = RootCertStore::empty();
+	roots.add(CertificateDer::from(cert.to_vec())).unwrap();
+
+	let mut config =
+		ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+			.with_safe_default_protocol_versions()
+			.unwrap()
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+	config.alpn_protocols = protocols.iter().map(|p| p.to_vec()).collect();
+
+	TlsConnector::from(Arc::new(config))
+		.connect(
+			ServerName::try_from("localhost").unwrap(),
+			TcpStream::connect(addr).await?,
+		)
+		.await
+}
+
+/// Send a raw HTTP/1.1 request to `stream` and get the response head
+async fn http_get<S: AsyncRead + AsyncWrite + Unpin>(
+	stream: &mut S,
+	host: &str,
+	path: &str,
+) -> String {
+	stream
+		.write_all(
+			format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n").as_bytes(),
+		)
+		.await
+		.unwrap();
+
+	let mut response = Vec::new();
+	let _ = stream.read_to_end(&mut response).await;
+	let response = String::from_utf8_lossy(&response);
+
+	response
+		.split("\r\n\r\n")
+		.next()
+		.unwrap()
+		.to_ascii_lowercase()
+}
+
+#[tokio::test]
+async fn tls() {
+	let (cert_a, cert_path, key_path) = self_signed("a");
+	let certs = Arc::new(tls::Certificates::load(cert_path.clone(), key_path.clone()).unwrap());
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let listener =
+		tls::TlsListener::new(listener, certs.server_config(), Duration::from_secs(10)).unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move { axum::serve(listener, app(Arc::default())).await });
+
+	let h2 = tls_connect(addr, &cert_a, &[b"h2", b"http/1.1"])
+		.await
+		.unwrap();
+	assert_eq!(h2.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+	let mut http1 = tls_connect(addr, &cert_a, &[b"http/1.1"]).await.unwrap();
+	assert_eq!(http1.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+	assert!(
+		http_get(&mut http1, "localhost", "/icon.svg")
+			.await
+			.starts_with("http/1.1 200")
+	);
+
+	// a new certificate is used for new connections once it's reloaded
+	let (cert_b, new_cert_path, new_key_path) = self_signed("b");
+	fs::rename(&new_cert_path, &cert_path).unwrap();
+	fs::rename(&new_key_path, &key_path).unwrap();
+	certs.reload().unwrap();
+
+	assert!(tls_connect(addr, &cert_a, &[]).await.is_err());
+	let mut conn = tls_connect(addr, &cert_b, &[]).await.unwrap();
+	assert!(
+		http_get(&mut conn, "localhost", "/")
+			.await
+			.starts_with("http/1.1 200")
+	);
+
+	// an invalid certificate isn't loaded, and the old one is kept
+	fs::write(&key_path, "not a key").unwrap();
+	assert!(matches!(certs.reload(), Err(tls::TlsError::Read { .. })));
+	assert!(tls_connect(addr, &cert_b, &[]).await.is_ok());
+
+	let (_, other_cert_path, other_key_path) = self_signed("c");
+	assert!(matches!(
+		tls::Certificates::load(cert_path.clone(), other_key_path.clone()),
+		Err(tls::TlsError::Invalid(_))
+	));
+	fs::write(&other_cert_path, "").unwrap();
+	assert!(matches!(
+		tls::Certificates::load(other_cert_path.clone(), other_key_path.clone()),
+		Err(tls::TlsError::NoCertificates(_))
+	));
+
+	for path in [cert_path, key_path, other_cert_path, other_key_path] {
+		fs::remove_file(path).unwrap();
+	}
+}
+
+#[tokio::test]
+async fn https_redirect() {
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move { axum::serve(listener, tls::redirect(8443)).await });
+
+	let mut conn = TcpStream::connect(addr).await.unwrap();
+	let response = http_get(&mut conn, "example.com:8080", "/api?x=1").await;
+	assert!(response.starts_with("http/1.1 308"), "{response}");
+	assert!(
+		response
+			.lines()
+			.any(|l| l == "location: https://example.com:8443/api?x=1")
+	);
+
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let addr = listener.local_addr().unwrap();
+	tokio::spawn(async move { axum::serve(listener, tls::redirect(443)).await });
+
+	let mut conn = TcpStream::connect(addr).await.unwrap();
+	let response = http_get(&mut conn, "example.com", "/").await;
+	assert!(
+		response
+			.lines()
+			.any(|l| l == "location: https://example.com/")
+	);
+}
+
+/// Load the settings for the command-line arguments `args` (without the
+/// program name) with the configuration file `config`
+fn settings(config: &str, args: &[&str]) -> Result<Settings, ConfigError> {
+	let path = env::temp_dir().join(format!("pinger-backend-test-{}-config.toml", process::id()));
+	fs::write(&path, config).unwrap();
+
+	let args = Args::try_parse_from(
+		["pinger-backend", "--config", path.to_str().unwrap()]
+			.iter()
+			.chain(args),
+	)
+	.unwrap();
+	let settings = Settings::load(args);
+
+	fs::remove_file(&path).unwrap();
+	settings
+}
+
+#[test]
+fn config() {
+	const CONFIG: &str = r#"
+		bind = ["127.0.0.1:8080", "[::1]:8080"]
+		id-range = "100-199"
+		buffer-size = 4
+		log = "pinger_backend=debug"
+		log-format = "compact"
+
+		[tls]
+		cert = "cert.pem"
+		key = "key.pem"
+		redirect-port = 8081
+	"#;
+
+	let defaults = settings("", &[]).unwrap();
+	assert_eq!(defaults.bind, [
+		"[::]:8000".parse::<SocketAddr>().unwrap(),
+		"0.0.0.0:8000".parse().unwrap()
+	]);
+	assert!(defaults.default_bind);
+	assert_eq!(defaults.id_range(), 10..=999);
+	assert!(defaults.tls.is_none());
+
+	let file = settings(CONFIG, &[]).unwrap();
+	assert_eq!(file.bind.len(), 2);
+	assert!(!file.default_bind);
+	assert_eq!(file.id_range(), 100..=199);
+	assert_eq!(file.buffer_size, 4);
+	assert_eq!(file.log_format, LogFormat::Compact);
+	assert_eq!(file.tls.as_ref().unwrap().redirect_port, Some(8081));
+	assert_eq!(file.tls.as_ref().unwrap().handshake_timeout, 10);
+
+	// flags take precedence over the file
+	let flags = settings(CONFIG, &[
+		"--bind",
+		"0.0.0.0:443,[::]:443",
+		"--id-range",
+		"500-599",
+		"--log-format",
+		"pretty",
+	])
+	.unwrap();
+	assert_eq!(flags.bind, [
+		"0.0.0.0:443".parse::<SocketAddr>().unwrap(),
+		"[::]:443".parse().unwrap()
+	]);
+	assert_eq!(flags.id_range(), 500..=599);
+	assert_eq!(flags.log_format, LogFormat::Pretty);
+	assert_eq!(flags.buffer_size, 4);
+
+	// the printed configuration can be loaded again
+	let printed = settings(&flags.to_toml(), &[]).unwrap();
+	assert_eq!(printed.to_toml(), flags.to_toml());
+
+	let port = settings("port = 9000", &[]).unwrap();
+	assert_eq!(port.bind[0].port(), 9000);
+
+	// the admin token isn't printed
+	let admin = settings("[admin]\nbind = [\"127.0.0.1:8001\"]", &[
+		"--admin-token",
+		"0123456789abcdef",
+	])
+	.unwrap();
+	let admin_settings = admin.admin.as_ref().unwrap();
+	assert_eq!(admin_settings.token.as_deref(), Some("0123456789abcdef"));
+	assert!(admin.to_toml().contains("127.0.0.1:8001"));
+	assert!(!admin.to_toml().contains("0123456789abcdef"));
+	assert!(defaults.admin.is_none());
+
+	// the admin API doesn't need a token on loopback addresses only
+	let local = settings("[admin]\nbind = [\"127.0.0.1:8001\", \"[::1]:8001\"]", &[]).unwrap();
+	assert!(local.admin.unwrap().token.is_none());
+	let public = settings("[admin]\nbind = [\"0.0.0.0:8001\"]", &[
+		"--admin-token",
+		"0123456789abcdef",
+	]);
+	assert!(public.is_ok());
+
+	for (config, args) in [
+		("id-range = \"5-50\"", &[][..]),
+		("id-range = \"200-100\"", &[]),
+		("buffer-size = 0", &[]),
+		("relay-timeout = 0", &[]),
+		("log = \"=^.^=\"", &[]),
+		("bind = []", &[]),
+		("bind = [\"[::]:1\", \"[::]:1\"]", &[]),
+		("port = 9000", &["--bind", "[::]:1"]),
+		("[tls]\ncert = \"cert.pem\"", &[]),
+		("[tls]\nredirect-port = 80", &[]),
+		("[tls]\ncert = \"a\"\nkey = \"b\"\nredirect-port = 8000", &[
+		]),
+		("[admin]\ntoken = \"short\"", &[]),
+		("[admin]\nbind = []", &[]),
+		("[admin]\nbind = [\"[::]:8000\"]", &[]),
+		("[admin]\nbind = [\"0.0.0.0:8001\"]", &[]),
+		("[admin]\nbind = [\"127.0.0.1:8001\", \"[::]:8001\"]", &[]),
+	] {
+		assert!(
+			matches!(settings(config, args), Err(ConfigError::Invalid(_))),
+			"{config} {args:?}"
+		);
+	}
+
+	assert!(matches!(
+		settings("bind = \"[::]:1\"", &[]),
+		Err(ConfigError::Parse { .. })
+	));
+	assert!(matches!(
+		settings("port = 9000\nhost = \"localhost\"", &[]),
+		Err(ConfigError::Parse { .. })
+	));
+	assert!(matches!(
+		settings("id-range = \"1\"", &[]),
+		Err(ConfigError::Parse { .. })
+	));
+}
+
+#[test]
+fn timeout_limits() {
+	let day = settings("idle-timeout = 86400\nping-interval = 86400", &[]).unwrap();
+	assert_eq!(day.idle_timeout(), Some(Duration::from_hours(24)));
+
+	// deadlines for larger values could overflow
+	for (config, args) in [
+		("idle-timeout = 86401", &[][..]),
+		("relay-timeout = 100000", &[]),
+		("", &["--ping-interval", "18446744073709551615"]),
+		("", &["--pong-timeout", "86401"]),
+	] {
+		assert!(
+			matches!(settings(config, args), Err(ConfigError::Invalid(_))),
+			"{config} {args:?}"
+		);
+	}
+}
+
+/// Send a websocket upgrade request to `/api` with the `origin`, to a server
+/// with `settings`, and get the response status
+async fn api_status(settings: &Settings, host: &str, origin: Option<&str>) -> StatusCode {
+	let mut req = Request::get("/api")
+		.header("host", host)
+		.header("connection", "upgrade")
+		.header("upgrade", "websocket")
+		.header("sec-websocket-version", "13")
+		.header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==");
+
+	if let Some(origin) = origin {
+		req = req.header("origin", origin);
+	}
+
+	app(Arc::new(Ctx::new(settings)))
+		.oneshot(req.body(Body::empty()).unwrap())
+		.await
+		.unwrap()
+		.status()
+}
+
+#[tokio::test]
+async fn origins() {
+	// allowed requests get to the websocket upgrade, which isn't possible here
+	let allowed = StatusCode::UPGRADE_REQUIRED;
+
+	let defaults = Settings::default();
+	assert_eq!(api_status(&defaults, "localhost", None).await, allowed);
+	assert_eq!(
+		api_status(
+			&defaults,
+			"pinger.example.com",
+			Some("https://pinger.example.com")
+		)
+		.await,
+		allowed
+	);
+	assert_eq!(
+		api_status(
+			&defaults,
+			"pinger.example.com",
+			Some("https://evil.example.com")
+		)
+		.await,
+		StatusCode::FORBIDDEN
+	);
+	assert_eq!(
+		api_status(&defaults, "pinger.example.com", Some("null")).await,
+		StatusCode::FORBIDDEN
+	);
+
+	let allow_list = settings(
+		"allowed-origins = [\"https://Web.Example.com/\", \"http://localhost:5173\"]\n\
+		 allow-missing-origin = false",
+		&[],
+	)
+	.unwrap();
+	assert_eq!(
+		api_status(&allow_list, "pinger.example.com", None).await,
+		StatusCode::FORBIDDEN
+	);
+	for origin in [
+		"https://web.example.com",
+		"http://localhost:5173",
+		"https://pinger.example.com",
+	] {
+		assert_eq!(
+			api_status(&allow_list, "pinger.example.com", Some(origin)).await,
+			allowed,
+			"{origin}"
+		);
+	}
+	for origin in [
+		"http://web.example.com",
+		"https://web.example.com:8443",
+		"http://localhost:5174",
+		"https://web.example.com.evil.com",
+		"https://web.example.com/path",
+	] {
+		assert_eq!(
+			api_status(&allow_list, "pinger.example.com", Some(origin)).await,
+			StatusCode::FORBIDDEN,
+			"{origin}"
+		);
+	}
+
+	let any = settings("allowed-origins = [\"*\"]", &[]).unwrap();
+	assert_eq!(
+		api_status(&any, "pinger.example.com", Some("https://evil.example.com")).await,
+		allowed
+	);
+
+	assert!(matches!(
+		settings("allowed-origins = [\"web.example.com\"]", &[]),
+		Err(ConfigError::Invalid(_))
+	));
+}
+
+#[tokio::test]
+async fn security_headers() {
+	let get = async |path: &str, settings: &Settings| {
+		app(Arc::new(Ctx::new(settings)))
+			.oneshot(Request::get(path).body(Body::empty()).unwrap())
+			.await
+			.unwrap()
+	};
+
+	let defaults = Settings::default();
+	for path in ["/", "/bug", "/icon.svg", "/api", "/not-found"] {
+		let res = get(path, &defaults).await;
+		let headers = res.headers();
+
+		assert_eq!(headers["x-content-type-options"], "nosniff", "{path}");
+		assert_eq!(headers["x-frame-options"], "DENY", "{path}");
+		assert_eq!(headers["referrer-policy"], "no-referrer", "{path}");
+		assert!(
+			headers["content-security-policy"]
+				.to_str()
+				.unwrap()
+				.contains("frame-ancestors 'none'"),
+			"{path}"
+		);
+		assert!(!headers.contains_key("strict-transport-security"), "{path}");
+	}
+
+	let csp = |res: &Response| {
+		res.headers()["content-security-policy"]
+			.to_str()
+			.unwrap()
+			.to_string()
+	};
+	assert!(csp(&get("/", &defaults).await).contains("style-src 'unsafe-inline'"));
+	assert!(!csp(&get("/", &defaults).await).contains("script-src"));
+	assert!(csp(&get("/bug", &defaults).await).contains("script-src 'unsafe-inline'"));
+	assert!(csp(&get("/icon.svg", &defaults).await).starts_with("default-src 'none'"));
+	assert_eq!(
+		get("/api", &defaults).await.headers()["cache-control"],
+		"no-store"
+	);
+
+	let tls = settings("[tls]\ncert = \"cert.pem\"\nkey = \"key.pem\"", &[]).unwrap();
+	assert!(
+		get("/", &tls)
+			.await
+			.headers()
+			.contains_key("strict-transport-security")
+	);
+}
+
+/// Start a server with `settings` on a random local port, and get its API URL
+/// and context
+async fn start_server(settings: &Settings) -> (String, Arc<Ctx>) {
+	let ctx = Arc::new(Ctx::new(settings));
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let url = format!("ws://{}/api", listener.local_addr().unwrap());
+
+	let app = app(Arc::clone(&ctx));
+	tokio::spawn(async move { axum::serve(listener, app).await });
+
+	(url, ctx)
+}
+
+/// Get the next message from a websocket client, skipping pings
+async fn next_message<S>(ws: &mut WebSocketStream<S>) -> Option<TMessage>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+{
+	loop {
+		match ws.next().await? {
+			Ok(TMessage::Ping(_)) => (),
+			Ok(msg) => return Some(msg),
+			Err(_) => return None,
+		}
+	}
+}
+
+/// Get the close code and reason of a close message
+fn close_frame(msg: Option<TMessage>) -> (u16, String) {
+	match msg {
+		Some(TMessage::Close(Some(frame))) => (frame.code.into(), frame.reason.to_string()),
+		msg => panic!("expected a close message, got {msg:?}"),
+	}
+}
+
+#[tokio::test]
+async fn websocket_limits() {
+	let settings = Settings {
+		ping_interval: 1,
+		pong_timeout: 1,
+		idle_timeout: 0,
+		max_message_size: 256,
+		..Settings::default()
+	};
+	let (url, ctx) = start_server(&settings).await;
+
+	// messages within the limit are handled
+	let (mut ws, _) = connect_async(&url).await.unwrap();
+	assert!(
+		matches!(next_message(&mut ws).await, Some(TMessage::Text(msg)) if msg.contains("connected"))
+	);
+	ws.send(TMessage::text(r#"{"to":1,"msg":"ping_ack"}"#))
+		.await
+		.unwrap();
+	assert!(
+		matches!(next_message(&mut ws).await, Some(TMessage::Text(msg)) if msg.contains("no_such_id"))
+	);
+
+	// larger messages close the connection
+	ws.send(TMessage::text(format!(
+		r#"{{"to":1,"msg":"ping_ack","x":"{}"}}"#,
+		"x".repeat(256)
+	)))
+	.await
+	.unwrap();
+	assert_eq!(
+		close_frame(next_message(&mut ws).await),
+		(1009, "message too big".to_string())
+	);
+
+	// so do text messages which aren't valid UTF-8
+	let (mut ws, _) = connect_async(&url).await.unwrap();
+	assert!(matches!(
+		next_message(&mut ws).await,
+		Some(TMessage::Text(_))
+	));
+	ws.send(TMessage::Frame(Frame::message(
+		&b"{\"to\":1,\"msg\":\"\xff\"}"[..],
+		OpCode::Data(Data::Text),
+		true,
+	)))
+	.await
+	.unwrap();
+	assert_eq!(
+		close_frame(next_message(&mut ws).await),
+		(1007, "invalid UTF-8".to_string())
+	);
+
+	// clients which answer pings stay connected
+	let (mut alive, _) = connect_async(&url).await.unwrap();
+	let (mut dead, _) = connect_async(&url).await.unwrap();
+	assert_eq!(ctx.connections.read().unwrap().len(), 2);
+
+	let reading = time::timeout(Duration::from_millis(3500), next_message(&mut alive));
+	assert!(matches!(reading.await, Ok(Some(TMessage::Text(_)))));
+	let reading = time::timeout(Duration::from_millis(3500), next_message(&mut alive));
+	assert!(reading.await.is_err());
+
+	// clients which don't answer pings (because they aren't read here) are
+	// disconnected
+	assert!(matches!(
+		next_message(&mut dead).await,
+		Some(TMessage::Text(_))
+	));
+	assert_eq!(
+		close_frame(next_message(&mut dead).await),
+		(1001, "ping timeout".to_string())
+	);
+	assert_eq!(ctx.connections.read().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn idle_timeout() {
+	let settings = Settings {
+		ping_interval: 1,
+		idle_timeout: 2,
+		..Settings::default()
+	};
+	let (url, ctx) = start_server(&settings).await;
+	let (mut ws, _) = connect_async(&url).await.unwrap();
+	assert!(matches!(
+		next_message(&mut ws).await,
+		Some(TMessage::Text(_))
+	));
+
+	// messages reset the idle timeout, pongs don't
+	time::sleep(Duration::from_millis(1500)).await;
+	ws.send(TMessage::text(r#"{"to":1,"msg":"ping_ack"}"#))
+		.await
+		.unwrap();
+	assert!(
+		matches!(next_message(&mut ws).await, Some(TMessage::Text(msg)) if msg.contains("no_such_id"))
+	);
+
+	let start = Instant::now();
+	assert_eq!(
+		close_frame(next_message(&mut ws).await),
+		(1000, "idle timeout".to_string())
+	);
+	assert!(start.elapsed() > Duration::from_millis(1500));
+	assert!(ctx.connections.read().unwrap().is_empty());
+}
+
+/// Send a request to the admin API with the bearer `token` and JSON `body`,
+/// and get the response status and JSON body
+async fn admin_request(
+	ctx: &Arc<Ctx>,
+	method: &str,
+	path: &str,
+	token: Option<&str>,
+	body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+	let mut req = Request::builder().method(method).uri(path);
+
+	if let Some(token) = token {
+		req = req.header("authorization", format!("Bearer {token}"));
+	}
+
+	let req = match body {
+		Some(body) => req
+			.header("content-type", "application/json")
+			.body(Body::from(body.to_string())),
+		None => req.body(Body::empty()),
+	};
+
+	let res = admin::router(Arc::clone(ctx))
+		.oneshot(req.unwrap())
+		.await
+		.unwrap();
+	let status = res.status();
+	let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+		.await
+		.unwrap();
+
+	(
+		status,
+		serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null),
+	)
+}
+
+/// Connect a websocket client to `url`, and get it and its ID
+async fn connect(url: &str) -> (WebSocketStream<impl AsyncRead + AsyncWrite + Unpin>, u64) {
+	let (mut ws, _) = connect_async(url).await.unwrap();
+	let Some(TMessage::Text(msg)) = next_message(&mut ws).await else {
+		panic!("expected a connected message");
+	};
+	let id = serde_json::from_str::<serde_json::Value>(&msg).unwrap()["id"]
+		.as_u64()
+		.unwrap();
+
+	(ws, id)
+}
+
+#[tokio::test]
+async fn admin() {
+	const TOKEN: &str = "0123456789abcdef";
+
+	let settings = Settings {
+		admin: Some(AdminSettings {
+			bind: Vec::new(),
+			token: Some(TOKEN.to_string()),
+		}),
+		..Settings::default()
+	};
+	let (url, ctx) = start_server(&settings).await;
+
+	// requests need the token
+	for token in [None, Some("fedcba9876543210"), Some("0123")] {
+		let (status, _) = admin_request(&ctx, "GET", "/admin/connections", token, None).await;
+		assert_eq!(status, StatusCode::UNAUTHORIZED);
+	}
+
+	let (mut a, a_id) = connect(&url).await;
+	let (mut b, b_id) = connect(&url).await;
+
+	let (status, list) = admin_request(&ctx, "GET", "/admin/connections", Some(TOKEN), None).await;
+	assert_eq!(status, StatusCode::OK);
+	assert_eq!(list["count"], 2);
+	assert_eq!(list["accepting"], true);
+	assert_eq!(list["connections"][0]["id"], a_id);
+	assert_eq!(list["connections"][1]["id"], b_id);
+	assert!(list["connections"][0]["age"].is_u64());
+
+	// notices are sent to everyone
+	let notice = serde_json::json!({ "details": "maintenance soon" });
+	let (status, sent) =
+		admin_request(&ctx, "POST", "/admin/notice", Some(TOKEN), Some(notice)).await;
+	assert_eq!(status, StatusCode::OK);
+	assert_eq!(sent["delivered"], 2);
+
+	for ws in [&mut a, &mut b] {
+		assert!(matches!(
+			next_message(ws).await,
+			Some(TMessage::Text(msg)) if msg == r#"{"msg":"notice","details":"maintenance soon"}"#
+		));
+	}
+
+	// clients can be disconnected
+	let path = format!("/admin/connections/{a_id}");
+	let (status, _) = admin_request(&ctx, "DELETE", &path, Some(TOKEN), None).await;
+	assert_eq!(status, StatusCode::NO_CONTENT);
+	assert_eq!(
+		close_frame(next_message(&mut a).await),
+		(1008, "disconnected by the operator".to_string())
+	);
+
+	while ctx
+		.connections
+		.read()
+		.unwrap()
+		.contains_key(&Id(u16::try_from(a_id).unwrap()))
+	{
+		time::sleep(Duration::from_millis(10)).await;
+	}
+
+	let (status, _) = admin_request(&ctx, "DELETE", &path, Some(TOKEN), None).await;
+	assert_eq!(status, StatusCode::NOT_FOUND);
+
+	// new connections can be refused, without affecting existing ones
+	let paused = serde_json::json!({ "accepting": false });
+	let (status, _) =
+		admin_request(&ctx, "PUT", "/admin/accepting", Some(TOKEN), Some(paused)).await;
+	assert_eq!(status, StatusCode::OK);
+	assert!(connect_async(&url).await.is_err());
+
+	let (_, list) = admin_request(&ctx, "GET", "/admin/connections", Some(TOKEN), None).await;
+	assert_eq!(list["count"], 1);
+	assert_eq!(list["accepting"], false);
+
+	b.send(TMessage::text(r#"{"to":1,"msg":"ping_ack"}"#))
+		.await
+		.unwrap();
+	assert!(
+		matches!(next_message(&mut b).await, Some(TMessage::Text(msg)) if msg.contains("no_such_id"))
+	);
+
+	let resumed = serde_json::json!({ "accepting": true });
+	admin_request(&ctx, "PUT", "/admin/accepting", Some(TOKEN), Some(resumed)).await;
+	connect(&url).await;
+
+	// without a token (on separate addresses) no authorization is needed
+	let ctx = Arc::new(Ctx::default());
+	let (status, list) = admin_request(&ctx, "GET", "/admin/connections", None, None).await;
+	assert_eq!(status, StatusCode::OK);
+	assert_eq!(list["count"], 0);
+}
+
+/// A log output which can be read back
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for LogBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Send a message with the fields `msg` from one client to another with the ID
+/// `to_id`, and check that it's received
+async fn relay<S>(from: &mut WebSocketStream<S>, to: &mut WebSocketStream<S>, to_id: u64, msg: &str)
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+{
+	from.send(TMessage::text(format!(r#"{{"to":{to_id},{msg}}}"#)))
+		.await
+		.unwrap();
+
+	assert!(matches!(
+		next_message(to).await,
+		Some(TMessage::Text(received)) if received.ends_with(&format!("{msg}}}"))
+	));
+}
+
+#[tokio::test]
+async fn log_redaction() {
+	let settings = Settings {
+		log: "trace".to_string(),
+		log_format: LogFormat::Json,
+		..Settings::default()
+	};
+	let logs = LogBuffer::default();
+	let writer = logs.clone();
+	let _subscriber =
+		tracing::subscriber::set_default(logging::subscriber(&settings, move || writer.clone()));
+
+	let alices_secret = EphemeralSecret::random();
+	let bobs_secret = EphemeralSecret::random();
+	let alices_key =
+		serde_json::to_string(&crate::PublicKey(PublicKey::from(&alices_secret))).unwrap();
+	let bobs_key = serde_json::to_string(&crate::PublicKey(PublicKey::from(&bobs_secret))).unwrap();
+	let info = PingInfo::new(
+		Timestamp(0x0012_3456_7890),
+		Degrees(12.345_678),
+		Degrees(98.765_432),
+		Meters(5.6),
+		Meters(7.8),
+	)
+	.unwrap()
+	.encrypt(alices_secret.diffie_hellman(&PublicKey::from(&bobs_secret)))
+	.unwrap();
+	let info = serde_json::to_string(&info).unwrap();
+
+	let (url, _) = start_server(&settings).await;
+	let (mut alice, alice_id) = connect(&url).await;
+	let (mut bob, bob_id) = connect(&url).await;
+
+	relay(
+		&mut alice,
+		&mut bob,
+		bob_id,
+		&format!(r#""msg":"ping_request","key":{alices_key}"#),
+	)
+	.await;
+	relay(
+		&mut bob,
+		&mut alice,
+		alice_id,
+		&format!(r#""msg":"accept_ping","key":{bobs_key}"#),
+	)
+	.await;
+	relay(
+		&mut alice,
+		&mut bob,
+		bob_id,
+		&format!(r#""msg":"ping","info":{info}"#),
+	)
+	.await;
+
+	let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+	let lines = logs.lines().collect::<Vec<_>>();
+
+	// every line is JSON, and relayed messages are logged in connection spans
+	for line in &lines {
+		serde_json::from_str::<serde_json::Value>(line).unwrap();
+	}
+
+	let relayed = lines
+		.iter()
+		.filter(|line| line.contains("Relaying message"))
+		.collect::<Vec<_>>();
+	assert_eq!(relayed.len(), 3);
+	assert!(relayed[2].contains(r#""msg":"ping""#));
+	assert!(relayed[2].contains(&format!(r#""id":"{alice_id}""#)));
+	assert!(relayed[2].contains(r#""conn":""#));
+
+	// keys, Ping info, and IP addresses are never logged
+	for secret in [&alices_key, &bobs_key, &info] {
+		assert!(!logs.contains(secret.trim_matches('"')));
+	}
+
+	assert!(!logs.contains("127.0.0.1"), "{logs}");
+}
@@ -0,0 +1,510 @@
+//! End-to-end tests, which run the server on a local port and drive websocket
+//! clients through the scenarios described in the README
+
+use pinger::{
+	SharedKey,
+	pake::{PakeMessage, PakeSecret},
+};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{MaybeTlsStream, tungstenite::Error as WsError};
+
+use super::*;
+use crate::config::IdRange;
+
+/// How long to wait for a message before failing the test
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before assuming that no message will be received
+const QUIET: Duration = Duration::from_millis(100);
+
+/// A websocket client connected to the test server
+struct Client {
+	/// The client's connection
+	ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+	/// The client's ID
+	id: u64,
+}
+
+impl Client {
+	/// Connect to the server at `url`, and wait for the client's ID
+	async fn connect(url: &str) -> Self {
+		let (ws, _) = connect_async(url).await.unwrap();
+		let mut client = Self { ws, id: 0 };
+
+		let connected = client.receive().await;
+		assert_eq!(connected["msg"], "connected");
+		client.id = connected["id"].as_u64().unwrap();
+
+		client
+	}
+
+	/// Send the message with the fields in `msg` to the client with the ID
+	/// `to`
+	async fn send_to(&mut self, to: u64, mut msg: Value) {
+		msg["to"] = to.into();
+		self.ws.send(TMessage::text(msg.to_string())).await.unwrap();
+	}
+
+	/// Get the next message, which must be JSON text
+	async fn receive(&mut self) -> Value {
+		match time::timeout(TIMEOUT, next_message(&mut self.ws))
+			.await
+			.expect("timed out waiting for a message")
+		{
+			Some(TMessage::Text(text)) => serde_json::from_str(&text).unwrap(),
+			msg => panic!("expected a text message, got {msg:?}"),
+		}
+	}
+
+	/// Check that no message is received for a while
+	async fn assert_nothing_received(&mut self) {
+		if let Ok(msg) = time::timeout(QUIET, next_message(&mut self.ws)).await {
+			panic!("expected no message, got {msg:?}");
+		}
+	}
+
+	/// Close the connection, and wait for the server to forget the client
+	async fn disconnect(mut self, ctx: &Ctx) {
+		self.ws.close(None).await.unwrap();
+		let id = Id(u16::try_from(self.id).unwrap());
+
+		time::timeout(TIMEOUT, async {
+			while ctx.connections.read().unwrap().contains_key(&id) {
+				time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.expect("the connection wasn't removed");
+	}
+}
+
+/// Generate a key pair, with the public key as it's sent in messages
+fn key_pair() -> (EphemeralSecret, Value) {
+	let secret = EphemeralSecret::random();
+	let public = serde_json::to_value(crate::PublicKey(PublicKey::from(&secret))).unwrap();
+
+	(secret, public)
+}
+
+/// Finish a key agreement with the public key `public` from a message
+fn shared_key(secret: EphemeralSecret, public: &Value) -> SharedKey {
+	let public = serde_json::from_str::<crate::PublicKey>(&public.to_string()).unwrap();
+	secret.diffie_hellman(&public.0).into()
+}
+
+/// Get some valid Ping info
+fn ping_info() -> PingInfo {
+	PingInfo::new(
+		Timestamp(0x0012_3456_7890),
+		Degrees(12.345_678),
+		Degrees(98.765_432),
+		Meters(5.6),
+		Meters(7.8),
+	)
+	.unwrap()
+}
+
+/// Check that a Ping in `msg` was encrypted with `key` and contains
+/// [`ping_info`]
+fn assert_ping(msg: &Value, key: SharedKey) {
+	let info = serde_json::from_str(&msg["info"].to_string()).unwrap();
+	assert_eq!(PingInfo::decrypt(info, key).unwrap(), ping_info());
+}
+
+#[tokio::test]
+async fn ping_exchange() {
+	let (url, _) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+
+	// messages are received `from` the sender, without the `to` field
+	let (alices_secret, alices_key) = key_pair();
+	alice
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	assert_eq!(
+		bob.receive().await,
+		json!({ "from": alice.id, "msg": "ping_request", "key": alices_key })
+	);
+
+	let (bobs_secret, bobs_key) = key_pair();
+	bob.send_to(alice.id, json!({ "msg": "accept_ping", "key": bobs_key }))
+		.await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "from": bob.id, "msg": "accept_ping", "key": bobs_key })
+	);
+
+	let alices_shared = shared_key(alices_secret, &bobs_key);
+	let bobs_shared = shared_key(bobs_secret, &alices_key);
+
+	let info = ping_info().encrypt(alices_shared).unwrap();
+	alice
+		.send_to(bob.id, json!({ "msg": "ping", "info": info }))
+		.await;
+	let ping = bob.receive().await;
+	assert_eq!(ping["from"], alice.id);
+	assert_eq!(ping["msg"], "ping");
+	assert_ping(&ping, bobs_shared);
+
+	bob.send_to(alice.id, json!({ "msg": "ping_ack" })).await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "from": bob.id, "msg": "ping_ack" })
+	);
+
+	alice.assert_nothing_received().await;
+	bob.assert_nothing_received().await;
+}
+
+#[tokio::test]
+async fn pake_exchange() {
+	let (url, _) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+
+	// the server relays the `pake` flag, but can't tell the messages apart
+	// from public keys otherwise
+	let message = |secret: &PakeSecret| {
+		serde_json::to_value(crate::PublicKey(PublicKey::from(
+			secret.message().to_bytes(),
+		)))
+		.unwrap()
+	};
+	let finish = |secret: PakeSecret, theirs: &Value| {
+		let theirs = serde_json::from_str::<crate::PublicKey>(&theirs.to_string()).unwrap();
+		secret
+			.finish(&PakeMessage::from_bytes(theirs.0.to_bytes()))
+			.unwrap()
+	};
+
+	let alices_secret = PakeSecret::new("1234");
+	let alices_message = message(&alices_secret);
+	alice
+		.send_to(
+			bob.id,
+			json!({ "msg": "ping_request", "key": alices_message, "pake": true }),
+		)
+		.await;
+	assert_eq!(
+		bob.receive().await,
+		json!({ "from": alice.id, "msg": "ping_request", "key": alices_message, "pake": true })
+	);
+
+	let bobs_secret = PakeSecret::new("12 34");
+	let bobs_message = message(&bobs_secret);
+	bob.send_to(
+		alice.id,
+		json!({ "msg": "accept_ping", "key": bobs_message, "pake": true }),
+	)
+	.await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "from": bob.id, "msg": "accept_ping", "key": bobs_message, "pake": true })
+	);
+
+	let info = ping_info()
+		.encrypt(finish(alices_secret, &bobs_message))
+		.unwrap();
+	alice
+		.send_to(bob.id, json!({ "msg": "ping", "info": info }))
+		.await;
+	assert_ping(&bob.receive().await, finish(bobs_secret, &alices_message));
+
+	// `"pake": false` is the same as leaving it out
+	alice
+		.send_to(
+			bob.id,
+			json!({ "msg": "ping_request", "key": alices_message, "pake": false }),
+		)
+		.await;
+	assert_eq!(
+		bob.receive().await,
+		json!({ "from": alice.id, "msg": "ping_request", "key": alices_message })
+	);
+}
+
+#[tokio::test]
+async fn rejected_ping() {
+	let (url, _) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+
+	let (_, alices_key) = key_pair();
+	alice
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	bob.receive().await;
+
+	bob.send_to(alice.id, json!({ "msg": "reject_ping" })).await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "from": bob.id, "msg": "reject_ping" })
+	);
+}
+
+#[tokio::test]
+async fn simultaneous_requests() {
+	let (url, _) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+	let mut carol = Client::connect(&url).await;
+
+	// Alice and Carol both send a Ping to Bob at the same time
+	let (alices_secret, alices_key) = key_pair();
+	let (carols_secret, carols_key) = key_pair();
+	alice
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	carol
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": carols_key }))
+		.await;
+
+	let mut requests = HashMap::new();
+
+	for _ in 0..2 {
+		let request = bob.receive().await;
+		assert_eq!(request["msg"], "ping_request");
+		requests.insert(request["from"].as_u64().unwrap(), request["key"].clone());
+	}
+
+	assert_eq!(requests[&alice.id], alices_key);
+	assert_eq!(requests[&carol.id], carols_key);
+
+	// Bob handles both requests
+	let mut bobs_shared = HashMap::new();
+
+	for (id, key) in requests {
+		let (secret, public) = key_pair();
+		bob.send_to(id, json!({ "msg": "accept_ping", "key": public }))
+			.await;
+		bobs_shared.insert(id, shared_key(secret, &key));
+	}
+
+	for (client, secret) in [(&mut alice, alices_secret), (&mut carol, carols_secret)] {
+		let accept = client.receive().await;
+		assert_eq!(accept["from"], bob.id);
+		assert_eq!(accept["msg"], "accept_ping");
+
+		let info = ping_info()
+			.encrypt(shared_key(secret, &accept["key"]))
+			.unwrap();
+		client
+			.send_to(bob.id, json!({ "msg": "ping", "info": info }))
+			.await;
+	}
+
+	for _ in 0..2 {
+		let ping = bob.receive().await;
+		let from = ping["from"].as_u64().unwrap();
+		assert_ping(&ping, bobs_shared.remove(&from).unwrap());
+		bob.send_to(from, json!({ "msg": "ping_ack" })).await;
+	}
+
+	for client in [&mut alice, &mut carol] {
+		assert_eq!(
+			client.receive().await,
+			json!({ "from": bob.id, "msg": "ping_ack" })
+		);
+	}
+}
+
+#[tokio::test]
+async fn no_such_id() {
+	let (url, ctx) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+	let mut carol = Client::connect(&url).await;
+
+	// IDs outside of the range are never connected
+	let (_, alices_key) = key_pair();
+	alice
+		.send_to(1, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "msg": "no_such_id", "id": 1 })
+	);
+
+	// the requester disconnects before the request is accepted
+	carol
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	bob.receive().await;
+	let carols_id = carol.id;
+	carol.disconnect(&ctx).await;
+
+	let (bobs_secret, bobs_key) = key_pair();
+	bob.send_to(carols_id, json!({ "msg": "accept_ping", "key": bobs_key }))
+		.await;
+	assert_eq!(
+		bob.receive().await,
+		json!({ "msg": "no_such_id", "id": carols_id })
+	);
+
+	// the respondee disconnects after accepting, before the Ping is sent
+	alice
+		.send_to(bob.id, json!({ "msg": "ping_request", "key": alices_key }))
+		.await;
+	bob.receive().await;
+	bob.send_to(alice.id, json!({ "msg": "accept_ping", "key": bobs_key }))
+		.await;
+	alice.receive().await;
+	let bobs_id = bob.id;
+	bob.disconnect(&ctx).await;
+
+	let info = ping_info()
+		.encrypt(bobs_secret.diffie_hellman(&PublicKey::from(&EphemeralSecret::random())))
+		.unwrap();
+	alice
+		.send_to(bobs_id, json!({ "msg": "ping", "info": info }))
+		.await;
+	assert_eq!(
+		alice.receive().await,
+		json!({ "msg": "no_such_id", "id": bobs_id })
+	);
+
+	// the connection stays open
+	let mut dave = Client::connect(&url).await;
+	alice.send_to(dave.id, json!({ "msg": "ping_ack" })).await;
+	assert_eq!(
+		dave.receive().await,
+		json!({ "from": alice.id, "msg": "ping_ack" })
+	);
+}
+
+#[tokio::test]
+async fn invalid_messages() {
+	let (url, _) = start_server(&Settings::default()).await;
+	let mut alice = Client::connect(&url).await;
+	let mut bob = Client::connect(&url).await;
+
+	let invalid = [
+		"not json".to_string(),
+		r#"{"msg":"ping_ack"}"#.to_string(),
+		format!(r#"{{"to":{},"msg":"pong"}}"#, bob.id),
+		format!(r#"{{"to":{},"msg":"ping_request","key":"AAAA"}}"#, bob.id),
+		format!(r#"{{"to":{},"msg":"connected","id":1}}"#, bob.id),
+		r#"{"to":100000,"msg":"ping_ack"}"#.to_string(),
+	];
+
+	for msg in invalid {
+		alice.ws.send(TMessage::text(msg)).await.unwrap();
+		assert_eq!(
+			alice.receive().await,
+			json!({ "msg": "error", "details": "could not deserialize message" })
+		);
+	}
+
+	alice
+		.ws
+		.send(TMessage::binary(
+			br#"{"to":10,"msg":"ping_ack"}"#.as_slice(),
+		))
+		.await
+		.unwrap();
+	assert_eq!(
+		alice.receive().await,
+		json!({
+			"msg": "error",
+			"details": "unsupported message type, only text messages are supported"
+		})
+	);
+
+	// nothing is relayed, and the connection stays open
+	bob.assert_nothing_received().await;
+	alice.send_to(bob.id, json!({ "msg": "ping_ack" })).await;
+	assert_eq!(
+		bob.receive().await,
+		json!({ "from": alice.id, "msg": "ping_ack" })
+	);
+}
+
+#[tokio::test]
+async fn id_exhaustion() {
+	let settings = Settings {
+		id_range: IdRange(10..=12),
+		..Settings::default()
+	};
+	let (url, ctx) = start_server(&settings).await;
+
+	let mut clients = Vec::new();
+
+	for _ in 0..3 {
+		clients.push(Client::connect(&url).await);
+	}
+
+	let mut ids = clients.iter().map(|client| client.id).collect::<Vec<_>>();
+	ids.sort_unstable();
+	assert_eq!(ids, [10, 11, 12]);
+
+	// there are no IDs left for new connections
+	match connect_async(&url).await {
+		Err(WsError::Http(res)) => assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE),
+		res => panic!("expected an HTTP error, got {res:?}"),
+	}
+
+	// IDs of disconnected clients are reused
+	let client = clients.swap_remove(1);
+	let freed = client.id;
+	client.disconnect(&ctx).await;
+	assert_eq!(Client::connect(&url).await.id, freed);
+}
+
+#[test]
+fn gen_id() {
+	let mut connections = HashMap::new();
+
+	for i in 0..3 {
+		let Ok(id) = Ctx::gen_id(&connections, 10..=12) else {
+			panic!("no ID generated");
+		};
+		assert!((10..=12).contains(&id.0));
+		assert!(connections.insert(id, i).is_none());
+	}
+
+	let Err(res) = Ctx::gen_id(&connections, 10..=12) else {
+		panic!("ID generated for a full range");
+	};
+	assert_eq!(
+		res.into_response().status(),
+		StatusCode::SERVICE_UNAVAILABLE
+	);
+}
+
+#[tokio::test]
+async fn relay_timeout() {
+	let settings = Settings {
+		buffer_size: 1,
+		relay_timeout: 1,
+		..Settings::default()
+	};
+	let ctx = Ctx::new(&settings);
+
+	// a client that doesn't receive its messages
+	let (sender, mut receiver) = mpsc::channel(settings.buffer_size);
+	let Ok((id, _)) = ctx.add_connection(sender) else {
+		panic!("connection not added");
+	};
+
+	ctx.send(id, Id(1), ClientClientMessage::PingAck)
+		.await
+		.unwrap();
+	assert!(matches!(
+		ctx.send(id, Id(1), ClientClientMessage::PingAck).await,
+		Err(SendError::TimedOut(to)) if to == id
+	));
+
+	assert!(matches!(
+		receiver.recv().await,
+		Some(ClientDownMessage::FromClient {
+			from: Id(1),
+			msg: ClientClientMessage::PingAck
+		})
+	));
+	assert!(matches!(
+		ctx.send(Id(2), id, ClientClientMessage::PingAck).await,
+		Err(SendError::NoSuchId(Id(2)))
+	));
+}
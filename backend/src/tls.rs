@@ -0,0 +1,301 @@
+//! TLS termination, so that the server can be used without a reverse proxy
+//!
+//! The certificate chain and private key are read from PEM files, and are
+//! reloaded on `SIGHUP` or when the files change, without interrupting
+//! existing connections.
+
+use std::{
+	fs, io,
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	sync::{Arc, RwLock},
+	time::{Duration, SystemTime},
+};
+
+use axum::{
+	Router,
+	http::{StatusCode, Uri, header::HOST, request::Parts},
+	response::{IntoResponse, Redirect, Response},
+	serve::Listener,
+};
+use rustls::{
+	ServerConfig,
+	crypto::{CryptoProvider, ring},
+	pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+	server::{ClientHello, ResolvesServerCert},
+	sign::CertifiedKey,
+};
+use thiserror::Error;
+use tokio::{
+	net::{TcpListener, TcpStream},
+	sync::mpsc,
+	time,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+use tracing::{debug, error, info, warn};
+
+use crate::logging::Ip;
+
+/// How often the certificate and key files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The number of established TLS connections waiting to be served
+const BACKLOG: usize = 64;
+
+/// An error when loading the TLS certificate chain or private key
+#[derive(Debug, Error)]
+pub enum TlsError {
+	#[error("can't read {}: {source}", path.display())]
+	Read {
+		path: PathBuf,
+		#[source]
+		source: rustls::pki_types::pem::Error,
+	},
+	#[error("no certificates in {}", .0.display())]
+	NoCertificates(PathBuf),
+	#[error("invalid certificate or key: {0}")]
+	Invalid(#[from] rustls::Error),
+}
+
+/// The server's certificate chain and private key, which can be reloaded
+/// while the server is running
+#[derive(Debug)]
+pub struct Certificates {
+	/// The path of the PEM certificate chain file
+	cert_path: PathBuf,
+	/// The path of the PEM private key file
+	key_path: PathBuf,
+	/// The current certificate chain and key, and the modification times of
+	/// the files they were loaded from
+	current: RwLock<(Arc<CertifiedKey>, [Option<SystemTime>; 2])>,
+}
+
+impl Certificates {
+	/// Load the certificate chain from `cert_path` and the private key from
+	/// `key_path`
+	///
+	/// # Errors
+	/// Returns an error if either file can't be read, or if they don't contain
+	/// a valid certificate chain and matching private key
+	pub fn load(cert_path: PathBuf, key_path: PathBuf) -> Result<Self, TlsError> {
+		let modified = modified(&cert_path, &key_path);
+		let key = load_key(&cert_path, &key_path)?;
+
+		Ok(Self {
+			cert_path,
+			key_path,
+			current: RwLock::new((key, modified)),
+		})
+	}
+
+	/// Load the certificate chain and private key again, keeping the current
+	/// ones if they can't be loaded
+	///
+	/// # Errors
+	/// Returns an error if the new certificate chain or key can't be loaded
+	pub fn reload(&self) -> Result<(), TlsError> {
+		let modified = modified(&self.cert_path, &self.key_path);
+		let key = load_key(&self.cert_path, &self.key_path)?;
+		*self.current.write().expect("lock poisoned") = (key, modified);
+
+		Ok(())
+	}
+
+	/// Reload the certificate chain and key on `SIGHUP` or when their files
+	/// change, forever
+	pub async fn watch(self: Arc<Self>) {
+		#[cfg(unix)]
+		let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+			.expect("can't listen for SIGHUP");
+		let mut interval = time::interval(WATCH_INTERVAL);
+
+		loop {
+			#[cfg(unix)]
+			let hangup = hangup.recv();
+			#[cfg(not(unix))]
+			let hangup = std::future::pending::<Option<()>>();
+
+			tokio::select! {
+				_ = hangup => info!("Received SIGHUP, reloading TLS certificate"),
+				_ = interval.tick() => {
+					let loaded = self.current.read().expect("lock poisoned").1;
+
+					if modified(&self.cert_path, &self.key_path) == loaded {
+						continue;
+					}
+
+					info!("TLS certificate or key changed, reloading it");
+				}
+			}
+
+			match self.reload() {
+				Ok(()) => info!("Reloaded TLS certificate"),
+				Err(e) => error!("Error reloading TLS certificate, keeping the old one: {e}"),
+			}
+		}
+	}
+
+	/// Get a TLS server configuration using these certificates, which
+	/// supports HTTP/2 and HTTP/1.1
+	pub fn server_config(self: &Arc<Self>) -> Arc<ServerConfig> {
+		let mut config = ServerConfig::builder_with_provider(provider())
+			.with_safe_default_protocol_versions()
+			.expect("the default protocol versions are unsupported")
+			.with_no_client_auth()
+			.with_cert_resolver(Arc::clone(self) as _);
+
+		config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+		Arc::new(config)
+	}
+}
+
+impl ResolvesServerCert for Certificates {
+	fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+		Some(Arc::clone(&self.current.read().expect("lock poisoned").0))
+	}
+}
+
+/// Get the cryptography provider used for TLS
+fn provider() -> Arc<CryptoProvider> {
+	Arc::new(ring::default_provider())
+}
+
+/// Get the modification times of the certificate and key files, if known
+fn modified(cert_path: &Path, key_path: &Path) -> [Option<SystemTime>; 2] {
+	[cert_path, key_path].map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+/// Load a certificate chain and its private key from PEM files
+fn load_key(cert_path: &Path, key_path: &Path) -> Result<Arc<CertifiedKey>, TlsError> {
+	let certs = CertificateDer::pem_file_iter(cert_path)
+		.and_then(Iterator::collect::<Result<Vec<_>, _>>)
+		.map_err(|source| TlsError::Read {
+			path: cert_path.to_path_buf(),
+			source,
+		})?;
+
+	if certs.is_empty() {
+		return Err(TlsError::NoCertificates(cert_path.to_path_buf()));
+	}
+
+	let key = PrivateKeyDer::from_pem_file(key_path).map_err(|source| TlsError::Read {
+		path: key_path.to_path_buf(),
+		source,
+	})?;
+
+	Ok(Arc::new(CertifiedKey::from_der(certs, key, &provider())?))
+}
+
+/// A listener for TLS connections
+///
+/// TLS handshakes are done in the background, so that slow clients don't
+/// hold up others.
+#[derive(Debug)]
+pub struct TlsListener {
+	/// Connections which completed the TLS handshake
+	connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+	/// The local address of the listener
+	addr: SocketAddr,
+}
+
+impl TlsListener {
+	/// Accept TLS connections with `config` on `listener`, giving clients
+	/// `handshake_timeout` to complete the handshake
+	///
+	/// # Errors
+	/// Returns an error if the local address of `listener` can't be determined
+	pub fn new(
+		listener: TcpListener,
+		config: Arc<ServerConfig>,
+		handshake_timeout: Duration,
+	) -> io::Result<Self> {
+		let addr = listener.local_addr()?;
+		let acceptor = TlsAcceptor::from(config);
+		let (sender, connections) = mpsc::channel(BACKLOG);
+
+		tokio::spawn(async move {
+			// stop once the listener is dropped
+			while !sender.is_closed() {
+				let (stream, addr) = match listener.accept().await {
+					Ok(conn) => conn,
+					Err(e) if is_connection_error(&e) => continue,
+					Err(e) => {
+						// probably out of file descriptors, so wait a bit
+						error!("Error accepting connection: {e}");
+						time::sleep(Duration::from_secs(1)).await;
+						continue;
+					}
+				};
+
+				let acceptor = acceptor.clone();
+				let connections = sender.clone();
+
+				tokio::spawn(async move {
+					match time::timeout(handshake_timeout, acceptor.accept(stream)).await {
+						Ok(Ok(stream)) => {
+							let _ = connections.send((stream, addr)).await;
+						}
+						Ok(Err(e)) => debug!("TLS handshake with {} failed: {e}", Ip(addr)),
+						Err(_) => debug!("TLS handshake with {} timed out", Ip(addr)),
+					}
+				});
+			}
+		});
+
+		Ok(Self { connections, addr })
+	}
+}
+
+impl Listener for TlsListener {
+	type Addr = SocketAddr;
+	type Io = TlsStream<TcpStream>;
+
+	async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+		self.connections
+			.recv()
+			.await
+			.expect("the TLS accept task stopped")
+	}
+
+	fn local_addr(&self) -> io::Result<Self::Addr> {
+		Ok(self.addr)
+	}
+}
+
+/// Check if `e` only affects a single connection, rather than the listener
+fn is_connection_error(e: &io::Error) -> bool {
+	matches!(
+		e.kind(),
+		io::ErrorKind::ConnectionRefused
+			| io::ErrorKind::ConnectionAborted
+			| io::ErrorKind::ConnectionReset
+	)
+}
+
+/// Get a router which redirects all HTTP requests to HTTPS on `https_port`
+pub fn redirect(https_port: u16) -> Router {
+	Router::new().fallback(move |parts: Parts| async move { redirect_to_https(&parts, https_port) })
+}
+
+/// Redirect the request in `parts` to HTTPS on `https_port`
+fn redirect_to_https(parts: &Parts, https_port: u16) -> Response {
+	let Some(host) = parts
+		.headers
+		.get(HOST)
+		.and_then(|host| host.to_str().ok())
+		.and_then(|host| host.parse::<Uri>().ok())
+		.and_then(|uri| uri.host().map(str::to_string))
+	else {
+		warn!("Not redirecting HTTP request without a valid host");
+		return StatusCode::BAD_REQUEST.into_response();
+	};
+
+	let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+
+	if https_port == 443 {
+		Redirect::permanent(&format!("https://{host}{path}")).into_response()
+	} else {
+		Redirect::permanent(&format!("https://{host}:{https_port}{path}")).into_response()
+	}
+}
@@ -0,0 +1,559 @@
+//! The configuration file, its profiles, and the effective settings
+//!
+//! The configuration file is `$XDG_CONFIG_HOME/pinger/config.toml` (or
+//! `~/.config/pinger/config.toml`), unless another one is given with
+//! `--config` or `PINGER_CONFIG`.
+//! It contains named profiles, one of which is used (selected with `--profile`
+//! or `PINGER_PROFILE`, the file's `default-profile`, or `default` if it
+//! exists), e.g.
+//!
+//! ```toml
+//! default-profile = "field"
+//!
+//! [profiles.field]
+//! server = "wss://pinger.example.com/api"
+//! source = "gpsd"
+//! format = "mgrs"
+//! position-error = 10
+//! precision = "grid:1000,altitude:omit"
+//! accept = "always"
+//!
+//! [profiles.field.tls]
+//! ca = "/etc/pinger/ca.pem"
+//! ```
+//!
+//! Settings given as command-line flags or environment variables take
+//! precedence over the profile, which takes precedence over the defaults.
+
+use std::{
+	collections::BTreeMap,
+	env,
+	fmt::{Display, Formatter, Result as FmtResult},
+	fs,
+	io::ErrorKind,
+	path::PathBuf,
+	str::FromStr,
+	sync::Arc,
+};
+
+use clap::{ArgMatches, parser::ValueSource};
+use colored::Colorize;
+use pinger::{
+	coords::CoordinateFormat, export::ExportFormat, geo::Distance, privacy::Precision,
+	replay::Freshness,
+};
+use rustls::{
+	ClientConfig, RootCertStore,
+	pki_types::{CertificateDer, pem::PemObject},
+};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use tokio_tungstenite::{Connector, tungstenite::http::Uri};
+
+use crate::{Args, MyLocation, format_coordinates, location::LocationSource, policy::AcceptPolicy};
+
+/// The default websocket URI of the Pinger API
+pub const DEFAULT_URL: &str = "wss://pinger.janm.dev/api";
+
+/// The default time to wait for the recipient of a Ping to respond, in seconds
+pub const DEFAULT_TIMEOUT: u64 = 120;
+
+/// The name of the profile used if no other is selected
+const DEFAULT_PROFILE: &str = "default";
+
+/// The contents of a configuration file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+	/// The profile used if none is selected on the command line
+	default_profile: Option<String>,
+	/// The profiles by name
+	profiles: BTreeMap<String, Profile>,
+}
+
+/// A named set of settings
+///
+/// The settings have the same names and formats as the corresponding
+/// command-line flags.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Profile {
+	server: Option<String>,
+	tls: Tls,
+	#[serde(deserialize_with = "parse")]
+	location: Option<MyLocation>,
+	#[serde(deserialize_with = "parse")]
+	source: Option<LocationSource>,
+	#[serde(deserialize_with = "parse")]
+	format: Option<CoordinateFormat>,
+	altitude: Option<f32>,
+	position_error: Option<f32>,
+	#[serde(deserialize_with = "parse")]
+	precision: Option<Precision>,
+	timeout: Option<u64>,
+	max_age: Option<u64>,
+	max_skew: Option<u64>,
+	export: Option<PathBuf>,
+	#[serde(deserialize_with = "parse")]
+	export_format: Option<ExportFormat>,
+	history: Option<PathBuf>,
+	history_max_age: Option<u64>,
+	history_max_entries: Option<usize>,
+	tui: Option<bool>,
+	#[serde(deserialize_with = "parse")]
+	accept: Option<AcceptPolicy>,
+}
+
+/// The TLS settings of a profile
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Tls {
+	/// A PEM file with additional trusted root certificates
+	ca: Option<PathBuf>,
+	/// Whether to trust the operating system's root certificates
+	native_roots: Option<bool>,
+}
+
+/// Deserialize an optional value from a string, using its `FromStr`
+/// implementation
+fn parse<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: FromStr,
+	T::Err: Display,
+{
+	Option::<String>::deserialize(de)?
+		.map(|s| s.parse().map_err(D::Error::custom))
+		.transpose()
+}
+
+/// Where the value of a setting comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+	/// A command-line flag or argument
+	Flag,
+	/// An environment variable
+	Env,
+	/// The selected profile
+	Profile,
+	/// The default value
+	Default,
+}
+
+impl Display for Origin {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::Flag => "command line",
+			Self::Env => "environment",
+			Self::Profile => "profile",
+			Self::Default => "default",
+		})
+	}
+}
+
+/// The effective value of a setting, and where it comes from
+#[derive(Clone, Debug)]
+pub struct Setting<T> {
+	/// The value of the setting
+	pub value: T,
+	/// Where the value comes from
+	pub origin: Origin,
+}
+
+impl<T> Setting<Option<T>> {
+	/// Use `default` if the setting has no value
+	fn or(self, default: T) -> Setting<T> {
+		match self.value {
+			Some(value) => Setting {
+				value,
+				origin: self.origin,
+			},
+			None => Setting {
+				value: default,
+				origin: Origin::Default,
+			},
+		}
+	}
+}
+
+/// The effective settings, combined from the command line, environment, and
+/// configuration file
+#[derive(Debug)]
+pub struct Settings {
+	/// The configuration file, if it exists
+	pub file: Option<PathBuf>,
+	/// The name of the selected profile, if any
+	pub profile: Option<String>,
+	/// The websocket URI of the Pinger API
+	pub server: Setting<String>,
+	/// A PEM file with additional trusted root certificates
+	pub tls_ca: Setting<Option<PathBuf>>,
+	/// Whether to trust the operating system's root certificates
+	pub native_roots: Setting<bool>,
+	/// The user's own location
+	pub location: Setting<Option<MyLocation>>,
+	/// Where to get the user's position from when sending Pings
+	pub source: Setting<Option<LocationSource>>,
+	/// The coordinate format for received Pings
+	pub format: Setting<CoordinateFormat>,
+	/// The altitude suggested when entering a position manually
+	pub altitude: Setting<Option<f32>>,
+	/// The position error suggested when entering a position manually
+	pub position_error: Setting<Option<f32>>,
+	/// The precision of the position in sent Pings
+	pub precision: Setting<Precision>,
+	/// The time to wait for the recipient of a Ping to respond, in seconds
+	pub timeout: Setting<u64>,
+	/// The maximum age of received Pings, in seconds
+	pub max_age: Setting<u64>,
+	/// The maximum amount of seconds by which received Pings may be in the
+	/// future
+	pub max_skew: Setting<u64>,
+	/// The file to which received Pings are appended
+	pub export: Setting<Option<PathBuf>>,
+	/// The format of the export file
+	pub export_format: Setting<Option<ExportFormat>>,
+	/// The history file
+	pub history: Setting<Option<PathBuf>>,
+	/// The maximum age of history entries, in days
+	pub history_max_age: Setting<Option<u64>>,
+	/// The maximum number of history entries
+	pub history_max_entries: Setting<Option<usize>>,
+	/// Whether to use the full-screen terminal interface
+	pub tui: Setting<bool>,
+	/// How incoming Ping requests are decided on
+	pub accept: Setting<AcceptPolicy>,
+}
+
+impl Settings {
+	/// Load the configuration file and combine it with the command-line
+	/// arguments (and environment variables) in `args`, which were parsed
+	/// from `matches`
+	///
+	/// # Errors
+	/// Returns an error if the configuration file can't be read or is
+	/// invalid, or if the selected profile doesn't exist
+	pub fn load(args: Args, matches: &ArgMatches) -> Result<Self, String> {
+		let (file, config) = match args.config.clone().or_else(default_path) {
+			Some(path) => match fs::read_to_string(&path) {
+				Ok(toml) => (
+					Some(path.clone()),
+					toml::from_str::<ConfigFile>(&toml)
+						.map_err(|e| format!("invalid configuration in {}: {e}", path.display()))?,
+				),
+				// only a missing default configuration file is ignored
+				Err(e) if e.kind() == ErrorKind::NotFound && args.config.is_none() => {
+					(None, ConfigFile::default())
+				}
+				Err(e) => return Err(format!("can't read {}: {e}", path.display())),
+			},
+			None => (None, ConfigFile::default()),
+		};
+
+		let mut profiles = config.profiles;
+		let (profile_name, profile) = match args.profile.clone().or(config.default_profile) {
+			Some(name) => match profiles.remove(&name) {
+				Some(profile) => (Some(name), profile),
+				None => {
+					return Err(format!(
+						"no profile named `{name}` in {}",
+						file.as_deref().map_or_else(
+							|| "the configuration".to_string(),
+							|file| file.display().to_string()
+						)
+					));
+				}
+			},
+			None => profiles.remove(DEFAULT_PROFILE).map_or_else(
+				|| (None, Profile::default()),
+				|profile| (Some(DEFAULT_PROFILE.to_string()), profile),
+			),
+		};
+
+		Ok(Self {
+			file,
+			profile: profile_name,
+			server: pick(matches, "server", args.server, profile.server)
+				.or(DEFAULT_URL.to_string()),
+			tls_ca: pick(matches, "tls_ca", args.tls_ca, profile.tls.ca),
+			native_roots: pick(
+				matches,
+				"no_native_roots",
+				args.no_native_roots.then_some(false),
+				profile.tls.native_roots,
+			)
+			.or(true),
+			location: pick(matches, "location", args.location, profile.location),
+			source: pick(matches, "source", args.source, profile.source),
+			format: pick(matches, "format", args.format, profile.format).or_default(),
+			altitude: pick(matches, "altitude", args.altitude, profile.altitude),
+			position_error: pick(
+				matches,
+				"position_error",
+				args.position_error,
+				profile.position_error,
+			),
+			precision: pick(matches, "precision", args.precision, profile.precision).or_default(),
+			timeout: pick(matches, "timeout", args.timeout, profile.timeout).or(DEFAULT_TIMEOUT),
+			max_age: pick(matches, "max_age", args.max_age, profile.max_age)
+				.or(Freshness::DEFAULT.max_age.as_secs()),
+			max_skew: pick(matches, "max_skew", args.max_skew, profile.max_skew)
+				.or(Freshness::DEFAULT.max_future_skew.as_secs()),
+			export: pick(matches, "export", args.export, profile.export),
+			export_format: pick(
+				matches,
+				"export_format",
+				args.export_format,
+				profile.export_format,
+			),
+			history: pick(matches, "history", args.history, profile.history),
+			history_max_age: pick(
+				matches,
+				"history_max_age",
+				args.history_max_age,
+				profile.history_max_age,
+			),
+			history_max_entries: pick(
+				matches,
+				"history_max_entries",
+				args.history_max_entries,
+				profile.history_max_entries,
+			),
+			tui: pick(matches, "tui", args.tui.then_some(true), profile.tui).or(false),
+			accept: pick(matches, "accept", args.accept, profile.accept).or_default(),
+		})
+	}
+
+	/// Check the settings which can't be checked while parsing them
+	///
+	/// # Errors
+	/// Returns a description of each invalid setting
+	pub fn validate(&self) -> Result<(), Vec<String>> {
+		let mut errors = Vec::new();
+
+		match self.server.value.parse::<Uri>() {
+			Ok(uri) if matches!(uri.scheme_str(), Some("ws" | "wss")) => (),
+			Ok(_) => errors.push("server: the URI must start with ws:// or wss://".to_string()),
+			Err(e) => errors.push(format!("server: {e}")),
+		}
+
+		if let Err(e) = self.tls_connector() {
+			errors.push(format!("tls: {e}"));
+		}
+
+		if self.altitude.value.is_some_and(|alt| !alt.is_finite()) {
+			errors.push("altitude: must be finite".to_string());
+		}
+
+		if self
+			.position_error
+			.value
+			.is_some_and(|err| !err.is_finite() || err.is_sign_negative())
+		{
+			errors.push("position-error: must be finite and positive".to_string());
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors)
+		}
+	}
+
+	/// Get the TLS connector for the server connection
+	///
+	/// # Errors
+	/// Returns an error if the additional root certificates can't be loaded,
+	/// or if no root certificates are trusted
+	pub fn tls_connector(&self) -> Result<Connector, String> {
+		let mut roots = RootCertStore::empty();
+
+		if self.native_roots.value {
+			roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+		}
+
+		if let Some(ca) = &self.tls_ca.value {
+			let certs = CertificateDer::pem_file_iter(ca)
+				.and_then(Iterator::collect::<Result<Vec<_>, _>>)
+				.map_err(|e| format!("can't load certificates from {}: {e}", ca.display()))?;
+
+			if certs.is_empty() {
+				return Err(format!("no certificates in {}", ca.display()));
+			}
+
+			for cert in certs {
+				roots
+					.add(cert)
+					.map_err(|e| format!("invalid certificate in {}: {e}", ca.display()))?;
+			}
+		}
+
+		if roots.is_empty() {
+			return Err("no trusted root certificates".to_string());
+		}
+
+		let config =
+			ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+				.with_safe_default_protocol_versions()
+				.map_err(|e| e.to_string())?
+				.with_root_certificates(roots)
+				.with_no_client_auth();
+
+		Ok(Connector::Rustls(Arc::new(config)))
+	}
+
+	/// Print the settings and where they come from
+	#[expect(clippy::too_many_lines, reason = "there is one line per setting")]
+	pub fn print(&self) {
+		let none = || "none".to_string();
+		let path = |path: &Option<PathBuf>| {
+			path.as_ref()
+				.map_or_else(none, |path| path.display().to_string())
+		};
+		let value = |value: Option<&dyn Display>| value.map_or_else(none, ToString::to_string);
+
+		println!("{} {}", "Configuration file:".bold(), path(&self.file));
+		println!(
+			"{} {}",
+			"Profile:".bold(),
+			self.profile.clone().unwrap_or_else(none)
+		);
+
+		let line = |name: &str, value: String, origin: Origin| {
+			println!(
+				"  {:<20} {value} {}",
+				format!("{name}:"),
+				format!("({origin})").dimmed()
+			);
+		};
+
+		line("server", self.server.value.clone(), self.server.origin);
+		line("tls.ca", path(&self.tls_ca.value), self.tls_ca.origin);
+		line(
+			"tls.native-roots",
+			self.native_roots.value.to_string(),
+			self.native_roots.origin,
+		);
+		line(
+			"location",
+			self.location.value.map_or_else(none, |MyLocation(info)| {
+				format!(
+					"{} (±{})",
+					format_coordinates(info.coordinates(), CoordinateFormat::Decimal),
+					Distance(info.err())
+				)
+			}),
+			self.location.origin,
+		);
+		line(
+			"source",
+			value(self.source.value.as_ref().map(|v| v as _)),
+			self.source.origin,
+		);
+		line(
+			"format",
+			self.format.value.id().to_string(),
+			self.format.origin,
+		);
+		line(
+			"altitude",
+			self.altitude
+				.value
+				.map_or_else(none, |alt| format!("{alt} mAMSL")),
+			self.altitude.origin,
+		);
+		line(
+			"position-error",
+			self.position_error
+				.value
+				.map_or_else(none, |err| format!("{err} m")),
+			self.position_error.origin,
+		);
+		line(
+			"precision",
+			self.precision.value.to_string(),
+			self.precision.origin,
+		);
+		line(
+			"timeout",
+			format!("{} s", self.timeout.value),
+			self.timeout.origin,
+		);
+		line(
+			"max-age",
+			format!("{} s", self.max_age.value),
+			self.max_age.origin,
+		);
+		line(
+			"max-skew",
+			format!("{} s", self.max_skew.value),
+			self.max_skew.origin,
+		);
+		line("export", path(&self.export.value), self.export.origin);
+		line(
+			"export-format",
+			self.export_format.value.map_or_else(
+				|| "from extension".to_string(),
+				|format| format.id().to_string(),
+			),
+			self.export_format.origin,
+		);
+		line("history", path(&self.history.value), self.history.origin);
+		line(
+			"history-max-age",
+			self.history_max_age
+				.value
+				.map_or_else(none, |days| format!("{days} day(s)")),
+			self.history_max_age.origin,
+		);
+		line(
+			"history-max-entries",
+			value(self.history_max_entries.value.as_ref().map(|v| v as _)),
+			self.history_max_entries.origin,
+		);
+		line("tui", self.tui.value.to_string(), self.tui.origin);
+		line("accept", self.accept.value.to_string(), self.accept.origin);
+	}
+}
+
+impl<T: Default> Setting<Option<T>> {
+	/// Use the default value of `T` if the setting has no value
+	fn or_default(self) -> Setting<T> {
+		self.or(T::default())
+	}
+}
+
+/// Get the value of a setting from the argument with the given `id` or the
+/// profile
+fn pick<T>(
+	matches: &ArgMatches,
+	id: &str,
+	arg: Option<T>,
+	profile: Option<T>,
+) -> Setting<Option<T>> {
+	match (arg, profile) {
+		(Some(value), _) => Setting {
+			value: Some(value),
+			origin: match matches.value_source(id) {
+				Some(ValueSource::EnvVariable) => Origin::Env,
+				_ => Origin::Flag,
+			},
+		},
+		(None, Some(value)) => Setting {
+			value: Some(value),
+			origin: Origin::Profile,
+		},
+		(None, None) => Setting {
+			value: None,
+			origin: Origin::Default,
+		},
+	}
+}
+
+/// Get the path of the default configuration file, in the XDG config directory
+fn default_path() -> Option<PathBuf> {
+	env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.filter(|dir| dir.is_absolute())
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+		.map(|dir| dir.join("pinger").join("config.toml"))
+}
@@ -0,0 +1,66 @@
+//! Appending received Pings to export files
+
+use std::{
+	fs::OpenOptions,
+	io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write},
+	path::Path,
+};
+
+use pinger::export::{ExportFormat, ReceivedPing};
+
+/// Append the Ping to the export file at `path`, creating it if it doesn't
+/// exist
+///
+/// The file is a complete document after every Ping, because the Ping is
+/// written over the footer of the existing document.
+///
+/// # Errors
+/// Returns an error if the file can't be read or written, or if it isn't an
+/// export in the given format
+pub fn append(path: &Path, format: ExportFormat, ping: &ReceivedPing<'_>) -> Result<()> {
+	let mut file = OpenOptions::new()
+		.read(true)
+		.write(true)
+		.create(true)
+		.truncate(false)
+		.open(path)?;
+	let len = file.metadata()?.len();
+
+	let header = format.header();
+	let footer = format.footer();
+	let mut out = String::new();
+
+	let first = if len == 0 {
+		out.push_str(header);
+		true
+	} else {
+		let not_export = || {
+			Error::new(
+				ErrorKind::InvalidData,
+				format!("{} is not a {format} export", path.display()),
+			)
+		};
+
+		let footer_start = len
+			.checked_sub(footer.len() as u64)
+			.ok_or_else(not_export)?;
+		let mut end = vec![0; footer.len()];
+		file.seek(SeekFrom::Start(footer_start))?;
+		file.read_exact(&mut end)?;
+
+		if end != footer.as_bytes() {
+			return Err(not_export());
+		}
+
+		file.seek(SeekFrom::Start(footer_start))?;
+		footer_start == header.len() as u64
+	};
+
+	format
+		.write_ping(&mut out, ping, first)
+		.map_err(Error::other)?;
+	out.push_str(footer);
+
+	file.write_all(out.as_bytes())?;
+	file.flush()
+}
@@ -0,0 +1,569 @@
+//! An encrypted local history of Ping exchanges
+//!
+//! The history file starts with a header containing a random salt, from which
+//! the key is derived from the user's passphrase with Argon2id.
+//! The header is followed by a check record, used to detect wrong passphrases,
+//! and the history entries.
+//! Each record is encrypted separately with XChaCha20-Poly1305, so that new
+//! entries can be appended without rewriting the file, with the header and the
+//! record's index as associated data, so that records can't be reordered or
+//! removed (except from the end of the file) without it being detected.
+
+use std::{
+	fmt::{Debug, Formatter, Result as FmtResult},
+	fs::{self, File, OpenOptions},
+	io::{Read, Write},
+	path::{Path, PathBuf},
+	process::ExitCode,
+	time::Duration,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+	AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+	aead::{Aead, OsRng, Payload, rand_core::RngCore},
+};
+use clap::Subcommand;
+use colored::{ColoredString, Colorize};
+use derive_more::Display;
+use inquire::Confirm;
+use pinger::{PingInfo, Timestamp, coords::CoordinateFormat, geo::Distance};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use zeroize::Zeroizing;
+
+use crate::{Id, format_coordinates};
+
+/// The environment variable from which the history passphrase is read, if set
+pub const PASSPHRASE_VAR: &str = "PINGER_HISTORY_PASSPHRASE";
+
+/// The start of every history file
+const MAGIC: &[u8; 8] = b"PNGRHIST";
+
+/// The version of the history file format
+const VERSION: u8 = 1;
+
+/// The length of the key derivation salt in bytes
+const SALT_LEN: usize = 16;
+
+/// The length of the history file header in bytes
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+
+/// The length of an XChaCha20-Poly1305 nonce in bytes
+const NONCE_LEN: usize = 24;
+
+/// The maximum length of a single record in bytes, to avoid huge allocations
+/// for corrupted files
+const MAX_RECORD_LEN: usize = 1 << 16;
+
+/// The number of seconds in a day
+const DAY: u64 = 24 * 60 * 60;
+
+/// Whether a Ping was sent or received
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+	/// The user sent the Ping
+	#[display("sent to")]
+	Sent,
+	/// The user received the Ping
+	#[display("received from")]
+	Received,
+}
+
+/// How a Ping exchange ended
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+	/// The Ping info was delivered
+	#[display("delivered")]
+	Delivered,
+	/// The Ping was rejected by its recipient
+	#[display("rejected")]
+	Rejected,
+	/// The other user didn't respond in time
+	#[display("timed out")]
+	TimedOut,
+	/// The Ping couldn't be delivered (e.g. because the ID was not found, or
+	/// the received Ping info was invalid)
+	#[display("failed")]
+	Failed,
+}
+
+impl Outcome {
+	/// Get the outcome as a colored string
+	fn colored(self) -> ColoredString {
+		let s = self.to_string();
+
+		match self {
+			Self::Delivered => s.green(),
+			Self::Rejected | Self::Failed => s.red(),
+			Self::TimedOut => s.yellow(),
+		}
+	}
+}
+
+/// A past Ping exchange
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+	/// When the exchange ended
+	pub ts: Timestamp,
+	/// Whether the Ping was sent or received
+	pub direction: Direction,
+	/// The ID of the other user
+	pub peer: Id,
+	/// How the exchange ended
+	pub outcome: Outcome,
+	/// The decrypted Ping info, for received Pings
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub info: Option<PingInfo>,
+}
+
+/// An error reading or writing the history
+#[derive(Debug, Display)]
+pub enum HistoryError {
+	/// Reading or writing the file failed
+	#[display("IO error: {_0}")]
+	Io(String),
+	/// The file is not a history file, or a newer version of it
+	#[display("not a history file, or an unsupported version")]
+	NotHistory,
+	/// The passphrase is wrong
+	#[display("wrong passphrase")]
+	WrongPassphrase,
+	/// The file is damaged
+	#[display("the history is damaged ({_0})")]
+	Corrupted(&'static str),
+}
+
+impl From<std::io::Error> for HistoryError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e.to_string())
+	}
+}
+
+/// Limits on how long and how many history entries are kept
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Retention {
+	/// The maximum age of entries
+	pub max_age: Option<Duration>,
+	/// The maximum number of entries, after which the oldest are deleted
+	pub max_entries: Option<usize>,
+}
+
+/// An open history file
+pub struct History {
+	/// The path of the history file
+	path: PathBuf,
+	/// The file's header, used as part of the associated data of all records
+	header: [u8; HEADER_LEN],
+	/// The cipher with the key derived from the passphrase
+	cipher: XChaCha20Poly1305,
+}
+
+impl Debug for History {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.debug_struct("History")
+			.field("path", &self.path)
+			.finish_non_exhaustive()
+	}
+}
+
+impl History {
+	/// Check whether a history file exists at `path`, i.e. whether opening it
+	/// would not create a new one
+	pub fn exists(path: &Path) -> bool {
+		fs::metadata(path).is_ok_and(|meta| meta.len() > 0)
+	}
+
+	/// Open the history file at `path`, creating it if it doesn't exist
+	///
+	/// # Errors
+	/// Returns an error if the file can't be read or created, if it isn't a
+	/// history file, or if the passphrase is wrong
+	pub fn open(path: &Path, passphrase: &str) -> Result<Self, HistoryError> {
+		if !Self::exists(path) {
+			let mut header = [0; HEADER_LEN];
+			header[..MAGIC.len()].copy_from_slice(MAGIC);
+			header[MAGIC.len()] = VERSION;
+			OsRng.fill_bytes(&mut header[MAGIC.len() + 1..]);
+
+			let history = Self::with_header(path, header, passphrase)?;
+			history.write(&[])?;
+
+			return Ok(history);
+		}
+
+		let data = fs::read(path)?;
+		let header = data
+			.get(..HEADER_LEN)
+			.and_then(|header| <[u8; HEADER_LEN]>::try_from(header).ok())
+			.filter(|header| header.starts_with(MAGIC) && header[MAGIC.len()] == VERSION)
+			.ok_or(HistoryError::NotHistory)?;
+
+		let history = Self::with_header(path, header, passphrase)?;
+
+		let check = history.records(&data).next();
+
+		match check {
+			Some(Ok(check)) if check.is_empty() => Ok(history),
+			Some(Err(HistoryError::Corrupted("authentication failed"))) => {
+				Err(HistoryError::WrongPassphrase)
+			}
+			Some(Err(e)) => Err(e),
+			_ => Err(HistoryError::Corrupted("missing check record")),
+		}
+	}
+
+	/// Derive the key for the file with the given header
+	fn with_header(
+		path: &Path,
+		header: [u8; HEADER_LEN],
+		passphrase: &str,
+	) -> Result<Self, HistoryError> {
+		let mut key = Zeroizing::new([0; 32]);
+		Argon2::default()
+			.hash_password_into(
+				passphrase.as_bytes(),
+				&header[MAGIC.len() + 1..],
+				key.as_mut_slice(),
+			)
+			.map_err(|_| HistoryError::Corrupted("invalid key derivation parameters"))?;
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			header,
+			cipher: XChaCha20Poly1305::new(key.as_slice().into()),
+		})
+	}
+
+	/// Get the associated data of the record with the given index (the check
+	/// record is `0`)
+	fn aad(&self, index: u64) -> [u8; HEADER_LEN + 8] {
+		let mut aad = [0; HEADER_LEN + 8];
+		aad[..HEADER_LEN].copy_from_slice(&self.header);
+		aad[HEADER_LEN..].copy_from_slice(&index.to_le_bytes());
+		aad
+	}
+
+	/// Encrypt the record with the given index, returning its length, nonce,
+	/// and ciphertext
+	fn seal(&self, index: u64, msg: &[u8]) -> Result<Vec<u8>, HistoryError> {
+		let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+		let ciphertext = self
+			.cipher
+			.encrypt(&nonce, Payload {
+				msg,
+				aad: &self.aad(index),
+			})
+			.map_err(|_| HistoryError::Corrupted("entry too large"))?;
+
+		let len = u32::try_from(NONCE_LEN + ciphertext.len())
+			.map_err(|_| HistoryError::Corrupted("entry too large"))?;
+		let mut record = len.to_le_bytes().to_vec();
+		record.extend_from_slice(&nonce);
+		record.extend_from_slice(&ciphertext);
+
+		Ok(record)
+	}
+
+	/// Decrypt the records in the file contents `data`, starting with the
+	/// check record
+	fn records<'a>(
+		&'a self,
+		data: &'a [u8],
+	) -> impl Iterator<Item = Result<Vec<u8>, HistoryError>> + 'a {
+		split_records(data).zip(0..).map(|(record, index)| {
+			let (nonce, ciphertext) = record?;
+
+			self.cipher
+				.decrypt(XNonce::from_slice(nonce), Payload {
+					msg: ciphertext,
+					aad: &self.aad(index),
+				})
+				.map_err(|_| HistoryError::Corrupted("authentication failed"))
+		})
+	}
+
+	/// Read all entries, oldest first
+	///
+	/// # Errors
+	/// Returns an error if the file can't be read or is damaged
+	pub fn entries(&self) -> Result<Vec<Entry>, HistoryError> {
+		let data = fs::read(&self.path)?;
+
+		self.records(&data)
+			.skip(1)
+			.map(|record| {
+				serde_json::from_slice(&record?)
+					.map_err(|_| HistoryError::Corrupted("invalid entry"))
+			})
+			.collect()
+	}
+
+	/// Add an entry to the end of the history
+	///
+	/// # Errors
+	/// Returns an error if the file can't be written
+	pub fn append(&self, entry: &Entry) -> Result<(), HistoryError> {
+		let json = serde_json::to_vec(entry).map_err(|e| HistoryError::Io(e.to_string()))?;
+
+		let mut file = OpenOptions::new()
+			.read(true)
+			.append(true)
+			.open(&self.path)?;
+		let mut data = Vec::new();
+		file.read_to_end(&mut data)?;
+
+		// the new record's index is the number of records already in the file
+		let index = split_records(&data).try_fold(0, |count, record| record.map(|_| count + 1))?;
+		let record = self.seal(index, &json)?;
+
+		file.write_all(&record)?;
+		file.sync_data()?;
+
+		Ok(())
+	}
+
+	/// Replace the file's contents with the given entries, re-encrypting all
+	/// of them
+	fn write(&self, entries: &[Entry]) -> Result<(), HistoryError> {
+		let mut data = self.header.to_vec();
+		data.extend(self.seal(0, &[])?);
+
+		for (entry, index) in entries.iter().zip(1..) {
+			let json = serde_json::to_vec(entry).map_err(|e| HistoryError::Io(e.to_string()))?;
+			data.extend(self.seal(index, &json)?);
+		}
+
+		// write to a temporary file first, so the history isn't lost if writing
+		// fails halfway through
+		let mut tmp = self.path.clone().into_os_string();
+		tmp.push(".tmp");
+
+		let mut file = File::create(&tmp)?;
+		file.write_all(&data)?;
+		file.sync_all()?;
+		drop(file);
+
+		if let Err(e) = fs::rename(&tmp, &self.path) {
+			let _ = fs::remove_file(&tmp);
+			return Err(e.into());
+		}
+
+		Ok(())
+	}
+
+	/// Keep only the entries for which `keep` returns `true`, returning the
+	/// number of deleted entries
+	///
+	/// # Errors
+	/// Returns an error if the file can't be read or written, or is damaged
+	pub fn retain(&self, mut keep: impl FnMut(&Entry) -> bool) -> Result<usize, HistoryError> {
+		let mut entries = self.entries()?;
+		let len = entries.len();
+		entries.retain(|entry| keep(entry));
+
+		if entries.len() != len {
+			self.write(&entries)?;
+		}
+
+		Ok(len - entries.len())
+	}
+
+	/// Delete entries exceeding the retention limits, returning the number of
+	/// deleted entries
+	///
+	/// # Errors
+	/// Returns an error if the file can't be read or written, or is damaged
+	pub fn prune(&self, retention: Retention, now: Timestamp) -> Result<usize, HistoryError> {
+		let mut entries = self.entries()?;
+		let len = entries.len();
+
+		if let Some(max_age) = retention.max_age {
+			entries.retain(|entry| now.0.saturating_sub(entry.ts.0) <= max_age.as_secs());
+		}
+
+		if let Some(max_entries) = retention.max_entries {
+			entries.drain(..entries.len().saturating_sub(max_entries));
+		}
+
+		if entries.len() != len {
+			self.write(&entries)?;
+		}
+
+		Ok(len - entries.len())
+	}
+}
+
+/// Split the file contents `data` into the nonces and ciphertexts of its
+/// records, starting with the check record
+fn split_records(data: &[u8]) -> impl Iterator<Item = Result<(&[u8], &[u8]), HistoryError>> {
+	let mut rest = &data[HEADER_LEN.min(data.len())..];
+
+	std::iter::from_fn(move || {
+		if rest.is_empty() {
+			return None;
+		}
+
+		let Some((len, tail)) = rest.split_first_chunk::<4>() else {
+			rest = &[];
+			return Some(Err(HistoryError::Corrupted("truncated record")));
+		};
+		let len = u32::from_le_bytes(*len) as usize;
+
+		if !(NONCE_LEN..=MAX_RECORD_LEN).contains(&len) || tail.len() < len {
+			rest = &[];
+			return Some(Err(HistoryError::Corrupted("truncated record")));
+		}
+
+		rest = &tail[len..];
+		Some(Ok(tail[..len].split_at(NONCE_LEN)))
+	})
+}
+
+/// A command for managing the history
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// List past Ping exchanges, oldest first
+	List {
+		/// Only list exchanges with this ID
+		#[arg(long, value_name = "ID")]
+		peer: Option<u16>,
+		/// Only list this many of the most recent exchanges
+		#[arg(short = 'n', long, value_name = "COUNT")]
+		limit: Option<usize>,
+	},
+	/// Show the details of a past Ping exchange
+	Show {
+		/// The number of the exchange, as shown by `history list`
+		number: usize,
+	},
+	/// Delete past Ping exchanges
+	Purge {
+		/// Only delete exchanges older than this many days
+		#[arg(long, value_name = "DAYS")]
+		older_than: Option<u64>,
+		/// Don't ask for confirmation
+		#[arg(short, long)]
+		yes: bool,
+	},
+}
+
+impl Command {
+	/// Run this command on the history
+	pub fn run(self, history: &History, format: CoordinateFormat) -> ExitCode {
+		let entries = match history.entries() {
+			Ok(entries) => entries,
+			Err(e) => return error("Couldn't read history:", &e),
+		};
+
+		match self {
+			Self::List { peer, limit } => {
+				let listed = entries
+					.iter()
+					.enumerate()
+					.filter(|(_, entry)| peer.is_none_or(|peer| entry.peer.0 == peer))
+					.collect::<Vec<_>>();
+				let skip = limit.map_or(0, |limit| listed.len().saturating_sub(limit));
+
+				if listed.is_empty() {
+					println!("{}", "No Ping exchanges in the history".dimmed());
+				}
+
+				for (i, entry) in listed.into_iter().skip(skip) {
+					let position = entry.info.map_or_else(String::new, |info| {
+						format_coordinates(info.coordinates(), format)
+					});
+
+					println!(
+						"{:>4}  {}  {:<19}  {:<9}  {}",
+						(i + 1).to_string().bold(),
+						format_time(entry.ts).dimmed(),
+						format!("{} {}", entry.direction, entry.peer),
+						entry.outcome.colored(),
+						position
+					);
+				}
+			}
+			Self::Show { number } => {
+				let Some(entry) = number.checked_sub(1).and_then(|i| entries.get(i)) else {
+					println!(
+						"{} {}",
+						"No such exchange:".red().bold(),
+						format!("the history contains {} exchange(s)", entries.len()).red()
+					);
+					return ExitCode::FAILURE;
+				};
+
+				println!("{}", format!("Exchange {number}").bold());
+				println!("  Ended:     {}", format_time(entry.ts));
+				println!("  Direction: {} {}", entry.direction, entry.peer);
+				println!("  Outcome:   {}", entry.outcome.colored());
+
+				if let Some(info) = entry.info {
+					println!(
+						"  Position:  {}",
+						format_coordinates(info.coordinates(), format)
+					);
+					println!("  Altitude:  {} mAMSL", info.alt().0);
+					println!("  Error:     ±{}", Distance(info.err()));
+					println!("  Sent:      {}", format_time(info.ts()));
+				}
+			}
+			Self::Purge { older_than, yes } => {
+				let what = older_than.map_or_else(
+					|| "all Ping exchanges".to_string(),
+					|days| format!("Ping exchanges older than {days} day(s)"),
+				);
+
+				if !yes
+					&& !Confirm::new(&format!("Delete {what} from the history?"))
+						.with_default(false)
+						.prompt()
+						.unwrap_or(false)
+				{
+					return ExitCode::SUCCESS;
+				}
+
+				let now = Timestamp::now();
+				let res = history.retain(|entry| {
+					older_than.is_some_and(|days| {
+						now.0.saturating_sub(entry.ts.0) <= days.saturating_mul(DAY)
+					})
+				});
+
+				match res {
+					Ok(deleted) => println!(
+						"{}",
+						format!("Deleted {deleted} exchange(s) from the history").bold()
+					),
+					Err(e) => return error("Couldn't purge history:", &e),
+				}
+			}
+		}
+
+		ExitCode::SUCCESS
+	}
+}
+
+/// Print an error, returning a failure exit code
+fn error(msg: &str, e: &HistoryError) -> ExitCode {
+	println!("{} {}", msg.red().bold(), format!("{e}").red());
+
+	ExitCode::FAILURE
+}
+
+/// Format a timestamp as an RFC 3339 date and time in UTC
+fn format_time(ts: Timestamp) -> String {
+	i64::try_from(ts.0)
+		.ok()
+		.and_then(|ts| OffsetDateTime::from_unix_timestamp(ts).ok())
+		.and_then(|time| time.format(&Rfc3339).ok())
+		.unwrap_or_else(|| format!("{} s", ts.0))
+}
+
+/// Convert a number of days to a duration
+pub const fn days(days: u64) -> Duration {
+	Duration::from_secs(days.saturating_mul(DAY))
+}
@@ -0,0 +1,97 @@
+//! Reading positions from JSON and GPX files
+//!
+//! JSON files contain a single object with `lat` and `lon` in degrees, and
+//! optionally `alt` (in meters above mean sea level), `err` (in meters) or
+//! `hdop`, and `ts` (in seconds since the unix epoch), e.g.
+//! `{ "lat": 46.05, "lon": 14.5, "alt": 295, "err": 10 }`.
+//! From GPX files, the last track point is used, or the last route point or
+//! waypoint if there are no tracks.
+
+use pinger::{Timestamp, geo::Coordinates};
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+
+use super::{Position, SourceError, UERE};
+
+/// A position in a JSON file
+#[derive(Debug, Deserialize)]
+struct JsonPosition {
+	/// The latitude in degrees
+	lat: f64,
+	/// The longitude in degrees
+	lon: f64,
+	/// The altitude above mean sea level in meters
+	alt: Option<f32>,
+	/// The position error in meters
+	err: Option<f32>,
+	/// The horizontal dilution of precision, if the position error is unknown
+	hdop: Option<f32>,
+	/// The timestamp of the position in seconds since the unix epoch
+	ts: Option<u64>,
+}
+
+/// Parse a position from a JSON file
+///
+/// # Errors
+/// Returns an error if the JSON is invalid
+pub fn parse_json(json: &[u8]) -> Result<Position, SourceError> {
+	let position = serde_json::from_slice::<JsonPosition>(json)
+		.map_err(|e| SourceError::Invalid(format!("invalid JSON position: {e}")))?;
+
+	Ok(Position {
+		ts: position.ts.map(Timestamp),
+		coords: Coordinates::new(position.lat, position.lon),
+		alt: position.alt,
+		err: position
+			.err
+			.or_else(|| position.hdop.map(|hdop| hdop * UERE)),
+	})
+}
+
+/// Parse the last position from a GPX file
+///
+/// # Errors
+/// Returns an error if the GPX is invalid, or [`SourceError::NoFix`] if it
+/// doesn't contain any points
+pub fn parse_gpx(gpx: &str) -> Result<Position, SourceError> {
+	let doc =
+		Document::parse(gpx).map_err(|e| SourceError::Invalid(format!("invalid GPX: {e}")))?;
+	let last = |names: &[&str]| {
+		doc.descendants()
+			.rfind(|n| n.is_element() && names.contains(&n.tag_name().name()))
+	};
+
+	let point = last(&["trkpt"])
+		.or_else(|| last(&["rtept", "wpt"]))
+		.ok_or(SourceError::NoFix)?;
+
+	let invalid = || SourceError::Invalid(format!("invalid GPX point at {}", point.range().start));
+	let attribute = |name| -> Result<f64, SourceError> {
+		point
+			.attribute(name)
+			.and_then(|v| v.trim().parse().ok())
+			.ok_or_else(invalid)
+	};
+	let child = |name| child_text(point, name);
+	let number = |name| -> Result<Option<f32>, SourceError> {
+		child(name)
+			.map(|v| v.trim().parse().map_err(|_| invalid()))
+			.transpose()
+	};
+
+	Ok(Position {
+		ts: child("time")
+			.map(|time| super::parse_time(time).ok_or_else(invalid))
+			.transpose()?,
+		coords: Coordinates::new(attribute("lat")?, attribute("lon")?),
+		alt: number("ele")?,
+		err: number("hdop")?.map(|hdop| hdop * UERE),
+	})
+}
+
+/// Get the text of the child element of `node` with the given name
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+	node.children()
+		.find(|c| c.is_element() && c.tag_name().name() == name)
+		.and_then(|c| c.text())
+}
@@ -0,0 +1,141 @@
+//! Reading positions from gpsd
+//!
+//! The client enables JSON watch mode and uses `TPV` (time-position-velocity)
+//! reports for the position, and `SKY` reports for the HDOP if the `TPV`
+//! reports don't contain any error estimates.
+
+use std::{
+	convert::Infallible,
+	io::{BufRead, BufReader, Write},
+	net::TcpStream,
+	thread,
+};
+
+use pinger::geo::Coordinates;
+use serde::Deserialize;
+
+use super::{Position, RETRY_DELAY, SharedPosition, SourceError, UERE};
+
+/// The command enabling JSON reports from gpsd
+const WATCH: &[u8] = b"?WATCH={\"enable\":true,\"json\":true};\n";
+
+/// A report from gpsd
+#[derive(Debug, Deserialize)]
+#[serde(tag = "class")]
+enum Report {
+	#[serde(rename = "TPV")]
+	Tpv(Tpv),
+	#[serde(rename = "SKY")]
+	Sky(Sky),
+	#[serde(other)]
+	Other,
+}
+
+/// A time-position-velocity report
+#[derive(Debug, Deserialize)]
+struct Tpv {
+	/// The fix mode (0 or 1 for no fix, 2 for 2D, 3 for 3D)
+	#[serde(default)]
+	mode: u8,
+	/// The time of the fix
+	time: Option<String>,
+	/// The latitude in degrees
+	lat: Option<f64>,
+	/// The longitude in degrees
+	lon: Option<f64>,
+	/// The altitude above mean sea level in meters
+	#[serde(rename = "altMSL")]
+	alt_msl: Option<f32>,
+	/// The altitude in meters (above mean sea level in older versions of gpsd)
+	alt: Option<f32>,
+	/// The estimated horizontal position error in meters
+	eph: Option<f32>,
+	/// The estimated longitude error in meters
+	epx: Option<f32>,
+	/// The estimated latitude error in meters
+	epy: Option<f32>,
+}
+
+/// A sky view report
+#[derive(Debug, Deserialize)]
+struct Sky {
+	/// The horizontal dilution of precision
+	hdop: Option<f32>,
+}
+
+/// A parser for gpsd reports
+#[derive(Clone, Debug, Default)]
+pub struct GpsdParser {
+	/// The most recently reported HDOP
+	hdop: Option<f32>,
+}
+
+impl GpsdParser {
+	/// Process one line of gpsd output, returning the position if it's a `TPV`
+	/// report with a fix
+	///
+	/// # Errors
+	/// Returns an error if the line is not a valid gpsd report
+	pub fn feed(&mut self, line: &str) -> Result<Option<Position>, SourceError> {
+		let report = serde_json::from_str::<Report>(line)
+			.map_err(|e| SourceError::Invalid(format!("invalid gpsd report: {e}")))?;
+
+		match report {
+			Report::Tpv(Tpv {
+				mode: 2..,
+				time,
+				lat: Some(lat),
+				lon: Some(lon),
+				alt_msl,
+				alt,
+				eph,
+				epx,
+				epy,
+			}) => Ok(Some(Position {
+				ts: time.as_deref().and_then(super::parse_time),
+				coords: Coordinates::new(lat, lon),
+				alt: alt_msl.or(alt),
+				err: eph
+					.or_else(|| epx.zip(epy).map(|(x, y)| x.hypot(y)))
+					.or_else(|| self.hdop.map(|hdop| hdop * UERE)),
+			})),
+			Report::Sky(Sky { hdop: Some(hdop) }) => {
+				self.hdop = Some(hdop);
+				Ok(None)
+			}
+			_ => Ok(None),
+		}
+	}
+}
+
+/// Start receiving reports from gpsd at `addr` in the background, storing the
+/// most recent position in `latest`
+///
+/// The client reconnects after errors.
+pub fn spawn_client(addr: String, latest: SharedPosition) {
+	thread::spawn(move || {
+		loop {
+			let Err(e) = watch(&addr, &latest);
+			*latest.lock().expect("lock poisoned") = Err(e);
+
+			thread::sleep(RETRY_DELAY);
+		}
+	});
+}
+
+/// Connect to gpsd and receive reports until the connection fails
+fn watch(addr: &str, latest: &SharedPosition) -> Result<Infallible, SourceError> {
+	let mut stream = TcpStream::connect(addr)?;
+	stream.write_all(WATCH)?;
+
+	let mut parser = GpsdParser::default();
+
+	for line in BufReader::new(stream).lines() {
+		// reports which can't be parsed (e.g. from newer versions of gpsd) are ignored
+		if let Ok(Some(position)) = parser.feed(&line?) {
+			*latest.lock().expect("lock poisoned") = Ok(position);
+		}
+	}
+
+	Err(SourceError::Io("gpsd closed the connection".to_string()))
+}
@@ -0,0 +1,216 @@
+//! Sources of the user's own position for sending Pings
+//!
+//! Instead of asking for the position on every Ping, it can be read from an
+//! NMEA 0183 receiver (or a recording of one), a local gpsd, or a JSON or GPX
+//! file.
+//! Receivers and gpsd are read continuously in the background, and the most
+//! recent fix is used when sending a Ping, while files are read each time a
+//! Ping is sent.
+
+pub mod file;
+pub mod gpsd;
+pub mod nmea;
+
+use std::{
+	fs,
+	path::PathBuf,
+	str,
+	str::FromStr,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use derive_more::Display;
+use pinger::{Meters, PingInfo, Timestamp, geo::Coordinates};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// The user equivalent range error of a typical consumer GPS receiver in
+/// meters, used to estimate the position error from the HDOP
+pub const UERE: f32 = 5.0;
+
+/// The position error in meters assumed if the source doesn't provide any
+/// estimate of it
+pub const UNKNOWN_ERR: f32 = 50.0;
+
+/// The baud rate of NMEA 0183 serial devices, unless specified otherwise
+pub const DEFAULT_BAUD_RATE: u32 = 4800;
+
+/// The address of gpsd, unless specified otherwise
+pub const DEFAULT_GPSD_ADDR: &str = "localhost:2947";
+
+/// The delay before reconnecting to or reopening a live source after an error
+pub const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A position reported by a location source
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+	/// The time of the fix, if known
+	pub ts: Option<Timestamp>,
+	/// The horizontal position
+	pub coords: Coordinates,
+	/// The altitude above mean sea level in meters, if known
+	pub alt: Option<f32>,
+	/// The horizontal position error in meters, if known
+	pub err: Option<f32>,
+}
+
+impl Position {
+	/// Convert this position into Ping info, using the current time, an
+	/// altitude of 0, and [`UNKNOWN_ERR`] for any missing values
+	///
+	/// # Errors
+	/// Returns an error if any of the values are invalid
+	pub fn to_ping_info(self) -> Result<PingInfo, pinger::Error> {
+		PingInfo::new(
+			self.ts.unwrap_or_else(Timestamp::now),
+			self.coords.lat,
+			self.coords.lon,
+			Meters(self.alt.unwrap_or(0.0)),
+			Meters(self.err.unwrap_or(UNKNOWN_ERR)),
+		)
+	}
+}
+
+/// An error from a location source
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum SourceError {
+	#[display("no position fix yet")]
+	NoFix,
+	#[display("I/O error: {_0}")]
+	Io(String),
+	#[display("invalid data: {_0}")]
+	Invalid(String),
+}
+
+impl From<std::io::Error> for SourceError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e.to_string())
+	}
+}
+
+/// Where to get the user's position from, as given on the command line
+#[derive(Clone, Debug, Display)]
+pub enum LocationSource {
+	/// NMEA 0183 sentences from a serial device (at the given baud rate) or a
+	/// file
+	#[display("NMEA from {}", _0.display())]
+	Nmea(PathBuf, Option<u32>),
+	/// A gpsd instance at the given address
+	#[display("gpsd at {_0}")]
+	Gpsd(String),
+	/// A file with the given format
+	#[display("{_1} file {}", _0.display())]
+	File(PathBuf, FileFormat),
+}
+
+/// The format of a file location source
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum FileFormat {
+	/// A JSON object with a single position
+	#[display("JSON")]
+	Json,
+	/// A GPX file, from which the last point is used
+	#[display("GPX")]
+	Gpx,
+}
+
+impl FileFormat {
+	/// Parse a position from the contents of a file in this format
+	///
+	/// # Errors
+	/// Returns an error if the file is invalid or contains no position
+	pub fn parse(self, contents: &[u8]) -> Result<Position, SourceError> {
+		match self {
+			Self::Json => file::parse_json(contents),
+			Self::Gpx => file::parse_gpx(
+				str::from_utf8(contents).map_err(|e| SourceError::Invalid(e.to_string()))?,
+			),
+		}
+	}
+}
+
+impl FromStr for LocationSource {
+	type Err = String;
+
+	/// Parse `nmea:PATH[@BAUD]`, `gpsd[:HOST:PORT]`, `json:PATH`, or `gpx:PATH`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+
+		match (kind.to_ascii_lowercase().as_str(), arg) {
+			("gpsd", "") => Ok(Self::Gpsd(DEFAULT_GPSD_ADDR.to_string())),
+			("gpsd", addr) => Ok(Self::Gpsd(addr.to_string())),
+			(_, "") => Err(format!("missing path or address in location source `{s}`")),
+			("nmea", path) => match path.rsplit_once('@') {
+				Some((path, baud)) => Ok(Self::Nmea(
+					path.into(),
+					Some(
+						baud.parse()
+							.map_err(|_| format!("invalid baud rate `{baud}`"))?,
+					),
+				)),
+				None => Ok(Self::Nmea(path.into(), None)),
+			},
+			("json", path) => Ok(Self::File(path.into(), FileFormat::Json)),
+			("gpx", path) => Ok(Self::File(path.into(), FileFormat::Gpx)),
+			_ => Err(format!(
+				"unknown location source `{kind}` (expected nmea, gpsd, json, or gpx)"
+			)),
+		}
+	}
+}
+
+impl LocationSource {
+	/// Start reading positions from this source
+	///
+	/// For NMEA and gpsd sources, this starts a background thread reading
+	/// positions as they are reported.
+	pub fn start(self) -> Locator {
+		let latest: SharedPosition = Arc::new(Mutex::new(Err(SourceError::NoFix)));
+
+		match self {
+			Self::Nmea(ref path, baud) => {
+				nmea::spawn_reader(path.clone(), baud, Arc::clone(&latest));
+			}
+			Self::Gpsd(ref addr) => gpsd::spawn_client(addr.clone(), Arc::clone(&latest)),
+			Self::File(path, format) => return Locator::File(path, format),
+		}
+
+		Locator::Live(self, latest)
+	}
+}
+
+/// The most recent position (or error) of a live location source
+pub type SharedPosition = Arc<Mutex<Result<Position, SourceError>>>;
+
+/// A running location source
+#[derive(Debug, Display)]
+pub enum Locator {
+	/// A source read continuously in the background
+	#[display("{_0}")]
+	Live(LocationSource, SharedPosition),
+	/// A file read on demand
+	#[display("{_1} file {}", _0.display())]
+	File(PathBuf, FileFormat),
+}
+
+impl Locator {
+	/// Get the current position
+	///
+	/// # Errors
+	/// Returns an error if the source hasn't reported a position, or if it
+	/// can't be read
+	pub fn position(&self) -> Result<Position, SourceError> {
+		match self {
+			Self::Live(_, latest) => latest.lock().expect("lock poisoned").clone(),
+			Self::File(path, format) => format.parse(&fs::read(path)?),
+		}
+	}
+}
+
+/// Parse an RFC 3339 (ISO 8601) date and time, as used by gpsd and GPX
+pub fn parse_time(s: &str) -> Option<Timestamp> {
+	OffsetDateTime::parse(s.trim(), &Rfc3339)
+		.ok()
+		.and_then(|dt| u64::try_from(dt.unix_timestamp()).ok())
+		.map(Timestamp)
+}
@@ -0,0 +1,340 @@
+//! Reading positions from NMEA 0183 receivers
+//!
+//! The position, time, altitude, and HDOP are taken from `GGA`, `RMC`, and
+//! `GSA` sentences from any talker (e.g. `$GPGGA` or `$GNGGA`), all other
+//! sentences are ignored.
+//! `GGA` sentences only contain the time of day, so until an `RMC` sentence
+//! provides the date, fixes are assumed to be from within 12 hours of the
+//! current time.
+
+use std::{
+	fs::{self, File},
+	io::{BufRead, BufReader, ErrorKind, Read},
+	path::{Path, PathBuf},
+	thread,
+	time::Duration as StdDuration,
+};
+
+use pinger::{Timestamp, geo::Coordinates};
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use super::{DEFAULT_BAUD_RATE, Position, RETRY_DELAY, SharedPosition, SourceError, UERE};
+
+/// The timeout for reading from a serial device, after which reading is simply
+/// retried
+const SERIAL_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+/// A parser combining NMEA sentences into positions
+///
+/// The most recent valid fix is kept when the receiver loses it, and its time
+/// is reported with it, so that receivers of a Ping can tell that it's old.
+#[derive(Clone, Debug, Default)]
+pub struct NmeaParser {
+	/// The date and time of the last valid `RMC` sentence
+	date_time: Option<PrimitiveDateTime>,
+	/// The time of the last fix
+	time: Option<Time>,
+	/// The coordinates of the last fix
+	coords: Option<Coordinates>,
+	/// The altitude above mean sea level of the last fix
+	alt: Option<f32>,
+	/// The most recently reported HDOP
+	hdop: Option<f32>,
+}
+
+impl NmeaParser {
+	/// Process one line of NMEA data, returning whether it contained a fix
+	///
+	/// # Errors
+	/// Returns an error if the line is not a valid NMEA sentence (e.g. because
+	/// its checksum doesn't match)
+	pub fn feed(&mut self, line: &str) -> Result<bool, SourceError> {
+		let (kind, fields) = split_sentence(line)?;
+		let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+
+		match kind {
+			"GGA" => {
+				if field(5).is_empty() || field(5) == "0" {
+					return Ok(false);
+				}
+
+				let time = parse_time(field(0))?;
+				let coords = parse_coordinates(field(1), field(2), field(3), field(4))?;
+				let hdop = parse_optional(field(7))?;
+				let alt = parse_optional(field(8))?;
+
+				self.time = Some(time);
+				self.coords = Some(coords);
+				self.alt = alt;
+				self.hdop = hdop.or(self.hdop);
+
+				Ok(true)
+			}
+			"RMC" => {
+				if field(1) != "A" {
+					return Ok(false);
+				}
+
+				let time = parse_time(field(0))?;
+				let coords = parse_coordinates(field(2), field(3), field(4), field(5))?;
+				let date = parse_date(field(8))?;
+
+				self.time = Some(time);
+				self.date_time = Some(PrimitiveDateTime::new(date, time));
+				self.coords = Some(coords);
+
+				Ok(true)
+			}
+			"GSA" => {
+				if (field(1) == "2" || field(1) == "3")
+					&& let Some(hdop) = parse_optional(field(15))?
+				{
+					self.hdop = Some(hdop);
+				}
+
+				Ok(false)
+			}
+			_ => Ok(false),
+		}
+	}
+
+	/// Get the position of the most recent fix, if there was one
+	pub fn position(&self) -> Option<Position> {
+		self.position_at(OffsetDateTime::now_utc())
+	}
+
+	/// Get the position of the most recent fix, if there was one, using `now`
+	/// as the current time if no `RMC` sentence provided the date yet
+	pub fn position_at(&self, now: OffsetDateTime) -> Option<Position> {
+		Some(Position {
+			ts: self.timestamp(now),
+			coords: self.coords?,
+			alt: self.alt,
+			err: self.hdop.map(|hdop| hdop * UERE),
+		})
+	}
+
+	/// Get the timestamp of the most recent fix, using the date from the last
+	/// `RMC` sentence (`GGA` sentences only contain the time), or the date
+	/// closest to `now` if there wasn't one
+	fn timestamp(&self, now: OffsetDateTime) -> Option<Timestamp> {
+		let time = self.time?;
+
+		let reference = self.date_time.unwrap_or_else(|| {
+			let now = now.to_offset(UtcOffset::UTC);
+			PrimitiveDateTime::new(now.date(), now.time())
+		});
+		let mut fix = reference.replace_time(time);
+
+		// the fix is from the day before or after the reference (e.g. the date
+		// changed since the last `RMC` sentence)
+		if fix < reference - Duration::hours(12) {
+			fix += Duration::days(1);
+		} else if fix > reference + Duration::hours(12) {
+			fix -= Duration::days(1);
+		}
+
+		u64::try_from(fix.assume_utc().unix_timestamp())
+			.ok()
+			.map(Timestamp)
+	}
+}
+
+/// Split an NMEA sentence into its type (e.g. `GGA`) and fields, checking its
+/// checksum if there is one
+fn split_sentence(line: &str) -> Result<(&str, Vec<&str>), SourceError> {
+	let line = line.trim();
+	let body = line
+		.strip_prefix('$')
+		.ok_or_else(|| SourceError::Invalid(format!("not an NMEA sentence: `{line}`")))?;
+
+	let body = match body.split_once('*') {
+		Some((body, checksum)) => {
+			let expected = u8::from_str_radix(checksum, 16)
+				.map_err(|_| SourceError::Invalid(format!("invalid checksum in `{line}`")))?;
+
+			if body.bytes().fold(0, |acc, b| acc ^ b) != expected {
+				return Err(SourceError::Invalid(format!(
+					"checksum mismatch in `{line}`"
+				)));
+			}
+
+			body
+		}
+		None => body,
+	};
+
+	let mut fields = body.split(',');
+	let address = fields.next().unwrap_or_default();
+
+	// proprietary sentences (`$P...`) don't have a talker ID
+	let kind = match address.get(2..) {
+		Some(kind) if !address.starts_with('P') => kind,
+		_ => "",
+	};
+
+	Ok((kind, fields.collect()))
+}
+
+/// Parse a UTC time in the `hhmmss.ss` format
+fn parse_time(s: &str) -> Result<Time, SourceError> {
+	let invalid = || SourceError::Invalid(format!("invalid time `{s}`"));
+	let num = |range| {
+		s.get(range)
+			.and_then(|n: &str| n.parse().ok())
+			.ok_or_else(invalid)
+	};
+
+	let millis = match s.get(6..) {
+		None | Some("") => 0,
+		Some(frac) => {
+			let digits = frac
+				.strip_prefix('.')
+				.filter(|d| d.bytes().all(|b| b.is_ascii_digit()))
+				.ok_or_else(invalid)?;
+
+			format!("{digits:0<3}")[..3]
+				.parse()
+				.map_err(|_| invalid())?
+		}
+	};
+
+	Time::from_hms_milli(num(0..2)?, num(2..4)?, num(4..6)?, millis).map_err(|_| invalid())
+}
+
+/// Parse a date in the `ddmmyy` format
+fn parse_date(s: &str) -> Result<Date, SourceError> {
+	let invalid = || SourceError::Invalid(format!("invalid date `{s}`"));
+	let num = |range| {
+		s.get(range)
+			.and_then(|n: &str| n.parse::<u8>().ok())
+			.ok_or_else(invalid)
+	};
+
+	if s.len() != 6 {
+		return Err(invalid());
+	}
+
+	// GPS was introduced in 1980
+	let year = match num(4..6)? {
+		year @ 0..80 => 2000 + i32::from(year),
+		year => 1900 + i32::from(year),
+	};
+	let month = Month::try_from(num(2..4)?).map_err(|_| invalid())?;
+
+	Date::from_calendar_date(year, month, num(0..2)?).map_err(|_| invalid())
+}
+
+/// Parse a latitude (`ddmm.mm`) and longitude (`dddmm.mm`) with their
+/// hemispheres
+fn parse_coordinates(lat: &str, ns: &str, lon: &str, ew: &str) -> Result<Coordinates, SourceError> {
+	let invalid = || SourceError::Invalid(format!("invalid coordinates `{lat},{ns},{lon},{ew}`"));
+	let degrees = |s: &str| {
+		let value = s.parse::<f64>().ok().filter(|v| *v >= 0.0)?;
+		let degrees = (value / 100.0).trunc();
+		let minutes = value - degrees * 100.0;
+
+		(minutes < 60.0).then_some(degrees + minutes / 60.0)
+	};
+
+	let lat = match ns {
+		"N" => degrees(lat),
+		"S" => degrees(lat).map(|v| -v),
+		_ => None,
+	}
+	.filter(|v| (-90.0..=90.0).contains(v))
+	.ok_or_else(invalid)?;
+
+	let lon = match ew {
+		"E" => degrees(lon),
+		"W" => degrees(lon).map(|v| -v),
+		_ => None,
+	}
+	.filter(|v| (-180.0..=180.0).contains(v))
+	.ok_or_else(invalid)?;
+
+	Ok(Coordinates::new(lat, lon))
+}
+
+/// Parse an optional number, which is empty if it's not available
+fn parse_optional(s: &str) -> Result<Option<f32>, SourceError> {
+	if s.is_empty() {
+		Ok(None)
+	} else {
+		s.parse()
+			.map(Some)
+			.map_err(|_| SourceError::Invalid(format!("invalid number `{s}`")))
+	}
+}
+
+/// Start reading NMEA sentences from the serial device or file at `path` in
+/// the background, storing the most recent position in `latest`
+///
+/// Serial devices are reopened after errors, but reading stops at the end of a
+/// regular file, keeping the last position from it.
+pub fn spawn_reader(path: PathBuf, baud: Option<u32>, latest: SharedPosition) {
+	thread::spawn(move || {
+		loop {
+			match read(&path, baud, &latest) {
+				Ok(()) => return,
+				Err(e) => *latest.lock().expect("lock poisoned") = Err(e),
+			}
+
+			thread::sleep(RETRY_DELAY);
+		}
+	});
+}
+
+/// Read NMEA sentences from `path` until the end of the file
+fn read(path: &Path, baud: Option<u32>, latest: &SharedPosition) -> Result<(), SourceError> {
+	let reader: Box<dyn Read + Send> = if baud.is_some() || is_device(path) {
+		Box::new(
+			serialport::new(path.to_string_lossy(), baud.unwrap_or(DEFAULT_BAUD_RATE))
+				.timeout(SERIAL_TIMEOUT)
+				.open()
+				.map_err(|e| SourceError::Io(e.to_string()))?,
+		)
+	} else {
+		Box::new(File::open(path)?)
+	};
+
+	let mut reader = BufReader::new(reader);
+	let mut parser = NmeaParser::default();
+	let mut buf = Vec::new();
+
+	loop {
+		match reader.read_until(b'\n', &mut buf) {
+			Ok(0) => return Ok(()),
+			Ok(_) => (),
+			// keep the partial line in `buf` and continue reading it
+			Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::Interrupted => {
+				continue;
+			}
+			Err(e) => return Err(e.into()),
+		}
+
+		// invalid sentences are expected (e.g. a partial one when starting to read
+		// or corrupted ones from a noisy connection), and are ignored
+		if parser.feed(&String::from_utf8_lossy(&buf)) == Ok(true)
+			&& let Some(position) = parser.position()
+		{
+			*latest.lock().expect("lock poisoned") = Ok(position);
+		}
+
+		buf.clear();
+	}
+}
+
+/// Check if `path` is a (serial) device rather than a regular file
+#[cfg(unix)]
+fn is_device(path: &Path) -> bool {
+	use std::os::unix::fs::FileTypeExt;
+
+	fs::metadata(path).is_ok_and(|m| m.file_type().is_char_device())
+}
+
+/// Check if `path` is a (serial) device rather than a regular file
+#[cfg(not(unix))]
+fn is_device(path: &Path) -> bool {
+	!fs::metadata(path).is_ok_and(|m| m.is_file())
+}
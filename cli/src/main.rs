@@ -3,32 +3,232 @@
 //! Run with `./executable-name [SERVER]`, where `[SERVER]` is the optional
 //! websocket URI of the Pinger API (`wss://pinger.janm.dev/api` by default if
 //! not specified)
+//!
+//! Type an ID to send a Ping to it (or `c` and the ID to authenticate it with a
+//! short code), or `a` or `r` and an ID to accept or reject a Ping from it.
+//! The options (see `--help`) can also be kept in profiles in a configuration
+//! file, and the `history` and `config` subcommands manage the Ping history
+//! and check the settings.
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, VecDeque},
 	env,
 	fmt::{Debug, Display, Error as FmtError, Formatter, Result as FmtResult},
 	io, mem,
+	path::{Path, PathBuf},
 	process::ExitCode,
+	str::FromStr,
 	sync::{Condvar, Mutex},
 	thread,
-	time::{Duration, SystemTime},
+	time::Duration,
 };
 
 use base64::engine::{Engine, general_purpose::URL_SAFE_NO_PAD};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use colored::Colorize;
 use derive_more::Display;
 use futures_util::{Sink, SinkExt, StreamExt};
 use inquire::{
-	CustomType,
+	CustomType, Password, Text,
 	validator::{ErrorMessage, Validation},
 };
-use pinger::{Degrees, EphemeralSecret, Meters, PingInfo, SharedKey, Timestamp};
+use pinger::{
+	EphemeralSecret, Meters, PingInfo, SharedKey, Timestamp,
+	coords::CoordinateFormat,
+	export::{ExportFormat, ReceivedPing},
+	geo::{Coordinates, Distance},
+	pake::{PakeMessage, PakeSecret, normalize_code},
+	privacy::Precision,
+	replay::Freshness,
+	sas::SafetyNumber,
+};
 use serde::{Deserialize, Serialize};
-use tokio::{select, signal, sync::mpsc};
+use tokio::{
+	select, signal,
+	sync::mpsc,
+	time::{self, Instant},
+};
 use tokio_tungstenite::tungstenite::Message;
 
-const DEFAULT_URL: &str = "wss://pinger.janm.dev/api";
+use crate::{
+	config::Settings,
+	history::{Direction, Entry, History, Outcome, Retention},
+	location::{LocationSource, Locator},
+	policy::{AcceptPolicy, Decider, Decision},
+};
+
+/// Show a message to the user, formatted like `println!`
+macro_rules! say {
+	($conn:expr, $($arg:tt)*) => {
+		$conn.say(format!($($arg)*))
+	};
+}
+
+mod config;
+mod export;
+mod history;
+mod location;
+mod policy;
+mod serde_public_key;
+mod tests;
+mod tui;
+
+/// The maximum number of messages kept in the TUI's log, after which the
+/// oldest ones are discarded
+const MAX_LOG_MESSAGES: usize = 1000;
+
+/// A command-line interface for Pinger
+///
+/// Settings which aren't given as flags or environment variables are taken
+/// from the selected profile of the configuration file, or have the default
+/// values described here.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+	/// The websocket URI of the Pinger API [default:
+	/// `wss://pinger.janm.dev/api`]
+	#[arg(env = "PINGER_SERVER")]
+	server: Option<String>,
+	/// The configuration file [default:
+	/// `$XDG_CONFIG_HOME/pinger/config.toml`]
+	#[arg(long, value_name = "FILE", env = "PINGER_CONFIG", global = true)]
+	config: Option<PathBuf>,
+	/// The profile from the configuration file to use [default: the file's
+	/// `default-profile`, or `default`]
+	#[arg(
+		short,
+		long,
+		value_name = "NAME",
+		env = "PINGER_PROFILE",
+		global = true
+	)]
+	profile: Option<String>,
+	/// A PEM file with additional root certificates to trust for the server
+	/// connection
+	#[arg(long, value_name = "FILE", env = "PINGER_TLS_CA")]
+	tls_ca: Option<PathBuf>,
+	/// Don't trust the operating system's root certificates
+	#[arg(long)]
+	no_native_roots: bool,
+	/// Your own location, used to show the distance and direction to received
+	/// Pings
+	#[arg(short, long, value_name = "LOCATION[, ERR]", env = "PINGER_LOCATION")]
+	location: Option<MyLocation>,
+	/// The coordinate format for received Pings (decimal, dms, geohash,
+	/// plus-code, utm, or mgrs) [default: decimal]
+	#[arg(short, long, env = "PINGER_FORMAT", global = true)]
+	format: Option<CoordinateFormat>,
+	/// Where to get your position from when sending Pings
+	/// (`nmea:PATH[@BAUD]`, `gpsd[:HOST:PORT]`, `json:PATH`, or `gpx:PATH`),
+	/// instead of entering it each time
+	#[arg(short, long, value_name = "SOURCE", env = "PINGER_SOURCE")]
+	source: Option<LocationSource>,
+	/// The altitude in meters above mean sea level suggested when entering your
+	/// position
+	#[arg(long, value_name = "METERS", env = "PINGER_ALTITUDE")]
+	altitude: Option<f32>,
+	/// The position error in meters suggested when entering your position
+	#[arg(long, value_name = "METERS", env = "PINGER_POSITION_ERROR")]
+	position_error: Option<f32>,
+	/// How precise your position is in sent Pings (exact, `grid:METERS` to
+	/// snap it to a grid, or `blur:METERS` to move it randomly), optionally
+	/// followed by `,altitude:coarsen:METERS` to round the altitude or
+	/// `,altitude:omit` to leave it out [default: exact]
+	#[arg(long, value_name = "PRECISION", env = "PINGER_PRECISION")]
+	precision: Option<Precision>,
+	/// Append received Pings to this file
+	#[arg(short, long, value_name = "FILE", env = "PINGER_EXPORT")]
+	export: Option<PathBuf>,
+	/// The format of the export file (gpx, geojson, or kml) [default:
+	/// determined by its extension]
+	#[arg(long, value_name = "FORMAT", env = "PINGER_EXPORT_FORMAT")]
+	export_format: Option<ExportFormat>,
+	/// The maximum age of received Pings in seconds [default: 900]
+	#[arg(long, value_name = "SECS", env = "PINGER_MAX_AGE")]
+	max_age: Option<u64>,
+	/// The maximum amount of seconds by which the timestamp of received Pings
+	/// may be in the future, to allow for clock differences [default: 60]
+	#[arg(long, value_name = "SECS", env = "PINGER_MAX_SKEW")]
+	max_skew: Option<u64>,
+	/// The number of seconds to wait for the recipient of a Ping to accept or
+	/// reject it and acknowledge the Ping info [default: 120]
+	#[arg(long, value_name = "SECS", env = "PINGER_TIMEOUT")]
+	timeout: Option<u64>,
+	/// How to decide on incoming Ping requests (ask, always, never, or a
+	/// comma-separated list of `window:HH:MM-HH:MM` (UTC) and `rate:N` (per
+	/// hour) conditions for accepting them, followed by `ask` to ask about
+	/// the others instead of rejecting them) [default: ask]
+	#[arg(long, value_name = "POLICY", env = "PINGER_ACCEPT")]
+	accept: Option<AcceptPolicy>,
+	/// Use the full-screen terminal interface instead of the line-based one
+	#[arg(long)]
+	tui: bool,
+	/// Keep an encrypted history of sent and received Pings in this file, with
+	/// the passphrase from `PINGER_HISTORY_PASSPHRASE` or entered at startup
+	#[arg(long, value_name = "FILE", env = "PINGER_HISTORY", global = true)]
+	history: Option<PathBuf>,
+	/// Delete history entries older than this many days
+	#[arg(
+		long,
+		value_name = "DAYS",
+		env = "PINGER_HISTORY_MAX_AGE",
+		global = true
+	)]
+	history_max_age: Option<u64>,
+	/// Keep at most this many history entries, deleting the oldest ones
+	#[arg(
+		long,
+		value_name = "COUNT",
+		env = "PINGER_HISTORY_MAX_ENTRIES",
+		global = true
+	)]
+	history_max_entries: Option<usize>,
+	#[command(subcommand)]
+	command: Option<Command>,
+}
+
+/// A command to run instead of connecting to the server
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// View or delete the history of sent and received Pings
+	#[command(subcommand)]
+	History(history::Command),
+	/// Check the configuration and print the effective settings
+	Config,
+}
+
+/// The user's own location, as given on the command line, as Ping info for
+/// comparison with received Pings
+#[derive(Clone, Copy, Debug)]
+struct MyLocation(PingInfo);
+
+impl FromStr for MyLocation {
+	type Err = String;
+
+	/// Parse a location in any supported format, optionally followed by a comma
+	/// and the position error in meters
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		// the whole string is tried first, because coordinates can contain commas
+		let (coords, err): (Coordinates, f32) = match (s.parse(), s.rsplit_once(',')) {
+			(Ok(coords), _) => (coords, 0.0),
+			(Err(e), Some((coords, err))) => match (coords.parse(), err.trim().parse::<f32>()) {
+				(Ok(coords), Ok(err)) => (coords, err),
+				_ => return Err(format!("{e}")),
+			},
+			(Err(e), None) => return Err(format!("{e}")),
+		};
+
+		PingInfo::new(
+			Timestamp(0),
+			coords.lat,
+			coords.lon,
+			Meters(0.0),
+			Meters(err),
+		)
+		.map(Self)
+		.map_err(|e| format!("{e}"))
+	}
+}
 
 /// A Ping ID, a 2- or 3-digit number
 #[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -84,16 +284,26 @@ enum ServerClientMessage {
 	NoSuchId { id: Id },
 	#[display("Error: {details}")]
 	Error { details: String },
+	#[display("Notice from the server: {details}")]
+	Notice { details: String },
 }
 
 /// A message sent from one client to another
 #[derive(Clone, Debug, Display, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "msg")]
 enum ClientClientMessage {
-	#[display("Ping requested with key {key}")]
-	PingRequest { key: PublicKey },
-	#[display("Ping accepted with key {key}")]
-	AcceptPing { key: PublicKey },
+	#[display("Ping requested with {}key {key}", if *pake { "code-authenticated " } else { "" })]
+	PingRequest {
+		key: PublicKey,
+		#[serde(default, skip_serializing_if = "is_false")]
+		pake: bool,
+	},
+	#[display("Ping accepted with {}key {key}", if *pake { "code-authenticated " } else { "" })]
+	AcceptPing {
+		key: PublicKey,
+		#[serde(default, skip_serializing_if = "is_false")]
+		pake: bool,
+	},
 	#[display("Ping rejected")]
 	RejectPing,
 	#[display("Ping received (ping info is encrypted)")]
@@ -102,6 +312,15 @@ enum ClientClientMessage {
 	PingAck,
 }
 
+/// Check if `b` is `false`, to leave out the default `pake` field
+#[expect(
+	clippy::trivially_copy_pass_by_ref,
+	reason = "serde's skip_serializing_if passes a reference"
+)]
+const fn is_false(b: &bool) -> bool {
+	!*b
+}
+
 /// A message sent by a client to the server or via the server to another client
 #[derive(Clone, Debug, Display, Serialize, Deserialize)]
 #[display("{msg} to {to}")]
@@ -144,12 +363,65 @@ impl<T> Display for OpaqueFmt<T> {
 	}
 }
 
+/// Our side of a key exchange, either a plain one or one authenticated with a
+/// short code
+enum KeyExchange {
+	DiffieHellman(EphemeralSecret),
+	Pake(Box<PakeSecret>),
+}
+
+impl KeyExchange {
+	/// Start a key exchange, authenticated with `code` if given
+	fn new(code: Option<&str>) -> Self {
+		code.map_or_else(
+			|| Self::DiffieHellman(EphemeralSecret::random()),
+			|code| Self::Pake(Box::new(PakeSecret::new(code))),
+		)
+	}
+
+	/// Check if this key exchange is authenticated with a code
+	const fn is_pake(&self) -> bool {
+		matches!(self, Self::Pake(_))
+	}
+
+	/// Get the public key (or PAKE message) to send to the other side
+	fn public_key(&self) -> PublicKey {
+		match self {
+			Self::DiffieHellman(secret) => PublicKey(secret.into()),
+			Self::Pake(secret) => PublicKey(secret.message().to_bytes().into()),
+		}
+	}
+
+	/// Finish the key exchange with the other side's public key (or PAKE
+	/// message, if `pake` is set), getting the shared key and its safety number
+	fn finish(
+		self,
+		theirs: PublicKey,
+		pake: bool,
+	) -> Result<(SharedKey, SafetyNumber), &'static str> {
+		let ours = self.public_key();
+
+		let key = match (self, pake) {
+			(Self::DiffieHellman(secret), false) => secret.diffie_hellman(&theirs.0).into(),
+			(Self::Pake(secret), true) => secret
+				.finish(&PakeMessage::from_bytes(theirs.0.to_bytes()))
+				.map_err(|_| "the key exchange message is invalid")?,
+			(Self::DiffieHellman(_), true) => return Err("they used a code, but you didn't"),
+			(Self::Pake(_), false) => return Err("you used a code, but they didn't"),
+		};
+
+		let number = SafetyNumber::new(&key, ours.0.to_bytes(), theirs.0.to_bytes());
+
+		Ok((key, number))
+	}
+}
+
 /// An incoming Ping info exchange, either waiting for a user decision or the
-/// encrypted Ping info
+/// encrypted Ping info, and whether it is authenticated with a short code
 #[derive(Debug)]
 enum IncomingExchange {
-	Deciding(PublicKey),
-	AwaitingPing(SharedKey),
+	Deciding(PublicKey, bool),
+	AwaitingPing(SharedKey, bool),
 }
 
 /// The outgoing Ping info exchange, either none (if the user hasn't Pinged
@@ -159,7 +431,7 @@ enum IncomingExchange {
 enum OutgoingExchange {
 	#[default]
 	None,
-	AwaitingDecision(Id, PingInfo, OpaqueFmt<EphemeralSecret>),
+	AwaitingDecision(Id, PingInfo, OpaqueFmt<KeyExchange>),
 	AwaitingAck(Id),
 }
 
@@ -173,26 +445,266 @@ impl OutgoingExchange {
 /// An open server connection
 #[derive(Debug, Default)]
 struct Connection {
+	/// The user's own location, if known
+	my_location: Option<MyLocation>,
+	/// The coordinate format for received Pings
+	format: CoordinateFormat,
+	/// The limits on the age of received Pings
+	freshness: Freshness,
+	/// The source of the user's position for sending Pings, if not entered
+	/// manually
+	locator: Option<Locator>,
+	/// The file to which received Pings are appended, and its format
+	export: Option<(PathBuf, ExportFormat)>,
 	/// The outgoing Ping info exchange
 	outgoing: OutgoingExchange,
 	/// The incoming Ping info exchanges from each ID
 	incoming: HashMap<Id, IncomingExchange>,
+	/// The user's own ID, once connected
+	id: Option<Id>,
+	/// The Pings received so far, with their senders
+	received: Vec<(Id, PingInfo)>,
+	/// The messages for the user, if they are shown in the TUI instead of
+	/// being printed
+	log: Option<VecDeque<String>>,
+	/// The history of Ping exchanges, if kept
+	history: Option<History>,
+	/// How long to wait for a response to a sent Ping
+	timeout: Duration,
+	/// When the outgoing Ping info exchange times out
+	deadline: Option<Instant>,
+	/// The altitude suggested when entering the user's position
+	altitude: Option<f32>,
+	/// The position error suggested when entering the user's position
+	position_error: Option<f32>,
+	/// The precision of the position in sent Pings
+	precision: Precision,
+	/// Decides on incoming Ping requests automatically
+	accept: Decider,
+}
+
+impl Connection {
+	/// Show a message to the user
+	fn say(&mut self, msg: String) {
+		match &mut self.log {
+			Some(log) => {
+				if log.len() == MAX_LOG_MESSAGES {
+					log.pop_front();
+				}
+
+				log.push_back(msg);
+			}
+			None => println!("{msg}"),
+		}
+	}
+
+	/// Add a Ping exchange to the history, if it is kept
+	fn record(&mut self, direction: Direction, peer: Id, outcome: Outcome, info: Option<PingInfo>) {
+		let Some(history) = &self.history else {
+			return;
+		};
+
+		let res = history.append(&Entry {
+			ts: Timestamp::now(),
+			direction,
+			peer,
+			outcome,
+			info,
+		});
+
+		if let Err(e) = res {
+			say!(
+				self,
+				"{} {}",
+				"Error saving history:".red().bold(),
+				format!("{e}").red()
+			);
+		}
+	}
+
+	/// Stop the outgoing Ping info exchange, if its recipient didn't respond in
+	/// time
+	fn time_out(&mut self) {
+		let (OutgoingExchange::AwaitingDecision(id, ..) | OutgoingExchange::AwaitingAck(id)) =
+			mem::take(&mut self.outgoing)
+		else {
+			return;
+		};
+
+		say!(
+			self,
+			"{}",
+			format!("Ping to {id} timed out, stopping outgoing ping").bold()
+		);
+		self.record(Direction::Sent, id, Outcome::TimedOut, None);
+	}
+
+	/// Wait until the outgoing Ping info exchange times out (forever if there
+	/// is none)
+	async fn timed_out(&self) {
+		match self.deadline {
+			Some(deadline) if !self.outgoing.is_none() => time::sleep_until(deadline).await,
+			_ => std::future::pending().await,
+		}
+	}
 }
 
 #[tokio::main]
 #[allow(clippy::too_many_lines)]
 async fn main() -> ExitCode {
-	let url = env::args()
-		.nth(1)
-		.unwrap_or_else(|| DEFAULT_URL.to_string());
+	let matches = Args::command().get_matches();
+	let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+	let command = args.command.take();
+
+	let settings = match Settings::load(args, &matches) {
+		Ok(settings) => settings,
+		Err(e) => {
+			println!("{} {}", "Invalid configuration:".red().bold(), e.red());
+			return ExitCode::FAILURE;
+		}
+	};
+
+	let is_config = matches!(command, Some(Command::Config));
 
-	let (mut write, mut read) = tokio_tungstenite::connect_async(url)
-		.await
-		.expect("can't connect to server")
-		.0
-		.split();
+	if is_config {
+		settings.print();
+	}
 
-	let mut conn = Connection::default();
+	if let Err(errors) = settings.validate() {
+		println!("{}", "The configuration is invalid:".red().bold());
+
+		for e in errors {
+			println!("  {}", e.red());
+		}
+
+		return ExitCode::FAILURE;
+	}
+
+	if is_config {
+		println!("{}", "The configuration is valid".green().bold());
+		return ExitCode::SUCCESS;
+	}
+
+	let export = match settings.export.value.clone().map(|path| {
+		let format = settings.export_format.value.or_else(|| {
+			path.extension()
+				.and_then(|ext| ext.to_str())
+				.and_then(ExportFormat::from_extension)
+		});
+
+		(path, format)
+	}) {
+		Some((path, Some(format))) => Some((path, format)),
+		Some((path, None)) => {
+			println!(
+				"{} {}",
+				"Unknown export format:".red().bold(),
+				format!(
+					"use a .gpx, .geojson, or .kml file, or specify the format with \
+					 --export-format ({})",
+					path.display()
+				)
+				.red()
+			);
+
+			return ExitCode::FAILURE;
+		}
+		None => None,
+	};
+
+	let retention = Retention {
+		max_age: settings.history_max_age.value.map(history::days),
+		max_entries: settings.history_max_entries.value,
+	};
+
+	if let Some(Command::History(command)) = command {
+		let Some(path) = settings
+			.history
+			.value
+			.clone()
+			.filter(|path| History::exists(path))
+		else {
+			println!(
+				"{} {}",
+				"No history:".red().bold(),
+				"specify an existing history file with --history FILE".red()
+			);
+
+			return ExitCode::FAILURE;
+		};
+
+		return match open_history(&path, retention) {
+			Ok(history) => command.run(&history, settings.format.value),
+			Err(e) => {
+				println!("{} {}", "Couldn't open history:".red().bold(), e.red());
+				ExitCode::FAILURE
+			}
+		};
+	}
+
+	let history = match settings
+		.history
+		.value
+		.as_deref()
+		.map(|path| open_history(path, retention))
+	{
+		Some(Ok(history)) => Some(history),
+		Some(Err(e)) => {
+			println!("{} {}", "Couldn't open history:".red().bold(), e.red());
+			return ExitCode::FAILURE;
+		}
+		None => None,
+	};
+
+	let connector = match settings.tls_connector() {
+		Ok(connector) => connector,
+		Err(e) => {
+			println!("{} {}", "Invalid TLS settings:".red().bold(), e.red());
+			return ExitCode::FAILURE;
+		}
+	};
+
+	let server = settings.server.value;
+	let (mut write, mut read) = match tokio_tungstenite::connect_async_tls_with_config(
+		server.as_str(),
+		None,
+		false,
+		Some(connector),
+	)
+	.await
+	{
+		Ok((stream, _)) => stream.split(),
+		Err(e) => {
+			println!(
+				"{} {}",
+				format!("Couldn't connect to {server}:").red().bold(),
+				format!("{e}").red()
+			);
+			return ExitCode::FAILURE;
+		}
+	};
+
+	let mut conn = Connection {
+		my_location: settings.location.value,
+		format: settings.format.value,
+		freshness: Freshness {
+			max_age: Duration::from_secs(settings.max_age.value),
+			max_future_skew: Duration::from_secs(settings.max_skew.value),
+		},
+		locator: settings.source.value.map(LocationSource::start),
+		export,
+		history,
+		timeout: Duration::from_secs(settings.timeout.value),
+		altitude: settings.altitude.value,
+		position_error: settings.position_error.value,
+		precision: settings.precision.value,
+		accept: Decider::new(settings.accept.value.clone()),
+		..Connection::default()
+	};
+
+	if settings.tui.value {
+		return tui::run(conn, server, write, read).await;
+	}
 
 	let (line_tx, mut line_rx) = mpsc::unbounded_channel();
 	let (stdin_locked, stdin_cv) = &*Box::leak(Box::new((Mutex::new(false), Condvar::new())));
@@ -223,7 +735,10 @@ async fn main() -> ExitCode {
 		}
 	});
 
-	println!("{}", "To send a ping to an ID, type that ID".blue());
+	println!(
+		"{}",
+		"To send a ping to an ID, type that ID, or c followed by it to send it with a code".blue()
+	);
 
 	loop {
 		select! {
@@ -285,11 +800,13 @@ async fn main() -> ExitCode {
 					PingAction::Accept
 				} else if line.starts_with('r') {
 					PingAction::Reject
+				} else if line.starts_with('c') {
+					PingAction::NewWithCode
 				} else {
 					PingAction::New
 				};
 
-				match line.strip_prefix(['a', 'r']).unwrap_or(&line).parse::<u16>().map(Id) {
+				match line.strip_prefix(['a', 'r', 'c']).unwrap_or(&line).parse::<u16>().map(Id) {
 					Ok(id) => {
 						action.perform(id, &mut conn, &mut write).await;
 						*stdin_locked.lock().expect("lock poisoned") = false;
@@ -302,6 +819,7 @@ async fn main() -> ExitCode {
 					},
 				}
 			}
+			() = conn.timed_out() => conn.time_out(),
 			_ = signal::ctrl_c() => {
 				break;
 			}
@@ -323,11 +841,43 @@ async fn main() -> ExitCode {
 	}
 }
 
+/// Open the history file, asking for the passphrase if it isn't set in the
+/// environment, and delete the entries exceeding the retention limits
+fn open_history(path: &Path, retention: Retention) -> Result<History, String> {
+	let passphrase = if let Ok(passphrase) = env::var(history::PASSPHRASE_VAR) {
+		passphrase
+	} else {
+		let help = format!("Used to encrypt the history in {}", path.display());
+		let prompt = Password::new("History passphrase:").with_help_message(&help);
+
+		if History::exists(path) {
+			prompt.without_confirmation()
+		} else {
+			prompt.with_custom_confirmation_message("Repeat the passphrase:")
+		}
+		.prompt()
+		.map_err(|e| e.to_string())?
+	};
+
+	if passphrase.is_empty() {
+		return Err("the passphrase must not be empty".to_string());
+	}
+
+	let history = History::open(path, &passphrase).map_err(|e| e.to_string())?;
+	history
+		.prune(retention, Timestamp::now())
+		.map_err(|e| e.to_string())?;
+
+	Ok(history)
+}
+
 /// A user action relating to a Ping
 #[derive(Debug)]
 enum PingAction {
 	/// Send a Ping
 	New,
+	/// Send a Ping authenticated with a short code
+	NewWithCode,
 	/// Accept an incoming Ping
 	Accept,
 	/// Reject an incoming Ping
@@ -335,6 +885,15 @@ enum PingAction {
 }
 
 impl PingAction {
+	/// Describe what was done by performing this action
+	const fn past_tense(&self) -> &'static str {
+		match self {
+			Self::New | Self::NewWithCode => "sent",
+			Self::Accept => "accepted",
+			Self::Reject => "rejected",
+		}
+	}
+
 	/// Perform this action
 	async fn perform<W>(self, id: Id, conn: &mut Connection, write: &mut W)
 	where
@@ -342,50 +901,23 @@ impl PingAction {
 		W::Error: ToString,
 	{
 		match self {
-			Self::New => send_ping(id, conn, write).await,
+			Self::New => send_ping(id, false, conn, write).await,
+			Self::NewWithCode => send_ping(id, true, conn, write).await,
 			Self::Accept => {
-				let my_key = EphemeralSecret::random();
-				let pubkey = PublicKey((&my_key).into());
-
-				let Some(exch) = conn.incoming.get_mut(&id) else {
-					println!(
-						"{} {}",
-						format!("Cannot accept ping from {id}:").red().bold(),
-						"No ongoing ping exchange with that ID".red()
-					);
-					return;
-				};
-
-				let IncomingExchange::Deciding(key) = exch else {
-					println!(
-						"{} {}",
-						format!("Cannot accept ping from {id}:").red().bold(),
-						"Not awaiting a decision on the exchange with that ID".red()
-					);
-					return;
-				};
-
-				*exch = IncomingExchange::AwaitingPing(my_key.diffie_hellman(&key.0).into());
-
-				let Ok(acc) = serde_json::to_string(&ClientUpMessage {
-					to: id,
-					msg: ClientClientMessage::AcceptPing { key: pubkey },
-				}) else {
-					println!("{}", "Error serializing message".red().bold());
-					return;
+				let code = match conn.incoming.get(&id) {
+					Some(IncomingExchange::Deciding(_, true)) => match prompt_code() {
+						Some(code) => Some(code),
+						None => return,
+					},
+					_ => None,
 				};
 
-				if let Err(e) = write.send(Message::Text(acc.into())).await {
-					println!(
-						"{} {}",
-						"Error sending acceptation".red().bold(),
-						e.to_string().dimmed()
-					);
-				}
+				accept_ping(id, code.as_deref(), conn, write).await;
 			}
 			Self::Reject => {
 				let Some(exch) = conn.incoming.get_mut(&id) else {
-					println!(
+					say!(
+						conn,
 						"{} {}",
 						format!("Cannot reject ping from {id}:").red().bold(),
 						"No ongoing ping exchange with that ID".red()
@@ -393,8 +925,9 @@ impl PingAction {
 					return;
 				};
 
-				let IncomingExchange::Deciding(_) = exch else {
-					println!(
+				let IncomingExchange::Deciding(..) = exch else {
+					say!(
+						conn,
 						"{} {}",
 						format!("Cannot reject ping from {id}:").red().bold(),
 						"Not awaiting a decision on the exchange with that ID".red()
@@ -406,70 +939,302 @@ impl PingAction {
 					to: id,
 					msg: ClientClientMessage::RejectPing,
 				}) else {
-					println!("{}", "Error serializing message".red().bold());
+					say!(conn, "{}", "Error serializing message".red().bold());
 					return;
 				};
 
 				if let Err(e) = write.send(Message::Text(rej.into())).await {
-					println!(
+					say!(
+						conn,
 						"{} {}",
 						"Error sending rejection".red().bold(),
 						e.to_string().dimmed()
 					);
+					return;
 				}
+
+				conn.record(Direction::Received, id, Outcome::Rejected, None);
 			}
 		}
 	}
 }
 
-/// Send a Ping to `id`
-async fn send_ping<W>(id: Id, conn: &mut Connection, write: &mut W)
+/// Accept the Ping request from `id`, with the `code` it is authenticated with
+/// (if any)
+async fn accept_ping<W>(id: Id, code: Option<&str>, conn: &mut Connection, write: &mut W)
 where
 	W: Sink<Message> + Unpin,
 	W::Error: ToString,
 {
-	let ts = SystemTime::UNIX_EPOCH
-		.elapsed()
-		.expect("it's after 1970")
-		.as_secs();
+	let my_key = KeyExchange::new(code);
+	let pubkey = my_key.public_key();
+	let pake = my_key.is_pake();
 
-	let Ok(lat) = CustomType::new("Latitude: ")
-		.with_help_message("Enter your latitude in degrees (between -90 and 90)")
-		.with_parser(&|s| s.trim().parse().map_err(|_| ()))
-		.with_validator(|v: &f64| {
-			Ok(if (-90.0..=90.0).contains(v) {
-				Validation::Valid
-			} else {
+	let Some(exch) = conn.incoming.get_mut(&id) else {
+		say!(
+			conn,
+			"{} {}",
+			format!("Cannot accept ping from {id}:").red().bold(),
+			"No ongoing ping exchange with that ID".red()
+		);
+		return;
+	};
+
+	let IncomingExchange::Deciding(key, their_pake) = exch else {
+		say!(
+			conn,
+			"{} {}",
+			format!("Cannot accept ping from {id}:").red().bold(),
+			"Not awaiting a decision on the exchange with that ID".red()
+		);
+		return;
+	};
+
+	let (key, number) = match my_key.finish(*key, *their_pake) {
+		Ok(finished) => finished,
+		Err(e) => {
+			say!(
+				conn,
+				"{} {}",
+				format!("Cannot accept ping from {id}:").red().bold(),
+				e.red()
+			);
+			return;
+		}
+	};
+
+	*exch = IncomingExchange::AwaitingPing(key, pake);
+	say_safety_number(conn, &format!("the ping from {id}"), number);
+
+	let Ok(acc) = serde_json::to_string(&ClientUpMessage {
+		to: id,
+		msg: ClientClientMessage::AcceptPing { key: pubkey, pake },
+	}) else {
+		say!(conn, "{}", "Error serializing message".red().bold());
+		return;
+	};
+
+	if let Err(e) = write.send(Message::Text(acc.into())).await {
+		say!(
+			conn,
+			"{} {}",
+			"Error sending acceptation".red().bold(),
+			e.to_string().dimmed()
+		);
+	}
+}
+
+/// Show the safety number of the key exchange for `exchange` (e.g. "the ping
+/// to 42"), for comparing it with the other person
+fn say_safety_number(conn: &mut Connection, exchange: &str, number: SafetyNumber) {
+	say!(
+		conn,
+		"{} {} {}",
+		format!("Safety number for {exchange}:").bold(),
+		number.to_string().cyan().bold(),
+		"(it has to be the same on both sides, otherwise the ping was intercepted)".dimmed()
+	);
+}
+
+/// Send a Ping to `id`, with the user's position from the locator or entered
+/// manually, and authenticated with a code entered by the user if `with_code`
+/// is set
+async fn send_ping<W>(id: Id, with_code: bool, conn: &mut Connection, write: &mut W)
+where
+	W: Sink<Message> + Unpin,
+	W::Error: ToString,
+{
+	let info = if conn.locator.is_some() {
+		locate(conn)
+	} else {
+		prompt_ping_info(conn.altitude, conn.position_error)
+	};
+
+	let Some(info) = info else {
+		return;
+	};
+
+	let code = if with_code {
+		let Some(code) = prompt_code() else {
+			return;
+		};
+
+		Some(code)
+	} else {
+		None
+	};
+
+	request_ping(id, info, code.as_deref(), conn, write).await;
+}
+
+/// Get the user's position from the locator
+fn locate(conn: &mut Connection) -> Option<PingInfo> {
+	let locator = conn.locator.as_ref()?;
+
+	match locator
+		.position()
+		.map_err(|e| e.to_string())
+		.and_then(|position| position.to_ping_info().map_err(|e| e.to_string()))
+	{
+		Ok(info) => {
+			let msg = format!(
+				"{} {}",
+				format!("Using your position from {locator}:").bold(),
+				format!(
+					"{} (±{})",
+					format_coordinates(info.coordinates(), conn.format),
+					Distance(info.err())
+				)
+				.dimmed()
+			);
+			conn.say(msg);
+
+			Some(info)
+		}
+		Err(e) => {
+			say!(
+				conn,
+				"{} {}",
+				"Error sending ping:".red().bold(),
+				format!("couldn't get your position ({e})").red()
+			);
+
+			None
+		}
+	}
+}
+
+/// Request to send a Ping with the given info (with its precision reduced as
+/// configured) to `id`, authenticated with `code` if given
+async fn request_ping<W>(
+	id: Id,
+	info: PingInfo,
+	code: Option<&str>,
+	conn: &mut Connection,
+	write: &mut W,
+) where
+	W: Sink<Message> + Unpin,
+	W::Error: ToString,
+{
+	let info = match conn.precision.apply(info) {
+		Ok(reduced) if conn.precision != Precision::default() => {
+			let msg = format!(
+				"{} {}",
+				format!(
+					"Reducing the precision of your position ({}):",
+					conn.precision
+				)
+				.bold(),
+				format!(
+					"{} (±{})",
+					format_coordinates(reduced.coordinates(), conn.format),
+					Distance(reduced.err())
+				)
+				.dimmed()
+			);
+			conn.say(msg);
+
+			reduced
+		}
+		Ok(info) => info,
+		Err(e) => {
+			say!(
+				conn,
+				"{} {}",
+				"Error sending ping:".red().bold(),
+				format!("couldn't reduce the precision of your position ({e})").red()
+			);
+			return;
+		}
+	};
+
+	let secret = KeyExchange::new(code);
+
+	let Ok(req) = serde_json::to_string(&ClientUpMessage {
+		to: id,
+		msg: ClientClientMessage::PingRequest {
+			key: secret.public_key(),
+			pake: secret.is_pake(),
+		},
+	}) else {
+		say!(conn, "{}", "Error serializing message".red().bold());
+		return;
+	};
+
+	if let Err(e) = write.send(Message::Text(req.into())).await {
+		say!(
+			conn,
+			"{} {}",
+			"Error sending ping request".red().bold(),
+			e.to_string().dimmed()
+		);
+		return;
+	}
+
+	conn.outgoing = OutgoingExchange::AwaitingDecision(id, info, OpaqueFmt(secret));
+	conn.deadline = Some(Instant::now() + conn.timeout);
+}
+
+/// Ask the user for the code agreed on with the other person
+fn prompt_code() -> Option<String> {
+	let Ok(code) = Text::new("Code: ")
+		.with_help_message(
+			"Enter the code you agreed on with the other person (it has to be the same on both \
+			 sides for the ping to be received)",
+		)
+		.with_validator(|s: &str| {
+			Ok(if normalize_code(s).is_empty() {
 				Validation::Invalid(ErrorMessage::Custom(
-					"The latitude must be between -90 and 90 degrees".to_string(),
+					"The code must not be empty".to_string(),
 				))
+			} else {
+				Validation::Valid
 			})
 		})
 		.prompt()
 	else {
-		println!("{}", "IO error while sending ping".red().bold());
-		return;
+		println!("{}", "IO error while entering code".red().bold());
+		return None;
 	};
 
-	let Ok(lon) = CustomType::new("Longitude: ")
-		.with_help_message("Enter your longitude in degrees (between -180 and 180)")
-		.with_parser(&|s| s.trim().parse().map_err(|_| ()))
-		.with_validator(|v: &f64| {
-			Ok(if (-180.0..=180.0).contains(v) {
-				Validation::Valid
-			} else {
-				Validation::Invalid(ErrorMessage::Custom(
-					"The longitude must be between -180 and 180 degrees".to_string(),
-				))
+	Some(code)
+}
+
+/// Ask the user for their position, suggesting the given altitude and position
+/// error
+fn prompt_ping_info(default_alt: Option<f32>, default_err: Option<f32>) -> Option<PingInfo> {
+	let ts = Timestamp::now();
+
+	let Ok(location) = Text::new("Location: ")
+		.with_help_message(
+			"Enter your location as decimal degrees, degrees-minutes-seconds, a geohash, a Plus \
+			 Code, UTM, or MGRS",
+		)
+		.with_validator(|s: &str| {
+			Ok(match s.parse::<Coordinates>() {
+				Ok(_) => Validation::Valid,
+				Err(e) => {
+					Validation::Invalid(ErrorMessage::Custom(format!("Invalid location: {e}")))
+				}
 			})
 		})
 		.prompt()
 	else {
 		println!("{}", "IO error while sending ping".red().bold());
-		return;
+		return None;
 	};
 
-	let Ok(alt) = CustomType::new("Altitude: ")
+	let Ok(coords) = location.parse::<Coordinates>() else {
+		println!("{}", "Invalid location".red().bold());
+		return None;
+	};
+
+	let mut alt_prompt = CustomType::new("Altitude: ");
+	if let Some(alt) = default_alt {
+		alt_prompt = alt_prompt.with_default(alt);
+	}
+
+	let Ok(alt) = alt_prompt
 		.with_help_message("Enter your altitude in meters above mean sea level")
 		.with_parser(&|s| s.trim().parse().map_err(|_| ()))
 		.with_validator(|v: &f32| {
@@ -484,10 +1249,15 @@ where
 		.prompt()
 	else {
 		println!("{}", "IO error while sending ping".red().bold());
-		return;
+		return None;
 	};
 
-	let Ok(err) = CustomType::new("Position Error: ")
+	let mut err_prompt = CustomType::new("Position Error: ");
+	if let Some(err) = default_err {
+		err_prompt = err_prompt.with_default(err);
+	}
+
+	let Ok(err) = err_prompt
 		.with_help_message("Enter your position error in meters")
 		.with_parser(&|s| s.trim().parse().map_err(|_| ()))
 		.with_validator(|v: &f32| {
@@ -502,39 +1272,20 @@ where
 		.prompt()
 	else {
 		println!("{}", "IO error while sending ping".red().bold());
-		return;
-	};
-
-	let info = PingInfo {
-		ts: Timestamp(ts),
-		lat: Degrees(lat),
-		lon: Degrees(lon),
-		alt: Meters(alt),
-		err: Meters(err),
+		return None;
 	};
 
-	let secret = EphemeralSecret::random();
-
-	let Ok(req) = serde_json::to_string(&ClientUpMessage {
-		to: id,
-		msg: ClientClientMessage::PingRequest {
-			key: PublicKey((&secret).into()),
-		},
-	}) else {
-		println!("{}", "Error serializing message".red().bold());
-		return;
-	};
-
-	if let Err(e) = write.send(Message::Text(req.into())).await {
-		println!(
-			"{} {}",
-			"Error sending ping request".red().bold(),
-			e.to_string().dimmed()
-		);
-		return;
+	match PingInfo::new(ts, coords.lat, coords.lon, Meters(alt), Meters(err)) {
+		Ok(info) => Some(info),
+		Err(e) => {
+			println!(
+				"{} {}",
+				"Error sending ping:".red().bold(),
+				format!("{e}").red()
+			);
+			None
+		}
 	}
-
-	conn.outgoing = OutgoingExchange::AwaitingDecision(id, info, OpaqueFmt(secret));
 }
 
 /// Handle an incoming websocket message
@@ -550,23 +1301,66 @@ where
 	match msg {
 		ClientDownMessage::FromClient {
 			from,
-			msg: ClientClientMessage::PingRequest { key },
+			msg: ClientClientMessage::PingRequest { key, pake },
 		} => {
-			println!(
+			conn.incoming
+				.insert(from, IncomingExchange::Deciding(key, pake));
+
+			let decision = conn
+				.incoming
+				.get(&from)
+				.and_then(|exch| conn.accept.decide(exch, Timestamp::now()));
+
+			let (action, reason) = match decision {
+				Some(Decision::Accept(reason)) => (PingAction::Accept, reason),
+				Some(Decision::Reject(reason)) => (PingAction::Reject, reason),
+				Some(Decision::Ask(reason)) => {
+					if let Some(reason) = reason {
+						say!(
+							conn,
+							"{}",
+							format!("Not automatically accepting the ping from {from} ({reason})")
+								.bold()
+						);
+					}
+
+					// the TUI has its own keys for this
+					if conn.log.is_none() {
+						println!(
+							"{}",
+							format!(
+								"To accept the ping from {from}, type {}{}, to reject it, type {}",
+								format!("a{from}").blue().italic(),
+								if pake {
+									" (and enter the code you agreed on)"
+								} else {
+									""
+								},
+								format!("r{from}").blue().italic()
+							)
+							.bold()
+						);
+					}
+
+					return;
+				}
+				None => return,
+			};
+
+			say!(
+				conn,
 				"{}",
 				format!(
-					"To accept the ping from {from}, type {}, to reject it, type {}",
-					format!("a{from}").blue().italic(),
-					format!("r{from}").blue().italic()
+					"Automatically {} the ping from {from} ({reason})",
+					action.past_tense()
 				)
 				.bold()
 			);
-
-			conn.incoming.insert(from, IncomingExchange::Deciding(key));
+			action.perform(from, conn, write).await;
 		}
 		ClientDownMessage::FromClient {
 			from,
-			msg: ClientClientMessage::AcceptPing { key },
+			msg: ClientClientMessage::AcceptPing { key, pake },
 		} => match conn.outgoing {
 			OutgoingExchange::AwaitingDecision(id, ..) if id == from => {
 				let OutgoingExchange::AwaitingDecision(_, info, OpaqueFmt(my_key)) =
@@ -574,56 +1368,68 @@ where
 				else {
 					unreachable!()
 				};
-				let key = my_key.diffie_hellman(&key.0);
-
-				match (|| {
-					Ok::<_, &str>(
-						write.send(Message::Text(
-							serde_json::to_string(&ClientUpMessage {
-								to: from,
-								msg: ClientClientMessage::Ping {
-									info: EncryptedPingInfo(
-										info.encrypt(key)
-											.map_err(|_| "error encrypting ping info")?,
-									),
-								},
-							})
-							.map_err(|_| "failed to serialize message")?
-							.into(),
-						)),
-					)
-				})() {
-					Ok(fut) => {
-						if let Err(e) = fut.await {
-							println!(
+				let (key, number) = match my_key.finish(key, pake) {
+					Ok(finished) => finished,
+					Err(e) => {
+						say!(conn, "{} {}", "Error sending ping:".red().bold(), e.red());
+						conn.outgoing = OutgoingExchange::None;
+						conn.record(Direction::Sent, from, Outcome::Failed, None);
+						return;
+					}
+				};
+				say_safety_number(conn, &format!("the ping to {from}"), number);
+
+				let ping = info
+					.encrypt(key)
+					.map_err(|_| "error encrypting ping info")
+					.and_then(|info| {
+						serde_json::to_string(&ClientUpMessage {
+							to: from,
+							msg: ClientClientMessage::Ping {
+								info: EncryptedPingInfo(info),
+							},
+						})
+						.map_err(|_| "failed to serialize message")
+					});
+
+				match ping {
+					Ok(ping) => {
+						if let Err(e) = write.send(Message::Text(ping.into())).await {
+							say!(
+								conn,
 								"{} {}",
 								"Error sending ping".red().bold(),
 								e.to_string().dimmed()
 							);
 							conn.outgoing = OutgoingExchange::None;
+							conn.record(Direction::Sent, from, Outcome::Failed, None);
 						}
 					}
 					Err(e) => {
-						println!("{} {}", "Error sending ping".red().bold(), e.dimmed());
+						say!(conn, "{} {}", "Error sending ping".red().bold(), e.dimmed());
 						conn.outgoing = OutgoingExchange::None;
+						conn.record(Direction::Sent, from, Outcome::Failed, None);
 					}
 				}
 			}
-			OutgoingExchange::AwaitingDecision(id, ..) => println!(
+			OutgoingExchange::AwaitingDecision(id, ..) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acceptation from {from}")
 					.red()
 					.bold(),
 				format!("(a decision is expected from {id})").dimmed()
 			),
-			OutgoingExchange::AwaitingAck(_) => println!(
+			OutgoingExchange::AwaitingAck(_) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acceptation from {from}")
 					.red()
 					.bold(),
 				"(a ping is being sent, but a decision is not expected)".dimmed()
 			),
-			OutgoingExchange::None => println!(
+			OutgoingExchange::None => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acknowledgement from {from}")
 					.red()
@@ -637,22 +1443,26 @@ where
 		} => match conn.outgoing {
 			OutgoingExchange::AwaitingDecision(id, ..) if id == from => {
 				conn.outgoing = OutgoingExchange::None;
+				conn.record(Direction::Sent, from, Outcome::Rejected, None);
 			}
-			OutgoingExchange::AwaitingDecision(id, ..) => println!(
+			OutgoingExchange::AwaitingDecision(id, ..) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected rejection from {from}")
 					.red()
 					.bold(),
 				format!("(a decision is expected from {id})").dimmed()
 			),
-			OutgoingExchange::AwaitingAck(_) => println!(
+			OutgoingExchange::AwaitingAck(_) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected rejection from {from}")
 					.red()
 					.bold(),
 				"(a ping is being sent, but a decision is not expected)".dimmed()
 			),
-			OutgoingExchange::None => println!(
+			OutgoingExchange::None => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acknowledgement from {from}")
 					.red()
@@ -666,22 +1476,26 @@ where
 		} => match conn.outgoing {
 			OutgoingExchange::AwaitingAck(id) if id == from => {
 				conn.outgoing = OutgoingExchange::None;
+				conn.record(Direction::Sent, from, Outcome::Delivered, None);
 			}
-			OutgoingExchange::AwaitingAck(id) => println!(
+			OutgoingExchange::AwaitingAck(id) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acknowledgement from {from}")
 					.red()
 					.bold(),
 				format!("(an acknowledgement is expected from {id})").dimmed()
 			),
-			OutgoingExchange::AwaitingDecision(..) => println!(
+			OutgoingExchange::AwaitingDecision(..) => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acknowledgement from {from}")
 					.red()
 					.bold(),
 				"(a ping is being sent, but an acknowledgement is not expected)".dimmed()
 			),
-			OutgoingExchange::None => println!(
+			OutgoingExchange::None => say!(
+				conn,
 				"{} {}",
 				format!("Received unexpected acknowledgement from {from}")
 					.red()
@@ -693,10 +1507,16 @@ where
 			from,
 			msg: ClientClientMessage::Ping { info },
 		} => {
-			let key = match conn.incoming.get(&from) {
-				Some(IncomingExchange::AwaitingPing(key)) => *key,
-				Some(IncomingExchange::Deciding(_)) => {
-					println!(
+			let received = Timestamp::now();
+			// the exchange ends with the first Ping, whether it's valid or not, so
+			// accepting a request only lets the sender send one Ping (and replays
+			// of it are unexpected)
+			let (key, pake) = match conn.incoming.remove(&from) {
+				Some(IncomingExchange::AwaitingPing(key, pake)) => (key, pake),
+				Some(deciding @ IncomingExchange::Deciding(..)) => {
+					conn.incoming.insert(from, deciding);
+					say!(
+						conn,
 						"{} {}",
 						format!("Received unexpected ping from {from}").red().bold(),
 						"(a ping exchange is ongoing with that id, but a ping was not expected)"
@@ -705,7 +1525,8 @@ where
 					return;
 				}
 				None => {
-					println!(
+					say!(
+						conn,
 						"{} {}",
 						format!("Received unexpected ping from {from}").red().bold(),
 						"(no ongoing ping exchange with that id)".dimmed()
@@ -714,114 +1535,163 @@ where
 				}
 			};
 
-			conn.incoming.remove(&from);
+			let decrypted = PingInfo::decrypt(info.0, &key)
+				.and_then(|info| conn.freshness.check(info.ts(), received).map(|()| info));
 
-			let Ok(info) = PingInfo::decrypt(info.0, key) else {
-				println!("{}", "Could not decrypt ping info".red().bold());
-				return;
+			let info = match decrypted {
+				Ok(info) => info,
+				Err(e) => {
+					say!(
+						conn,
+						"{} {}",
+						format!("Rejected ping info from {from}:").red().bold(),
+						match e {
+							pinger::Error::BadMagic => {
+								"The ping is malformed (it doesn't start with the magic number)"
+									.to_string()
+							}
+							pinger::Error::BadLength | pinger::Error::BadEncoding => {
+								"The ping is malformed (it has an invalid length or encoding)"
+									.to_string()
+							}
+							pinger::Error::Authentication if pake => {
+								"The ping could not be authenticated (the codes you entered may \
+								 not match, or it may have been modified)"
+									.to_string()
+							}
+							pinger::Error::Authentication => {
+								"The ping could not be authenticated (it may have been modified or \
+								 encrypted with the wrong key)"
+									.to_string()
+							}
+							pinger::Error::InvalidValue(field) => {
+								format!("The ping contains an invalid {field}")
+							}
+							pinger::Error::Stale(age) => {
+								format!(
+									"The ping is stale (it was sent {} second(s) ago)",
+									age.as_secs()
+								)
+							}
+							pinger::Error::FromFuture(skew) => format!(
+								"The ping is from the future (it was sent {} second(s) from now, \
+								 check both devices' clocks)",
+								skew.as_secs()
+							),
+							e => format!("{e}"),
+						}
+						.red()
+					);
+
+					conn.record(Direction::Received, from, Outcome::Failed, None);
+					return;
+				}
 			};
 
-			println!(
+			say!(
+				conn,
 				"{} {}",
 				format!(
-					"{from} was at {:.4}°, {:.4}° {} second(s) ago",
-					info.lat.0,
-					info.lon.0,
-					(SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(info.ts.0)))
-						.unwrap_or_else(|| SystemTime::now() + Duration::from_mins(1))
-						.elapsed()
-						.map_or_else(
-							|err| format!("-{}", err.duration().as_secs()),
-							|d| d.as_secs().to_string(),
-						)
+					"{from} was at {} {} second(s) ago",
+					format_coordinates(info.coordinates(), conn.format),
+					// timestamps slightly in the future (within `--max-skew`) are shown as
+					// just now
+					received.0.saturating_sub(info.ts().0)
 				)
 				.bold(),
 				format!(
 					"(ts = {} s, lat = {}°, lon = {}°, alt = {} mAMSL, err = {} m)",
-					info.ts.0, info.lat.0, info.lon.0, info.alt.0, info.err.0
+					info.ts().0,
+					info.lat().0,
+					info.lon().0,
+					info.alt().0,
+					info.err().0
 				)
 				.dimmed()
 			);
 
+			if let Some(my_location) = conn.my_location {
+				let relative = info.relative_to(&my_location.0);
+
+				say!(
+					conn,
+					"{}",
+					format!(
+						"{from} was {} {} of you (±{})",
+						Distance(relative.distance),
+						relative.direction,
+						Distance(relative.err)
+					)
+					.bold()
+				);
+			}
+
+			conn.received.push((from, info));
+			conn.record(Direction::Received, from, Outcome::Delivered, Some(info));
+
+			if let Some((path, format)) = &conn.export {
+				let ping = ReceivedPing {
+					info,
+					from: &from.to_string(),
+					received,
+				};
+
+				if let Err(e) = export::append(path, *format, &ping) {
+					say!(
+						conn,
+						"{} {}",
+						format!("Error exporting ping to {}:", path.display())
+							.red()
+							.bold(),
+						format!("{e}").red()
+					);
+				}
+			}
+
 			let Ok(ack) = serde_json::to_string(&ClientUpMessage {
 				to: from,
 				msg: ClientClientMessage::PingAck,
 			}) else {
-				println!("{}", "Error serializing message".red().bold());
+				say!(conn, "{}", "Error serializing message".red().bold());
 				return;
 			};
 
 			if let Err(e) = write.send(Message::Text(ack.into())).await {
-				println!(
+				say!(
+					conn,
 					"{} {}",
 					"Error sending acknowledgement".red().bold(),
 					e.to_string().dimmed()
 				);
 			}
 		}
+		ClientDownMessage::FromServer {
+			msg: ServerClientMessage::Connected { id },
+		} => conn.id = Some(id),
 		ClientDownMessage::FromServer {
 			msg: ServerClientMessage::NoSuchId { id },
 		} if !conn.outgoing.is_none() => {
 			conn.outgoing = OutgoingExchange::None;
-			println!(
+			say!(
+				conn,
 				"{}",
 				format!("Id {id} not found, stopping outgoing ping").bold()
 			);
+			conn.record(Direction::Sent, id, Outcome::Failed, None);
 		}
 
 		_ => (),
 	}
 }
 
-/// Serde support for the public key
-mod serde_public_key {
-	use core::{
-		fmt::{Formatter, Result as FmtResult},
-		str,
-	};
-
-	use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
-	use serde::{
-		Deserialize, Deserializer, Serializer,
-		de::{Error as DeError, Expected, Unexpected},
-		ser::Error as SerError,
-	};
-
-	/// Serialize the public key
-	pub fn serialize<S: Serializer>(val: &pinger::PublicKey, ser: S) -> Result<S::Ok, S::Error> {
-		let mut buf = [0u8; 43];
-
-		let n = URL_SAFE_NO_PAD
-			.encode_slice(val, &mut buf)
-			.map_err(|_| SerError::custom("failed to base64-encode"))?;
-
-		ser.serialize_str(
-			str::from_utf8(&buf[..n])
-				.map_err(|_| SerError::custom("failed to create base64 string"))?,
+/// Format the coordinates in the given format, falling back to decimal degrees
+/// if the format can't represent them
+fn format_coordinates(coords: Coordinates, format: CoordinateFormat) -> String {
+	coords
+		.format(format)
+		.or_else(|_| coords.format(CoordinateFormat::Decimal))
+		.map_or_else(
+			|_| format!("{}°, {}°", coords.lat.0, coords.lon.0),
+			|formatted| formatted.to_string(),
 		)
-	}
-
-	/// Deserialize a public key
-	pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<pinger::PublicKey, D::Error> {
-		struct Expected32ByteSlice;
-
-		impl Expected for Expected32ByteSlice {
-			fn fmt(&self, f: &mut Formatter) -> FmtResult {
-				write!(f, "a base64-encoded 32-byte slice")
-			}
-		}
-
-		let str = <&str as Deserialize>::deserialize(de)?;
-		let mut buf = [0u8; 32];
-
-		let n = URL_SAFE_NO_PAD
-			.decode_slice(str, &mut buf)
-			.map_err(|_| DeError::invalid_value(Unexpected::Str(str), &Expected32ByteSlice))?;
-
-		if n != 32 {
-			return Err(DeError::invalid_length(n, &Expected32ByteSlice));
-		}
-
-		Ok(pinger::PublicKey::from(buf))
-	}
 }
@@ -0,0 +1,322 @@
+//! Deciding on incoming Ping requests automatically
+//!
+//! An accept policy is either `ask` (the default), `always`, `never`, or a
+//! comma-separated list of conditions under which requests are accepted
+//! automatically:
+//!
+//! - `window:HH:MM-HH:MM` accepts requests only during that time of day (in
+//!   UTC, possibly wrapping around midnight)
+//! - `rate:N` accepts at most `N` requests per hour
+//!
+//! Requests which don't meet all of the conditions are rejected, or left for
+//! the user to decide on if the list also contains `ask`, e.g.
+//! `window:06:00-18:00,rate:20,ask`.
+//!
+//! Requests authenticated with a short code are always left for the user to
+//! decide on (unless the policy is `never`), because the code has to be entered
+//! to accept them.
+
+use std::{
+	collections::VecDeque,
+	fmt::{Display, Formatter, Result as FmtResult},
+	str::FromStr,
+};
+
+use pinger::Timestamp;
+
+use crate::IncomingExchange;
+
+/// The number of seconds in the period over which `rate:N` is counted
+const HOUR: u64 = 60 * 60;
+
+/// The number of seconds in a day
+const DAY: u64 = 24 * HOUR;
+
+/// How incoming Ping requests are decided on
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AcceptPolicy {
+	/// Ask the user to accept or reject each request
+	#[default]
+	Ask,
+	/// Accept all requests
+	Always,
+	/// Reject all requests
+	Never,
+	/// Accept requests which meet all of the conditions, and reject the others
+	/// or ask the user about them
+	When {
+		/// The conditions for accepting a request
+		conditions: Vec<Condition>,
+		/// Whether to ask the user about requests which don't meet the
+		/// conditions, instead of rejecting them
+		ask: bool,
+	},
+}
+
+impl Display for AcceptPolicy {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			Self::Ask => f.write_str("ask"),
+			Self::Always => f.write_str("always"),
+			Self::Never => f.write_str("never"),
+			Self::When { conditions, ask } => {
+				for (i, condition) in conditions.iter().enumerate() {
+					if i > 0 {
+						f.write_str(",")?;
+					}
+
+					write!(f, "{condition}")?;
+				}
+
+				if *ask {
+					f.write_str(",ask")?;
+				}
+
+				Ok(())
+			}
+		}
+	}
+}
+
+impl FromStr for AcceptPolicy {
+	type Err = String;
+
+	/// Parse `ask`, `always`, `never`, or a comma-separated list of conditions
+	/// optionally followed by `ask`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim().to_ascii_lowercase();
+
+		match s.as_str() {
+			"ask" => return Ok(Self::Ask),
+			"always" => return Ok(Self::Always),
+			"never" => return Ok(Self::Never),
+			_ => (),
+		}
+
+		let mut conditions = Vec::new();
+		let mut ask = false;
+
+		for rule in s.split(',').map(str::trim) {
+			match rule {
+				"ask" if !ask => ask = true,
+				"ask" => return Err("`ask` is given more than once".to_string()),
+				"always" | "never" => {
+					return Err(format!("`{rule}` can't be combined with other rules"));
+				}
+				_ => conditions.push(rule.parse()?),
+			}
+		}
+
+		if conditions.is_empty() {
+			return Err(format!(
+				"unknown accept policy `{s}` (expected ask, always, never, or a list of \
+				 conditions)"
+			));
+		}
+
+		Ok(Self::When { conditions, ask })
+	}
+}
+
+/// A condition for accepting a Ping request automatically
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+	/// The request is received during this time of day
+	Window(Window),
+	/// Fewer than this many requests were accepted automatically in the last
+	/// hour
+	Rate(u32),
+}
+
+impl Display for Condition {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			Self::Window(window) => write!(f, "window:{window}"),
+			Self::Rate(n) => write!(f, "rate:{n}"),
+		}
+	}
+}
+
+impl FromStr for Condition {
+	type Err = String;
+
+	/// Parse `window:HH:MM-HH:MM` or `rate:N`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once(':') {
+			Some(("window", window)) => Ok(Self::Window(window.parse()?)),
+			Some(("rate", n)) => match n.trim().parse() {
+				Ok(0) => Err("`rate:0` never accepts anything, use `never` instead".to_string()),
+				Ok(n) => Ok(Self::Rate(n)),
+				Err(e) => Err(format!("invalid rate `{n}`: {e}")),
+			},
+			_ => Err(format!(
+				"unknown accept rule `{s}` (expected ask, window:HH:MM-HH:MM, or rate:N)"
+			)),
+		}
+	}
+}
+
+/// A daily time window in UTC, from its start (inclusive) to its end
+/// (exclusive)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+	/// The start of the window, in minutes since midnight
+	start: u16,
+	/// The end of the window, in minutes since midnight
+	end: u16,
+}
+
+impl Window {
+	/// Check if the window contains the time of day of `ts`
+	pub const fn contains(self, ts: Timestamp) -> bool {
+		let minute = (ts.0 % DAY / 60) as u16;
+
+		if self.start < self.end {
+			self.start <= minute && minute < self.end
+		} else {
+			self.start <= minute || minute < self.end
+		}
+	}
+}
+
+impl Display for Window {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{:02}:{:02}-{:02}:{:02}",
+			self.start / 60,
+			self.start % 60,
+			self.end / 60,
+			self.end % 60
+		)
+	}
+}
+
+impl FromStr for Window {
+	type Err = String;
+
+	/// Parse `HH:MM-HH:MM`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let time = |time: &str| {
+			let (h, m) = time.trim().split_once(':')?;
+			let (h, m) = (h.parse::<u16>().ok()?, m.parse::<u16>().ok()?);
+			(h < 24 && m < 60).then_some(h * 60 + m)
+		};
+
+		let invalid = || format!("invalid time window `{s}` (expected HH:MM-HH:MM)");
+		let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+		let (start, end) = (
+			time(start).ok_or_else(invalid)?,
+			time(end).ok_or_else(invalid)?,
+		);
+
+		if start == end {
+			return Err(format!("the time window `{s}` is empty"));
+		}
+
+		Ok(Self { start, end })
+	}
+}
+
+/// Why a Ping request was decided on the way it was
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+	/// The policy accepts or rejects all requests, or the request met all of
+	/// its conditions
+	Policy(String),
+	/// The request was received outside of the time window
+	Outside(Window),
+	/// Too many requests were already accepted in the last hour
+	Rate(u32),
+	/// The request is authenticated with a code, which the user has to enter
+	Code,
+}
+
+impl Display for Reason {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			Self::Policy(policy) => write!(f, "accept policy: {policy}"),
+			Self::Outside(window) => write!(f, "outside of {window} UTC"),
+			Self::Rate(n) => write!(f, "already accepted {n} in the last hour"),
+			Self::Code => f.write_str("it has to be accepted with a code"),
+		}
+	}
+}
+
+/// A decision on an incoming Ping request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+	/// Accept the request
+	Accept(Reason),
+	/// Reject the request
+	Reject(Reason),
+	/// Leave the request for the user to decide on, possibly because it
+	/// didn't meet a condition of the policy
+	Ask(Option<Reason>),
+}
+
+/// Decides on incoming Ping requests according to an accept policy
+#[derive(Debug, Default)]
+pub struct Decider {
+	/// The policy
+	policy: AcceptPolicy,
+	/// When requests were accepted automatically in the last hour, oldest
+	/// first
+	accepted: VecDeque<Timestamp>,
+}
+
+impl Decider {
+	/// Create a new decider for `policy`
+	pub const fn new(policy: AcceptPolicy) -> Self {
+		Self {
+			policy,
+			accepted: VecDeque::new(),
+		}
+	}
+
+	/// Decide on an incoming Ping info exchange at `now`
+	///
+	/// Returns `None` if the exchange isn't waiting for a decision. Requests
+	/// authenticated with a code are never accepted automatically, since the
+	/// user has to enter the code.
+	pub fn decide(&mut self, exchange: &IncomingExchange, now: Timestamp) -> Option<Decision> {
+		let IncomingExchange::Deciding(_, pake) = *exchange else {
+			return None;
+		};
+
+		while self
+			.accepted
+			.front()
+			.is_some_and(|ts| ts.0.saturating_add(HOUR) <= now.0)
+		{
+			self.accepted.pop_front();
+		}
+
+		let (conditions, ask) = match &self.policy {
+			AcceptPolicy::Ask => return Some(Decision::Ask(None)),
+			AcceptPolicy::Always if pake => return Some(Decision::Ask(Some(Reason::Code))),
+			AcceptPolicy::Always => {
+				return Some(Decision::Accept(Reason::Policy(self.policy.to_string())));
+			}
+			AcceptPolicy::Never => {
+				return Some(Decision::Reject(Reason::Policy(self.policy.to_string())));
+			}
+			AcceptPolicy::When { conditions, ask } => (conditions, *ask),
+		};
+
+		let unmet = conditions.iter().find_map(|condition| match *condition {
+			Condition::Window(window) => (!window.contains(now)).then_some(Reason::Outside(window)),
+			Condition::Rate(n) => (self.accepted.len() >= n as usize).then_some(Reason::Rate(n)),
+		});
+
+		Some(match unmet {
+			Some(reason) if ask => Decision::Ask(Some(reason)),
+			Some(reason) => Decision::Reject(reason),
+			None if pake => Decision::Ask(Some(Reason::Code)),
+			None => {
+				self.accepted.push_back(now);
+				Decision::Accept(Reason::Policy(self.policy.to_string()))
+			}
+		})
+	}
+}
@@ -0,0 +1,946 @@
+#![cfg(test)]
+
+use std::{collections::VecDeque, env, fs, process, time::Duration};
+
+use clap::{CommandFactory, FromArgMatches};
+use futures_util::sink;
+use pinger::{
+	Degrees, EphemeralSecret, Meters, PingInfo, SharedKey, Timestamp,
+	export::{ExportFormat, ReceivedPing},
+	geo::Coordinates,
+	privacy::{Altitude, Horizontal, Precision},
+};
+use time::OffsetDateTime;
+
+use crate::{
+	Args, ClientClientMessage, ClientDownMessage, ClientUpMessage, Connection, EncryptedPingInfo,
+	Id, IncomingExchange, KeyExchange, MAX_LOG_MESSAGES, MyLocation, PublicKey,
+	config::{Origin, Settings},
+	export, handle_message,
+	history::{Direction, Entry, History, HistoryError, Outcome, Retention},
+	location::{
+		FileFormat, LocationSource, Position, SourceError, UERE, UNKNOWN_ERR,
+		file::{parse_gpx, parse_json},
+		gpsd::GpsdParser,
+		nmea::NmeaParser,
+	},
+	policy::{AcceptPolicy, Condition, Decider, Decision, Reason},
+	tui::plot_position,
+};
+
+/// NMEA output recorded from a receiver getting a fix, starting mid-sentence
+/// and containing a corrupted sentence
+const TRACK: &str = include_str!("../tests/fixtures/track.nmea");
+
+/// Check that `a` and `b` are within about 1 cm of each other
+fn assert_close(a: Coordinates, b: Coordinates) {
+	assert!(
+		(a.lat.0 - b.lat.0).abs() < 1e-7 && (a.lon.0 - b.lon.0).abs() < 1e-7,
+		"{a:?} != {b:?}"
+	);
+}
+
+#[test]
+fn nmea_recording() {
+	let mut parser = NmeaParser::default();
+	let mut fixes = 0;
+	let mut errors = 0;
+
+	for line in TRACK.lines() {
+		match parser.feed(line) {
+			Ok(true) => fixes += 1,
+			Ok(false) => (),
+			Err(_) => errors += 1,
+		}
+
+		if fixes == 0 {
+			assert_eq!(parser.position(), None);
+		}
+	}
+
+	// `RMC` and `GGA` for 9 seconds with a fix, and a partial and a corrupted
+	// sentence
+	assert_eq!(fixes, 18);
+	assert_eq!(errors, 2);
+
+	let position = parser.position().unwrap();
+	assert_eq!(position.ts, Some(Timestamp(1_715_675_401)));
+	assert_close(
+		position.coords,
+		Coordinates::new(46.0 + 3.0784 / 60.0, 14.0 + 30.3283 / 60.0),
+	);
+	assert_eq!(position.alt, Some(296.5));
+	assert_eq!(position.err, Some(0.8 * UERE));
+}
+
+#[test]
+fn nmea_sentences() {
+	let mut parser = NmeaParser::default();
+
+	// no fix
+	assert_eq!(
+		parser.feed("$GNGGA,235959.00,,,,,0,00,99.99,,,,,,*79"),
+		Ok(false)
+	);
+	assert_eq!(parser.position(), None);
+
+	// `GGA` without a date, and without a checksum
+	assert_eq!(
+		parser.feed("$GNGGA,235959.5,3352.1280,S,15112.6110,W,2,07,1.9,12,M,,M,,"),
+		Ok(true)
+	);
+	// ... is assumed to be from the day closest to the current time (here
+	// shortly after and 11 hours before midnight on 2024-05-14)
+	let at = |now| parser.position_at(OffsetDateTime::from_unix_timestamp(now).unwrap());
+	let position = at(1_715_644_830).unwrap();
+	assert_eq!(position.ts, Some(Timestamp(1_715_644_799)));
+	assert_eq!(
+		at(1_715_605_200).unwrap().ts,
+		Some(Timestamp(1_715_644_799))
+	);
+	assert_close(
+		position.coords,
+		Coordinates::new(-(33.0 + 52.128 / 60.0), -(151.0 + 12.611 / 60.0)),
+	);
+	assert_eq!(position.alt, Some(12.0));
+	assert_eq!(position.err, Some(1.9 * UERE));
+
+	// `RMC` provides the date, `GSA` the HDOP
+	assert_eq!(
+		parser.feed("$GNRMC,235959.50,A,3352.1280,S,15112.6110,W,,,311299,,,D*43"),
+		Ok(true)
+	);
+	assert_eq!(
+		parser.feed("$GNGSA,A,2,05,07,12,,,,,,,,,,2.4,1.6,1.8*24"),
+		Ok(false)
+	);
+	let position = parser.position().unwrap();
+	assert_eq!(position.ts, Some(Timestamp(946_684_799)));
+	assert_eq!(position.err, Some(1.6 * UERE));
+
+	// the date changes at midnight, before the next `RMC` sentence
+	assert_eq!(
+		parser.feed("$GNGGA,000001.00,3352.1280,S,15112.6110,W,2,07,1.9,12,M,,M,,"),
+		Ok(true)
+	);
+	assert_eq!(parser.position().unwrap().ts, Some(Timestamp(946_684_801)));
+
+	// the last fix is kept after it's lost
+	assert_eq!(
+		parser.feed("$GNRMC,000002.00,V,,,,,,,010100,,,N*61"),
+		Ok(false)
+	);
+	assert_eq!(parser.position().unwrap().ts, Some(Timestamp(946_684_801)));
+
+	// other and proprietary sentences are ignored
+	assert_eq!(parser.feed("$GPVTG,78.3,T,,M,0.8,N,1.5,K,A*3D"), Ok(false));
+	assert_eq!(parser.feed("$PUBX,00,000002.00*33"), Ok(false));
+}
+
+#[test]
+fn nmea_errors() {
+	let mut parser = NmeaParser::default();
+
+	for sentence in [
+		"",
+		"GPGGA,083001.00,4603.0784,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,",
+		"$GPGGA,083001.00,4603.0784,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,*6B",
+		"$GPGGA,083001.00,4603.0784,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,*XY",
+		"$GPGGA,083001.00,4603.0784,X,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,",
+		"$GPGGA,083001.00,4675.0000,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,",
+		"$GPGGA,083001.00,9100.0000,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,",
+		"$GPGGA,256001.00,4603.0784,N,01430.3283,E,1,10,0.8,296.5,M,46.9,M,,",
+		"$GPGGA,083001.00,4603.0784,N,01430.3283,E,1,10,0.8,high,M,46.9,M,,",
+		"$GPRMC,083001.00,A,4603.0784,N,01430.3283,E,0.8,78.3,311324,,,A",
+	] {
+		assert!(parser.feed(sentence).is_err(), "{sentence:?} was accepted");
+	}
+
+	assert_eq!(parser.position(), None);
+}
+
+#[test]
+fn gpsd_reports() {
+	let mut parser = GpsdParser::default();
+
+	assert_eq!(
+		parser.feed(r#"{"class":"VERSION","release":"3.25","rev":"3.25","proto_major":3}"#),
+		Ok(None)
+	);
+	assert_eq!(
+		parser.feed(r#"{"class":"TPV","device":"/dev/ttyACM0","mode":1}"#),
+		Ok(None)
+	);
+	assert_eq!(parser.feed(r#"{"class":"SKY","hdop":1.5}"#), Ok(None));
+
+	// the HDOP is used if there are no error estimates
+	let position = parser
+		.feed(
+			r#"{"class":"TPV","mode":2,"time":"2024-05-14T08:30:01.000Z","lat":46.05,"lon":14.5}"#,
+		)
+		.unwrap()
+		.unwrap();
+	assert_eq!(position, Position {
+		ts: Some(Timestamp(1_715_675_401)),
+		coords: Coordinates::new(46.05, 14.5),
+		alt: None,
+		err: Some(1.5 * UERE),
+	});
+
+	let position = parser
+		.feed(
+			r#"{"class":"TPV","mode":3,"lat":46.05,"lon":14.5,"alt":340.0,"altMSL":295.0,"epx":3.0,"epy":4.0}"#,
+		)
+		.unwrap()
+		.unwrap();
+	assert_eq!(position.ts, None);
+	assert_eq!(position.alt, Some(295.0));
+	assert_eq!(position.err, Some(5.0));
+
+	let position = parser
+		.feed(
+			r#"{"class":"TPV","mode":3,"lat":46.05,"lon":14.5,"alt":295.0,"eph":7.5,"epx":3.0,"epy":4.0}"#,
+		)
+		.unwrap()
+		.unwrap();
+	assert_eq!(position.alt, Some(295.0));
+	assert_eq!(position.err, Some(7.5));
+
+	assert!(parser.feed("{").is_err());
+}
+
+#[test]
+fn json_file() {
+	assert_eq!(
+		parse_json(br#"{ "lat": 46.05, "lon": 14.5, "alt": 295, "err": 10, "ts": 1715675401 }"#),
+		Ok(Position {
+			ts: Some(Timestamp(1_715_675_401)),
+			coords: Coordinates::new(46.05, 14.5),
+			alt: Some(295.0),
+			err: Some(10.0),
+		})
+	);
+
+	let position = parse_json(br#"{ "lat": 46.05, "lon": 14.5, "hdop": 2 }"#).unwrap();
+	assert_eq!(position.err, Some(2.0 * UERE));
+
+	let info = parse_json(br#"{ "lat": 46.05, "lon": 14.5 }"#)
+		.unwrap()
+		.to_ping_info()
+		.unwrap();
+	assert_eq!(info.alt(), Meters(0.0));
+	assert_eq!(info.err(), Meters(UNKNOWN_ERR));
+
+	assert!(parse_json(br#"{ "lat": 46.05 }"#).is_err());
+	assert!(
+		parse_json(br#"{ "lat": 146.05, "lon": 14.5 }"#)
+			.unwrap()
+			.to_ping_info()
+			.is_err()
+	);
+}
+
+#[test]
+fn gpx_file() {
+	let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+		<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+			<wpt lat="46.0" lon="14.0"><name>Start</name></wpt>
+			<trk>
+				<trkseg>
+					<trkpt lat="46.0510" lon="14.5050"><ele>295.2</ele><time>2024-05-14T08:29:59Z</time></trkpt>
+				</trkseg>
+				<trkseg>
+					<trkpt lat="46.0511" lon="14.5051">
+						<ele>295.4</ele>
+						<time>2024-05-14T08:30:01Z</time>
+						<hdop>1.2</hdop>
+					</trkpt>
+				</trkseg>
+			</trk>
+			<wpt lat="47.0" lon="15.0"><name>End</name></wpt>
+		</gpx>"#;
+
+	assert_eq!(
+		parse_gpx(gpx),
+		Ok(Position {
+			ts: Some(Timestamp(1_715_675_401)),
+			coords: Coordinates::new(46.0511, 14.5051),
+			alt: Some(295.4),
+			err: Some(1.2 * UERE),
+		})
+	);
+
+	let waypoints = r#"<gpx><wpt lat="46.0" lon="14.0"/><wpt lat="-47.5" lon="-15.25"/></gpx>"#;
+	assert_eq!(
+		parse_gpx(waypoints),
+		Ok(Position {
+			ts: None,
+			coords: Coordinates::new(-47.5, -15.25),
+			alt: None,
+			err: None,
+		})
+	);
+
+	assert_eq!(parse_gpx("<gpx></gpx>"), Err(SourceError::NoFix));
+	assert!(parse_gpx("<gpx>").is_err());
+	assert!(parse_gpx(r#"<gpx><trkpt lat="46.0"/></gpx>"#).is_err());
+	assert!(
+		parse_gpx(r#"<gpx><trkpt lat="46.0" lon="14.0"><time>yesterday</time></trkpt></gpx>"#)
+			.is_err()
+	);
+}
+
+#[test]
+fn location_source() {
+	assert!(matches!(
+		"nmea:/dev/ttyUSB0@9600".parse(),
+		Ok(LocationSource::Nmea(path, Some(9600))) if path.to_str() == Some("/dev/ttyUSB0")
+	));
+	assert!(matches!(
+		"nmea:track.nmea".parse(),
+		Ok(LocationSource::Nmea(path, None)) if path.to_str() == Some("track.nmea")
+	));
+	assert!(matches!(
+		"gpsd".parse(),
+		Ok(LocationSource::Gpsd(addr)) if addr == "localhost:2947"
+	));
+	assert!(matches!(
+		"gpsd:[::1]:2948".parse(),
+		Ok(LocationSource::Gpsd(addr)) if addr == "[::1]:2948"
+	));
+	assert!(matches!(
+		"GPX:C:\\track.gpx".parse(),
+		Ok(LocationSource::File(path, FileFormat::Gpx)) if path.to_str() == Some("C:\\track.gpx")
+	));
+	assert!(matches!(
+		"json:position.json".parse(),
+		Ok(LocationSource::File(_, FileFormat::Json))
+	));
+
+	for invalid in [
+		"",
+		"nmea",
+		"nmea:",
+		"nmea:/dev/ttyUSB0@fast",
+		"kml:track.kml",
+	] {
+		assert!(
+			invalid.parse::<LocationSource>().is_err(),
+			"{invalid:?} was accepted"
+		);
+	}
+}
+
+#[test]
+fn export_append() {
+	let info = PingInfo::new(
+		Timestamp(1_715_675_401),
+		Degrees(46.05),
+		Degrees(14.5),
+		Meters(295.0),
+		Meters(10.0),
+	)
+	.unwrap();
+	let pings = ["42", "123"].map(|from| ReceivedPing {
+		info,
+		from,
+		received: Timestamp(1_715_675_403),
+	});
+
+	for format in ExportFormat::ALL {
+		let path =
+			env::temp_dir().join(format!("pinger-cli-test-{}.{}", process::id(), format.id()));
+		let _ = fs::remove_file(&path);
+
+		for ping in &pings {
+			export::append(&path, format, ping).unwrap();
+		}
+
+		let mut expected = String::new();
+		format.write_document(&mut expected, &pings).unwrap();
+		assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+
+		// files which aren't exports in the same format are left alone
+		fs::write(&path, "not an export").unwrap();
+		assert!(export::append(&path, format, &pings[0]).is_err());
+		assert_eq!(fs::read_to_string(&path).unwrap(), "not an export");
+
+		fs::remove_file(&path).unwrap();
+	}
+}
+
+#[test]
+fn plot_positions() {
+	let origin = Coordinates::new(46.05, 14.5);
+
+	assert_eq!(plot_position(origin, origin), (0.0, 0.0));
+
+	// 0.01° of latitude is about 1112 m
+	let (x, y) = plot_position(origin, Coordinates::new(46.06, 14.5));
+	assert!(x.abs() < 0.01 && (y - 1112.0).abs() < 1.0, "({x}, {y})");
+
+	let (x, y) = plot_position(origin, Coordinates::new(46.04, 14.5));
+	assert!(x.abs() < 0.01 && (y + 1112.0).abs() < 1.0, "({x}, {y})");
+
+	// 0.01° of longitude is about 773 m at this latitude
+	let (x, y) = plot_position(origin, Coordinates::new(46.05, 14.51));
+	assert!((x - 773.0).abs() < 1.0 && y.abs() < 1.0, "({x}, {y})");
+
+	let (x, y) = plot_position(origin, Coordinates::new(46.05, 14.49));
+	assert!((x + 773.0).abs() < 1.0 && y.abs() < 1.0, "({x}, {y})");
+}
+
+#[test]
+fn my_location() {
+	let location = |s: &str| {
+		let MyLocation(info) = s.parse().unwrap();
+		(info.lat(), info.lon(), info.err())
+	};
+
+	assert_eq!(
+		location("46,14"),
+		(Degrees(46.0), Degrees(14.0), Meters(0.0))
+	);
+	assert_eq!(
+		location("46, 14, 10"),
+		(Degrees(46.0), Degrees(14.0), Meters(10.0))
+	);
+	assert_eq!(location("33T VL 61716 99842,25").2, Meters(25.0));
+	assert_eq!(location("u24mfp66f").2, Meters(0.0));
+
+	assert!("46".parse::<MyLocation>().is_err());
+	assert!("46, 14, -10".parse::<MyLocation>().is_err());
+}
+
+#[test]
+fn log_limit() {
+	let mut conn = Connection {
+		log: Some(VecDeque::new()),
+		..Connection::default()
+	};
+
+	for i in 0..MAX_LOG_MESSAGES + 5 {
+		conn.say(i.to_string());
+	}
+
+	let log = conn.log.unwrap();
+	assert_eq!(log.len(), MAX_LOG_MESSAGES);
+	assert_eq!(log.front().map(String::as_str), Some("5"));
+	assert_eq!(log.back(), Some(&(MAX_LOG_MESSAGES + 4).to_string()));
+}
+
+#[tokio::test]
+async fn single_ping() {
+	let key = SharedKey::from(
+		EphemeralSecret::random().diffie_hellman(&(&EphemeralSecret::random()).into()),
+	);
+	let mut conn = Connection {
+		log: Some(VecDeque::new()),
+		..Connection::default()
+	};
+	conn.incoming
+		.insert(Id(42), IncomingExchange::AwaitingPing(key.clone(), false));
+
+	let ping = || {
+		let info = PingInfo::new(
+			Timestamp::now(),
+			Degrees(46.056_95),
+			Degrees(14.505_75),
+			Meters(295.0),
+			Meters(12.0),
+		)
+		.unwrap();
+
+		ClientDownMessage::FromClient {
+			from: Id(42),
+			msg: ClientClientMessage::Ping {
+				info: EncryptedPingInfo(info.encrypt(&key).unwrap()),
+			},
+		}
+	};
+
+	// the first Ping ends the exchange, so later ones aren't accepted, even with
+	// new nonces
+	handle_message(ping(), &mut conn, &mut sink::drain()).await;
+	assert_eq!(conn.received.len(), 1);
+	assert!(conn.incoming.is_empty());
+
+	handle_message(ping(), &mut conn, &mut sink::drain()).await;
+	assert_eq!(conn.received.len(), 1);
+	assert!(
+		conn.log
+			.unwrap()
+			.back()
+			.unwrap()
+			.contains("Received unexpected ping from 42")
+	);
+}
+
+/// Get a path for a temporary history file, which doesn't exist yet
+fn history_path(name: &str) -> std::path::PathBuf {
+	let path = env::temp_dir().join(format!("pinger-cli-test-{}-{name}.history", process::id()));
+	let _ = fs::remove_file(&path);
+
+	path
+}
+
+/// Get a history entry for a Ping exchange with `peer` that ended at `ts`
+fn entry(ts: u64, peer: u16) -> Entry {
+	Entry {
+		ts: Timestamp(ts),
+		direction: Direction::Received,
+		peer: Id(peer),
+		outcome: Outcome::Delivered,
+		info: Some(
+			PingInfo::new(
+				Timestamp(ts - 1),
+				Degrees(46.05),
+				Degrees(14.5),
+				Meters(295.0),
+				Meters(10.0),
+			)
+			.unwrap(),
+		),
+	}
+}
+
+#[test]
+fn history() {
+	let path = history_path("history");
+	assert!(!History::exists(&path));
+
+	let history = History::open(&path, "correct horse").unwrap();
+	assert!(History::exists(&path));
+	assert_eq!(history.entries().unwrap(), []);
+
+	let sent = Entry {
+		ts: Timestamp(1_715_675_400),
+		direction: Direction::Sent,
+		peer: Id(123),
+		outcome: Outcome::TimedOut,
+		info: None,
+	};
+	history.append(&sent).unwrap();
+	history.append(&entry(1_715_675_401, 42)).unwrap();
+
+	let reopened = History::open(&path, "correct horse").unwrap();
+	assert_eq!(reopened.entries().unwrap(), [
+		sent,
+		entry(1_715_675_401, 42)
+	]);
+
+	// the file doesn't contain anything in plain text
+	let data = fs::read(&path).unwrap();
+	assert!(!data.windows(4).any(|w| w == b"46.0" || w == b"sent"));
+
+	assert!(matches!(
+		History::open(&path, "battery staple"),
+		Err(HistoryError::WrongPassphrase)
+	));
+
+	// records can't be removed or reordered, because their index is
+	// authenticated (after the 25-byte header, each record starts with its
+	// 4-byte length)
+	let mut records = Vec::new();
+	let mut rest = &data[25..];
+	while let Some((len, _)) = rest.split_first_chunk::<4>() {
+		let (record, tail) = rest.split_at(4 + u32::from_le_bytes(*len) as usize);
+		records.push(record);
+		rest = tail;
+	}
+	assert_eq!(records.len(), 3);
+
+	for order in [[0, 2].as_slice(), &[0, 2, 1]] {
+		let mut modified = data[..25].to_vec();
+		modified.extend(order.iter().flat_map(|&i| records[i]));
+		fs::write(&path, modified).unwrap();
+
+		assert!(matches!(
+			reopened.entries(),
+			Err(HistoryError::Corrupted("authentication failed"))
+		));
+	}
+
+	// an interrupted write is detected
+	fs::write(&path, &data[..data.len() - 3]).unwrap();
+	assert!(matches!(
+		reopened.entries(),
+		Err(HistoryError::Corrupted(_))
+	));
+
+	fs::write(&path, "not a history").unwrap();
+	assert!(matches!(
+		History::open(&path, "correct horse"),
+		Err(HistoryError::NotHistory)
+	));
+
+	fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn history_retention() {
+	let path = history_path("retention");
+	let history = History::open(&path, "correct horse").unwrap();
+
+	for i in 0..10 {
+		history.append(&entry(1_000_000 + i * 100, 42)).unwrap();
+	}
+
+	let now = Timestamp(1_001_000);
+	assert_eq!(history.prune(Retention::default(), now).unwrap(), 0);
+
+	let retention = Retention {
+		max_age: Some(Duration::from_secs(500)),
+		max_entries: None,
+	};
+	assert_eq!(history.prune(retention, now).unwrap(), 5);
+	assert_eq!(history.entries().unwrap()[0], entry(1_000_500, 42));
+
+	let retention = Retention {
+		max_age: None,
+		max_entries: Some(2),
+	};
+	assert_eq!(history.prune(retention, now).unwrap(), 3);
+	assert_eq!(history.entries().unwrap(), [
+		entry(1_000_800, 42),
+		entry(1_000_900, 42)
+	]);
+
+	assert_eq!(history.retain(|e| e.ts.0 > 1_000_800).unwrap(), 1);
+	assert_eq!(history.retain(|_| false).unwrap(), 1);
+	assert_eq!(history.entries().unwrap(), []);
+
+	// entries are re-encrypted with the same key
+	assert_eq!(
+		History::open(&path, "correct horse")
+			.unwrap()
+			.entries()
+			.unwrap(),
+		[]
+	);
+
+	fs::remove_file(&path).unwrap();
+}
+
+/// Load the settings for the command-line arguments `args` (without the
+/// program name) with the configuration file `config`
+fn settings(config: &str, args: &[&str]) -> Result<Settings, String> {
+	let path = env::temp_dir().join(format!("pinger-cli-test-{}-config.toml", process::id()));
+	fs::write(&path, config).unwrap();
+
+	let matches = Args::command()
+		.try_get_matches_from(
+			["pinger-cli", "--config", path.to_str().unwrap()]
+				.iter()
+				.chain(args),
+		)
+		.unwrap();
+	let args = Args::from_arg_matches(&matches).unwrap();
+	let settings = Settings::load(args, &matches);
+
+	fs::remove_file(&path).unwrap();
+	settings
+}
+
+#[test]
+fn config_profiles() {
+	const CONFIG: &str = r#"
+		default-profile = "field"
+
+		[profiles.default]
+		server = "ws://localhost/api"
+
+		[profiles.field]
+		server = "wss://field.example.com/api"
+		accept = "always"
+		timeout = 30
+		precision = "grid:1000,altitude:omit"
+
+		[profiles.field.tls]
+		native-roots = false
+	"#;
+
+	let field = settings(CONFIG, &[]).unwrap();
+	assert_eq!(field.profile.as_deref(), Some("field"));
+	assert_eq!(field.server.value, "wss://field.example.com/api");
+	assert_eq!(field.server.origin, Origin::Profile);
+	assert_eq!(field.accept.value, AcceptPolicy::Always);
+	assert_eq!(field.timeout.value, 30);
+	assert_eq!(field.precision.value, Precision {
+		horizontal: Horizontal::Grid(Meters(1000.0)),
+		altitude: Altitude::Omit,
+	});
+	assert!(!field.native_roots.value);
+	assert_eq!(field.max_age.origin, Origin::Default);
+	// without native roots or a CA there's nothing to trust
+	assert!(
+		field
+			.validate()
+			.unwrap_err()
+			.iter()
+			.any(|e| e.contains("root certificates"))
+	);
+
+	let flags = settings(CONFIG, &[
+		"-p",
+		"default",
+		"--accept",
+		"never",
+		"--precision",
+		"blur:50,altitude:coarsen:10",
+		"wss://flag/api",
+	])
+	.unwrap();
+	assert_eq!(flags.profile.as_deref(), Some("default"));
+	assert_eq!(flags.server.value, "wss://flag/api");
+	assert_eq!(flags.server.origin, Origin::Flag);
+	assert_eq!(flags.accept.value, AcceptPolicy::Never);
+	assert_eq!(flags.timeout.origin, Origin::Default);
+	assert_eq!(flags.precision.value, Precision {
+		horizontal: Horizontal::Blur(Meters(50.0)),
+		altitude: Altitude::Coarsen(Meters(10.0)),
+	});
+	assert_eq!(flags.precision.origin, Origin::Flag);
+	assert!(flags.native_roots.value);
+
+	let invalid = settings("[profiles.default]\nserver = \"https://example.com\"", &[]).unwrap();
+	assert!(
+		invalid
+			.validate()
+			.unwrap_err()
+			.iter()
+			.any(|e| e.starts_with("server:"))
+	);
+
+	assert!(settings(CONFIG, &["-p", "missing"]).is_err());
+	assert!(settings("[profiles.default]\nserver-url = \"ws://localhost\"", &[]).is_err());
+	assert!(settings("[profiles.default]\naccept = \"maybe\"", &[]).is_err());
+	assert!(settings("[profiles.default]\nprecision = \"grid:0\"", &[]).is_err());
+	assert!(
+		settings("[profiles.default]\nprecision = \"altitude:coarsen:0\"", &[
+		])
+		.is_err()
+	);
+	assert_eq!(
+		settings("", &[]).unwrap().precision.value,
+		Precision::default()
+	);
+}
+
+#[test]
+fn accept_policies() {
+	assert_eq!("ask".parse(), Ok(AcceptPolicy::Ask));
+	assert_eq!(" Always ".parse(), Ok(AcceptPolicy::Always));
+
+	let policy = "window:22:00-06:30, rate:2, ask"
+		.parse::<AcceptPolicy>()
+		.unwrap();
+	assert_eq!(policy.to_string(), "window:22:00-06:30,rate:2,ask");
+	assert_eq!(policy.to_string().parse(), Ok(policy.clone()));
+	let AcceptPolicy::When { conditions, ask } = &policy else {
+		panic!("{policy:?}");
+	};
+	assert!(ask);
+	assert_eq!(conditions[1], Condition::Rate(2));
+
+	for invalid in [
+		"sometimes",
+		"window:08:00",
+		"window:08:00-24:00",
+		"window:08:00-08:00",
+		"rate:0",
+		"rate:-1",
+		"rate:2,always",
+		"ask,ask",
+	] {
+		assert!(invalid.parse::<AcceptPolicy>().is_err(), "{invalid}");
+	}
+}
+
+#[test]
+fn accept_decisions() {
+	// 2024-05-14 00:00:00 UTC
+	const MIDNIGHT: u64 = 1_715_644_800;
+
+	let key = PublicKey((&EphemeralSecret::random()).into());
+	let deciding = IncomingExchange::Deciding(key, false);
+	let with_code = IncomingExchange::Deciding(key, true);
+	let awaiting = IncomingExchange::AwaitingPing(
+		EphemeralSecret::random().diffie_hellman(&key.0).into(),
+		false,
+	);
+	let at = |h: u64, m: u64| Timestamp(MIDNIGHT + h * 3600 + m * 60);
+
+	let mut ask = Decider::new(AcceptPolicy::Ask);
+	assert_eq!(ask.decide(&deciding, at(12, 0)), Some(Decision::Ask(None)));
+	assert_eq!(ask.decide(&awaiting, at(12, 0)), None);
+
+	let mut always = Decider::new(AcceptPolicy::Always);
+	let mut never = Decider::new(AcceptPolicy::Never);
+	for _ in 0..100 {
+		assert!(matches!(
+			always.decide(&deciding, at(3, 0)),
+			Some(Decision::Accept(Reason::Policy(_)))
+		));
+		assert!(matches!(
+			never.decide(&deciding, at(3, 0)),
+			Some(Decision::Reject(Reason::Policy(_)))
+		));
+	}
+	assert_eq!(never.decide(&awaiting, at(3, 0)), None);
+
+	// requests with a code are never accepted automatically
+	assert_eq!(
+		always.decide(&with_code, at(3, 0)),
+		Some(Decision::Ask(Some(Reason::Code)))
+	);
+	assert!(matches!(
+		never.decide(&with_code, at(3, 0)),
+		Some(Decision::Reject(Reason::Policy(_)))
+	));
+
+	// the window wraps around midnight
+	let mut window = Decider::new("window:22:00-06:30".parse().unwrap());
+	for (time, accepted) in [
+		(at(22, 0), true),
+		(at(23, 59), true),
+		(at(0, 0), true),
+		(at(6, 29), true),
+		(at(6, 30), false),
+		(at(12, 0), false),
+		(at(21, 59), false),
+	] {
+		assert_eq!(
+			matches!(window.decide(&deciding, time), Some(Decision::Accept(_))),
+			accepted,
+			"{time:?}"
+		);
+	}
+	assert!(matches!(
+		window.decide(&deciding, at(12, 0)),
+		Some(Decision::Reject(Reason::Outside(_)))
+	));
+	assert_eq!(
+		window.decide(&with_code, at(23, 0)),
+		Some(Decision::Ask(Some(Reason::Code)))
+	);
+	assert!(matches!(
+		window.decide(&with_code, at(12, 0)),
+		Some(Decision::Reject(Reason::Outside(_)))
+	));
+
+	// the rate is counted over the last hour, only for accepted requests
+	let mut rate = Decider::new("window:08:00-18:00,rate:2,ask".parse().unwrap());
+	assert!(matches!(
+		rate.decide(&deciding, at(7, 0)),
+		Some(Decision::Ask(Some(Reason::Outside(_))))
+	));
+	assert!(matches!(
+		rate.decide(&deciding, at(8, 0)),
+		Some(Decision::Accept(_))
+	));
+	assert_eq!(rate.decide(&awaiting, at(8, 10)), None);
+	assert!(matches!(
+		rate.decide(&deciding, at(8, 20)),
+		Some(Decision::Accept(_))
+	));
+	assert_eq!(
+		rate.decide(&deciding, at(8, 40)),
+		Some(Decision::Ask(Some(Reason::Rate(2))))
+	);
+	assert!(matches!(
+		rate.decide(&deciding, at(9, 0)),
+		Some(Decision::Accept(_))
+	));
+	assert_eq!(
+		rate.decide(&deciding, at(9, 10)),
+		Some(Decision::Ask(Some(Reason::Rate(2))))
+	);
+	assert!(matches!(
+		rate.decide(&deciding, at(9, 20)),
+		Some(Decision::Accept(_))
+	));
+}
+
+#[test]
+fn key_exchanges() {
+	let info = PingInfo::new(
+		Timestamp(1_715_675_401),
+		Degrees(46.05),
+		Degrees(14.5),
+		Meters(295.0),
+		Meters(10.0),
+	)
+	.unwrap();
+	let exchange = |alices_code: Option<&str>, bobs_code: Option<&str>| {
+		let alice = KeyExchange::new(alices_code);
+		let bob = KeyExchange::new(bobs_code);
+		let (alices_public_key, alices_pake) = (alice.public_key(), alice.is_pake());
+		let (bobs_public_key, bobs_pake) = (bob.public_key(), bob.is_pake());
+
+		let (alices_key, alices_number) = alice.finish(bobs_public_key, bobs_pake)?;
+		let (bobs_key, bobs_number) = bob.finish(alices_public_key, alices_pake)?;
+		let encrypted = info.encrypt(alices_key).unwrap();
+
+		Ok::<_, &str>((
+			PingInfo::decrypt(encrypted, bobs_key),
+			alices_number == bobs_number,
+		))
+	};
+
+	assert_eq!(exchange(None, None), Ok((Ok(info), true)));
+	assert_eq!(
+		exchange(Some("12-34"), Some(" 1234 ")),
+		Ok((Ok(info), true))
+	);
+	assert_eq!(
+		exchange(Some("1234"), Some("1235")),
+		Ok((Err(pinger::Error::Authentication), false))
+	);
+	assert!(exchange(Some("1234"), None).is_err());
+	assert!(exchange(None, Some("1234")).is_err());
+}
+
+#[test]
+fn pake_messages() {
+	let key = PublicKey((&EphemeralSecret::random()).into());
+
+	let json = serde_json::to_string(&ClientUpMessage {
+		to: Id(42),
+		msg: ClientClientMessage::PingRequest { key, pake: true },
+	})
+	.unwrap();
+	assert_eq!(
+		json,
+		format!(r#"{{"to":42,"msg":"ping_request","key":{key},"pake":true}}"#)
+	);
+
+	// the flag is left out of (and defaults to `false` in) normal messages
+	let json = serde_json::to_string(&ClientUpMessage {
+		to: Id(42),
+		msg: ClientClientMessage::AcceptPing { key, pake: false },
+	})
+	.unwrap();
+	assert_eq!(
+		json,
+		format!(r#"{{"to":42,"msg":"accept_ping","key":{key}}}"#)
+	);
+
+	let down = format!(r#"{{"from":42,"msg":"accept_ping","key":{key}}}"#);
+	assert!(matches!(
+		serde_json::from_str(&down).unwrap(),
+		ClientDownMessage::FromClient {
+			from: Id(42),
+			msg: ClientClientMessage::AcceptPing { pake: false, .. }
+		}
+	));
+
+	let down = format!(r#"{{"from":42,"msg":"ping_request","key":{key},"pake":true}}"#);
+	assert!(matches!(
+		serde_json::from_str(&down).unwrap(),
+		ClientDownMessage::FromClient {
+			from: Id(42),
+			msg: ClientClientMessage::PingRequest { pake: true, .. }
+		}
+	));
+}
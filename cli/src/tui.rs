@@ -0,0 +1,692 @@
+//! A full-screen terminal user interface, as an alternative to the default
+//! line-based one
+//!
+//! The TUI shows the user's ID and the connection status, the pending incoming
+//! Ping requests (which can be accepted with `a` or rejected with `r`), the
+//! state of the outgoing Ping, the received Pings, a plot of their positions
+//! relative to each other (or to the user's own location, if known), and the
+//! log of messages.
+//! Pings are sent by typing the ID and pressing enter, or `c` to send them
+//! with a code agreed on with the recipient, which is also entered before
+//! accepting requests sent with one.
+
+use std::{
+	collections::VecDeque,
+	fmt::{Display, Formatter, Result as FmtResult},
+	mem,
+	process::ExitCode,
+	time::Duration,
+};
+
+use ansi_to_tui::IntoText;
+use colored::Colorize;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use pinger::{
+	Meters, PingInfo, Timestamp,
+	geo::{Coordinates, Distance},
+	pake::normalize_code,
+};
+use ratatui::{
+	Frame,
+	layout::{Constraint, Layout, Position, Rect},
+	style::{Color, Style},
+	symbols::Marker,
+	text::{Line, Span, Text},
+	widgets::{
+		Block, List, ListItem, ListState, Paragraph, Wrap,
+		canvas::{Canvas, Circle, Points},
+	},
+};
+use tokio::{select, time};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use crate::{
+	ClientDownMessage, Connection, Id, IncomingExchange, MyLocation, OutgoingExchange, PingAction,
+	accept_ping, format_coordinates, locate, request_ping,
+};
+
+/// How often the TUI is redrawn without any events, to keep the ages of Pings
+/// up to date
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The minimum distance from the center to the edge of the plot
+const MIN_PLOT_EXTENT: f32 = 10.0;
+
+/// The state of the TUI
+#[derive(Debug)]
+struct Tui {
+	/// The websocket URI of the Pinger API
+	server: String,
+	/// Whether the server connection is open
+	connected: bool,
+	/// The text entered by the user
+	input: String,
+	/// What the entered text is for, if it isn't the ID to Ping
+	prompt: Option<Prompt>,
+	/// The index of the selected pending request
+	selected: usize,
+}
+
+/// What the user is entering in the TUI, other than the ID to Ping
+#[derive(Debug)]
+enum Prompt {
+	/// The user's location, for the Ping to the ID with the entered code (if
+	/// any)
+	Location(Id, Option<String>),
+	/// The code for the Ping to the ID
+	SendCode(Id),
+	/// The code for accepting the Ping request from the ID
+	AcceptCode(Id),
+}
+
+/// Run the TUI until the user quits
+pub async fn run<W, R>(mut conn: Connection, server: String, mut write: W, mut read: R) -> ExitCode
+where
+	W: Sink<Message> + Unpin,
+	W::Error: ToString,
+	R: Stream<Item = Result<Message, WsError>> + Unpin,
+{
+	conn.log = Some(VecDeque::new());
+
+	let mut tui = Tui {
+		server,
+		connected: true,
+		input: String::new(),
+		prompt: None,
+		selected: 0,
+	};
+	let mut events = EventStream::new();
+	let mut redraw = time::interval(REDRAW_INTERVAL);
+	let mut terminal = ratatui::init();
+
+	let res = loop {
+		if let Err(e) = terminal.draw(|frame| tui.draw(frame, &conn)) {
+			break Err(e);
+		}
+
+		select! {
+			msg = read.next(), if tui.connected => tui.receive(msg, &mut conn, &mut write).await,
+			event = events.next() => match event {
+				Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+					if !tui.handle_key(key, &mut conn, &mut write).await {
+						break Ok(());
+					}
+				}
+				Some(Ok(_)) => (),
+				Some(Err(e)) => break Err(e),
+				None => break Ok(()),
+			},
+			() = conn.timed_out() => conn.time_out(),
+			_ = redraw.tick() => (),
+		}
+	};
+
+	ratatui::restore();
+
+	if let Err(e) = res {
+		println!(
+			"{}\n{}",
+			"Error in the terminal interface:".red().bold(),
+			format!("{e}").red()
+		);
+
+		return ExitCode::FAILURE;
+	}
+
+	if tui.connected
+		&& let Err(e) = write.close().await
+	{
+		println!(
+			"{}\n{}",
+			"Couldn't disconnect from server:".red().bold(),
+			e.to_string().red()
+		);
+
+		ExitCode::FAILURE
+	} else {
+		println!("{}", "Disconnected from server".bold());
+
+		ExitCode::SUCCESS
+	}
+}
+
+impl Tui {
+	/// Handle a websocket message, or the end of the connection
+	async fn receive<W>(
+		&mut self,
+		msg: Option<Result<Message, WsError>>,
+		conn: &mut Connection,
+		write: &mut W,
+	) where
+		W: Sink<Message> + Unpin,
+		W::Error: ToString,
+	{
+		let json = match msg {
+			Some(Ok(Message::Text(json))) => json,
+			Some(Ok(Message::Close(_)) | Err(_)) | None => {
+				if let Some(Err(e)) = msg {
+					say!(
+						conn,
+						"{}\n{}",
+						"Error while reading websocket:".red().bold(),
+						format!("{e}").red()
+					);
+				}
+
+				say!(conn, "{}", "Disconnected from server".bold());
+				self.connected = false;
+				return;
+			}
+			Some(Ok(_)) => return,
+		};
+
+		let Ok(msg) = serde_json::from_str::<ClientDownMessage>(&json) else {
+			say!(
+				conn,
+				"{} {}",
+				"Couldn't parse message from server".red().bold(),
+				format!("({json})").red().dimmed()
+			);
+			return;
+		};
+
+		say!(
+			conn,
+			"{} {}",
+			format!("{msg} ").bold(),
+			format!("({json})").dimmed()
+		);
+
+		crate::handle_message(msg, conn, write).await;
+	}
+
+	/// Handle a key press, returning `false` if the TUI should be closed
+	async fn handle_key<W>(&mut self, key: KeyEvent, conn: &mut Connection, write: &mut W) -> bool
+	where
+		W: Sink<Message> + Unpin,
+		W::Error: ToString,
+	{
+		match key.code {
+			KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+			KeyCode::Esc => {
+				self.prompt = None;
+				self.input.clear();
+			}
+			KeyCode::Backspace => {
+				self.input.pop();
+			}
+			KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+			KeyCode::Down => self.selected = self.selected.saturating_add(1),
+			KeyCode::Enter => self.submit(conn, write).await,
+			KeyCode::Char(c) if self.prompt.is_some() => self.input.push(c),
+			KeyCode::Char(c) if c.is_ascii_digit() => self.input.push(c),
+			KeyCode::Char('c') => match mem::take(&mut self.input).parse::<u16>().map(Id) {
+				Ok(id) => self.prompt = Some(Prompt::SendCode(id)),
+				Err(e) => say!(
+					conn,
+					"{} {}",
+					"Error sending ping: invalid ID".red().bold(),
+					format!("({e})").dimmed()
+				),
+			},
+			KeyCode::Char(c @ ('a' | 'r')) => {
+				let Some(&id) = pending(conn).get(self.selected) else {
+					say!(conn, "{}", "No ping request selected".red().bold());
+					return true;
+				};
+
+				// the code is entered in the TUI instead of being prompted for
+				if c == 'a'
+					&& matches!(
+						conn.incoming.get(&id),
+						Some(IncomingExchange::Deciding(_, true))
+					) {
+					self.input.clear();
+					self.prompt = Some(Prompt::AcceptCode(id));
+					return true;
+				}
+
+				let action = if c == 'a' {
+					PingAction::Accept
+				} else {
+					PingAction::Reject
+				};
+
+				action.perform(id, conn, write).await;
+			}
+			KeyCode::Char('q') => return false,
+			_ => (),
+		}
+
+		true
+	}
+
+	/// Send a Ping to the entered ID, or with the entered location or code, or
+	/// accept a Ping request with the entered code
+	async fn submit<W>(&mut self, conn: &mut Connection, write: &mut W)
+	where
+		W: Sink<Message> + Unpin,
+		W::Error: ToString,
+	{
+		let input = mem::take(&mut self.input);
+
+		match self.prompt.take() {
+			Some(Prompt::Location(id, code)) => send(id, &input, code, conn, write).await,
+			Some(Prompt::SendCode(id)) if normalize_code(&input).is_empty() => {
+				say!(conn, "{}", "The code must not be empty".red().bold());
+				self.prompt = Some(Prompt::SendCode(id));
+			}
+			Some(Prompt::SendCode(id)) => self.locate(id, Some(input), conn, write).await,
+			Some(Prompt::AcceptCode(id)) if normalize_code(&input).is_empty() => {
+				say!(conn, "{}", "The code must not be empty".red().bold());
+				self.prompt = Some(Prompt::AcceptCode(id));
+			}
+			Some(Prompt::AcceptCode(id)) => accept_ping(id, Some(&input), conn, write).await,
+			None if input.is_empty() => (),
+			None => match input.parse::<u16>().map(Id) {
+				Ok(id) => self.locate(id, None, conn, write).await,
+				Err(e) => say!(
+					conn,
+					"{} {}",
+					"Error sending ping: invalid ID".red().bold(),
+					format!("({e})").dimmed()
+				),
+			},
+		}
+	}
+
+	/// Send a Ping to `id` with the location from the locator, or let the user
+	/// enter it if there is no locator
+	async fn locate<W>(
+		&mut self,
+		id: Id,
+		code: Option<String>,
+		conn: &mut Connection,
+		write: &mut W,
+	) where
+		W: Sink<Message> + Unpin,
+		W::Error: ToString,
+	{
+		if conn.locator.is_none() {
+			self.prompt = Some(Prompt::Location(id, code));
+		} else if let Some(info) = locate(conn) {
+			request_ping(id, info, code.as_deref(), conn, write).await;
+		}
+	}
+
+	/// Draw the TUI
+	fn draw(&mut self, frame: &mut Frame<'_>, conn: &Connection) {
+		let [status, exchanges, pings, log, input] = Layout::vertical([
+			Constraint::Length(4),
+			Constraint::Length(7),
+			Constraint::Min(10),
+			Constraint::Length(8),
+			Constraint::Length(3),
+		])
+		.areas(frame.area());
+		let [incoming, outgoing] =
+			Layout::horizontal([Constraint::Percentage(50); 2]).areas(exchanges);
+		let [received, plot] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(pings);
+
+		self.draw_status(frame, status, conn);
+		self.draw_incoming(frame, incoming, conn);
+		draw_outgoing(frame, outgoing, conn);
+		draw_received(frame, received, conn);
+		draw_plot(frame, plot, conn);
+		draw_log(frame, log, conn);
+		self.draw_input(frame, input);
+	}
+
+	/// Draw the user's ID, the connection status, and the available keys
+	fn draw_status(&self, frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+		let id = conn
+			.id
+			.map_or_else(|| "unknown".to_string(), |id| id.to_string());
+		let connection = if self.connected {
+			Span::styled(
+				format!("connected to {}", self.server),
+				Style::new().green(),
+			)
+		} else {
+			Span::styled(
+				format!("disconnected from {}", self.server),
+				Style::new().red(),
+			)
+		};
+
+		let text = Text::from(vec![
+			Line::from(vec![
+				Span::styled("Your ID: ", Style::new().bold()),
+				Span::styled(id, Style::new().bold().cyan()),
+				Span::raw(" · "),
+				connection,
+			]),
+			Line::styled(
+				"Type an ID and press enter to ping it (or c to ping it with a code) · ↑/↓ select \
+				 a request · a accept · r reject · q quit",
+				Style::new().dim(),
+			),
+		]);
+
+		frame.render_widget(
+			Paragraph::new(text).block(Block::bordered().title(" Pinger ")),
+			area,
+		);
+	}
+
+	/// Draw the list of pending incoming Ping requests
+	fn draw_incoming(&mut self, frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+		let pending = pending(conn);
+		self.selected = self.selected.min(pending.len().saturating_sub(1));
+
+		let items = pending
+			.iter()
+			.map(|id| {
+				if matches!(
+					conn.incoming.get(id),
+					Some(IncomingExchange::Deciding(_, true))
+				) {
+					ListItem::new(format!("Ping request from {id} (with a code)"))
+				} else {
+					ListItem::new(format!("Ping request from {id}"))
+				}
+			})
+			.collect::<Vec<_>>();
+		let mut state =
+			ListState::default().with_selected((!pending.is_empty()).then_some(self.selected));
+
+		frame.render_stateful_widget(
+			List::new(items)
+				.block(Block::bordered().title(" Incoming requests "))
+				.highlight_symbol("> ")
+				.highlight_style(Style::new().reversed()),
+			area,
+			&mut state,
+		);
+	}
+
+	/// Draw the input line
+	fn draw_input(&self, frame: &mut Frame<'_>, area: Rect) {
+		let title = match &self.prompt {
+			None => " Ping an ID ".to_string(),
+			Some(Prompt::Location(id, _)) => {
+				format!(" Your location for the ping to {id} (LOCATION[, ERR], esc to cancel) ")
+			}
+			Some(Prompt::SendCode(id)) => {
+				format!(" The code you agreed on for the ping to {id} (esc to cancel) ")
+			}
+			Some(Prompt::AcceptCode(id)) => {
+				format!(" The code you agreed on for the ping from {id} (esc to cancel) ")
+			}
+		};
+
+		frame.render_widget(
+			Paragraph::new(self.input.as_str()).block(Block::bordered().title(title)),
+			area,
+		);
+
+		let offset = u16::try_from(self.input.chars().count()).unwrap_or(u16::MAX);
+		frame.set_cursor_position(Position::new(
+			area.x
+				.saturating_add(offset)
+				.saturating_add(1)
+				.min(area.right().saturating_sub(2)),
+			area.y + 1,
+		));
+	}
+}
+
+/// Send a Ping to `id` with the location entered as `input`, authenticated with
+/// `code` if given
+async fn send<W>(id: Id, input: &str, code: Option<String>, conn: &mut Connection, write: &mut W)
+where
+	W: Sink<Message> + Unpin,
+	W::Error: ToString,
+{
+	match input.parse::<MyLocation>().and_then(|location| {
+		let MyLocation(info) = location;
+		// the configured defaults are used if no position error is entered
+		let err = if info.err().0 > 0.0 {
+			info.err()
+		} else {
+			Meters(conn.position_error.unwrap_or(0.0))
+		};
+
+		PingInfo::new(
+			Timestamp::now(),
+			info.lat(),
+			info.lon(),
+			Meters(conn.altitude.unwrap_or(0.0)),
+			err,
+		)
+		.map_err(|e| format!("{e}"))
+	}) {
+		Ok(info) => request_ping(id, info, code.as_deref(), conn, write).await,
+		Err(e) => say!(
+			conn,
+			"{} {}",
+			"Error sending ping: invalid location".red().bold(),
+			format!("({e})").dimmed()
+		),
+	}
+}
+
+/// Get the IDs with pending incoming Ping requests, in ascending order
+fn pending(conn: &Connection) -> Vec<Id> {
+	let mut pending = conn
+		.incoming
+		.iter()
+		.filter(|(_, exch)| matches!(exch, IncomingExchange::Deciding(..)))
+		.map(|(&id, _)| id)
+		.collect::<Vec<_>>();
+	pending.sort_unstable_by_key(|id| id.0);
+
+	pending
+}
+
+/// Draw the state of the outgoing Ping
+fn draw_outgoing(frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+	let state = match conn.outgoing {
+		OutgoingExchange::None => "No ping is being sent".to_string(),
+		OutgoingExchange::AwaitingDecision(id, ..) => {
+			format!("Waiting for {id} to accept or reject your ping")
+		}
+		OutgoingExchange::AwaitingAck(id) => {
+			format!("Ping sent to {id}, waiting for acknowledgement")
+		}
+	};
+
+	frame.render_widget(
+		Paragraph::new(state)
+			.wrap(Wrap { trim: true })
+			.block(Block::bordered().title(" Outgoing ping ")),
+		area,
+	);
+}
+
+/// Draw the list of received Pings, newest first
+fn draw_received(frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+	let now = Timestamp::now();
+
+	let items = conn
+		.received
+		.iter()
+		.rev()
+		.map(|(from, info)| {
+			let mut lines = vec![Line::from(vec![
+				Span::styled(format!("{from}"), Style::new().bold().yellow()),
+				Span::styled(
+					format!(
+						" {} ago",
+						// timestamps slightly in the future are shown as just now
+						Age(now.0.saturating_sub(info.ts().0))
+					),
+					Style::new().dim(),
+				),
+			])];
+
+			lines.push(Line::from(format!(
+				"  {} (±{}, {} mAMSL)",
+				format_coordinates(info.coordinates(), conn.format),
+				Distance(info.err()),
+				info.alt().0
+			)));
+
+			if let Some(my_location) = conn.my_location {
+				let relative = info.relative_to(&my_location.0);
+
+				lines.push(Line::from(format!(
+					"  {} {} of you (±{})",
+					Distance(relative.distance),
+					relative.direction,
+					Distance(relative.err)
+				)));
+			}
+
+			ListItem::new(lines)
+		})
+		.collect::<Vec<_>>();
+
+	frame.render_widget(
+		List::new(items)
+			.block(Block::bordered().title(format!(" Received pings ({}) ", conn.received.len()))),
+		area,
+	);
+}
+
+/// Draw a plot of the positions of the received Pings, relative to the user's
+/// own location if known, or to the latest Ping
+fn draw_plot(frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+	let origin = conn
+		.my_location
+		.map(|location| location.0.coordinates())
+		.or_else(|| conn.received.last().map(|(_, info)| info.coordinates()));
+
+	let Some(origin) = origin else {
+		frame.render_widget(
+			Paragraph::new(Span::styled("No pings received yet", Style::new().dim()))
+				.block(Block::bordered().title(" Map ")),
+			area,
+		);
+		return;
+	};
+
+	let points = conn
+		.received
+		.iter()
+		.map(|(from, info)| (*from, plot_position(origin, info.coordinates()), info.err()))
+		.collect::<Vec<_>>();
+
+	let extent = points
+		.iter()
+		.map(|(_, (x, y), err)| x.abs().max(y.abs()) + err.0)
+		.fold(MIN_PLOT_EXTENT, f32::max)
+		* 1.1;
+
+	// terminal cells are about twice as high as they are wide
+	let (width, height) = (
+		f32::from(area.width.max(1)),
+		f32::from(area.height.max(1)) * 2.0,
+	);
+	let (x_extent, y_extent) = if width > height {
+		(extent * width / height, extent)
+	} else {
+		(extent, extent * height / width)
+	};
+
+	let canvas = Canvas::default()
+		.block(Block::bordered().title(format!(" Map (±{}) ", Distance(Meters(y_extent)))))
+		.marker(Marker::Braille)
+		.x_bounds([f64::from(-x_extent), f64::from(x_extent)])
+		.y_bounds([f64::from(-y_extent), f64::from(y_extent)])
+		.paint(|ctx| {
+			for (_, (x, y), err) in &points {
+				ctx.draw(&Circle {
+					x: f64::from(*x),
+					y: f64::from(*y),
+					radius: f64::from(err.0),
+					color: Color::DarkGray,
+				});
+			}
+
+			ctx.layer();
+
+			let coords = points
+				.iter()
+				.map(|(_, (x, y), _)| (f64::from(*x), f64::from(*y)))
+				.collect::<Vec<_>>();
+			ctx.draw(&Points {
+				coords: &coords,
+				color: Color::Yellow,
+			});
+
+			if conn.my_location.is_some() {
+				ctx.print(0.0, 0.0, Span::styled("you", Style::new().bold().cyan()));
+			}
+
+			for (from, (x, y), _) in &points {
+				ctx.print(
+					f64::from(*x),
+					f64::from(*y),
+					Span::styled(format!("{from}"), Style::new().yellow()),
+				);
+			}
+		});
+
+	frame.render_widget(canvas, area);
+}
+
+/// Get the position of `coords` on a plot centered on `origin`, in meters east
+/// and north of it
+pub fn plot_position(origin: Coordinates, coords: Coordinates) -> (f32, f32) {
+	let distance = origin.distance_to(coords).0;
+	let bearing = origin.bearing_to(coords).0.to_radians();
+
+	#[expect(
+		clippy::cast_possible_truncation,
+		reason = "the bearing only determines the direction, which doesn't need f64 precision"
+	)]
+	let (sin, cos) = (bearing.sin() as f32, bearing.cos() as f32);
+
+	(distance * sin, distance * cos)
+}
+
+/// Draw the most recent messages that fit into the area
+///
+/// Only the visible messages are converted from ANSI escape codes, starting
+/// from the most recent one.
+fn draw_log(frame: &mut Frame<'_>, area: Rect, conn: &Connection) {
+	let height = usize::from(area.height.saturating_sub(2));
+	let mut lines = Vec::new();
+
+	for msg in conn.log.iter().flatten().rev() {
+		if lines.len() >= height {
+			break;
+		}
+
+		let text = msg.into_text().unwrap_or_else(|_| Text::raw(msg.clone()));
+		lines.extend(text.lines.into_iter().rev());
+	}
+
+	lines.truncate(height);
+	lines.reverse();
+
+	frame.render_widget(
+		Paragraph::new(lines).block(Block::bordered().title(" Messages ")),
+		area,
+	);
+}
+
+/// An age in seconds, formatted with the largest fitting unit
+struct Age(u64);
+
+impl Display for Age {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self.0 {
+			secs @ 0..60 => write!(f, "{secs} s"),
+			secs @ 60..3600 => write!(f, "{} min", secs / 60),
+			secs => write!(f, "{} h", secs / 3600),
+		}
+	}
+}
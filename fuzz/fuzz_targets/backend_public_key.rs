@@ -0,0 +1,20 @@
+//! Deserialize attacker-controlled public keys with the backend's serde support
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../backend/src/serde_support.rs"]
+mod serde_support;
+
+/// A public key, as in the backend's messages
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PublicKey(#[serde(with = "serde_support::public_key")] pinger::PublicKey);
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(key) = serde_json::from_slice::<PublicKey>(data) {
+		let json = serde_json::to_string(&key).expect("serialization failed");
+		let decoded = serde_json::from_str::<PublicKey>(&json).expect("round trip failed");
+		assert_eq!(decoded.0, key.0);
+	}
+});
@@ -0,0 +1,20 @@
+//! Deserialize attacker-controlled public keys with the CLI's serde support
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../cli/src/serde_public_key.rs"]
+mod serde_public_key;
+
+/// A public key, as in the CLI's messages
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PublicKey(#[serde(with = "serde_public_key")] pinger::PublicKey);
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(key) = serde_json::from_slice::<PublicKey>(data) {
+		let json = serde_json::to_string(&key).expect("serialization failed");
+		let decoded = serde_json::from_str::<PublicKey>(&json).expect("round trip failed");
+		assert_eq!(decoded.0, key.0);
+	}
+});
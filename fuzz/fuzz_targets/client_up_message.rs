@@ -0,0 +1,32 @@
+//! Parse attacker-controlled websocket messages like the backend does, and
+//! relay them to the recipient
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use crate::messages::{ClientDownMessage, ClientUpMessage};
+
+#[path = "../../backend/src/serde_support.rs"]
+mod serde_support;
+
+#[path = "../../backend/src/messages.rs"]
+mod messages;
+
+fuzz_target!(|data: &str| {
+	let Ok(msg) = serde_json::from_str::<ClientUpMessage>(data) else {
+		return;
+	};
+
+	let relayed = serde_json::to_string(&ClientDownMessage::FromClient {
+		from: msg.to,
+		msg: msg.msg,
+	})
+	.expect("serialization failed");
+
+	// the recipient can always parse relayed messages
+	assert!(matches!(
+		serde_json::from_str::<ClientDownMessage>(&relayed),
+		Ok(ClientDownMessage::FromClient { .. })
+	));
+});
@@ -0,0 +1,23 @@
+//! Decrypt attacker-controlled encrypted Ping info with an arbitrary key
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pinger::{EncryptedPingInfo, PingInfo, SharedKey};
+
+fuzz_target!(|input: ([u8; 32], &[u8])| {
+	let (key, bytes) = input;
+	let key = SharedKey::from_bytes(key);
+
+	let Ok(encrypted) = EncryptedPingInfo::try_from(bytes) else {
+		return;
+	};
+
+	if let Ok(info) = PingInfo::decrypt(encrypted, &key) {
+		// anything that decrypts is valid, and can be encrypted again
+		let encrypted = info
+			.encrypt(&key)
+			.expect("decrypted Ping info can't be encrypted");
+		assert_eq!(PingInfo::decrypt(encrypted, &key), Ok(info));
+	}
+});
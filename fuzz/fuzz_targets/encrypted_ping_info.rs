@@ -0,0 +1,22 @@
+//! Decode attacker-controlled base64 encrypted Ping info, both directly and
+//! from JSON (as in a `ping` message)
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pinger::EncryptedPingInfo;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(str) = std::str::from_utf8(data)
+		&& let Ok(encrypted) = EncryptedPingInfo::from_base64(str)
+	{
+		let json = serde_json::to_string(&encrypted).expect("serialization failed");
+		assert_eq!(json.trim_matches('"'), str);
+	}
+
+	if let Ok(encrypted) = serde_json::from_slice::<EncryptedPingInfo>(data) {
+		let json = serde_json::to_string(&encrypted).expect("serialization failed");
+		let decoded = serde_json::from_str::<EncryptedPingInfo>(&json).expect("round trip failed");
+		assert_eq!(decoded.as_ref(), encrypted.as_ref());
+	}
+});
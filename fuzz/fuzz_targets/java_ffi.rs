@@ -0,0 +1,32 @@
+//! Decode attacker-controlled arguments of the JNI functions
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pinger::{
+	EncryptedPingInfo, Error,
+	java_ffi::{decode_public_key, ping_info_from_java},
+};
+
+fuzz_target!(|input: (i64, f64, f64, f32, f32, &str)| {
+	let (ts, lat, lon, alt, err, str) = input;
+
+	match ping_info_from_java(ts, lat, lon, alt, err) {
+		Ok(info) => {
+			assert_eq!(info.lat().0.to_bits(), lat.to_bits());
+			assert_eq!(info.lon().0.to_bits(), lon.to_bits());
+			assert_eq!(info.alt().0.to_bits(), alt.to_bits());
+			assert_eq!(info.err().0.to_bits(), err.to_bits());
+		}
+		Err(e) => assert!(matches!(e, Error::InvalidValue(_))),
+	}
+
+	// these are the string arguments, the public key and the encrypted Ping info
+	if let Err(e) = decode_public_key(str) {
+		assert!(matches!(e, Error::BadEncoding | Error::BadLength));
+	}
+
+	if let Err(e) = EncryptedPingInfo::from_base64(str) {
+		assert!(matches!(e, Error::BadEncoding | Error::BadLength));
+	}
+});
@@ -0,0 +1,204 @@
+//! Degrees, minutes, and seconds, e.g. `46°03′03.9″N 14°30′18.5″E`
+
+use core::fmt::{Formatter, Result as FmtResult};
+
+use libm::{copysign, fabs, round, trunc};
+
+use super::CoordinateFormat;
+use crate::{CoordinateError, geo::Coordinates};
+
+/// The error returned for any invalid input
+const INVALID: CoordinateError = CoordinateError::Invalid(CoordinateFormat::Dms);
+
+/// Format the coordinates with a precision of a tenth of a second
+pub fn fmt(coords: Coordinates, f: &mut Formatter<'_>) -> FmtResult {
+	fmt_angle(coords.lat.0, ['N', 'S'], f)?;
+	f.write_str(" ")?;
+	fmt_angle(coords.lon.0, ['E', 'W'], f)
+}
+
+/// Format a single angle with the given positive and negative hemisphere
+/// letters
+fn fmt_angle(deg: f64, [pos, neg]: [char; 2], f: &mut Formatter<'_>) -> FmtResult {
+	#[expect(
+		clippy::cast_possible_truncation,
+		clippy::cast_sign_loss,
+		reason = "the value is rounded, positive, and at most 180 * 36000"
+	)]
+	let tenths = round(fabs(deg) * 36_000.0) as u32;
+
+	write!(
+		f,
+		"{}°{:02}′{:02}.{}″{}",
+		tenths / 36_000,
+		tenths / 600 % 60,
+		tenths / 10 % 60,
+		tenths % 10,
+		if deg < 0.0 { neg } else { pos }
+	)
+}
+
+/// A token of the input
+#[derive(Clone, Copy, Debug)]
+enum Token {
+	/// A number, with the unit (0 for degrees, 1 for minutes, 2 for seconds)
+	/// given by the symbol following it, if any
+	Number(f64, Option<usize>),
+	/// A hemisphere letter (uppercase `N`, `S`, `E`, or `W`)
+	Hemisphere(char),
+	/// A comma separating the latitude and longitude
+	Comma,
+}
+
+/// Split the input into tokens
+fn tokenize(s: &str) -> impl Iterator<Item = Result<Token, CoordinateError>> {
+	let mut rest = s.trim_start();
+
+	core::iter::from_fn(move || {
+		let c = rest.chars().next()?;
+
+		let token = if c.is_ascii_digit() || matches!(c, '.' | '-' | '+') {
+			let sign_len = usize::from(matches!(c, '-' | '+'));
+			let end = rest[sign_len..]
+				.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+				.map_or(rest.len(), |end| end + sign_len);
+			let number = rest[..end].parse::<f64>().map_err(|_| INVALID);
+			rest = rest[end..].trim_start();
+
+			let unit = if let Some(r) = rest.strip_prefix(['°', 'º']) {
+				rest = r;
+				Some(0)
+			} else if let Some(r) = rest.strip_prefix("''") {
+				rest = r;
+				Some(2)
+			} else if let Some(r) = rest.strip_prefix(['′', '\'', '’']) {
+				rest = r;
+				Some(1)
+			} else if let Some(r) = rest.strip_prefix(['″', '"', '”']) {
+				rest = r;
+				Some(2)
+			} else {
+				None
+			};
+
+			number.map(|number| Token::Number(number, unit))
+		} else {
+			rest = &rest[c.len_utf8()..];
+
+			match c.to_ascii_uppercase() {
+				h @ ('N' | 'S' | 'E' | 'W') => Ok(Token::Hemisphere(h)),
+				',' => Ok(Token::Comma),
+				_ => Err(INVALID),
+			}
+		};
+
+		rest = rest.trim_start();
+		Some(token)
+	})
+}
+
+/// One of the two angles in the input
+#[derive(Clone, Copy, Debug, Default)]
+struct Angle {
+	/// The hemisphere letter, if any
+	hemisphere: Option<char>,
+	/// The degrees, minutes, and seconds
+	parts: [Option<f64>; 3],
+	/// The unit of the next number without a symbol
+	next_unit: usize,
+}
+
+impl Angle {
+	/// Check if this angle has any numbers
+	fn has_numbers(&self) -> bool {
+		self.parts.iter().any(Option::is_some)
+	}
+
+	/// Get the value of this angle in degrees, negative for the southern and
+	/// western hemispheres
+	fn value(&self) -> Result<f64, CoordinateError> {
+		let [Some(deg), min, sec] = self.parts else {
+			return Err(INVALID);
+		};
+
+		// minutes are only allowed after whole degrees, seconds only after whole
+		// minutes, and neither can be negative or 60 or more
+		#[expect(clippy::float_cmp, reason = "this checks for exact whole numbers")]
+		let whole = |v: f64| v == trunc(v);
+		let valid_part = |v: Option<f64>| v.is_none_or(|v| (0.0..60.0).contains(&v));
+		if (min.is_some() && !whole(deg))
+			|| (sec.is_some() && !min.is_some_and(whole))
+			|| !valid_part(min)
+			|| !valid_part(sec)
+		{
+			return Err(INVALID);
+		}
+
+		let abs = fabs(deg) + min.unwrap_or(0.0) / 60.0 + sec.unwrap_or(0.0) / 3600.0;
+
+		match self.hemisphere {
+			Some(_) if deg.is_sign_negative() => Err(INVALID),
+			Some('S' | 'W') => Ok(-abs),
+			Some(_) => Ok(abs),
+			None => Ok(copysign(abs, deg)),
+		}
+	}
+}
+
+/// Parse degrees, minutes, and seconds
+///
+/// The hemispheres are given either by letters (before or after each angle)
+/// or by signs, and minutes and seconds are optional, e.g.
+/// `46°03′03.9″N 14°30′18.5″E`, `N 46 3.065 E 14 30.308`, or `-46° 3' 3.9",
+/// 14° 30' 18.5"`.
+pub fn parse(s: &str) -> Result<Coordinates, CoordinateError> {
+	let mut angles = [Angle::default(); 2];
+	let mut i = 0;
+
+	for token in tokenize(s) {
+		let angle = angles.get_mut(i).ok_or(INVALID)?;
+
+		match token? {
+			Token::Hemisphere(h) if angle.hemisphere.is_none() => {
+				angle.hemisphere = Some(h);
+
+				if angle.has_numbers() {
+					i += 1;
+				}
+			}
+			Token::Hemisphere(h) if angle.has_numbers() => {
+				i += 1;
+				let next = angles.get_mut(i).ok_or(INVALID)?;
+				next.hemisphere = Some(h);
+			}
+			Token::Hemisphere(_) => return Err(INVALID),
+			Token::Comma if angle.has_numbers() => i += 1,
+			Token::Comma => (),
+			Token::Number(n, unit) => {
+				let unit = unit.unwrap_or(angle.next_unit);
+
+				let angle = if angle.has_numbers() && unit < angle.next_unit {
+					i += 1;
+					angles.get_mut(i).ok_or(INVALID)?
+				} else {
+					angle
+				};
+
+				let part = angle.parts.get_mut(unit).ok_or(INVALID)?;
+				if part.replace(n).is_some() {
+					return Err(INVALID);
+				}
+				angle.next_unit = unit + 1;
+			}
+		}
+	}
+
+	let [first, second] = angles;
+	let (lat, lon) = match (first.hemisphere, second.hemisphere) {
+		(Some('N' | 'S') | None, Some('E' | 'W') | None) => (first, second),
+		(Some('E' | 'W'), Some('N' | 'S')) => (second, first),
+		_ => return Err(INVALID),
+	};
+
+	Ok(Coordinates::new(lat.value()?, lon.value()?))
+}
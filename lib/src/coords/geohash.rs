@@ -0,0 +1,88 @@
+//! Geohashes, e.g. `u24mfp66f`
+
+use core::fmt::{Formatter, Result as FmtResult, Write};
+
+use super::CoordinateFormat;
+use crate::{CoordinateError, geo::Coordinates};
+
+/// The geohash base32 alphabet
+const ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// The length of formatted geohashes (a cell of about 5 by 5 meters)
+const LENGTH: usize = 9;
+
+/// The maximum length of parsed geohashes (a cell of a few centimeters)
+const MAX_LENGTH: usize = 12;
+
+/// Format the coordinates as a geohash
+pub fn fmt(coords: Coordinates, f: &mut Formatter<'_>) -> FmtResult {
+	let mut lat = (-90.0, 90.0);
+	let mut lon = (-180.0, 180.0);
+	let mut is_lon = true;
+
+	for _ in 0..LENGTH {
+		let mut index = 0;
+
+		for _ in 0..5 {
+			let (range, value) = if is_lon {
+				(&mut lon, coords.lon.0)
+			} else {
+				(&mut lat, coords.lat.0)
+			};
+			let mid = f64::midpoint(range.0, range.1);
+
+			index <<= 1;
+			if value >= mid {
+				index |= 1;
+				range.0 = mid;
+			} else {
+				range.1 = mid;
+			}
+
+			is_lon = !is_lon;
+		}
+
+		f.write_char(char::from(ALPHABET[index]))?;
+	}
+
+	Ok(())
+}
+
+/// Parse a geohash (case-insensitive), returning the center of its cell
+pub fn parse(s: &str) -> Result<Coordinates, CoordinateError> {
+	let invalid = CoordinateError::Invalid(CoordinateFormat::Geohash);
+	let s = s.trim();
+
+	if s.is_empty() || s.len() > MAX_LENGTH {
+		return Err(invalid);
+	}
+
+	let mut lat = (-90.0, 90.0);
+	let mut lon = (-180.0, 180.0);
+	let mut is_lon = true;
+
+	for c in s.bytes() {
+		let index = ALPHABET
+			.iter()
+			.position(|&a| a == c.to_ascii_lowercase())
+			.ok_or(invalid)?;
+
+		for bit in (0..5).rev() {
+			let range = if is_lon { &mut lon } else { &mut lat };
+			let mid = f64::midpoint(range.0, range.1);
+
+			if index & (1 << bit) == 0 {
+				range.1 = mid;
+			} else {
+				range.0 = mid;
+			}
+
+			is_lon = !is_lon;
+		}
+	}
+
+	Ok(Coordinates::new(
+		f64::midpoint(lat.0, lat.1),
+		f64::midpoint(lon.0, lon.1),
+	))
+}
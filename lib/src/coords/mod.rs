@@ -0,0 +1,258 @@
+//! Parsing and formatting of coordinates in different formats
+//!
+//! The supported formats are decimal degrees, degrees-minutes-seconds,
+//! geohash, Plus Codes (Open Location Code), UTM, and MGRS (see
+//! [`CoordinateFormat`]).
+//! Coordinates can be parsed in a specific format using
+//! [`CoordinateFormat::parse`], or in any supported format by using
+//! [`Coordinates`]' `FromStr` implementation, which detects the format
+//! automatically.
+//!
+//! Formatted coordinates have a precision of a few meters or better, which is
+//! more than the precision of most position data.
+
+use core::{
+	fmt::{Display, Formatter, Result as FmtResult},
+	str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CoordinateError, geo::Coordinates};
+
+mod dms;
+mod geohash;
+mod olc;
+mod utm;
+
+/// A format for writing down coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoordinateFormat {
+	/// Decimal degrees, e.g. `46.05108°, 14.50513°`
+	#[default]
+	Decimal,
+	/// Degrees, minutes, and seconds, e.g. `46°03′03.9″N 14°30′18.5″E`
+	Dms,
+	/// A 9-character geohash, e.g. `u24mfp66f`
+	Geohash,
+	/// An 11-digit Plus Code (Open Location Code), e.g. `8FRP3G24+C3J`
+	PlusCode,
+	/// Universal Transverse Mercator with a latitude band, e.g. `33T 461716
+	/// 5099842` (only between 80°S and 84°N)
+	Utm,
+	/// Military Grid Reference System with a precision of 1 meter, e.g. `33T
+	/// VL 61716 99841` (only between 80°S and 84°N)
+	Mgrs,
+}
+
+impl CoordinateFormat {
+	/// All supported coordinate formats
+	pub const ALL: [Self; 6] = [
+		Self::Decimal,
+		Self::Dms,
+		Self::Geohash,
+		Self::PlusCode,
+		Self::Utm,
+		Self::Mgrs,
+	];
+	/// The order in which formats are tried when detecting the format
+	/// automatically, from the most to the least specific
+	const DETECTION_ORDER: [Self; 6] = [
+		Self::PlusCode,
+		Self::Mgrs,
+		Self::Utm,
+		Self::Decimal,
+		Self::Dms,
+		Self::Geohash,
+	];
+
+	/// Get the short identifier of this format, as accepted by its `FromStr`
+	/// implementation (e.g. `plus-code`)
+	#[must_use]
+	pub const fn id(self) -> &'static str {
+		match self {
+			Self::Decimal => "decimal",
+			Self::Dms => "dms",
+			Self::Geohash => "geohash",
+			Self::PlusCode => "plus-code",
+			Self::Utm => "utm",
+			Self::Mgrs => "mgrs",
+		}
+	}
+
+	/// Parse coordinates in this format
+	///
+	/// # Errors
+	/// Returns [`CoordinateError::Invalid`] if the input is not valid in this
+	/// format, or [`CoordinateError::OutOfRange`] if the coordinates are not on
+	/// earth
+	pub fn parse(self, s: &str) -> Result<Coordinates, CoordinateError> {
+		let coords = match self {
+			Self::Decimal => parse_decimal(s),
+			Self::Dms => dms::parse(s),
+			Self::Geohash => geohash::parse(s),
+			Self::PlusCode => olc::parse(s),
+			Self::Utm => utm::parse_utm(s),
+			Self::Mgrs => utm::parse_mgrs(s),
+		}?;
+
+		check_range(coords)?;
+		Ok(coords)
+	}
+
+	/// Format the coordinates in this format
+	///
+	/// # Errors
+	/// Returns [`CoordinateError::OutOfRange`] if the coordinates are not on
+	/// earth, or can not be represented in this format (e.g. UTM near the
+	/// poles)
+	pub fn format(self, coords: Coordinates) -> Result<FormattedCoordinates, CoordinateError> {
+		check_range(coords)?;
+
+		let repr = match self {
+			Self::Utm | Self::Mgrs => Repr::Utm(utm::Utm::from_coordinates(coords)?),
+			_ => Repr::Coordinates(coords),
+		};
+
+		Ok(FormattedCoordinates { repr, format: self })
+	}
+}
+
+impl Display for CoordinateFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::Decimal => "decimal degrees",
+			Self::Dms => "degrees-minutes-seconds",
+			Self::Geohash => "geohash",
+			Self::PlusCode => "Plus Code",
+			Self::Utm => "UTM",
+			Self::Mgrs => "MGRS",
+		})
+	}
+}
+
+impl FromStr for CoordinateFormat {
+	type Err = CoordinateError;
+
+	/// Parse a format from its [identifier](Self::id) (case-insensitive)
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|format| format.id().eq_ignore_ascii_case(s.trim()))
+			.ok_or(CoordinateError::UnknownFormat)
+	}
+}
+
+impl FromStr for Coordinates {
+	type Err = CoordinateError;
+
+	/// Parse coordinates in any supported format, detecting the format
+	/// automatically
+	///
+	/// Numbers alone (e.g. `46`) are valid geohashes, but are much more likely
+	/// to be a mistake, so they aren't detected as geohashes.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut out_of_range = false;
+		let digits = s.trim().bytes().all(|b| b.is_ascii_digit());
+
+		for format in CoordinateFormat::DETECTION_ORDER {
+			if digits && format == CoordinateFormat::Geohash {
+				continue;
+			}
+
+			match format.parse(s) {
+				Ok(coords) => return Ok(coords),
+				Err(CoordinateError::OutOfRange) => out_of_range = true,
+				Err(_) => (),
+			}
+		}
+
+		Err(if out_of_range {
+			CoordinateError::OutOfRange
+		} else {
+			CoordinateError::UnknownFormat
+		})
+	}
+}
+
+impl Coordinates {
+	/// Format these coordinates in the given format
+	///
+	/// # Errors
+	/// See [`CoordinateFormat::format`]
+	pub fn format(self, format: CoordinateFormat) -> Result<FormattedCoordinates, CoordinateError> {
+		format.format(self)
+	}
+}
+
+/// Coordinates formatted in a specific [`CoordinateFormat`], created by
+/// [`CoordinateFormat::format`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormattedCoordinates {
+	/// The coordinates, already converted if necessary for the format
+	repr: Repr,
+	/// The format
+	format: CoordinateFormat,
+}
+
+/// The representation of coordinates used for formatting
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Repr {
+	/// Latitude and longitude
+	Coordinates(Coordinates),
+	/// UTM coordinates
+	Utm(utm::Utm),
+}
+
+impl Display for FormattedCoordinates {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match (self.repr, self.format) {
+			(Repr::Coordinates(coords), CoordinateFormat::Dms) => dms::fmt(coords, f),
+			(Repr::Coordinates(coords), CoordinateFormat::Geohash) => geohash::fmt(coords, f),
+			(Repr::Coordinates(coords), CoordinateFormat::PlusCode) => olc::fmt(coords, f),
+			(Repr::Coordinates(coords), _) => {
+				write!(f, "{:.5}°, {:.5}°", coords.lat.0, coords.lon.0)
+			}
+			(Repr::Utm(utm), CoordinateFormat::Mgrs) => utm.fmt_mgrs(f),
+			(Repr::Utm(utm), _) => utm.fmt_utm(f),
+		}
+	}
+}
+
+/// Check that the coordinates are finite and on earth
+fn check_range(coords: Coordinates) -> Result<(), CoordinateError> {
+	if (-90.0..=90.0).contains(&coords.lat.0) && (-180.0..=180.0).contains(&coords.lon.0) {
+		Ok(())
+	} else {
+		Err(CoordinateError::OutOfRange)
+	}
+}
+
+/// Parse a number of degrees, with an optional degree sign
+fn parse_degrees(s: &str) -> Option<f64> {
+	let s = s.trim();
+	let s = s.strip_suffix(['°', 'º']).unwrap_or(s);
+
+	s.parse::<f64>().ok().filter(|deg| deg.is_finite())
+}
+
+/// Parse decimal degrees, e.g. `46.05108, 14.50513` or `46.05108° 14.50513°`
+fn parse_decimal(s: &str) -> Result<Coordinates, CoordinateError> {
+	let invalid = CoordinateError::Invalid(CoordinateFormat::Decimal);
+
+	let (lat, lon) = if let Some(parts) = s.split_once(',') {
+		parts
+	} else {
+		let mut parts = s.split_whitespace();
+		let (Some(lat), Some(lon), None) = (parts.next(), parts.next(), parts.next()) else {
+			return Err(invalid);
+		};
+		(lat, lon)
+	};
+
+	Ok(Coordinates::new(
+		parse_degrees(lat).ok_or(invalid)?,
+		parse_degrees(lon).ok_or(invalid)?,
+	))
+}
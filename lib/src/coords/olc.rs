@@ -0,0 +1,145 @@
+//! Plus Codes (Open Location Code), e.g. `8FRP3G24+C3J`
+//!
+//! Only full codes are supported, as short codes (e.g. `3G24+C3 Ljubljana`)
+//! need a reference location to be decoded.
+
+use core::fmt::{Formatter, Result as FmtResult, Write};
+
+use libm::{floor, round};
+
+use super::CoordinateFormat;
+use crate::{CoordinateError, geo::Coordinates};
+
+/// The Open Location Code base20 alphabet
+const ALPHABET: &[u8; 20] = b"23456789CFGHJMPQRVWX";
+
+/// The separator between the 8th and 9th digits
+const SEPARATOR: char = '+';
+
+/// The character used to pad codes shorter than 8 digits
+const PADDING: char = '0';
+
+/// The position of the separator
+const SEPARATOR_POSITION: usize = 8;
+
+/// The number of digits encoding pairs of latitude and longitude
+const PAIR_LENGTH: usize = 10;
+
+/// The maximum number of digits in a code (10 pair digits and 5 grid digits)
+const MAX_LENGTH: usize = 15;
+
+/// The number of digits in formatted codes (a cell of about 3.5 by 2.8 meters)
+const LENGTH: usize = 11;
+
+/// The number of rows of the grid used after the pair digits
+const GRID_ROWS: usize = 5;
+
+/// The number of columns of the grid used after the pair digits
+const GRID_COLUMNS: usize = 4;
+
+/// The latitude precision of a full-length code, in cells per degree
+const LAT_PRECISION: f64 = 8_000.0 * 3_125.0;
+
+/// The longitude precision of a full-length code, in cells per degree
+const LON_PRECISION: f64 = 8_000.0 * 1_024.0;
+
+/// Format the coordinates as a Plus Code
+#[expect(
+	clippy::cast_possible_truncation,
+	clippy::cast_sign_loss,
+	reason = "the values are floored and clamped to the valid range"
+)]
+pub fn fmt(coords: Coordinates, f: &mut Formatter<'_>) -> FmtResult {
+	// rounding before flooring avoids floating-point error at cell boundaries
+	let to_cells = |deg: f64, precision: f64| floor(round(deg * precision * 1e6) / 1e6) as i64;
+
+	let mut lat = to_cells(coords.lat.0 + 90.0, LAT_PRECISION)
+		.clamp(0, (180.0 * LAT_PRECISION) as i64 - 1) as usize;
+	let mut lon = to_cells(coords.lon.0 + 180.0, LON_PRECISION)
+		.rem_euclid((360.0 * LON_PRECISION) as i64) as usize;
+
+	let mut digits = [0u8; MAX_LENGTH];
+
+	for digit in digits[PAIR_LENGTH..].iter_mut().rev() {
+		*digit = ALPHABET[lat % GRID_ROWS * GRID_COLUMNS + lon % GRID_COLUMNS];
+		lat /= GRID_ROWS;
+		lon /= GRID_COLUMNS;
+	}
+
+	for pair in digits[..PAIR_LENGTH].chunks_exact_mut(2).rev() {
+		pair[0] = ALPHABET[lat % ALPHABET.len()];
+		pair[1] = ALPHABET[lon % ALPHABET.len()];
+		lat /= ALPHABET.len();
+		lon /= ALPHABET.len();
+	}
+
+	for (i, &digit) in digits[..LENGTH].iter().enumerate() {
+		if i == SEPARATOR_POSITION {
+			f.write_char(SEPARATOR)?;
+		}
+
+		f.write_char(char::from(digit))?;
+	}
+
+	Ok(())
+}
+
+/// Parse a full Plus Code (case-insensitive), returning the center of its cell
+pub fn parse(s: &str) -> Result<Coordinates, CoordinateError> {
+	let invalid = CoordinateError::Invalid(CoordinateFormat::PlusCode);
+
+	let (before, after) = s.trim().split_once(SEPARATOR).ok_or(invalid)?;
+	let digits = before.trim_end_matches(PADDING);
+	let padding = before.len() - digits.len();
+
+	if before.len() != SEPARATOR_POSITION
+		|| digits.len() < 2
+		|| digits.len() % 2 != 0
+		|| (padding > 0 && !after.is_empty())
+		|| after.len() == 1
+		|| SEPARATOR_POSITION + after.len() > MAX_LENGTH
+	{
+		return Err(invalid);
+	}
+
+	let mut indices = digits.bytes().chain(after.bytes()).map(|c| {
+		ALPHABET
+			.iter()
+			.position(|&a| a == c.to_ascii_uppercase())
+			.ok_or(invalid)
+	});
+
+	let (mut lat, mut lon) = (-90.0, -180.0);
+	let (mut lat_res, mut lon_res) = (400.0, 400.0);
+
+	for _ in 0..PAIR_LENGTH / 2 {
+		let (Some(lat_index), Some(lon_index)) = (indices.next(), indices.next()) else {
+			break;
+		};
+
+		lat_res /= 20.0;
+		lon_res /= 20.0;
+		lat += f64::from(u8::try_from(lat_index?).map_err(|_| invalid)?) * lat_res;
+		lon += f64::from(u8::try_from(lon_index?).map_err(|_| invalid)?) * lon_res;
+	}
+
+	for index in indices {
+		let index = u8::try_from(index?).map_err(|_| invalid)?;
+
+		lat_res /= 5.0;
+		lon_res /= 4.0;
+		lat += f64::from(index / 4) * lat_res;
+		lon += f64::from(index % 4) * lon_res;
+	}
+
+	// the first latitude digit can't be more than 8 (`C`, 90°N) and the first
+	// longitude digit can't be more than 17 (`V`, 180°E)
+	if lat >= 90.0 || lon >= 180.0 {
+		return Err(invalid);
+	}
+
+	Ok(Coordinates::new(
+		(lat + lat_res / 2.0).min(90.0),
+		lon + lon_res / 2.0,
+	))
+}
@@ -0,0 +1,407 @@
+//! Universal Transverse Mercator (e.g. `33T 461716 5099842`) and the Military
+//! Grid Reference System (e.g. `33T VL 61716 99841`)
+//!
+//! The projection uses the Krüger series to the third order in the third
+//! flattening, which is accurate to well under a millimeter within a zone.
+//! Polar regions (covered by the Universal Polar Stereographic system instead)
+//! are not supported.
+
+#![expect(
+	clippy::suboptimal_flops,
+	reason = "`mul_add` is not available without `std`, and the formulas are more readable \
+	          without it"
+)]
+
+use core::fmt::{Formatter, Result as FmtResult};
+
+use libm::{asin, atan, atanh, ceil, cos, cosh, floor, sin, sinh, sqrt};
+
+use super::CoordinateFormat;
+use crate::{CoordinateError, geo::Coordinates};
+
+/// The semi-major axis of the WGS 84 ellipsoid in meters
+const WGS84_A: f64 = 6_378_137.0;
+
+/// The flattening of the WGS 84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// The third flattening of the WGS 84 ellipsoid
+const N: f64 = WGS84_F / (2.0 - WGS84_F);
+
+/// The scale factor on the central meridian
+const K0: f64 = 0.9996;
+
+/// The false easting in meters
+const FALSE_EASTING: f64 = 500_000.0;
+
+/// The false northing of the southern hemisphere in meters
+const FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// The latitude band letters, from 80°S to 84°N
+const BANDS: &[u8; 20] = b"CDEFGHJKLMNPQRSTUVWX";
+
+/// The MGRS 100 km column letters, for zones 1, 2, and 3 (repeating)
+const MGRS_COLUMNS: [&[u8; 8]; 3] = [b"ABCDEFGH", b"JKLMNPQR", b"STUVWXYZ"];
+
+/// The MGRS 100 km row letters
+const MGRS_ROWS: &[u8; 20] = b"ABCDEFGHJKLMNPQRSTUV";
+
+/// The size of an MGRS grid square in meters
+const MGRS_SQUARE: f64 = 100_000.0;
+
+/// The distance after which MGRS row letters repeat, in meters
+const MGRS_ROW_CYCLE: f64 = 2_000_000.0;
+
+/// UTM coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Utm {
+	/// The zone number (`1..=60`)
+	zone: u8,
+	/// The latitude band letter
+	band: u8,
+	/// The easting in meters
+	easting: f64,
+	/// The northing in meters
+	northing: f64,
+}
+
+impl Utm {
+	/// Project the coordinates into their UTM zone
+	pub fn from_coordinates(coords: Coordinates) -> Result<Self, CoordinateError> {
+		let (lat, lon) = (coords.lat.0, coords.lon.0);
+
+		if !(-80.0..=84.0).contains(&lat) {
+			return Err(CoordinateError::OutOfRange);
+		}
+
+		let zone = zone(lat, lon);
+		let (easting, northing) = forward(lat, lon, zone);
+
+		#[expect(
+			clippy::cast_possible_truncation,
+			clippy::cast_sign_loss,
+			reason = "the value is floored and in the range 0..20"
+		)]
+		let band = BANDS[(floor((lat + 80.0) / 8.0) as usize).min(BANDS.len() - 1)];
+
+		Ok(Self {
+			zone,
+			band,
+			easting,
+			northing,
+		})
+	}
+
+	/// Get the coordinates of this UTM position
+	fn to_coordinates(self) -> Result<Coordinates, CoordinateError> {
+		let (lat, lon) = inverse(self.easting, self.northing, self.zone, self.is_north());
+
+		if !(-80.5..=84.5).contains(&lat) {
+			return Err(CoordinateError::OutOfRange);
+		}
+
+		Ok(Coordinates::new(lat, normalize_lon(lon)))
+	}
+
+	/// Check if the latitude band is in the northern hemisphere
+	const fn is_north(self) -> bool {
+		self.band >= b'N'
+	}
+
+	/// Format as UTM with a precision of 1 meter
+	pub fn fmt_utm(self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{}{} {:.0} {:.0}",
+			self.zone,
+			char::from(self.band),
+			self.easting,
+			self.northing
+		)
+	}
+
+	/// Format as MGRS with a precision of 1 meter
+	#[expect(
+		clippy::cast_possible_truncation,
+		clippy::cast_sign_loss,
+		reason = "the values are floored and within a zone"
+	)]
+	pub fn fmt_mgrs(self, f: &mut Formatter<'_>) -> FmtResult {
+		let (column, row) = self.mgrs_square();
+		let easting = floor(self.easting) as u32 % 100_000;
+		let northing = floor(self.northing) as u32 % 100_000;
+
+		write!(
+			f,
+			"{}{} {}{} {easting:05} {northing:05}",
+			self.zone,
+			char::from(self.band),
+			char::from(column),
+			char::from(row),
+		)
+	}
+
+	/// Get the MGRS 100 km square column and row letters
+	#[expect(
+		clippy::cast_possible_truncation,
+		clippy::cast_sign_loss,
+		reason = "the values are floored and within a zone"
+	)]
+	fn mgrs_square(self) -> (u8, u8) {
+		let columns = MGRS_COLUMNS[usize::from(self.zone - 1) % 3];
+		let column = (floor(self.easting / MGRS_SQUARE) as usize).clamp(1, columns.len()) - 1;
+		let row = floor(self.northing / MGRS_SQUARE) as usize + row_offset(self.zone);
+
+		(columns[column], MGRS_ROWS[row % MGRS_ROWS.len()])
+	}
+}
+
+/// Get the UTM zone for the coordinates, including the exceptions around
+/// Norway and Svalbard
+#[expect(
+	clippy::cast_possible_truncation,
+	clippy::cast_sign_loss,
+	reason = "the value is floored and in the range 0..=60"
+)]
+fn zone(lat: f64, lon: f64) -> u8 {
+	if (56.0..64.0).contains(&lat) && (3.0..12.0).contains(&lon) {
+		return 32;
+	}
+
+	if (72.0..=84.0).contains(&lat) && (0.0..42.0).contains(&lon) {
+		return match lon {
+			..9.0 => 31,
+			..21.0 => 33,
+			..33.0 => 35,
+			_ => 37,
+		};
+	}
+
+	(floor((lon + 180.0) / 6.0) as u8) % 60 + 1
+}
+
+/// Get the longitude of the central meridian of the zone, in degrees
+fn central_meridian(zone: u8) -> f64 {
+	f64::from(zone) * 6.0 - 183.0
+}
+
+/// Get the offset of the MGRS row letters in the zone
+const fn row_offset(zone: u8) -> usize {
+	if zone.is_multiple_of(2) { 5 } else { 0 }
+}
+
+/// Normalize a longitude to `-180..=180`
+fn normalize_lon(lon: f64) -> f64 {
+	if lon > 180.0 {
+		lon - 360.0
+	} else if lon < -180.0 {
+		lon + 360.0
+	} else {
+		lon
+	}
+}
+
+/// The rectifying radius, scaled by `K0`
+fn scaled_rectifying_radius() -> f64 {
+	K0 * WGS84_A / (1.0 + N) * (1.0 + N * N / 4.0 + N * N * N * N / 64.0)
+}
+
+/// Project a latitude and longitude (in degrees) into the given zone,
+/// returning the easting and northing
+fn forward(lat: f64, lon: f64, zone: u8) -> (f64, f64) {
+	let alpha = [
+		N / 2.0 - 2.0 / 3.0 * N * N + 5.0 / 16.0 * N * N * N,
+		13.0 / 48.0 * N * N - 3.0 / 5.0 * N * N * N,
+		61.0 / 240.0 * N * N * N,
+	];
+
+	let phi = lat.to_radians();
+	let lambda = (lon - central_meridian(zone)).to_radians();
+	let c = 2.0 * sqrt(N) / (1.0 + N);
+
+	let t = sinh(atanh(sin(phi)) - c * atanh(c * sin(phi)));
+	let xi_prime = atan(t / cos(lambda));
+	let eta_prime = atanh(sin(lambda) / sqrt(1.0 + t * t));
+
+	let (mut xi, mut eta) = (xi_prime, eta_prime);
+	for (j, alpha) in (1..).map(f64::from).zip(alpha) {
+		xi += alpha * sin(2.0 * j * xi_prime) * cosh(2.0 * j * eta_prime);
+		eta += alpha * cos(2.0 * j * xi_prime) * sinh(2.0 * j * eta_prime);
+	}
+
+	let k = scaled_rectifying_radius();
+	let false_northing = if lat < 0.0 { FALSE_NORTHING_SOUTH } else { 0.0 };
+
+	(FALSE_EASTING + k * eta, false_northing + k * xi)
+}
+
+/// Convert an easting and northing in the given zone to a latitude and
+/// longitude (in degrees)
+fn inverse(easting: f64, northing: f64, zone: u8, is_north: bool) -> (f64, f64) {
+	let beta = [
+		N / 2.0 - 2.0 / 3.0 * N * N + 37.0 / 96.0 * N * N * N,
+		1.0 / 48.0 * N * N + 1.0 / 15.0 * N * N * N,
+		17.0 / 480.0 * N * N * N,
+	];
+	let delta = [
+		2.0 * N - 2.0 / 3.0 * N * N - 2.0 * N * N * N,
+		7.0 / 3.0 * N * N - 8.0 / 5.0 * N * N * N,
+		56.0 / 15.0 * N * N * N,
+	];
+
+	let k = scaled_rectifying_radius();
+	let false_northing = if is_north { 0.0 } else { FALSE_NORTHING_SOUTH };
+	let xi = (northing - false_northing) / k;
+	let eta = (easting - FALSE_EASTING) / k;
+
+	let (mut xi_prime, mut eta_prime) = (xi, eta);
+	for (j, beta) in (1..).map(f64::from).zip(beta) {
+		xi_prime -= beta * sin(2.0 * j * xi) * cosh(2.0 * j * eta);
+		eta_prime -= beta * cos(2.0 * j * xi) * sinh(2.0 * j * eta);
+	}
+
+	let chi = asin(sin(xi_prime) / cosh(eta_prime));
+	let mut phi = chi;
+	for (j, delta) in (1..).map(f64::from).zip(delta) {
+		phi += delta * sin(2.0 * j * chi);
+	}
+
+	let lambda = atan(sinh(eta_prime) / cos(xi_prime));
+
+	(
+		phi.to_degrees(),
+		central_meridian(zone) + lambda.to_degrees(),
+	)
+}
+
+/// Parse a zone number (`1..=60`) and latitude band letter
+fn parse_zone_band(zone: &str, band: &str) -> Option<(u8, u8)> {
+	let zone = zone
+		.parse::<u8>()
+		.ok()
+		.filter(|zone| (1..=60).contains(zone))?;
+	let [band] = band.as_bytes() else {
+		return None;
+	};
+	let band = band.to_ascii_uppercase();
+
+	BANDS.contains(&band).then_some((zone, band))
+}
+
+/// Parse UTM coordinates with a latitude band, e.g. `33T 461716 5099842` or
+/// `33 T 461716, 5099842`
+pub fn parse_utm(s: &str) -> Result<Coordinates, CoordinateError> {
+	let invalid = CoordinateError::Invalid(CoordinateFormat::Utm);
+
+	let mut parts = s
+		.split(|c: char| c.is_whitespace() || c == ',')
+		.filter(|part| !part.is_empty());
+	let mut next = || parts.next().ok_or(invalid);
+
+	let first = next()?;
+	let (zone, band) = match first.split_at_checked(first.len().saturating_sub(1)) {
+		Some((zone, band)) if !zone.is_empty() => parse_zone_band(zone, band),
+		_ => None,
+	}
+	.map_or_else(|| parse_zone_band(first, next()?).ok_or(invalid), Ok)?;
+
+	let mut number = || {
+		next()?
+			.parse::<f64>()
+			.ok()
+			.filter(|n| n.is_finite())
+			.ok_or(invalid)
+	};
+	let easting = number()?;
+	let northing = number()?;
+
+	if parts.next().is_some()
+		|| !(0.0..1_000_000.0).contains(&easting)
+		|| !(0.0..=FALSE_NORTHING_SOUTH).contains(&northing)
+	{
+		return Err(invalid);
+	}
+
+	Utm {
+		zone,
+		band,
+		easting,
+		northing,
+	}
+	.to_coordinates()
+}
+
+/// Parse MGRS coordinates with a precision of 1 to 100 000 meters (e.g. `33T
+/// VL 61716 99841`, `33TVL6199`, or `33TVL`), returning the center of the
+/// grid square
+pub fn parse_mgrs(s: &str) -> Result<Coordinates, CoordinateError> {
+	let invalid = CoordinateError::Invalid(CoordinateFormat::Mgrs);
+
+	// the longest possible MGRS reference without whitespace is 15 characters
+	let mut buf = [0u8; 15];
+	let mut len = 0;
+	for c in s.chars().filter(|c| !c.is_whitespace()) {
+		let byte = u8::try_from(c).map_err(|_| invalid)?;
+		*buf.get_mut(len).ok_or(invalid)? = byte.to_ascii_uppercase();
+		len += 1;
+	}
+
+	let zone_len = buf[..len].iter().take_while(|c| c.is_ascii_digit()).count();
+	let (zone, rest) = buf[..len].split_at(zone_len);
+	let [band, column, row, digits @ ..] = rest else {
+		return Err(invalid);
+	};
+
+	let (zone, band) = parse_zone_band(
+		core::str::from_utf8(zone).map_err(|_| invalid)?,
+		core::str::from_utf8(&[*band]).map_err(|_| invalid)?,
+	)
+	.ok_or(invalid)?;
+
+	if digits.len() % 2 != 0 || !digits.iter().all(u8::is_ascii_digit) {
+		return Err(invalid);
+	}
+
+	let (easting_digits, northing_digits) = digits.split_at(digits.len() / 2);
+	let scale = 10_u32
+		.checked_pow(
+			5_u32
+				.checked_sub(u32::try_from(easting_digits.len()).map_err(|_| invalid)?)
+				.ok_or(invalid)?,
+		)
+		.ok_or(invalid)?;
+	let in_square = |digits: &[u8]| {
+		let value = digits
+			.iter()
+			.fold(0, |value, digit| value * 10 + u32::from(digit - b'0'));
+		f64::from(value * scale) + f64::from(scale) / 2.0
+	};
+
+	let column = MGRS_COLUMNS[usize::from(zone - 1) % 3]
+		.iter()
+		.position(|c| c == column)
+		.ok_or(invalid)?;
+	let row = MGRS_ROWS.iter().position(|c| c == row).ok_or(invalid)?;
+	let row = (row + MGRS_ROWS.len() - row_offset(zone)) % MGRS_ROWS.len();
+
+	let easting = f64::from(u32::try_from(column + 1).map_err(|_| invalid)?) * MGRS_SQUARE
+		+ in_square(easting_digits);
+	let mut northing = f64::from(u32::try_from(row).map_err(|_| invalid)?) * MGRS_SQUARE
+		+ in_square(northing_digits);
+
+	// the row letters repeat every 2000 km, so the latitude band is used to
+	// find the right cycle, starting from the band's southern edge
+	let band_index = BANDS.iter().position(|&b| b == band).ok_or(invalid)?;
+	let band_south = f64::from(u8::try_from(band_index).map_err(|_| invalid)?) * 8.0 - 80.0;
+	let (_, min_northing) = forward(band_south, central_meridian(zone), zone);
+	northing +=
+		ceil((min_northing - MGRS_SQUARE - northing) / MGRS_ROW_CYCLE).max(0.0) * MGRS_ROW_CYCLE;
+
+	Utm {
+		zone,
+		band,
+		easting,
+		northing,
+	}
+	.to_coordinates()
+}
@@ -0,0 +1,153 @@
+//! Errors returned by this library
+
+use core::{
+	fmt::{Display, Formatter, Result as FmtResult},
+	time::Duration,
+};
+
+use crate::coords::CoordinateFormat;
+
+/// An error during a cryptographic or encoding operation, or when checking a
+/// received Ping
+///
+/// The variants distinguish between different kinds of failures, but never
+/// contain any key material, plaintext, or ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Error {
+	/// The encrypted Ping info does not start with the `b"PING"` magic number
+	BadMagic,
+	/// The encrypted Ping info, public key, or key exchange message does not
+	/// have the expected length (64 bytes for Ping info, 32 bytes for keys and
+	/// messages)
+	BadLength,
+	/// The encrypted Ping info, public key, or key exchange message is not
+	/// valid (urlsafe, unpadded) base64
+	BadEncoding,
+	/// The encrypted Ping info could not be authenticated, because it was
+	/// encrypted using a different key or modified after encryption
+	Authentication,
+	/// The Ping info could not be encrypted
+	Encryption,
+	/// The decrypted Ping info contains an invalid value in the given field
+	InvalidValue(Field),
+	/// The Ping was already received before
+	Replayed,
+	/// The Ping's timestamp is too far in the past, by the given age
+	Stale(Duration),
+	/// The Ping's timestamp is too far in the future, by the given amount
+	FromFuture(Duration),
+	/// The other side's message of a key exchange authenticated with a short
+	/// code (see [`pake`](crate::pake)) is not valid
+	BadPakeMessage,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			Self::BadMagic => write!(f, "the encrypted ping info is missing the magic number"),
+			Self::BadLength => write!(f, "the input has an invalid length"),
+			Self::BadEncoding => write!(f, "the input is not valid base64"),
+			Self::Authentication => write!(f, "the encrypted ping info could not be authenticated"),
+			Self::Encryption => write!(f, "error encrypting ping info"),
+			Self::InvalidValue(field) => write!(f, "the ping info contains an invalid {field}"),
+			Self::Replayed => write!(f, "the ping was already received"),
+			Self::Stale(age) => write!(f, "the ping is too old ({} s)", age.as_secs()),
+			Self::FromFuture(skew) => {
+				write!(
+					f,
+					"the ping is from the future ({} s ahead)",
+					skew.as_secs()
+				)
+			}
+			Self::BadPakeMessage => write!(f, "the key exchange message is invalid"),
+		}
+	}
+}
+
+impl core::error::Error for Error {}
+
+/// An error while parsing or formatting coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CoordinateError {
+	/// The input is not in any supported coordinate format, or is not the name
+	/// of a supported format
+	UnknownFormat,
+	/// The input is not valid in the given coordinate format
+	Invalid(CoordinateFormat),
+	/// The coordinates are not on earth, or can not be represented in the
+	/// requested format
+	OutOfRange,
+}
+
+impl Display for CoordinateError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			Self::UnknownFormat => write!(f, "unknown coordinate format"),
+			Self::Invalid(format) => write!(f, "invalid {format} coordinates"),
+			Self::OutOfRange => write!(f, "the coordinates are out of range"),
+		}
+	}
+}
+
+impl core::error::Error for CoordinateError {}
+
+/// An error when parsing the name of an
+/// [`ExportFormat`](crate::export::ExportFormat)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnknownExportFormat;
+
+impl Display for UnknownExportFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "unknown export format")
+	}
+}
+
+impl core::error::Error for UnknownExportFormat {}
+
+/// An error when parsing or applying an invalid
+/// [`Precision`](crate::privacy::Precision)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InvalidPrecision;
+
+impl Display for InvalidPrecision {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"invalid precision (expected \"exact\", \"grid:METERS\", or \"blur:METERS\", \
+			 optionally followed by \",altitude:keep\", \",altitude:coarsen:METERS\", or \
+			 \",altitude:omit\")"
+		)
+	}
+}
+
+impl core::error::Error for InvalidPrecision {}
+
+/// A field of [`PingInfo`](crate::PingInfo)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Field {
+	/// [`PingInfo::ts`](crate::PingInfo::ts)
+	Timestamp,
+	/// [`PingInfo::lat`](crate::PingInfo::lat)
+	Latitude,
+	/// [`PingInfo::lon`](crate::PingInfo::lon)
+	Longitude,
+	/// [`PingInfo::alt`](crate::PingInfo::alt)
+	Altitude,
+	/// [`PingInfo::err`](crate::PingInfo::err)
+	PositionError,
+}
+
+impl Display for Field {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::Timestamp => "timestamp",
+			Self::Latitude => "latitude",
+			Self::Longitude => "longitude",
+			Self::Altitude => "altitude",
+			Self::PositionError => "position error",
+		})
+	}
+}
@@ -0,0 +1,291 @@
+//! Exporting received Pings to GPX, GeoJSON, and KML
+//!
+//! Pings are written as GPX 1.1 waypoints, GeoJSON (RFC 7946) point features
+//! in a `FeatureCollection`, or KML 2.2 placemarks, for use in GIS software
+//! and map viewers.
+//! Documents consist of a [header](ExportFormat::header), any number of
+//! [Pings](ExportFormat::write_ping), and a [footer](ExportFormat::footer),
+//! so that Pings can be appended to an existing document by overwriting its
+//! footer.
+//!
+//! All writers only need [`core::fmt::Write`], so they work without `std`.
+
+use core::{
+	fmt::{Display, Formatter, Result as FmtResult, Write},
+	str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PingInfo, Timestamp, UnknownExportFormat};
+
+/// A received Ping, with information about where it came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReceivedPing<'a> {
+	/// The Ping info
+	pub info: PingInfo,
+	/// The sender of the Ping (e.g. their Ping ID)
+	pub from: &'a str,
+	/// The time at which the Ping was received
+	pub received: Timestamp,
+}
+
+/// A file format for exporting Pings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+	/// GPX 1.1, with a waypoint for each Ping
+	Gpx,
+	/// A GeoJSON `FeatureCollection`, with a point feature for each Ping
+	///
+	/// The altitude above mean sea level is a property of the feature, because
+	/// GeoJSON positions use heights above the WGS 84 ellipsoid.
+	GeoJson,
+	/// KML 2.2, with a placemark for each Ping
+	Kml,
+}
+
+impl ExportFormat {
+	/// All supported export formats
+	pub const ALL: [Self; 3] = [Self::Gpx, Self::GeoJson, Self::Kml];
+
+	/// Get the short identifier of this format, as accepted by its `FromStr`
+	/// implementation (e.g. `geojson`)
+	#[must_use]
+	pub const fn id(self) -> &'static str {
+		match self {
+			Self::Gpx => "gpx",
+			Self::GeoJson => "geojson",
+			Self::Kml => "kml",
+		}
+	}
+
+	/// Get the format for the given file extension (case-insensitive, e.g.
+	/// `gpx` or `json`)
+	#[must_use]
+	pub const fn from_extension(ext: &str) -> Option<Self> {
+		if ext.eq_ignore_ascii_case("gpx") {
+			Some(Self::Gpx)
+		} else if ext.eq_ignore_ascii_case("geojson") || ext.eq_ignore_ascii_case("json") {
+			Some(Self::GeoJson)
+		} else if ext.eq_ignore_ascii_case("kml") {
+			Some(Self::Kml)
+		} else {
+			None
+		}
+	}
+
+	/// The start of a document, before any Pings
+	#[must_use]
+	pub const fn header(self) -> &'static str {
+		match self {
+			Self::Gpx => {
+				r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Pinger" xmlns="http://www.topografix.com/GPX/1/1" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd">"#
+			}
+			Self::GeoJson => r#"{"type":"FeatureCollection","features":["#,
+			Self::Kml => {
+				r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>
+<name>Pinger</name>"#
+			}
+		}
+	}
+
+	/// The end of a document, after all Pings
+	#[must_use]
+	pub const fn footer(self) -> &'static str {
+		match self {
+			Self::Gpx => "\n</gpx>\n",
+			Self::GeoJson => "\n]}\n",
+			Self::Kml => "\n</Document>\n</kml>\n",
+		}
+	}
+
+	/// Write a Ping, to be placed after the header or another Ping (`first`
+	/// specifies which), and before the footer
+	///
+	/// # Errors
+	/// Returns an error if writing to `w` fails
+	pub fn write_ping(self, w: &mut impl Write, ping: &ReceivedPing<'_>, first: bool) -> FmtResult {
+		if !first && self == Self::GeoJson {
+			w.write_char(',')?;
+		}
+
+		w.write_char('\n')?;
+
+		let info = &ping.info;
+		let (lat, lon, alt, err) = (info.lat().0, info.lon().0, info.alt().0, info.err().0);
+		let (ts, received) = (Rfc3339(info.ts()), Rfc3339(ping.received));
+		let from = Xml(ping.from);
+
+		match self {
+			Self::Gpx => {
+				// the GPX schema requires longitudes in `-180..180`
+				let lon = if lon >= 180.0 { lon - 360.0 } else { lon };
+
+				write!(w, r#"<wpt lat="{lat}" lon="{lon}">"#)?;
+				write!(
+					w,
+					"<ele>{alt}</ele><time>{ts}</time><name>Ping from {from}</name>"
+				)?;
+				write!(
+					w,
+					"<desc>Position error: {err} m, received at {received}</desc>"
+				)?;
+				write!(w, "<type>Ping</type></wpt>")
+			}
+			Self::GeoJson => {
+				write!(w, r#"{{"type":"Feature","#)?;
+				write!(
+					w,
+					r#""geometry":{{"type":"Point","coordinates":[{lon},{lat}]}},"#
+				)?;
+				write!(
+					w,
+					r#""properties":{{"from":"{}","time":"{ts}","#,
+					Json(ping.from)
+				)?;
+				write!(w, r#""received":"{received}","alt":{alt},"err":{err}}}}}"#)
+			}
+			Self::Kml => {
+				write!(w, "<Placemark><name>Ping from {from}</name>")?;
+				write!(
+					w,
+					"<description>Position error: {err} m, received at {received}</description>"
+				)?;
+				write!(w, "<TimeStamp><when>{ts}</when></TimeStamp><ExtendedData>")?;
+				write!(w, r#"<Data name="from"><value>{from}</value></Data>"#)?;
+				write!(
+					w,
+					r#"<Data name="received"><value>{received}</value></Data>"#
+				)?;
+				write!(w, r#"<Data name="alt"><value>{alt}</value></Data>"#)?;
+				write!(w, r#"<Data name="err"><value>{err}</value></Data>"#)?;
+				write!(
+					w,
+					"</ExtendedData><Point><altitudeMode>absolute</altitudeMode>"
+				)?;
+				write!(
+					w,
+					"<coordinates>{lon},{lat},{alt}</coordinates></Point></Placemark>"
+				)
+			}
+		}
+	}
+
+	/// Write a complete document containing the given Pings
+	///
+	/// # Errors
+	/// Returns an error if writing to `w` fails
+	pub fn write_document<'a>(
+		self,
+		w: &mut impl Write,
+		pings: impl IntoIterator<Item = &'a ReceivedPing<'a>>,
+	) -> FmtResult {
+		w.write_str(self.header())?;
+
+		for (i, ping) in pings.into_iter().enumerate() {
+			self.write_ping(w, ping, i == 0)?;
+		}
+
+		w.write_str(self.footer())
+	}
+}
+
+impl Display for ExportFormat {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::Gpx => "GPX",
+			Self::GeoJson => "GeoJSON",
+			Self::Kml => "KML",
+		})
+	}
+}
+
+impl FromStr for ExportFormat {
+	type Err = UnknownExportFormat;
+
+	/// Parse a format from its [identifier](Self::id) (case-insensitive)
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|format| format.id().eq_ignore_ascii_case(s.trim()))
+			.ok_or(UnknownExportFormat)
+	}
+}
+
+/// A timestamp formatted as an RFC 3339 date and time in UTC (e.g.
+/// `2024-05-14T08:30:01Z`), which is also a valid `xsd:dateTime`
+struct Rfc3339(Timestamp);
+
+impl Display for Rfc3339 {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		let secs = self.0.0 % 86400;
+
+		// Howard Hinnant's `civil_from_days`, for days since 1970-01-01
+		let days = self.0.0 / 86400 + 719_468;
+		let era = days / 146_097;
+		let doe = days % 146_097;
+		let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+		let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+		let mp = (5 * doy + 2) / 153;
+		let day = doy - (153 * mp + 2) / 5 + 1;
+		let month = if mp < 10 { mp + 3 } else { mp - 9 };
+		let year = yoe + era * 400 + u64::from(month <= 2);
+
+		write!(
+			f,
+			"{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+			secs / 3600,
+			secs / 60 % 60,
+			secs % 60
+		)
+	}
+}
+
+/// A string escaped for use in XML text and attribute values
+struct Xml<'a>(&'a str);
+
+impl Display for Xml<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		for c in self.0.chars() {
+			match c {
+				'&' => f.write_str("&amp;")?,
+				'<' => f.write_str("&lt;")?,
+				'>' => f.write_str("&gt;")?,
+				'"' => f.write_str("&quot;")?,
+				'\'' => f.write_str("&apos;")?,
+				// not allowed in XML 1.0
+				'\0'..='\x08' | '\x0b' | '\x0c' | '\x0e'..='\x1f' | '\u{fffe}' | '\u{ffff}' => {
+					f.write_char(char::REPLACEMENT_CHARACTER)?;
+				}
+				c => f.write_char(c)?,
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// A string escaped for use in a JSON string
+struct Json<'a>(&'a str);
+
+impl Display for Json<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		for c in self.0.chars() {
+			match c {
+				'"' => f.write_str("\\\"")?,
+				'\\' => f.write_str("\\\\")?,
+				'\n' => f.write_str("\\n")?,
+				'\r' => f.write_str("\\r")?,
+				'\t' => f.write_str("\\t")?,
+				'\0'..='\x1f' => write!(f, "\\u{:04x}", u32::from(c))?,
+				c => f.write_char(c)?,
+			}
+		}
+
+		Ok(())
+	}
+}
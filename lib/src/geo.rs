@@ -0,0 +1,346 @@
+//! Geodesic calculations between Ping positions
+//!
+//! Distances and bearings are calculated on the WGS 84 ellipsoid using
+//! Vincenty's inverse formula, falling back to a spherical approximation for
+//! nearly antipodal points, for which Vincenty's formula may not converge.
+
+#![expect(
+	clippy::suboptimal_flops,
+	reason = "`mul_add` is not available without `std`, and the formulas are more readable \
+	          without it"
+)]
+
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+use libm::{atan, atan2, cos, fabs, fmod, round, sin, sqrt, sqrtf, tan};
+use serde::{Deserialize, Serialize};
+
+use crate::{Degrees, Meters, PingInfo};
+
+/// The semi-major axis of the WGS 84 ellipsoid in meters
+const WGS84_A: f64 = 6_378_137.0;
+
+/// The flattening of the WGS 84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// The semi-minor axis of the WGS 84 ellipsoid in meters
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+
+/// The mean radius of the earth in meters, used for the spherical fallback and
+/// for [`privacy`](crate::privacy)
+pub(crate) const MEAN_EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The maximum number of iterations of Vincenty's formula
+const MAX_ITERATIONS: usize = 200;
+
+/// The convergence threshold for Vincenty's formula in radians (about 0.06 mm)
+const CONVERGENCE_THRESHOLD: f64 = 1e-12;
+
+/// A position on the earth's surface
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+	/// The latitude in degrees
+	pub lat: Degrees,
+	/// The longitude in degrees
+	pub lon: Degrees,
+}
+
+impl Coordinates {
+	/// Create `Coordinates` from a latitude and longitude in degrees
+	#[must_use]
+	pub const fn new(lat: f64, lon: f64) -> Self {
+		Self {
+			lat: Degrees(lat),
+			lon: Degrees(lon),
+		}
+	}
+
+	/// Calculate the ellipsoidal distance from these coordinates to `other`
+	#[must_use]
+	pub fn distance_to(self, other: Self) -> Meters {
+		#[expect(
+			clippy::cast_possible_truncation,
+			reason = "distances on earth fit into an f32 with sub-meter precision"
+		)]
+		Meters(inverse(self, other).0 as f32)
+	}
+
+	/// Calculate the initial bearing from these coordinates towards `other`, in
+	/// degrees clockwise from true north (`0..360`)
+	#[must_use]
+	pub fn bearing_to(self, other: Self) -> Degrees {
+		Degrees(inverse(self, other).1)
+	}
+}
+
+impl PingInfo {
+	/// Get the coordinates of this Ping
+	#[must_use]
+	pub const fn coordinates(&self) -> Coordinates {
+		Coordinates {
+			lat: self.lat,
+			lon: self.lon,
+		}
+	}
+
+	/// Calculate the ellipsoidal distance from this Ping to `other`
+	#[must_use]
+	pub fn distance_to(&self, other: &Self) -> Meters {
+		self.coordinates().distance_to(other.coordinates())
+	}
+
+	/// Calculate the initial bearing from this Ping towards `other`, in degrees
+	/// clockwise from true north (`0..360`)
+	#[must_use]
+	pub fn bearing_to(&self, other: &Self) -> Degrees {
+		self.coordinates().bearing_to(other.coordinates())
+	}
+
+	/// Calculate the combined uncertainty of the positions of this Ping and
+	/// `other`
+	///
+	/// The position errors are assumed to be independent, so they are combined
+	/// as the square root of the sum of their squares.
+	#[must_use]
+	pub fn combined_error(&self, other: &Self) -> Meters {
+		Meters(sqrtf(self.err.0 * self.err.0 + other.err.0 * other.err.0))
+	}
+
+	/// Describe the position of this Ping relative to the `origin` Ping
+	#[must_use]
+	pub fn relative_to(&self, origin: &Self) -> Relative {
+		let (distance, bearing) = inverse(origin.coordinates(), self.coordinates());
+
+		#[expect(
+			clippy::cast_possible_truncation,
+			reason = "distances on earth fit into an f32 with sub-meter precision"
+		)]
+		Relative {
+			distance: Meters(distance as f32),
+			bearing: Degrees(bearing),
+			direction: CompassDirection::from_bearing(Degrees(bearing)),
+			err: self.combined_error(origin),
+		}
+	}
+}
+
+/// The position of a Ping relative to another one
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Relative {
+	/// The distance between the two Pings
+	pub distance: Meters,
+	/// The initial bearing from the origin to the Ping
+	pub bearing: Degrees,
+	/// The compass direction from the origin to the Ping
+	pub direction: CompassDirection,
+	/// The combined uncertainty of both positions
+	pub err: Meters,
+}
+
+impl Display for Relative {
+	/// Format as e.g. `1.3 km NE (±25 m)`
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{} {} (±{})",
+			Distance(self.distance),
+			self.direction,
+			Distance(self.err)
+		)
+	}
+}
+
+/// A distance formatted for humans, with a precision appropriate for its
+/// magnitude (e.g. `25 m`, `1.3 km`, or `42 km`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Distance(pub Meters);
+
+impl Display for Distance {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		let m = f64::from(self.0.0);
+
+		if m < 999.5 {
+			write!(f, "{m:.0} m")
+		} else if m < 9_950.0 {
+			write!(f, "{:.1} km", m / 1000.0)
+		} else {
+			write!(f, "{:.0} km", m / 1000.0)
+		}
+	}
+}
+
+/// One of the 8 principal compass directions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompassDirection {
+	/// North
+	N,
+	/// Northeast
+	NE,
+	/// East
+	E,
+	/// Southeast
+	SE,
+	/// South
+	S,
+	/// Southwest
+	SW,
+	/// West
+	W,
+	/// Northwest
+	NW,
+}
+
+impl CompassDirection {
+	/// Get the compass direction closest to the given bearing (in degrees
+	/// clockwise from true north)
+	///
+	/// Bearings outside of `0..360` are wrapped around, e.g. `-400` is `320`
+	/// (NW). Bearings which are not finite (`NaN` or infinite) don't have a
+	/// direction, and are treated as north.
+	#[must_use]
+	pub fn from_bearing(bearing: Degrees) -> Self {
+		let mut degrees = fmod(bearing.0, 360.0);
+		if degrees < 0.0 {
+			degrees += 360.0;
+		}
+
+		#[expect(
+			clippy::cast_possible_truncation,
+			clippy::cast_sign_loss,
+			reason = "the value is rounded and in the range 0..=8 (or NaN, which becomes 0)"
+		)]
+		match round(degrees / 45.0) as u8 % 8 {
+			0 => Self::N,
+			1 => Self::NE,
+			2 => Self::E,
+			3 => Self::SE,
+			4 => Self::S,
+			5 => Self::SW,
+			6 => Self::W,
+			_ => Self::NW,
+		}
+	}
+}
+
+impl Display for CompassDirection {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.write_str(match self {
+			Self::N => "N",
+			Self::NE => "NE",
+			Self::E => "E",
+			Self::SE => "SE",
+			Self::S => "S",
+			Self::SW => "SW",
+			Self::W => "W",
+			Self::NW => "NW",
+		})
+	}
+}
+
+/// Solve the inverse geodesic problem, returning the distance in meters and
+/// the initial bearing in degrees (`0..360`) from `from` to `to`
+#[expect(
+	clippy::many_single_char_names,
+	clippy::similar_names,
+	reason = "the names follow Vincenty's formula"
+)]
+fn inverse(from: Coordinates, to: Coordinates) -> (f64, f64) {
+	let phi1 = from.lat.0.to_radians();
+	let phi2 = to.lat.0.to_radians();
+	let l = (to.lon.0 - from.lon.0).to_radians();
+
+	let u1 = atan((1.0 - WGS84_F) * tan(phi1));
+	let u2 = atan((1.0 - WGS84_F) * tan(phi2));
+	let (sin_u1, cos_u1) = (sin(u1), cos(u1));
+	let (sin_u2, cos_u2) = (sin(u2), cos(u2));
+
+	let mut lambda = l;
+
+	for _ in 0..MAX_ITERATIONS {
+		let (sin_lambda, cos_lambda) = (sin(lambda), cos(lambda));
+
+		let x = cos_u2 * sin_lambda;
+		let y = cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda;
+		let sin_sigma = sqrt(x * x + y * y);
+
+		if sin_sigma == 0.0 {
+			// coincident points
+			return (0.0, 0.0);
+		}
+
+		let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+		let sigma = atan2(sin_sigma, cos_sigma);
+		let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+		let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+		let cos_2sigma_m = if cos2_alpha == 0.0 {
+			// equatorial line
+			0.0
+		} else {
+			cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+		};
+		let cos2_2sigma_m = cos_2sigma_m * cos_2sigma_m;
+		let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+
+		let prev_lambda = lambda;
+		lambda = l
+			+ (1.0 - c)
+				* WGS84_F * sin_alpha
+				* (sigma
+					+ c * sin_sigma
+						* (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos2_2sigma_m)));
+
+		if fabs(lambda - prev_lambda) < CONVERGENCE_THRESHOLD {
+			let u2 = cos2_alpha * (WGS84_A * WGS84_A - WGS84_B * WGS84_B) / (WGS84_B * WGS84_B);
+			let a = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+			let b = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+			let delta_sigma = b
+				* sin_sigma * (cos_2sigma_m
+				+ b / 4.0
+					* (cos_sigma * (-1.0 + 2.0 * cos2_2sigma_m)
+						- b / 6.0
+							* cos_2sigma_m * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+							* (-3.0 + 4.0 * cos2_2sigma_m)));
+
+			let distance = WGS84_B * a * (sigma - delta_sigma);
+			let bearing = atan2(cos_u2 * sin_lambda, y);
+
+			return (distance, normalize_bearing(bearing));
+		}
+	}
+
+	spherical_inverse(from, to)
+}
+
+/// Solve the inverse geodesic problem on a sphere with the earth's mean radius,
+/// returning the distance in meters and the initial bearing in degrees
+/// (`0..360`) from `from` to `to`
+fn spherical_inverse(from: Coordinates, to: Coordinates) -> (f64, f64) {
+	let phi1 = from.lat.0.to_radians();
+	let phi2 = to.lat.0.to_radians();
+	let delta_phi = phi2 - phi1;
+	let delta_lambda = (to.lon.0 - from.lon.0).to_radians();
+
+	let sin_half_delta_phi = sin(delta_phi / 2.0);
+	let sin_half_delta_lambda = sin(delta_lambda / 2.0);
+	let h = sin_half_delta_phi * sin_half_delta_phi
+		+ cos(phi1) * cos(phi2) * sin_half_delta_lambda * sin_half_delta_lambda;
+	let distance = 2.0 * MEAN_EARTH_RADIUS * atan2(sqrt(h), sqrt(1.0 - h));
+
+	let bearing = atan2(
+		sin(delta_lambda) * cos(phi2),
+		cos(phi1) * sin(phi2) - sin(phi1) * cos(phi2) * cos(delta_lambda),
+	);
+
+	(distance, normalize_bearing(bearing))
+}
+
+/// Convert a bearing in radians (`-π..=π`) to degrees (`0..360`)
+fn normalize_bearing(bearing: f64) -> f64 {
+	let degrees = bearing.to_degrees();
+
+	if degrees < 0.0 {
+		degrees + 360.0
+	} else {
+		degrees
+	}
+}
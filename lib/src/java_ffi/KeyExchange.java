@@ -1,7 +1,26 @@
 package dev.janm.pinger;
 
-public class KeyExchange {
-	private final byte[] ephemeralSecret;
+/**
+ * One side of an x25519 key exchange, or of a key exchange authenticated with a
+ * short code
+ *
+ * The ephemeral secret key and the resulting shared key are stored in native
+ * memory and only referenced from Java using opaque handles. The secret key can
+ * be used for exactly one Diffie-Hellman key exchange. Both the key exchange and
+ * the shared key must be closed once they are no longer needed.
+ *
+ * With a code, the server can't intercept the Ping, but the shared key is only
+ * the same on both sides if both people used the same code (otherwise
+ * decrypting the Ping fails with a {@link PingerException.Authentication}).
+ *
+ * Either way, both people can compare the safety number of the resulting shared
+ * key (e.g. over a phone call), which is only the same on both sides if nobody
+ * intercepted the key exchange.
+ */
+public class KeyExchange implements AutoCloseable {
+	private long ephemeralSecret;
+	private final boolean withCode;
+	private final String publicKey;
 
 	static {
 		System.loadLibrary("pinger-lib");
@@ -9,38 +28,105 @@ public class KeyExchange {
 
 	public KeyExchange() {
 		ephemeralSecret = generateEphemeralSecret();
+		withCode = false;
+		publicKey = calculatePublicKey(ephemeralSecret);
+	}
+
+	/** Start a key exchange authenticated with the code both people agreed on */
+	public KeyExchange(String code) {
+		ephemeralSecret = startPake(code);
+		withCode = true;
+		publicKey = calculatePakeMessage(ephemeralSecret);
+	}
+
+	/** Whether this key exchange is authenticated with a code */
+	public boolean isWithCode() {
+		return withCode;
 	}
 
 	public String getPublicKey() {
-		return calculatePublicKey(ephemeralSecret);
+		return publicKey;
+	}
+
+	public synchronized SharedKey diffieHellman(String otherPublicKey) {
+		long secret = ephemeralSecret;
+		ephemeralSecret = 0;
+
+		long sharedKey = withCode ? finishPake(secret, otherPublicKey) : performDiffieHellman(secret, otherPublicKey);
+
+		try {
+			return new SharedKey(sharedKey, calculateSafetyNumber(sharedKey, publicKey, otherPublicKey));
+		} catch (RuntimeException e) {
+			SharedKey.freeSharedKey(sharedKey);
+			throw e;
+		}
 	}
 
-	public SharedKey diffieHellman(String otherPublicKey) {
-		return new SharedKey(performDiffieHellman(ephemeralSecret, otherPublicKey));
+	@Override
+	public synchronized void close() {
+		if (ephemeralSecret != 0) {
+			if (withCode) {
+				freePakeSecret(ephemeralSecret);
+			} else {
+				freeEphemeralSecret(ephemeralSecret);
+			}
+
+			ephemeralSecret = 0;
+		}
 	}
 
-	private static native byte[] generateEphemeralSecret();
+	private static native long generateEphemeralSecret();
+
+	private static native String calculatePublicKey(long secret);
 
-	private static native String calculatePublicKey(byte[] secret);
+	private static native long performDiffieHellman(long ourSecret, String theirPublicKey);
 
-	private static native byte[] performDiffieHellman(byte[] ourSecret, String theirPublicKey);
-	
-	public static class SharedKey {
-		private final byte[] sharedSecret;
+	private static native void freeEphemeralSecret(long secret);
 
-		private SharedKey(byte[] sharedSecret) {
-			this.sharedSecret = sharedSecret;
+	private static native long startPake(String code);
+
+	private static native String calculatePakeMessage(long secret);
+
+	private static native long finishPake(long ourSecret, String theirMessage);
+
+	private static native void freePakeSecret(long secret);
+
+	private static native String calculateSafetyNumber(long sharedKey, String ourPublicKey, String theirPublicKey);
+
+	public static class SharedKey implements AutoCloseable {
+		private long sharedKey;
+		private final String safetyNumber;
+
+		private SharedKey(long sharedKey, String safetyNumber) {
+			this.sharedKey = sharedKey;
+			this.safetyNumber = safetyNumber;
 		}
 
-		private static native String base64Encode(byte[] sharedSecret);
+		/**
+		 * Get the safety number of the key exchange (e.g. {@code "0123 4567 8901"}),
+		 * which both people can compare to check that nobody intercepted it
+		 */
+		public String getSafetyNumber() {
+			return safetyNumber;
+		}
+
+		private static native void freeSharedKey(long sharedKey);
 
-		byte[] getSharedSecret() {
-			return this.sharedSecret;
+		synchronized long getHandle() {
+			return this.sharedKey;
+		}
+
+		@Override
+		public synchronized void close() {
+			if (sharedKey != 0) {
+				freeSharedKey(sharedKey);
+				sharedKey = 0;
+			}
 		}
 
 		@Override
 		public String toString() {
-			return base64Encode(sharedSecret);
+			return "SharedKey(...)";
 		}
 	}
 }
@@ -34,17 +34,70 @@ public class PingInfo {
 			this.lon,
 			this.alt,
 			this.err,
-			key.getSharedSecret()
+			key.getHandle()
 		);
 	}
 
 	public static PingInfo decrypt(String str, KeyExchange.SharedKey key) {
-		return decryptFFI(str, key.getSharedSecret());
+		return decryptFFI(str, key.getHandle());
 	}
 
-	private static native PingInfo decryptFFI(String str, byte[] sharedKey);
+	/** The ellipsoidal distance from this Ping to {@code other}, in meters */
+	public float distanceTo(PingInfo other) {
+		return distanceFFI(this.lat, this.lon, other.lat, other.lon);
+	}
+
+	/** The initial bearing from this Ping towards {@code other}, in degrees clockwise from true north */
+	public double bearingTo(PingInfo other) {
+		return bearingFFI(this.lat, this.lon, other.lat, other.lon);
+	}
+
+	/** The compass direction (e.g. {@code "NE"}) from this Ping towards {@code other} */
+	public String compassDirectionTo(PingInfo other) {
+		return compassDirectionFFI(bearingTo(other));
+	}
+
+	/** The combined uncertainty of the positions of this Ping and {@code other}, in meters */
+	public float combinedError(PingInfo other) {
+		return combinedErrorFFI(this.err, other.err);
+	}
+
+	/** Describe the position of this Ping relative to {@code origin}, e.g. "1.3 km NE (&plusmn;25 m)" */
+	public String describeRelativeTo(PingInfo origin) {
+		return describeRelativeFFI(this.lat, this.lon, this.err, origin.lat, origin.lon, origin.err);
+	}
+
+	/**
+	 * A copy of this Ping with its location made less precise before sending it,
+	 * with the position error increased to match
+	 *
+	 * The precision is {@code "exact"}, {@code "grid:METERS"} (snap to the center
+	 * of a grid cell of about this size), or {@code "blur:METERS"} (move randomly
+	 * by up to this distance), optionally followed by {@code ",altitude:keep"}
+	 * (the default), {@code ",altitude:coarsen:METERS"} (round the altitude to a
+	 * multiple of this size), or {@code ",altitude:omit"} (set the altitude to 0),
+	 * e.g. {@code "grid:1000,altitude:omit"}. An invalid precision throws an
+	 * {@link IllegalArgumentException}.
+	 */
+	public PingInfo withPrecision(String precision) {
+		return withPrecisionFFI(this.ts, this.lat, this.lon, this.alt, this.err, precision);
+	}
+
+	private static native float distanceFFI(double lat, double lon, double otherLat, double otherLon);
+
+	private static native double bearingFFI(double lat, double lon, double otherLat, double otherLon);
+
+	private static native String compassDirectionFFI(double bearing);
+
+	private static native float combinedErrorFFI(float err, float otherErr);
+
+	private static native String describeRelativeFFI(double lat, double lon, float err, double originLat, double originLon, float originErr);
+
+	private static native PingInfo withPrecisionFFI(long ts, double lat, double lon, float alt, float err, String precision);
+
+	private static native PingInfo decryptFFI(String str, long sharedKey);
 
-	private static native String encryptFFI(long ts, double lat, double lon, float alt, float err, byte[] sharedKey);
+	private static native String encryptFFI(long ts, double lat, double lon, float alt, float err, long sharedKey);
 
 	@Override
 	public String toString() {
@@ -0,0 +1,78 @@
+package dev.janm.pinger;
+
+/** An error in a Pinger cryptographic or encoding operation */
+public class PingerException extends RuntimeException {
+	public PingerException(String message) {
+		super(message);
+	}
+
+	/** The encrypted Ping info is missing the magic number */
+	public static class BadMagic extends PingerException {
+		public BadMagic(String message) {
+			super(message);
+		}
+	}
+
+	/** The encrypted Ping info, public key, or key exchange message has an invalid length */
+	public static class BadLength extends PingerException {
+		public BadLength(String message) {
+			super(message);
+		}
+	}
+
+	/** The encrypted Ping info, public key, or key exchange message is not valid base64 */
+	public static class BadEncoding extends PingerException {
+		public BadEncoding(String message) {
+			super(message);
+		}
+	}
+
+	/** The encrypted Ping info could not be authenticated (wrong key or modified data) */
+	public static class Authentication extends PingerException {
+		public Authentication(String message) {
+			super(message);
+		}
+	}
+
+	/** The Ping info could not be encrypted */
+	public static class Encryption extends PingerException {
+		public Encryption(String message) {
+			super(message);
+		}
+	}
+
+	/** The decrypted Ping info contains an invalid value */
+	public static class InvalidValue extends PingerException {
+		public InvalidValue(String message) {
+			super(message);
+		}
+	}
+
+	/** The Ping was already received before */
+	public static class Replayed extends PingerException {
+		public Replayed(String message) {
+			super(message);
+		}
+	}
+
+	/** The Ping's timestamp is too far in the past */
+	public static class Stale extends PingerException {
+		public Stale(String message) {
+			super(message);
+		}
+	}
+
+	/** The Ping's timestamp is too far in the future */
+	public static class FromFuture extends PingerException {
+		public FromFuture(String message) {
+			super(message);
+		}
+	}
+
+	/** The other side's message of a key exchange authenticated with a code is invalid */
+	public static class BadPakeMessage extends PingerException {
+		public BadPakeMessage(String message) {
+			super(message);
+		}
+	}
+}
@@ -1,32 +1,169 @@
 //! Java/Kotlin FFI for this library
 
-use core::{array, str};
+use core::{
+	str,
+	sync::atomic::{AtomicI64, Ordering},
+};
 use std::{
 	backtrace::Backtrace,
+	collections::BTreeMap,
 	panic::{self, AssertUnwindSafe},
+	sync::Mutex,
 };
 
-use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use base64::{DecodeSliceError, Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use jni::{
 	JNIEnv,
-	objects::{JByteArray, JClass, JObject, JString, JValueGen},
+	objects::{JClass, JObject, JString, JValueGen},
 	sys::{jdouble, jfloat, jlong},
 };
-use x25519_dalek::StaticSecret;
 
-use crate::{Degrees, EncryptedPingInfo, Meters, PingInfo, PublicKey, SharedKey, Timestamp};
+use crate::{
+	Degrees, EncryptedPingInfo, EphemeralSecret, Error, InvalidPrecision, Meters, PingInfo,
+	PublicKey, SharedKey, Timestamp,
+	geo::CompassDirection,
+	pake::{PakeMessage, PakeSecret},
+	privacy::Precision,
+	sas::SafetyNumber,
+};
+
+/// The ephemeral secret keys currently held on behalf of Java
+static EPHEMERAL_SECRETS: KeyStore<EphemeralSecret> = KeyStore::new();
+
+/// The secrets of key exchanges authenticated with a short code currently held
+/// on behalf of Java
+static PAKE_SECRETS: KeyStore<PakeSecret> = KeyStore::new();
+
+/// The shared keys currently held on behalf of Java, from either kind of key
+/// exchange
+static SHARED_KEYS: KeyStore<SharedKey> = KeyStore::new();
+
+/// The next key handle to be given out, shared between all key stores
+///
+/// Handles are never reused, and `0` is never a valid handle, so Java code can
+/// use it to represent a key that was already used or freed.
+static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
+
+/// Rust-owned storage for secret keys, which are referenced from Java using
+/// opaque `long` handles
+///
+/// Keys never leave this storage, and are zeroized when removed from it (the
+/// stored key types zeroize themselves on drop), except for the state of
+/// authenticated key exchanges, which the SPAKE2 implementation doesn't
+/// zeroize.
+struct KeyStore<T> {
+	keys: Mutex<BTreeMap<jlong, T>>,
+}
+
+impl<T> KeyStore<T> {
+	/// Create a new empty key store
+	const fn new() -> Self {
+		Self {
+			keys: Mutex::new(BTreeMap::new()),
+		}
+	}
+
+	/// Store a key, returning its new handle
+	fn insert(&self, key: T) -> jlong {
+		let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+		self.keys.lock().expect("lock poisoned").insert(handle, key);
+		handle
+	}
+
+	/// Remove the key with the given handle from storage, giving up ownership
+	/// of it
+	fn take(&self, handle: jlong) -> Result<T, Exception> {
+		self.keys
+			.lock()
+			.expect("lock poisoned")
+			.remove(&handle)
+			.ok_or_else(Self::invalid_handle)
+	}
+
+	/// Call `f` with a reference to the key with the given handle
+	fn with<R>(&self, handle: jlong, f: impl FnOnce(&T) -> R) -> Result<R, Exception> {
+		self.keys
+			.lock()
+			.expect("lock poisoned")
+			.get(&handle)
+			.map(f)
+			.ok_or_else(Self::invalid_handle)
+	}
+
+	/// Remove and drop the key with the given handle, if it exists
+	fn free(&self, handle: jlong) {
+		drop(self.keys.lock().expect("lock poisoned").remove(&handle));
+	}
+
+	/// The exception for a handle not found in this store
+	fn invalid_handle() -> Exception {
+		Exception {
+			class: "java/lang/IllegalStateException",
+			msg: "invalid key handle (the key was already used or freed)".to_string(),
+		}
+	}
+}
+
+/// A Java exception to be thrown from a JNI function
+struct Exception {
+	/// The JNI name of the exception class
+	class: &'static str,
+	/// The exception message
+	msg: String,
+}
+
+impl From<String> for Exception {
+	fn from(msg: String) -> Self {
+		Self {
+			class: "java/lang/RuntimeException",
+			msg,
+		}
+	}
+}
+
+impl From<Error> for Exception {
+	fn from(err: Error) -> Self {
+		let class = match err {
+			Error::BadMagic => "dev/janm/pinger/PingerException$BadMagic",
+			Error::BadLength => "dev/janm/pinger/PingerException$BadLength",
+			Error::BadEncoding => "dev/janm/pinger/PingerException$BadEncoding",
+			Error::Authentication => "dev/janm/pinger/PingerException$Authentication",
+			Error::Encryption => "dev/janm/pinger/PingerException$Encryption",
+			Error::InvalidValue(_) => "dev/janm/pinger/PingerException$InvalidValue",
+			Error::BadPakeMessage => "dev/janm/pinger/PingerException$BadPakeMessage",
+			Error::Replayed => "dev/janm/pinger/PingerException$Replayed",
+			Error::Stale(_) => "dev/janm/pinger/PingerException$Stale",
+			Error::FromFuture(_) => "dev/janm/pinger/PingerException$FromFuture",
+		};
+
+		Self {
+			class,
+			msg: err.to_string(),
+		}
+	}
+}
+
+impl From<InvalidPrecision> for Exception {
+	fn from(err: InvalidPrecision) -> Self {
+		Self {
+			class: "java/lang/IllegalArgumentException",
+			msg: err.to_string(),
+		}
+	}
+}
 
+/// Convert any error into a generic `RuntimeException` with the error's message
 trait ErrStr {
 	type Ok;
 
-	fn str(self) -> Result<Self::Ok, String>;
+	fn str(self) -> Result<Self::Ok, Exception>;
 }
 
 impl<T, E: ToString> ErrStr for Result<T, E> {
 	type Ok = T;
 
-	fn str(self) -> Result<T, String> {
-		self.map_err(|e| e.to_string())
+	fn str(self) -> Result<T, Exception> {
+		self.map_err(|e| e.to_string().into())
 	}
 }
 
@@ -39,12 +176,12 @@ macro_rules! handle_err {
 		#[forbid(unsafe_code)]
 		{
 			let ref_env = &mut $env;
-			match panic::catch_unwind(AssertUnwindSafe(move || -> Result<$ret, String> {
+			match panic::catch_unwind(AssertUnwindSafe(move || -> Result<$ret, Exception> {
 				(|$param: $param_ty| $x)(ref_env)
 			})) {
 				Ok(Ok(res)) => res,
 				Ok(Err(err)) => {
-					let _ = $env.throw_new("java/lang/RuntimeException", err);
+					let _ = $env.throw_new(err.class, err.msg);
 					<$ret>::default()
 				}
 				Err(_) => {
@@ -57,9 +194,13 @@ macro_rules! handle_err {
 }
 
 /// **`String dev.janm.pinger.PingInfo.encryptFFI(long ts, double lat, double
-/// lon, float alt, float err, byte[] sharedKey)`**
+/// lon, float alt, float err, long sharedKey)`**
 ///
-/// Encrypt and base64-encode the given Ping info using the given shared key
+/// Encrypt and base64-encode the given Ping info using the shared key with the
+/// given handle
+///
+/// Invalid Ping info (see [`PingInfo::new`]) is rejected with a
+/// `PingerException.InvalidValue`.
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
@@ -74,21 +215,11 @@ pub extern "system" fn Java_dev_janm_pinger_PingInfo_encryptFFI<'e>(
 	lon: jdouble,
 	alt: jfloat,
 	err: jfloat,
-	key: JByteArray,
+	key: jlong,
 ) -> JString<'e> {
 	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
-		let info = PingInfo {
-			ts: Timestamp(java_u64_to_rust(ts)),
-			lat: Degrees(lat),
-			lon: Degrees(lon),
-			alt: Meters(alt),
-			err: Meters(err),
-		};
-
-		let mut buf = [0i8; 32];
-		let () = env.get_byte_array_region(key, 0, &mut buf).str()?;
-		let buf = java_u8_array_to_rust(buf);
-		let encrypted = info.encrypt(SharedKey::from_bytes(buf)).str()?;
+		let info = ping_info_from_java(ts, lat, lon, alt, err)?;
+		let encrypted = SHARED_KEYS.with(key, |key| info.encrypt(key))??;
 
 		let mut buf = [0u8; 86];
 		let n = URL_SAFE_NO_PAD.encode_slice(encrypted.0, &mut buf).str()?;
@@ -96,11 +227,11 @@ pub extern "system" fn Java_dev_janm_pinger_PingInfo_encryptFFI<'e>(
 	}}
 }
 
-/// **`PingInfo dev.janm.pinger.PingInfo.decryptFFI(String str, byte[]
+/// **`PingInfo dev.janm.pinger.PingInfo.decryptFFI(String str, long
 /// sharedKey)`**
 ///
-/// Decrypt the given (base64-encoded) encrypted Ping info using the given
-/// shared key
+/// Decrypt the given (base64-encoded) encrypted Ping info using the shared key
+/// with the given handle
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
@@ -111,33 +242,201 @@ pub extern "system" fn Java_dev_janm_pinger_PingInfo_decryptFFI<'e>(
 	mut env: JNIEnv<'e>,
 	class: JClass,
 	str: JString<'e>,
-	key: JByteArray<'e>,
+	key: jlong,
 ) -> JObject<'e> {
 	handle_err! { env -> JObject<'e>: |env = &mut JNIEnv<'e>| {
-		let mut buf = [0i8; 32];
-		let () = env.get_byte_array_region(key, 0, &mut buf).str()?;
-		let key = SharedKey::from_bytes(java_u8_array_to_rust(buf));
+		let encrypted = EncryptedPingInfo::from_base64(env.get_string(&str).str()?.to_str().str()?)?;
+		let info = SHARED_KEYS.with(key, |key| {
+			PingInfo::decrypt(encrypted, key)
+		})??;
+
+		env.new_object(class, "(JDDFF)V", &[
+				JValueGen::Long(rust_u64_to_java(info.ts().0)),
+				JValueGen::Double(info.lat().0),
+				JValueGen::Double(info.lon().0),
+				JValueGen::Float(info.alt().0),
+				JValueGen::Float(info.err().0),
+			])
+			.str()
+	}}
+}
+
+/// **`float dev.janm.pinger.PingInfo.distanceFFI(double lat, double lon, double
+/// otherLat, double otherLon)`**
+///
+/// Calculate the ellipsoidal distance (in meters) between two positions
+///
+/// Invalid positions are rejected with an `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_distanceFFI<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	lat: jdouble,
+	lon: jdouble,
+	other_lat: jdouble,
+	other_lon: jdouble,
+) -> jfloat {
+	handle_err! { env -> jfloat: |_env = &mut JNIEnv<'e>| {
+		Ok(position(lat, lon, 0.0)?.distance_to(&position(other_lat, other_lon, 0.0)?).0)
+	}}
+}
+
+/// **`double dev.janm.pinger.PingInfo.bearingFFI(double lat, double lon, double
+/// otherLat, double otherLon)`**
+///
+/// Calculate the initial bearing (in degrees clockwise from true north) from
+/// one position towards another
+///
+/// Invalid positions are rejected with an `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_bearingFFI<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	lat: jdouble,
+	lon: jdouble,
+	other_lat: jdouble,
+	other_lon: jdouble,
+) -> jdouble {
+	handle_err! { env -> jdouble: |_env = &mut JNIEnv<'e>| {
+		Ok(position(lat, lon, 0.0)?.bearing_to(&position(other_lat, other_lon, 0.0)?).0)
+	}}
+}
+
+/// **`String dev.janm.pinger.PingInfo.compassDirectionFFI(double bearing)`**
+///
+/// Get the compass direction (e.g. `"NE"`) closest to the given bearing
+///
+/// A bearing that is not finite is rejected with an
+/// `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_compassDirectionFFI<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	bearing: jdouble,
+) -> JString<'e> {
+	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
+		if !bearing.is_finite() {
+			return Err(Exception {
+				class: "java/lang/IllegalArgumentException",
+				msg: "the bearing must be finite".to_string(),
+			});
+		}
+
+		env.new_string(CompassDirection::from_bearing(Degrees(bearing)).to_string()).str()
+	}}
+}
+
+/// **`float dev.janm.pinger.PingInfo.combinedErrorFFI(float err, float
+/// otherErr)`**
+///
+/// Calculate the combined uncertainty (in meters) of two position errors
+///
+/// Invalid position errors are rejected with an `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_combinedErrorFFI<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	err: jfloat,
+	other_err: jfloat,
+) -> jfloat {
+	handle_err! { env -> jfloat: |_env = &mut JNIEnv<'e>| {
+		Ok(position(0.0, 0.0, err)?.combined_error(&position(0.0, 0.0, other_err)?).0)
+	}}
+}
+
+/// **`String dev.janm.pinger.PingInfo.describeRelativeFFI(double lat, double
+/// lon, float err, double originLat, double originLon, float originErr)`**
+///
+/// Describe a position relative to the origin position, e.g. `"1.3 km NE (±25
+/// m)"`
+///
+/// Invalid positions are rejected with an `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_describeRelativeFFI<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	lat: jdouble,
+	lon: jdouble,
+	err: jfloat,
+	origin_lat: jdouble,
+	origin_lon: jdouble,
+	origin_err: jfloat,
+) -> JString<'e> {
+	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
+		let relative = position(lat, lon, err)?.relative_to(&position(origin_lat, origin_lon, origin_err)?);
+		env.new_string(relative.to_string()).str()
+	}}
+}
 
-		let mut buf = [0u8; 64];
-		let n = URL_SAFE_NO_PAD
-			.decode_slice(env.get_string(&str).str()?.to_str().str()?, &mut buf)
-			.str()?;
-		let info = PingInfo::decrypt(EncryptedPingInfo(buf[..n].try_into().str()?), key).str()?;
+/// **`PingInfo dev.janm.pinger.PingInfo.withPrecisionFFI(long ts, double lat,
+/// double lon, float alt, float err, String precision)`**
+///
+/// Reduce the precision of the given Ping info (see [`Precision`]), with the
+/// precision given as `"exact"`, `"grid:METERS"`, or `"blur:METERS"`,
+/// optionally followed by `",altitude:keep"`, `",altitude:coarsen:METERS"`, or
+/// `",altitude:omit"`
+///
+/// An invalid precision is rejected with an `IllegalArgumentException`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_PingInfo_withPrecisionFFI<'e>(
+	mut env: JNIEnv<'e>,
+	class: JClass<'e>,
+	ts: jlong,
+	lat: jdouble,
+	lon: jdouble,
+	alt: jfloat,
+	err: jfloat,
+	precision: JString<'e>,
+) -> JObject<'e> {
+	handle_err! { env -> JObject<'e>: |env = &mut JNIEnv<'e>| {
+		let precision: Precision = env.get_string(&precision).str()?.to_str().str()?.parse()?;
+		let info = precision.apply(ping_info_from_java(ts, lat, lon, alt, err)?)?;
 
 		env.new_object(class, "(JDDFF)V", &[
-				JValueGen::Long(rust_u64_to_java(info.ts.0)),
-				JValueGen::Double(info.lat.0),
-				JValueGen::Double(info.lon.0),
-				JValueGen::Float(info.alt.0),
-				JValueGen::Float(info.err.0),
+				JValueGen::Long(rust_u64_to_java(info.ts().0)),
+				JValueGen::Double(info.lat().0),
+				JValueGen::Double(info.lon().0),
+				JValueGen::Float(info.alt().0),
+				JValueGen::Float(info.err().0),
 			])
 			.str()
 	}}
 }
 
-/// **`String dev.janm.pinger.KeyExchange.calculatePublicKey(byte[] secret)`**
+/// **`String dev.janm.pinger.KeyExchange.calculatePublicKey(long secret)`**
 ///
-/// Calculate the public key (as a base64 string) for the given private key
+/// Calculate the public key (as a base64 string) for the private key with the
+/// given handle
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
@@ -147,29 +446,24 @@ pub extern "system" fn Java_dev_janm_pinger_PingInfo_decryptFFI<'e>(
 pub extern "system" fn Java_dev_janm_pinger_KeyExchange_calculatePublicKey<'e>(
 	mut env: JNIEnv<'e>,
 	_class: JClass<'e>,
-	secret: JByteArray<'e>,
+	secret: jlong,
 ) -> JString<'e> {
 	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
-		let mut buf = [0i8; 32];
-		let () = env.get_byte_array_region(secret, 0, &mut buf).str()?;
-		let buf = java_u8_array_to_rust(buf);
-		let secret = StaticSecret::from(buf);
-
-		let mut buf = [0u8; 43];
-		let n = URL_SAFE_NO_PAD
-			.encode_slice(PublicKey::from(&secret).to_bytes(), &mut buf)
-			.str()?;
-
-		let str: &str = str::from_utf8(&buf[..n]).str()?;
-		env.new_string(str).str()
+		let public_key = EPHEMERAL_SECRETS.with(secret, |secret| PublicKey::from(secret))?;
+		env.new_string(encode_key(public_key.to_bytes())?).str()
 	}}
 }
 
-/// **`byte[] dev.janm.pinger.KeyExchange.performDiffieHellman(byte[] ourSecret,
+/// **`long dev.janm.pinger.KeyExchange.performDiffieHellman(long ourSecret,
 /// String theirPublicKey)`**
 ///
 /// Perform the key exchange with our private key and the other party's
-/// (base64-encoded) public key
+/// (base64-encoded) public key, returning the handle of the shared key
+///
+/// The handle of our private key is consumed, even if the key exchange fails,
+/// so each private key can only be used for one key exchange.
+/// Invalid public keys are rejected with a `PingerException.BadEncoding` or
+/// `PingerException.BadLength`.
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
@@ -179,28 +473,22 @@ pub extern "system" fn Java_dev_janm_pinger_KeyExchange_calculatePublicKey<'e>(
 pub extern "system" fn Java_dev_janm_pinger_KeyExchange_performDiffieHellman<'e>(
 	mut env: JNIEnv<'e>,
 	_class: JClass<'e>,
-	secret: JByteArray<'e>,
+	secret: jlong,
 	public_key: JString<'e>,
-) -> JByteArray<'e> {
-	handle_err! { env -> JByteArray<'e>: |env = &mut JNIEnv<'e>| {
-		let mut buf = [0i8; 32];
-		let () = env.get_byte_array_region(secret, 0, &mut buf).str()?;
-		let secret = StaticSecret::from(java_u8_array_to_rust(buf));
-
-		let mut buf = [0u8; 32];
-		let n = URL_SAFE_NO_PAD
-			.decode_slice(env.get_string(&public_key).str()?.to_str().str()?, &mut buf)
-			.str()?;
-		let public_key = PublicKey::from(<[u8; 32]>::try_from(&buf[..n]).str()?);
-
-		let shared_secret = secret.diffie_hellman(&public_key);
-		env.byte_array_from_slice(&shared_secret.to_bytes()).str()
+) -> jlong {
+	handle_err! { env -> jlong: |env = &mut JNIEnv<'e>| {
+		let secret = EPHEMERAL_SECRETS.take(secret)?;
+
+		let public_key = decode_public_key(env.get_string(&public_key).str()?.to_str().str()?)?;
+		let shared = secret.diffie_hellman(&public_key);
+		Ok(SHARED_KEYS.insert(shared.into()))
 	}}
 }
 
-/// **`byte[] dev.janm.pinger.KeyExchange.generateEphemeralSecret()`**
+/// **`long dev.janm.pinger.KeyExchange.generateEphemeralSecret()`**
 ///
-/// Generate a random ephemeral secret key for the key exchange as a byte array
+/// Generate a random ephemeral secret key for the key exchange, returning its
+/// handle
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
@@ -210,48 +498,253 @@ pub extern "system" fn Java_dev_janm_pinger_KeyExchange_performDiffieHellman<'e>
 pub extern "system" fn Java_dev_janm_pinger_KeyExchange_generateEphemeralSecret<'e>(
 	mut env: JNIEnv<'e>,
 	_class: JClass<'e>,
-) -> JByteArray<'e> {
-	handle_err! { env -> JByteArray<'e>: |env = &mut JNIEnv<'e>| {
-		let secret = StaticSecret::random();
-		env.byte_array_from_slice(&secret.to_bytes()).str()
+) -> jlong {
+	handle_err! { env -> jlong: |_env = &mut JNIEnv<'e>| {
+		Ok(EPHEMERAL_SECRETS.insert(EphemeralSecret::random()))
+	}}
+}
+
+/// **`void dev.janm.pinger.KeyExchange.freeEphemeralSecret(long secret)`**
+///
+/// Zeroize and free the ephemeral secret key with the given handle, if it has
+/// not yet been used or freed
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_freeEphemeralSecret<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	secret: jlong,
+) {
+	handle_err! { env -> (): |_env = &mut JNIEnv<'e>| {
+		EPHEMERAL_SECRETS.free(secret);
+		Ok(())
 	}}
 }
 
-/// **`String dev.janm.pinger.KeyExchange.SharedKey.base64Encode(byte[]
-/// sharedSecret)`**
+/// **`long dev.janm.pinger.KeyExchange.startPake(String code)`**
 ///
-/// Encode the given byte array into a base64 string
+/// Start a key exchange authenticated with the given short code (see the
+/// [`pake`](crate::pake) module), returning the handle of its secret
 #[expect(
 	unsafe_code,
 	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
 	          soundness requirements of the attribute"
 )]
 #[unsafe(no_mangle)]
-pub extern "system" fn Java_dev_janm_pinger_KeyExchange_00024SharedKey_base64Encode<'e>(
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_startPake<'e>(
 	mut env: JNIEnv<'e>,
 	_class: JClass<'e>,
-	shared_secret: JByteArray<'e>,
+	code: JString<'e>,
+) -> jlong {
+	handle_err! { env -> jlong: |env = &mut JNIEnv<'e>| {
+		let code = env.get_string(&code).str()?;
+		Ok(PAKE_SECRETS.insert(PakeSecret::new(code.to_str().str()?)))
+	}}
+}
+
+/// **`String dev.janm.pinger.KeyExchange.calculatePakeMessage(long secret)`**
+///
+/// Get the message (as a base64 string) to send to the other side of the
+/// authenticated key exchange with the given handle
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_calculatePakeMessage<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	secret: jlong,
 ) -> JString<'e> {
 	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
-		let mut buf = [0i8; 32];
-		let () = env.get_byte_array_region(shared_secret, 0, &mut buf).str()?;
-		let shared_secret = java_u8_array_to_rust(buf);
+		let message = PAKE_SECRETS.with(secret, PakeSecret::message)?;
+		env.new_string(encode_key(message.to_bytes())?).str()
+	}}
+}
 
-		let mut buf = [0u8; 43];
-		let n = URL_SAFE_NO_PAD
-			.encode_slice(shared_secret, &mut buf)
-			.str()?;
+/// **`long dev.janm.pinger.KeyExchange.finishPake(long ourSecret, String
+/// theirMessage)`**
+///
+/// Finish the authenticated key exchange with our secret and the other party's
+/// (base64-encoded) message, returning the handle of the shared key
+///
+/// The handle of our secret is consumed, even if the key exchange fails.
+/// Invalid messages are rejected with a `PingerException.BadEncoding`,
+/// `PingerException.BadLength`, or `PingerException.BadPakeMessage`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_finishPake<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	secret: jlong,
+	message: JString<'e>,
+) -> jlong {
+	handle_err! { env -> jlong: |env = &mut JNIEnv<'e>| {
+		let secret = PAKE_SECRETS.take(secret)?;
+		let message = decode_key(env.get_string(&message).str()?.to_str().str()?)?;
+		let shared = secret.finish(&PakeMessage::from_bytes(message))?;
 
-		let str: &str = str::from_utf8(&buf[..n]).str()?;
-		env.new_string(str).str()
+		Ok(SHARED_KEYS.insert(shared))
 	}}
 }
 
-/// Cast a Java byte array (`[i8; _]` in Rust) bitwise to a Rust byte array
-/// (`[u8; _]`)
-#[must_use]
-fn java_u8_array_to_rust<const N: usize>(array: [i8; N]) -> [u8; N] {
-	array::from_fn(|i| u8::from_ne_bytes(array[i].to_ne_bytes()))
+/// **`void dev.janm.pinger.KeyExchange.freePakeSecret(long secret)`**
+///
+/// Free the secret of the authenticated key exchange with the given handle, if
+/// it has not yet been used or freed
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_freePakeSecret<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	secret: jlong,
+) {
+	handle_err! { env -> (): |_env = &mut JNIEnv<'e>| {
+		PAKE_SECRETS.free(secret);
+		Ok(())
+	}}
+}
+
+/// **`String dev.janm.pinger.KeyExchange.calculateSafetyNumber(long
+/// sharedKey, String ourPublicKey, String theirPublicKey)`**
+///
+/// Calculate the safety number (see the [`sas`](crate::sas) module) of the key
+/// exchange which resulted in the shared key with the given handle, from both
+/// (base64-encoded) public keys or PAKE messages, formatted as three groups of
+/// four digits
+///
+/// Invalid public keys are rejected with a `PingerException.BadEncoding` or
+/// `PingerException.BadLength`.
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_calculateSafetyNumber<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	shared_key: jlong,
+	our_public_key: JString<'e>,
+	their_public_key: JString<'e>,
+) -> JString<'e> {
+	handle_err! { env -> JString<'e>: |env = &mut JNIEnv<'e>| {
+		let ours = decode_key(env.get_string(&our_public_key).str()?.to_str().str()?)?;
+		let theirs = decode_key(env.get_string(&their_public_key).str()?.to_str().str()?)?;
+		let number = SHARED_KEYS.with(shared_key, |key| {
+			SafetyNumber::new(key, ours, theirs)
+		})?;
+
+		env.new_string(number.to_string()).str()
+	}}
+}
+
+/// **`void dev.janm.pinger.KeyExchange.SharedKey.freeSharedKey(long
+/// sharedKey)`**
+///
+/// Zeroize and free the shared key with the given handle, if it has not yet
+/// been freed
+#[expect(
+	unsafe_code,
+	reason = "no_mangle is required for Java FFI, and the user is expected to uphold the \
+	          soundness requirements of the attribute"
+)]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_dev_janm_pinger_KeyExchange_00024SharedKey_freeSharedKey<'e>(
+	mut env: JNIEnv<'e>,
+	_class: JClass<'e>,
+	shared_key: jlong,
+) {
+	handle_err! { env -> (): |_env = &mut JNIEnv<'e>| {
+		SHARED_KEYS.free(shared_key);
+		Ok(())
+	}}
+}
+
+/// Create Ping info from the JNI arguments of
+/// [`Java_dev_janm_pinger_PingInfo_encryptFFI`]
+///
+/// This and [`decode_public_key`] are all of the decoding of JNI arguments
+/// (apart from [`EncryptedPingInfo::from_base64`]), and are public (but
+/// hidden from the docs) so that they can be fuzzed without a JVM.
+///
+/// # Errors
+/// If the Ping info is invalid, [`Error::InvalidValue`] is returned (see
+/// [`PingInfo::new`])
+#[doc(hidden)]
+pub fn ping_info_from_java(
+	ts: jlong,
+	lat: jdouble,
+	lon: jdouble,
+	alt: jfloat,
+	err: jfloat,
+) -> Result<PingInfo, Error> {
+	PingInfo::new(
+		Timestamp(java_u64_to_rust(ts)),
+		Degrees(lat),
+		Degrees(lon),
+		Meters(alt),
+		Meters(err),
+	)
+}
+
+/// Decode a (urlsafe, unpadded) base64-encoded public key, as passed to
+/// [`Java_dev_janm_pinger_KeyExchange_performDiffieHellman`]
+///
+/// # Errors
+/// If the string is not valid base64, [`Error::BadEncoding`] is returned, and
+/// if it doesn't decode to exactly 32 bytes, [`Error::BadLength`] is returned
+#[doc(hidden)]
+pub fn decode_public_key(str: &str) -> Result<PublicKey, Error> {
+	decode_key(str).map(PublicKey::from)
+}
+
+/// Decode a (urlsafe, unpadded) base64-encoded public key or PAKE message
+fn decode_key(str: &str) -> Result<[u8; 32], Error> {
+	let mut buf = [0u8; 32];
+
+	let n = URL_SAFE_NO_PAD
+		.decode_slice(str, &mut buf)
+		.map_err(|e| match e {
+			DecodeSliceError::OutputSliceTooSmall => Error::BadLength,
+			DecodeSliceError::DecodeError(_) => Error::BadEncoding,
+		})?;
+
+	if n != 32 {
+		return Err(Error::BadLength);
+	}
+
+	Ok(buf)
+}
+
+/// Base64-encode a public key or PAKE message
+fn encode_key(key: [u8; 32]) -> Result<String, Exception> {
+	let mut buf = [0u8; 43];
+	let n = URL_SAFE_NO_PAD.encode_slice(key, &mut buf).str()?;
+
+	Ok(str::from_utf8(&buf[..n]).str()?.to_string())
+}
+
+/// Create Ping info with only a position, for the geodesic calculations,
+/// rejecting invalid positions with an `IllegalArgumentException`
+fn position(lat: jdouble, lon: jdouble, err: jfloat) -> Result<PingInfo, Exception> {
+	ping_info_from_java(0, lat, lon, 0.0, err).map_err(|e| Exception {
+		class: "java/lang/IllegalArgumentException",
+		msg: e.to_string(),
+	})
 }
 
 /// Cast a Java 64-bit integer (`i64` in Rust) bitwise to a Rust `u64`
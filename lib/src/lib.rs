@@ -1,5 +1,25 @@
 //! Basic Pinger types and cryptographic operations
 //!
+//! # Authenticated key exchanges
+//!
+//! Instead of the x25519 key exchange, two people who agreed on a short code
+//! can use a password-authenticated key exchange, which prevents the server
+//! from intercepting their Ping (see the `pake` module, which requires the
+//! `std` feature).
+//!
+//! # Safety numbers
+//!
+//! To check that nobody intercepted a key exchange, both people can compare
+//! the safety number derived from it (see the `sas` module), e.g. over a phone
+//! call.
+//!
+//! # Location privacy
+//!
+//! Before encrypting a Ping, its sender can reduce the precision of the
+//! location in it, by snapping it to a grid or blurring it randomly (see the
+//! `privacy` module). The position error is increased to match. The altitude
+//! can separately be kept, coarsened, or omitted.
+//!
 //! # Java FFI
 //!
 //! If the `java-ffi` feature is enabled, this crate exposes several `no_mangle`
@@ -10,42 +30,55 @@
 //! `java-ffi` feature is enabled) no other symbols conflict with these ones,
 //! i.e. that no other part of the final program/object file defines symbols
 //! starting with `Java_dev_janm_pinger_`.
+//! Secret keys used through the JNI functions never leave Rust, and are instead
+//! referenced from Java using opaque handles (see the `java_ffi` module).
+//!
+//! # WebAssembly
+//!
+//! If the `wasm` feature is enabled, this crate exposes `wasm-bindgen` bindings
+//! for the key exchange and Ping info encryption (see the `wasm` module).
+//! When compiling for `wasm32-unknown-unknown` with this feature enabled,
+//! randomness is sourced from the Web Crypto API
+//! (`crypto.getRandomValues`), so the resulting module can only be used in
+//! browsers or in JavaScript runtimes that provide it (such as Node.js).
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(not(feature = "java-ffi"), forbid(unsafe_code))]
 
 use core::{
-	fmt::{Debug, Display, Formatter, Result as FmtResult},
+	fmt::{Debug, Formatter, Result as FmtResult},
 	str,
 };
 
-use chacha20poly1305::{
-	AeadCore, AeadInPlace, ChaCha20Poly1305, Error as ChaChaError, KeyInit, aead::OsRng,
-};
+use base64::{DecodeSliceError, Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chacha20poly1305::{AeadCore, AeadInPlace, ChaCha20Poly1305, KeyInit, aead::OsRng};
 use serde::{Deserialize, Serialize};
 pub use x25519_dalek::{EphemeralSecret, PublicKey, SharedSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[cfg(feature = "java-ffi")]
-pub mod java_ffi;
+pub use crate::error::{CoordinateError, Error, Field, InvalidPrecision, UnknownExportFormat};
 
-/// An error during a cryptographic operation, opaque on purpose
-#[derive(Debug)]
-pub struct CryptoError;
+pub mod coords;
+mod error;
+pub mod export;
+pub mod geo;
+#[cfg(feature = "std")]
+pub mod pake;
+pub mod privacy;
+pub mod replay;
+pub mod sas;
+mod tests;
 
-impl Display for CryptoError {
-	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-		write!(f, "error performing cryptographic operation")
-	}
-}
-
-impl From<ChaChaError> for CryptoError {
-	fn from(_: ChaChaError) -> Self {
-		Self
-	}
-}
+#[cfg(feature = "java-ffi")]
+pub mod java_ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// The shared symmetric encryption key
-#[derive(Clone, Copy)]
+///
+/// The key is zeroized when it is dropped, so it is not `Copy`, and copies
+/// of it have to be made explicitly with `clone`.
+#[derive(Clone)]
 pub struct SharedKey([u8; 32]);
 
 impl SharedKey {
@@ -55,16 +88,45 @@ impl SharedKey {
 		Self(bytes)
 	}
 
-	/// Convert this `SharedKey` into a byte array
+	/// Copy this `SharedKey` into a byte array
+	///
+	/// Unlike the `SharedKey` itself, the returned copy is not zeroized
+	/// automatically.
 	#[must_use]
-	pub const fn to_bytes(self) -> [u8; 32] {
+	pub const fn to_bytes(&self) -> [u8; 32] {
 		self.0
 	}
+
+	/// Get a reference to the bytes of this `SharedKey`
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; 32] {
+		&self.0
+	}
+}
+
+impl Drop for SharedKey {
+	fn drop(&mut self) {
+		self.0.zeroize();
+	}
 }
 
+impl ZeroizeOnDrop for SharedKey {}
+
 impl From<SharedSecret> for SharedKey {
 	fn from(value: SharedSecret) -> Self {
-		Self::from_bytes(value.to_bytes())
+		Self::from_bytes(*value.as_bytes())
+	}
+}
+
+impl From<&SharedSecret> for SharedKey {
+	fn from(value: &SharedSecret) -> Self {
+		Self::from_bytes(*value.as_bytes())
+	}
+}
+
+impl From<&Self> for SharedKey {
+	fn from(value: &Self) -> Self {
+		value.clone()
 	}
 }
 
@@ -81,6 +143,43 @@ impl Debug for SharedKey {
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct EncryptedPingInfo(#[serde(with = "serde_encrypted_ping_info")] [u8; 64]);
 
+impl EncryptedPingInfo {
+	/// Decode encrypted Ping info from a (urlsafe, unpadded) base64 string
+	///
+	/// # Errors
+	/// If the string is not valid base64, [`Error::BadEncoding`] is returned,
+	/// and if it doesn't decode to exactly 64 bytes, [`Error::BadLength`] is
+	/// returned
+	pub fn from_base64(str: &str) -> Result<Self, Error> {
+		let mut buf = [0u8; 64];
+
+		let n = URL_SAFE_NO_PAD
+			.decode_slice(str, &mut buf)
+			.map_err(|e| match e {
+				DecodeSliceError::OutputSliceTooSmall => Error::BadLength,
+				DecodeSliceError::DecodeError(_) => Error::BadEncoding,
+			})?;
+
+		Self::try_from(&buf[..n])
+	}
+
+	/// Get the AEAD nonce, which is random and unique for each encryption
+	#[must_use]
+	pub fn nonce(&self) -> [u8; 12] {
+		let mut nonce = [0u8; 12];
+		nonce.copy_from_slice(&self.0[4..16]);
+		nonce
+	}
+}
+
+impl TryFrom<&[u8]> for EncryptedPingInfo {
+	type Error = Error;
+
+	fn try_from(value: &[u8]) -> Result<Self, Error> {
+		Ok(Self(value.try_into().map_err(|_| Error::BadLength)?))
+	}
+}
+
 impl AsRef<[u8]> for EncryptedPingInfo {
 	fn as_ref(&self) -> &[u8] {
 		&self.0[..]
@@ -88,31 +187,128 @@ impl AsRef<[u8]> for EncryptedPingInfo {
 }
 
 /// Information about a ping
+///
+/// The values are always valid, i.e. the latitude and longitude are on earth,
+/// the altitude is finite, and the position error is finite and not negative.
+/// This is checked when creating the Ping info with [`PingInfo::new`], when
+/// decrypting it, and when deserializing it.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedPingInfo")]
 pub struct PingInfo {
 	/// The timestamp of the position data
-	pub ts: Timestamp,
+	ts: Timestamp,
 	/// The latitude in degrees
-	pub lat: Degrees,
+	lat: Degrees,
 	/// The longitude in degrees
-	pub lon: Degrees,
+	lon: Degrees,
 	/// The altitude in meters above mean sea level
-	pub alt: Meters,
+	alt: Meters,
 	/// The position error in meters
-	pub err: Meters,
+	err: Meters,
+}
+
+/// [`PingInfo`] which has not been validated yet, used for deserialization
+#[derive(Deserialize)]
+struct UncheckedPingInfo {
+	/// The timestamp of the position data
+	ts: Timestamp,
+	/// The latitude in degrees
+	lat: Degrees,
+	/// The longitude in degrees
+	lon: Degrees,
+	/// The altitude in meters above mean sea level
+	alt: Meters,
+	/// The position error in meters
+	err: Meters,
+}
+
+impl TryFrom<UncheckedPingInfo> for PingInfo {
+	type Error = Error;
+
+	fn try_from(info: UncheckedPingInfo) -> Result<Self, Self::Error> {
+		Self::new(info.ts, info.lat, info.lon, info.alt, info.err)
+	}
+}
+
+impl PingInfo {
+	/// Create new Ping info from a timestamp, latitude, longitude, altitude
+	/// (above mean sea level), and position error
+	///
+	/// # Errors
+	/// If the latitude is not in `-90..=90`, the longitude is not in
+	/// `-180..=180`, the altitude is not finite, or the position error is not
+	/// finite or negative, [`Error::InvalidValue`] is returned
+	pub fn new(
+		ts: Timestamp,
+		lat: Degrees,
+		lon: Degrees,
+		alt: Meters,
+		err: Meters,
+	) -> Result<Self, Error> {
+		for (valid, field) in [
+			((-90.0..=90.0).contains(&lat.0), Field::Latitude),
+			((-180.0..=180.0).contains(&lon.0), Field::Longitude),
+			(alt.0.is_finite(), Field::Altitude),
+			(err.0.is_finite() && err.0 >= 0.0, Field::PositionError),
+		] {
+			if !valid {
+				return Err(Error::InvalidValue(field));
+			}
+		}
+
+		Ok(Self {
+			ts,
+			lat,
+			lon,
+			alt,
+			err,
+		})
+	}
+
+	/// The timestamp of the position data
+	#[must_use]
+	pub const fn ts(&self) -> Timestamp {
+		self.ts
+	}
+
+	/// The latitude in degrees (`-90..=90`)
+	#[must_use]
+	pub const fn lat(&self) -> Degrees {
+		self.lat
+	}
+
+	/// The longitude in degrees (`-180..=180`)
+	#[must_use]
+	pub const fn lon(&self) -> Degrees {
+		self.lon
+	}
+
+	/// The altitude in meters above mean sea level
+	#[must_use]
+	pub const fn alt(&self) -> Meters {
+		self.alt
+	}
+
+	/// The position error in meters (not negative)
+	#[must_use]
+	pub const fn err(&self) -> Meters {
+		self.err
+	}
 }
 
 impl PingInfo {
 	/// Encode an encrypt this `PingInfo` using the given shared key
 	///
 	/// # Errors
-	/// If encryption fails, a [`CryptoError`] is returned
-	pub fn encrypt(self, key: impl Into<SharedKey>) -> Result<EncryptedPingInfo, CryptoError> {
+	/// If encryption fails, [`Error::Encryption`] is returned
+	pub fn encrypt(self, key: impl Into<SharedKey>) -> Result<EncryptedPingInfo, Error> {
 		let mut encoded = self.encode();
 		let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
 
-		let chacha = ChaCha20Poly1305::new(&key.into().to_bytes().into());
-		let tag = chacha.encrypt_in_place_detached(&nonce, b"", &mut encoded)?;
+		let chacha = ChaCha20Poly1305::new(key.into().as_bytes().into());
+		let tag = chacha
+			.encrypt_in_place_detached(&nonce, b"", &mut encoded)
+			.map_err(|_| Error::Encryption)?;
 
 		let mut buf = [0u8; 64];
 		buf[0..4].copy_from_slice(b"PING");
@@ -126,31 +322,32 @@ impl PingInfo {
 	/// Decrypt and decode the given Ping info using the given shared key
 	///
 	/// # Errors
-	/// If the magic number is missing or decryption fails, a [`CryptoError`] is
-	/// returned
+	/// If the magic number is missing, [`Error::BadMagic`] is returned, if the
+	/// Ping info can't be authenticated, [`Error::Authentication`] is returned,
+	/// and if the decrypted Ping info contains invalid values (see
+	/// [`PingInfo::new`]), [`Error::InvalidValue`] is returned
 	#[expect(
 		clippy::missing_panics_doc,
 		reason = "the possibly-panicking unwrap is converting a 32-byte slice into a 32-byte \
 		          array, and therefore can't panic"
 	)]
-	pub fn decrypt(
-		bytes: EncryptedPingInfo,
-		key: impl Into<SharedKey>,
-	) -> Result<Self, CryptoError> {
+	pub fn decrypt(bytes: EncryptedPingInfo, key: impl Into<SharedKey>) -> Result<Self, Error> {
 		let mut bytes = bytes.0;
 		let (&mut ref ping, bytes) = bytes.split_at_mut(4);
 		let (&mut ref nonce, bytes) = bytes.split_at_mut(12);
 		let (buf, &mut ref tag) = bytes.split_at_mut(32);
 
 		if ping != b"PING" {
-			return Err(CryptoError);
+			return Err(Error::BadMagic);
 		}
 
-		let chacha = ChaCha20Poly1305::new(&key.into().to_bytes().into());
+		let chacha = ChaCha20Poly1305::new(key.into().as_bytes().into());
 
-		let () = chacha.decrypt_in_place_detached(nonce.into(), b"", buf, tag.into())?;
+		let () = chacha
+			.decrypt_in_place_detached(nonce.into(), b"", buf, tag.into())
+			.map_err(|_| Error::Authentication)?;
 
-		Ok(Self::decode(buf.try_into().unwrap()))
+		Self::decode(buf.try_into().unwrap())
 	}
 
 	/// Encode this [`PingInfo`] into bytes
@@ -164,21 +361,22 @@ impl PingInfo {
 		buf
 	}
 
-	/// Decode a [`PingInfo`] from bytes
-	fn decode(bytes: [u8; 32]) -> Self {
+	/// Decode a [`PingInfo`] from bytes, rejecting invalid values (see
+	/// [`PingInfo::new`])
+	fn decode(bytes: [u8; 32]) -> Result<Self, Error> {
 		let ts = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
 		let lat = f64::from_be_bytes(bytes[8..16].try_into().unwrap());
 		let lon = f64::from_be_bytes(bytes[16..24].try_into().unwrap());
 		let alt = f32::from_be_bytes(bytes[24..28].try_into().unwrap());
 		let err = f32::from_be_bytes(bytes[28..32].try_into().unwrap());
 
-		Self {
-			ts: Timestamp(ts),
-			lat: Degrees(lat),
-			lon: Degrees(lon),
-			alt: Meters(alt),
-			err: Meters(err),
-		}
+		Self::new(
+			Timestamp(ts),
+			Degrees(lat),
+			Degrees(lon),
+			Meters(alt),
+			Meters(err),
+		)
 	}
 }
 
@@ -194,6 +392,23 @@ pub struct Meters(pub f32);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Timestamp(pub u64);
 
+impl Timestamp {
+	/// Get the current time
+	///
+	/// # Panics
+	/// Panics if the system clock is set to before 1970
+	#[cfg(feature = "std")]
+	#[must_use]
+	pub fn now() -> Self {
+		Self(
+			std::time::SystemTime::UNIX_EPOCH
+				.elapsed()
+				.expect("it's after 1970")
+				.as_secs(),
+		)
+	}
+}
+
 mod serde_encrypted_ping_info {
 	use core::{
 		fmt::{Formatter, Result as FmtResult},
@@ -0,0 +1,134 @@
+//! Key exchanges authenticated with a short code
+//!
+//! The public keys of a normal key exchange are relayed by the server, which
+//! could replace them with its own and read the Ping without either person
+//! noticing. To prevent this, the two people can agree on a short code (e.g.
+//! by saying it aloud), and use it for a password-authenticated key exchange
+//! (SPAKE2, in its symmetric mode) instead. The resulting [`SharedKey`]
+//! replaces the one from the Diffie-Hellman key exchange, and is only the same
+//! on both sides if both used the same code. A server in the middle only gets
+//! one guess at the code per exchange, and a wrong guess (or a mistyped code)
+//! makes the Ping fail to decrypt with [`Error::Authentication`].
+//!
+//! [`PakeMessage`]s are 32 bytes long like x25519 public keys, so they are sent
+//! in the `key` field of `ping_request` and `accept_ping` messages, along with
+//! `"pake": true`.
+
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+
+use crate::{Error, SharedKey};
+
+/// The SPAKE2 identity, which is the same for both sides of the exchange
+const IDENTITY: &[u8] = b"dev.janm.pinger ping";
+
+/// The first byte of all symmetric SPAKE2 messages, which is left out of
+/// [`PakeMessage`]s
+const SYMMETRIC_SIDE: u8 = b'S';
+
+/// One side of a key exchange authenticated with a short code
+///
+/// Like [`EphemeralSecret`](crate::EphemeralSecret), this can only be used for
+/// one key exchange.
+pub struct PakeSecret {
+	/// The SPAKE2 state
+	spake: Spake2<Ed25519Group>,
+	/// The message to send to the other side
+	message: PakeMessage,
+}
+
+impl PakeSecret {
+	/// Start a key exchange using the `code` both people agreed on
+	///
+	/// The code is normalized with [`normalize_code`] first, so e.g. `"12 34"`
+	/// and `"1234"` are the same code.
+	#[must_use]
+	#[expect(
+		clippy::missing_panics_doc,
+		reason = "symmetric SPAKE2 messages are always 33 bytes long"
+	)]
+	pub fn new(code: &str) -> Self {
+		let (spake, message) = Spake2::<Ed25519Group>::start_symmetric(
+			&Password::new(normalize_code(code)),
+			&Identity::new(IDENTITY),
+		);
+
+		Self {
+			spake,
+			message: PakeMessage(message[1..].try_into().unwrap()),
+		}
+	}
+
+	/// Get the message to send to the other side
+	#[must_use]
+	pub const fn message(&self) -> PakeMessage {
+		self.message
+	}
+
+	/// Finish the key exchange with the other side's message, and get the
+	/// shared key
+	///
+	/// # Errors
+	/// If the other side's message is not valid, or is the same as ours (e.g.
+	/// because it was reflected back to us), [`Error::BadPakeMessage`] is
+	/// returned
+	pub fn finish(self, theirs: &PakeMessage) -> Result<SharedKey, Error> {
+		if *theirs == self.message {
+			return Err(Error::BadPakeMessage);
+		}
+
+		let mut message = [SYMMETRIC_SIDE; 33];
+		message[1..].copy_from_slice(&theirs.0);
+
+		let key = self
+			.spake
+			.finish(&message)
+			.map_err(|_| Error::BadPakeMessage)?;
+
+		Ok(SharedKey::from_bytes(
+			key.try_into().map_err(|_| Error::BadPakeMessage)?,
+		))
+	}
+}
+
+impl Debug for PakeSecret {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.debug_struct("PakeSecret").finish_non_exhaustive()
+	}
+}
+
+/// A message of a key exchange authenticated with a short code, sent to the
+/// other side in place of a public key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PakeMessage([u8; 32]);
+
+impl PakeMessage {
+	/// Create a `PakeMessage` from the given byte array
+	#[must_use]
+	pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+		Self(bytes)
+	}
+
+	/// Convert this `PakeMessage` into a byte array
+	#[must_use]
+	pub const fn to_bytes(self) -> [u8; 32] {
+		self.0
+	}
+}
+
+impl AsRef<[u8]> for PakeMessage {
+	fn as_ref(&self) -> &[u8] {
+		&self.0[..]
+	}
+}
+
+/// Normalize a code by removing whitespace and dashes and making it lowercase,
+/// so that small differences in how it was typed don't matter
+#[must_use]
+pub fn normalize_code(code: &str) -> String {
+	code.chars()
+		.filter(|c| !c.is_whitespace() && *c != '-')
+		.flat_map(char::to_lowercase)
+		.collect()
+}
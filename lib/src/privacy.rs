@@ -0,0 +1,293 @@
+//! Reducing the precision of outgoing Pings
+//!
+//! To share roughly where they are with people they don't fully trust, senders
+//! can reduce the precision of their Ping info before encrypting it, either by
+//! snapping it to the center of a grid cell, or by moving it in a random
+//! direction. Either way, the position error is increased by the largest
+//! possible displacement, so that the true position is still within the
+//! position error of the Ping.
+//!
+//! The altitude is handled separately, since it can give away which floor of a
+//! building someone is on even when the horizontal position is coarse. It can
+//! be kept, rounded to a multiple of some size, or omitted (set to `0`, i.e.
+//! sea level, because Pings always have an altitude).
+//!
+//! Each blurred Ping is moved randomly, so many blurred Pings from the same
+//! place can be averaged to get closer to the true position. Grid cells don't
+//! have this problem, but Pings from near the edge of a cell move to the next
+//! cell's center as soon as the edge is crossed.
+
+#![expect(
+	clippy::suboptimal_flops,
+	reason = "`mul_add` is not available without `std`, and the formulas are more readable \
+	          without it"
+)]
+
+use core::{
+	f64::consts::PI,
+	fmt::{Display, Formatter, Result as FmtResult},
+	str::FromStr,
+};
+
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use libm::{asin, atan2, ceilf, cos, floor, round, roundf, sin, sqrt};
+
+use crate::{
+	Degrees, InvalidPrecision, Meters, PingInfo,
+	geo::{Coordinates, MEAN_EARTH_RADIUS},
+};
+
+/// The largest grid cell size, blur radius, or altitude step in meters, about
+/// half of the earth's circumference
+pub const MAX_SIZE: f32 = 20_000_000.0;
+
+/// The precision of an outgoing Ping
+///
+/// This is parsed from and displayed as the horizontal precision (`exact`,
+/// `grid:METERS`, or `blur:METERS`), optionally followed by the altitude
+/// precision (`,altitude:keep`, `,altitude:coarsen:METERS`, or
+/// `,altitude:omit`), e.g. `grid:1000,altitude:omit`. Either part can be left
+/// out, and defaults to keeping it exact.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Precision {
+	/// The precision of the position
+	pub horizontal: Horizontal,
+	/// The precision of the altitude
+	pub altitude: Altitude,
+}
+
+/// The precision of the position of an outgoing Ping
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Horizontal {
+	/// The position is not changed
+	#[default]
+	Exact,
+	/// The position is snapped to the center of a grid cell of about this
+	/// size (along the meridians and the parallel through its center)
+	Grid(Meters),
+	/// The position is moved by a random distance (up to this radius) in a
+	/// random direction
+	Blur(Meters),
+}
+
+/// The precision of the altitude of an outgoing Ping
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Altitude {
+	/// The altitude is not changed
+	#[default]
+	Keep,
+	/// The altitude is rounded to a multiple of this size
+	Coarsen(Meters),
+	/// The altitude is set to `0`
+	Omit,
+}
+
+impl Precision {
+	/// Reduce the precision of `info`, increasing its position error
+	/// accordingly
+	///
+	/// # Errors
+	/// If the grid cell size, blur radius, or altitude step is not positive or
+	/// is larger than [`MAX_SIZE`], [`InvalidPrecision`] is returned
+	pub fn apply(self, info: PingInfo) -> Result<PingInfo, InvalidPrecision> {
+		let (coords, displacement) = match self.horizontal {
+			Horizontal::Exact => (info.coordinates(), Meters(0.0)),
+			Horizontal::Grid(size) if is_valid(size) => snap(info.coordinates(), size),
+			Horizontal::Blur(radius) if is_valid(radius) => {
+				(blur(info.coordinates(), radius), radius)
+			}
+			Horizontal::Grid(_) | Horizontal::Blur(_) => return Err(InvalidPrecision),
+		};
+
+		let alt = match self.altitude {
+			Altitude::Keep => info.alt(),
+			Altitude::Coarsen(size) if is_valid(size) => {
+				Meters(roundf(info.alt().0 / size.0) * size.0)
+			}
+			Altitude::Omit => Meters(0.0),
+			Altitude::Coarsen(_) => return Err(InvalidPrecision),
+		};
+
+		PingInfo::new(
+			info.ts(),
+			coords.lat,
+			coords.lon,
+			alt,
+			Meters(info.err().0 + displacement.0),
+		)
+		.map_err(|_| InvalidPrecision)
+	}
+}
+
+impl Display for Precision {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self.horizontal {
+			Horizontal::Exact => f.write_str("exact")?,
+			Horizontal::Grid(size) => write!(f, "grid:{}", size.0)?,
+			Horizontal::Blur(radius) => write!(f, "blur:{}", radius.0)?,
+		}
+
+		match self.altitude {
+			Altitude::Keep => Ok(()),
+			Altitude::Coarsen(size) => write!(f, ",altitude:coarsen:{}", size.0),
+			Altitude::Omit => f.write_str(",altitude:omit"),
+		}
+	}
+}
+
+impl FromStr for Precision {
+	type Err = InvalidPrecision;
+
+	/// Parse the comma-separated horizontal and altitude precision, e.g.
+	/// `blur:500` or `grid:1000,altitude:coarsen:100`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut horizontal = None;
+		let mut altitude = None;
+
+		for part in s.split(',') {
+			let part = part.trim();
+
+			if let Some(rest) = strip_kind(part, "altitude") {
+				if altitude.replace(parse_altitude(rest)?).is_some() {
+					return Err(InvalidPrecision);
+				}
+			} else if horizontal.replace(parse_horizontal(part)?).is_some() {
+				return Err(InvalidPrecision);
+			}
+		}
+
+		Ok(Self {
+			horizontal: horizontal.unwrap_or_default(),
+			altitude: altitude.unwrap_or_default(),
+		})
+	}
+}
+
+/// Parse `exact`, `grid:METERS`, or `blur:METERS`
+fn parse_horizontal(s: &str) -> Result<Horizontal, InvalidPrecision> {
+	if s.eq_ignore_ascii_case("exact") {
+		Ok(Horizontal::Exact)
+	} else if let Some(size) = strip_kind(s, "grid") {
+		Ok(Horizontal::Grid(parse_size(size)?))
+	} else if let Some(radius) = strip_kind(s, "blur") {
+		Ok(Horizontal::Blur(parse_size(radius)?))
+	} else {
+		Err(InvalidPrecision)
+	}
+}
+
+/// Parse `keep`, `coarsen:METERS`, or `omit`
+fn parse_altitude(s: &str) -> Result<Altitude, InvalidPrecision> {
+	if s.eq_ignore_ascii_case("keep") {
+		Ok(Altitude::Keep)
+	} else if s.eq_ignore_ascii_case("omit") {
+		Ok(Altitude::Omit)
+	} else if let Some(size) = strip_kind(s, "coarsen") {
+		Ok(Altitude::Coarsen(parse_size(size)?))
+	} else {
+		Err(InvalidPrecision)
+	}
+}
+
+/// Get the rest of `s` after `kind` and a colon (ignoring case and whitespace),
+/// if `s` starts with them
+fn strip_kind<'s>(s: &'s str, kind: &str) -> Option<&'s str> {
+	let (start, rest) = s.split_once(':')?;
+	start.trim().eq_ignore_ascii_case(kind).then(|| rest.trim())
+}
+
+/// Parse a valid size in meters
+fn parse_size(s: &str) -> Result<Meters, InvalidPrecision> {
+	let size = Meters(s.parse().map_err(|_| InvalidPrecision)?);
+	is_valid(size).then_some(size).ok_or(InvalidPrecision)
+}
+
+/// Check if `size` is a valid grid cell size, blur radius, or altitude step
+fn is_valid(size: Meters) -> bool {
+	size.0 > 0.0 && size.0 <= MAX_SIZE
+}
+
+/// Snap `coords` to the center of their grid cell of about `size`, returning
+/// the center and the distance from it to the farthest corner or edge of the
+/// cell
+///
+/// The grid has rows of equal height, and each row has columns of equal width,
+/// with a whole number of both around the earth, so that the cells don't
+/// overlap.
+fn snap(coords: Coordinates, size: Meters) -> (Coordinates, Meters) {
+	let size = f64::from(size.0) / (MEAN_EARTH_RADIUS * PI / 180.0);
+
+	let rows = round(180.0 / size).max(1.0);
+	let height = 180.0 / rows;
+	let row = floor((coords.lat.0 + 90.0) / height).min(rows - 1.0);
+	// rounding errors could put the poles slightly out of range
+	let south = (row * height - 90.0).max(-90.0);
+	let north = ((row + 1.0) * height - 90.0).min(90.0);
+	let lat = (row + 0.5) * height - 90.0;
+
+	let columns = round(360.0 * cos(lat.to_radians()) / size).max(1.0);
+	let width = 360.0 / columns;
+	// 180° is the same meridian as -180°, so it's in the first column
+	let column = floor((coords.lon.0 + 180.0) / width) % columns;
+	let west = column * width - 180.0;
+	let east = west + width;
+	let lon = west + width / 2.0;
+
+	let center = Coordinates::new(lat, lon);
+	let farthest = [
+		(south, west),
+		(south, lon),
+		(south, east),
+		(lat, west),
+		(lat, east),
+		(north, west),
+		(north, lon),
+		(north, east),
+	]
+	.into_iter()
+	.map(|(lat, lon)| center.distance_to(Coordinates::new(lat, lon)).0)
+	.fold(0.0, f32::max);
+
+	(center, Meters(ceilf(farthest)))
+}
+
+/// Move `coords` by a random distance up to `radius` in a random direction,
+/// uniformly distributed over the area within `radius`
+fn blur(coords: Coordinates, radius: Meters) -> Coordinates {
+	let lat = coords.lat.0.to_radians();
+	let lon = coords.lon.0.to_radians();
+
+	loop {
+		let distance = f64::from(radius.0) * sqrt(random()) / MEAN_EARTH_RADIUS;
+		let bearing = 2.0 * PI * random();
+
+		let new_lat = asin(
+			(sin(lat) * cos(distance) + cos(lat) * sin(distance) * cos(bearing)).clamp(-1.0, 1.0),
+		);
+		let new_lon = lon
+			+ atan2(
+				sin(bearing) * sin(distance) * cos(lat),
+				cos(distance) - sin(lat) * sin(new_lat),
+			);
+
+		let blurred = Coordinates {
+			lat: Degrees(new_lat.to_degrees().clamp(-90.0, 90.0)),
+			lon: Degrees((new_lon.to_degrees() + 540.0) % 360.0 - 180.0),
+		};
+
+		// the displacement is calculated on a sphere, so it may be slightly
+		// further on the ellipsoid, which would make the position error wrong
+		if blurred.distance_to(coords).0 <= radius.0 {
+			return blurred;
+		}
+	}
+}
+
+/// Get a uniformly distributed random number in `0..1`
+#[expect(
+	clippy::cast_precision_loss,
+	reason = "both numbers fit into an f64's 53-bit significand exactly"
+)]
+fn random() -> f64 {
+	(OsRng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,130 @@
+//! Protection against replayed and stale Pings
+//!
+//! [`PingInfo::decrypt`] only checks that Ping info was encrypted with the
+//! right key, so a Ping captured by the server (or anyone else on the way) can
+//! be delivered again later.
+//! A [`ReplayGuard`] remembers the nonces of recently received Pings (nonces
+//! are random and never reused by honest senders), and checks the Ping's
+//! timestamp against the current time using the configured [`Freshness`].
+//! A separate guard should be used for each Ping exchange.
+
+use core::time::Duration;
+
+use crate::{EncryptedPingInfo, Error, PingInfo, SharedKey, Timestamp};
+
+/// Limits on the age of received Pings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Freshness {
+	/// The maximum age of a Ping's timestamp
+	pub max_age: Duration,
+	/// The maximum amount by which a Ping's timestamp may be in the future, to
+	/// allow for clock differences between devices
+	pub max_future_skew: Duration,
+}
+
+impl Freshness {
+	/// The default limits, allowing Pings with a timestamp of up to 15 minutes
+	/// ago (e.g. from an older location fix) and up to 1 minute in the future
+	pub const DEFAULT: Self = Self {
+		max_age: Duration::from_mins(15),
+		max_future_skew: Duration::from_mins(1),
+	};
+
+	/// Check the timestamp of a Ping against the current time
+	///
+	/// # Errors
+	/// If the timestamp is older than `max_age`, [`Error::Stale`] is returned,
+	/// and if it is more than `max_future_skew` in the future,
+	/// [`Error::FromFuture`] is returned
+	pub const fn check(&self, ts: Timestamp, now: Timestamp) -> Result<(), Error> {
+		if ts.0 <= now.0 {
+			let age = Duration::from_secs(now.0 - ts.0);
+
+			if age.as_secs() > self.max_age.as_secs() {
+				return Err(Error::Stale(age));
+			}
+		} else {
+			let skew = Duration::from_secs(ts.0 - now.0);
+
+			if skew.as_secs() > self.max_future_skew.as_secs() {
+				return Err(Error::FromFuture(skew));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl Default for Freshness {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+/// Decrypts Pings, rejecting replayed and stale ones
+#[derive(Debug, Clone)]
+pub struct ReplayGuard {
+	/// The limits on the age of received Pings
+	freshness: Freshness,
+	/// The nonces of recently received Pings, used as a ring buffer
+	seen: [[u8; 12]; Self::CAPACITY],
+	/// The number of nonces in `seen`
+	len: usize,
+	/// The index in `seen` where the next nonce will be stored
+	next: usize,
+}
+
+impl ReplayGuard {
+	/// The number of nonces remembered, after which the oldest ones are
+	/// forgotten
+	pub const CAPACITY: usize = 32;
+
+	/// Create a new replay guard with the given limits on the age of Pings
+	#[must_use]
+	pub const fn new(freshness: Freshness) -> Self {
+		Self {
+			freshness,
+			seen: [[0; 12]; Self::CAPACITY],
+			len: 0,
+			next: 0,
+		}
+	}
+
+	/// Decrypt the given Ping info (like [`PingInfo::decrypt`]), and check that
+	/// it was not received before and that its timestamp is recent compared to
+	/// `now`
+	///
+	/// Pings are remembered once they are authenticated, so a replayed Ping is
+	/// reported as replayed even if it is also stale.
+	///
+	/// # Errors
+	/// Returns any error from [`PingInfo::decrypt`], [`Error::Replayed`] if the
+	/// Ping was already received, and any error from [`Freshness::check`]
+	pub fn decrypt(
+		&mut self,
+		bytes: EncryptedPingInfo,
+		key: impl Into<SharedKey>,
+		now: Timestamp,
+	) -> Result<PingInfo, Error> {
+		let info = PingInfo::decrypt(bytes, key)?;
+		let nonce = bytes.nonce();
+
+		if self.seen[..self.len].contains(&nonce) {
+			return Err(Error::Replayed);
+		}
+
+		self.seen[self.next] = nonce;
+		self.next = (self.next + 1) % Self::CAPACITY;
+		self.len = (self.len + 1).min(Self::CAPACITY);
+
+		self.freshness.check(info.ts(), now)?;
+
+		Ok(info)
+	}
+}
+
+impl Default for ReplayGuard {
+	fn default() -> Self {
+		Self::new(Freshness::DEFAULT)
+	}
+}
@@ -0,0 +1,78 @@
+//! Safety numbers for comparing key exchanges
+//!
+//! After a key exchange, both sides can derive a [`SafetyNumber`] from the two
+//! public keys (or [`pake`](crate::pake) messages) and the [`SharedKey`]. If
+//! the server replaced the keys to read the Ping, each side ends up with a
+//! different shared key, so the two people see different safety numbers, which
+//! they can compare e.g. over a phone call before sending a sensitive Ping.
+//!
+//! The first public key is sent before the second one is chosen, so a server in
+//! the middle can try many keys to get matching safety numbers. This is why
+//! they are 12 digits long, which would take about a trillion key exchanges
+//! within the timeout of one Ping exchange.
+
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+use sha2::{Digest, Sha256};
+
+use crate::SharedKey;
+
+/// The domain separation prefix of the hashed transcript
+const DOMAIN: &[u8] = b"dev.janm.pinger safety number";
+
+/// The number of possible safety numbers, 10^12
+const MODULUS: u64 = 1_000_000_000_000;
+
+/// A 12-digit number derived from a key exchange, which is the same on both
+/// sides only if they ended up with the same shared key
+///
+/// It is displayed in groups of 4 digits, e.g. `0123 4567 8901`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SafetyNumber(u64);
+
+impl SafetyNumber {
+	/// Derive the safety number of a key exchange from our and their public
+	/// keys (or PAKE messages) and the resulting shared key
+	///
+	/// The order of the public keys doesn't matter, so both sides get the same
+	/// safety number.
+	#[must_use]
+	pub fn new(key: &SharedKey, ours: [u8; 32], theirs: [u8; 32]) -> Self {
+		let (first, second) = if ours <= theirs {
+			(ours, theirs)
+		} else {
+			(theirs, ours)
+		};
+
+		let hash = Sha256::new()
+			.chain_update(DOMAIN)
+			.chain_update(first)
+			.chain_update(second)
+			.chain_update(key.0)
+			.finalize();
+
+		let mut bytes = [0u8; 8];
+		bytes.copy_from_slice(&hash[..8]);
+
+		// the modulo bias of 2^64 / 10^12 is negligible
+		Self(u64::from_be_bytes(bytes) % MODULUS)
+	}
+
+	/// Get the value of this safety number, which is less than 10^12
+	#[must_use]
+	pub const fn value(self) -> u64 {
+		self.0
+	}
+}
+
+impl Display for SafetyNumber {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{:04} {:04} {:04}",
+			self.0 / 100_000_000,
+			self.0 / 10_000 % 10_000,
+			self.0 % 10_000
+		)
+	}
+}
@@ -0,0 +1,247 @@
+use crate::{
+	CoordinateError,
+	coords::CoordinateFormat,
+	geo::{Coordinates, Distance},
+};
+
+/// Positions all over the world, including near the edges of UTM zones and
+/// bands, the special zones around Norway and Svalbard, and the antimeridian
+const POSITIONS: [(f64, f64); 12] = [
+	(46.051_08, 14.505_13),
+	(-33.8688, 151.2093),
+	(47.365_59, 8.524_997),
+	(0.0, 0.0),
+	(-0.5, -179.9),
+	(83.9, 20.0),
+	(-79.9, -70.0),
+	(60.0, 5.0),
+	(78.0, 15.0),
+	(1.0, -0.1),
+	(-45.0, 170.0),
+	(89.9, -179.9),
+];
+
+/// Assert that two positions are at most `max` meters apart
+#[track_caller]
+fn assert_near(a: Coordinates, b: Coordinates, max: f32) {
+	let distance = a.distance_to(b).0;
+	assert!(distance <= max, "{a:?} and {b:?} are {distance} m apart");
+}
+
+/// Format the coordinates in the given format
+fn format(lat: f64, lon: f64, format: CoordinateFormat) -> String {
+	Coordinates::new(lat, lon)
+		.format(format)
+		.unwrap()
+		.to_string()
+}
+
+#[test]
+fn reference_values() {
+	// checked against independent implementations
+	let (lat, lon) = POSITIONS[0];
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Decimal),
+		"46.05108°, 14.50513°"
+	);
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Dms),
+		"46°03′03.9″N 14°30′18.5″E"
+	);
+	assert_eq!(format(lat, lon, CoordinateFormat::Geohash), "u24mfp66f");
+	assert_eq!(format(lat, lon, CoordinateFormat::PlusCode), "8FRP3G24+C3J");
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Utm),
+		"33T 461716 5099842"
+	);
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Mgrs),
+		"33T VL 61716 99841"
+	);
+
+	let (lat, lon) = POSITIONS[1];
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Dms),
+		"33°52′07.7″S 151°12′33.5″E"
+	);
+	assert_eq!(format(lat, lon, CoordinateFormat::Geohash), "r3gx2f77b");
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Utm),
+		"56H 334369 6250948"
+	);
+	assert_eq!(
+		format(lat, lon, CoordinateFormat::Mgrs),
+		"56H LH 34368 50948"
+	);
+
+	// Norway and Svalbard
+	assert_eq!(
+		format(60.0, 5.0, CoordinateFormat::Mgrs),
+		"32V KM 76979 58157"
+	);
+	assert_eq!(
+		format(78.0, 15.0, CoordinateFormat::Mgrs),
+		"33X WG 00000 58369"
+	);
+
+	// from the Open Location Code and geohash documentation
+	assert_near(
+		CoordinateFormat::PlusCode.parse("8FVC9G8F+6X").unwrap(),
+		Coordinates::new(47.365_562, 8.524_937),
+		0.1,
+	);
+	assert_near(
+		CoordinateFormat::Geohash.parse("ezs42").unwrap(),
+		Coordinates::new(42.605, -5.603),
+		5.0,
+	);
+}
+
+#[test]
+fn round_trip() {
+	// the maximum expected error of each format in meters
+	let formats = [
+		(CoordinateFormat::Decimal, 1.0),
+		(CoordinateFormat::Dms, 2.0),
+		(CoordinateFormat::Geohash, 3.5),
+		(CoordinateFormat::PlusCode, 2.5),
+		(CoordinateFormat::Utm, 1.0),
+		(CoordinateFormat::Mgrs, 1.0),
+	];
+
+	for (lat, lon) in POSITIONS {
+		let coords = Coordinates::new(lat, lon);
+
+		for (format, max) in formats {
+			let Ok(formatted) = coords.format(format) else {
+				assert!(matches!(
+					format,
+					CoordinateFormat::Utm | CoordinateFormat::Mgrs
+				));
+				assert!(lat > 84.0);
+				continue;
+			};
+			let formatted = formatted.to_string();
+
+			let parsed = format.parse(&formatted).unwrap();
+			assert_near(parsed, coords, max);
+			assert_eq!(formatted.parse::<Coordinates>(), Ok(parsed), "{formatted}");
+		}
+	}
+}
+
+#[test]
+fn parse_variants() {
+	let expected = Coordinates::new(46.051_08, 14.505_13);
+
+	for s in [
+		"46.05108, 14.50513",
+		"46.05108° 14.50513°",
+		"46°03′03.9″N 14°30′18.5″E",
+		"46°3'3.9\"N, 14°30'18.5\"E",
+		"N 46 3 3.9 E 14 30 18.5",
+		"46 3.065 n 14 30.308 e",
+		"14°30′18.5″E 46°03′03.9″N",
+		"u24mfp66f",
+		"U24MFP66F",
+		"8FRP3G24+C3J",
+		"8frp3g24+c3j",
+		"33T 461716 5099842",
+		"33 T 461716, 5099842",
+		"33T VL 61716 99841",
+		"33TVL6171699841",
+	] {
+		assert_near(s.parse().unwrap(), expected, 3.0);
+	}
+
+	assert_near(
+		"46°03′03.9″S 14°30′18.5″W".parse().unwrap(),
+		Coordinates::new(-46.051_08, -14.505_13),
+		3.0,
+	);
+	assert_near(
+		"-46° 3' 3.9\", -14° 30' 18.5\"".parse().unwrap(),
+		Coordinates::new(-46.051_08, -14.505_13),
+		3.0,
+	);
+
+	// lower precision MGRS references give the center of the square
+	let square = CoordinateFormat::Mgrs.parse("33TVL").unwrap();
+	assert_eq!(Distance(square.distance_to(expected)).to_string(), "51 km");
+	assert_near(
+		CoordinateFormat::Mgrs.parse("33T VL 61 99").unwrap(),
+		expected,
+		1000.0,
+	);
+
+	// padded Plus Codes give the center of the larger area
+	assert_near(
+		CoordinateFormat::PlusCode.parse("8FRP0000+").unwrap(),
+		Coordinates::new(46.5, 14.5),
+		1.0,
+	);
+}
+
+#[test]
+fn parse_errors() {
+	for s in [
+		"",
+		"hello",
+		"46.05108",
+		"46.05108, 14.50513, 12",
+		"NaN, 0",
+		"46°61′N 14°E",
+		"46.5°30′N 14°E",
+		"-46°N 14°E",
+		"46°N 14°N",
+		"46 3 3.9 14 30 18.5",
+		"u24mfp66fu24m",
+		"8FRP3G24+C",
+		"8FRP3G+24C3J",
+		"8FRP0000+C3",
+		"3G24+C3",
+		"61T 461716 5099842",
+		"33I 461716 5099842",
+		"33T 461716",
+		"33T VL 61716 9984",
+		"33T VI 61716 99841",
+		"46",
+		" 12 ",
+	] {
+		assert_eq!(
+			s.parse::<Coordinates>(),
+			Err(CoordinateError::UnknownFormat),
+			"{s}"
+		);
+	}
+
+	assert_eq!(
+		"91, 0".parse::<Coordinates>(),
+		Err(CoordinateError::OutOfRange)
+	);
+	assert_eq!(
+		CoordinateFormat::Utm.parse("46.05108, 14.50513"),
+		Err(CoordinateError::Invalid(CoordinateFormat::Utm))
+	);
+	assert_eq!(
+		Coordinates::new(85.0, 0.0).format(CoordinateFormat::Mgrs),
+		Err(CoordinateError::OutOfRange)
+	);
+	assert_eq!(
+		Coordinates::new(0.0, 181.0).format(CoordinateFormat::Geohash),
+		Err(CoordinateError::OutOfRange)
+	);
+}
+
+#[test]
+fn format_names() {
+	for format in CoordinateFormat::ALL {
+		assert_eq!(format.id().parse(), Ok(format));
+	}
+
+	assert_eq!("Plus-Code".parse(), Ok(CoordinateFormat::PlusCode));
+	assert_eq!(
+		"olc".parse::<CoordinateFormat>(),
+		Err(CoordinateError::UnknownFormat)
+	);
+}
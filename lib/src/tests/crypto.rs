@@ -0,0 +1,174 @@
+use chacha20poly1305::{AeadCore, AeadInPlace, ChaCha20Poly1305, KeyInit, aead::OsRng};
+
+use crate::*;
+
+/// A Ping info for testing
+pub(super) const PING_INFO: PingInfo = PingInfo {
+	ts: Timestamp(0x0012_3456_7890),
+	lat: Degrees(1.2),
+	lon: Degrees(3.4),
+	alt: Meters(5.6),
+	err: Meters(7.8),
+};
+
+/// Perform a key exchange, returning both parties' shared keys
+pub(super) fn key_pair() -> (SharedKey, SharedKey) {
+	let alices_secret = EphemeralSecret::random();
+	let bobs_secret = EphemeralSecret::random();
+	let alices_public_key = PublicKey::from(&alices_secret);
+	let bobs_public_key = PublicKey::from(&bobs_secret);
+
+	(
+		alices_secret.diffie_hellman(&bobs_public_key).into(),
+		bobs_secret.diffie_hellman(&alices_public_key).into(),
+	)
+}
+
+/// Encrypt raw encoded Ping info, bypassing any checks in [`PingInfo::encode`]
+fn encrypt_raw(mut encoded: [u8; 32], key: &SharedKey) -> EncryptedPingInfo {
+	let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+	let chacha = ChaCha20Poly1305::new(key.as_bytes().into());
+	let tag = chacha
+		.encrypt_in_place_detached(&nonce, b"", &mut encoded)
+		.unwrap();
+
+	let mut buf = [0u8; 64];
+	buf[0..4].copy_from_slice(b"PING");
+	buf[4..16].copy_from_slice(&nonce);
+	buf[16..48].copy_from_slice(&encoded);
+	buf[48..64].copy_from_slice(&tag);
+
+	EncryptedPingInfo(buf)
+}
+
+#[test]
+fn round_trip() {
+	let (alices_key, bobs_key) = key_pair();
+	let encrypted = PING_INFO.encrypt(&alices_key).unwrap();
+
+	assert_eq!(PingInfo::decrypt(encrypted, &bobs_key), Ok(PING_INFO));
+}
+
+#[test]
+fn decrypt_errors() {
+	let (alices_key, bobs_key) = key_pair();
+	let (eves_key, _) = key_pair();
+	let encrypted = PING_INFO.encrypt(&alices_key).unwrap();
+
+	let mut bad_magic = encrypted;
+	bad_magic.0[0..4].copy_from_slice(b"PONG");
+	assert_eq!(
+		PingInfo::decrypt(bad_magic, &bobs_key),
+		Err(Error::BadMagic)
+	);
+
+	let mut modified = encrypted;
+	modified.0[20] ^= 1;
+	assert_eq!(
+		PingInfo::decrypt(modified, &bobs_key),
+		Err(Error::Authentication)
+	);
+
+	assert_eq!(
+		PingInfo::decrypt(encrypted, &eves_key),
+		Err(Error::Authentication)
+	);
+
+	for (field, range) in [(Field::Latitude, 8..16), (Field::Longitude, 16..24)] {
+		let mut encoded = PING_INFO.encode();
+		encoded[range].copy_from_slice(&f64::NAN.to_be_bytes());
+		assert_eq!(
+			PingInfo::decrypt(encrypt_raw(encoded, &alices_key), &bobs_key),
+			Err(Error::InvalidValue(field))
+		);
+	}
+
+	for (field, range) in [(Field::Altitude, 24..28), (Field::PositionError, 28..32)] {
+		let mut encoded = PING_INFO.encode();
+		encoded[range].copy_from_slice(&f32::INFINITY.to_be_bytes());
+		assert_eq!(
+			PingInfo::decrypt(encrypt_raw(encoded, &alices_key), &bobs_key),
+			Err(Error::InvalidValue(field))
+		);
+	}
+
+	let mut encoded = PING_INFO.encode();
+	encoded[8..16].copy_from_slice(&500.0f64.to_be_bytes());
+	assert_eq!(
+		PingInfo::decrypt(encrypt_raw(encoded, &alices_key), &bobs_key),
+		Err(Error::InvalidValue(Field::Latitude))
+	);
+
+	let mut encoded = PING_INFO.encode();
+	encoded[28..32].copy_from_slice(&(-1.0f32).to_be_bytes());
+	assert_eq!(
+		PingInfo::decrypt(encrypt_raw(encoded, &alices_key), &bobs_key),
+		Err(Error::InvalidValue(Field::PositionError))
+	);
+}
+
+#[test]
+fn validation() {
+	let new = |lat, lon, alt, err| {
+		PingInfo::new(
+			Timestamp(0),
+			Degrees(lat),
+			Degrees(lon),
+			Meters(alt),
+			Meters(err),
+		)
+	};
+
+	assert!(new(90.0, 180.0, -400.0, 0.0).is_ok());
+	assert!(new(-90.0, -180.0, 8_849.0, 1_000.0).is_ok());
+
+	for (lat, lon, alt, err, field) in [
+		(90.1, 0.0, 0.0, 0.0, Field::Latitude),
+		(f64::NAN, 0.0, 0.0, 0.0, Field::Latitude),
+		(0.0, -180.1, 0.0, 0.0, Field::Longitude),
+		(0.0, f64::INFINITY, 0.0, 0.0, Field::Longitude),
+		(0.0, 0.0, f32::NEG_INFINITY, 0.0, Field::Altitude),
+		(0.0, 0.0, 0.0, -0.5, Field::PositionError),
+		(0.0, 0.0, 0.0, f32::NAN, Field::PositionError),
+	] {
+		assert_eq!(
+			new(lat, lon, alt, err),
+			Err(Error::InvalidValue(field)),
+			"{lat} {lon} {alt} {err}"
+		);
+	}
+
+	let json = serde_json::to_string(&PING_INFO).unwrap();
+	assert_eq!(serde_json::from_str::<PingInfo>(&json).unwrap(), PING_INFO);
+	assert!(serde_json::from_str::<PingInfo>(&json.replace("1.2", "123.4")).is_err());
+}
+
+#[test]
+fn base64_errors() {
+	let (alices_key, _) = key_pair();
+	let encrypted = PING_INFO.encrypt(&alices_key).unwrap();
+	let str = serde_json::to_string(&encrypted).unwrap();
+	let str = str.trim_matches('"');
+
+	assert_eq!(
+		EncryptedPingInfo::from_base64(str).map(|e| e.0),
+		Ok(encrypted.0)
+	);
+	assert_eq!(
+		EncryptedPingInfo::from_base64(&str[..44]).map(|e| e.0),
+		Err(Error::BadLength)
+	);
+	assert_eq!(
+		EncryptedPingInfo::from_base64(&format!("{str}AAAA")).map(|e| e.0),
+		Err(Error::BadLength)
+	);
+	assert_eq!(
+		EncryptedPingInfo::from_base64(&str.replace(|c: char| c.is_ascii_digit(), "+"))
+			.map(|e| e.0),
+		Err(Error::BadEncoding)
+	);
+	assert_eq!(
+		EncryptedPingInfo::try_from(&[0u8; 32][..]).map(|e| e.0),
+		Err(Error::BadLength)
+	);
+}
@@ -0,0 +1,171 @@
+use crate::{
+	export::{ExportFormat, ReceivedPing},
+	*,
+};
+
+/// A received Ping for testing
+fn ping(from: &str, lon: f64) -> ReceivedPing<'_> {
+	ReceivedPing {
+		info: PingInfo::new(
+			Timestamp(1_715_675_401),
+			Degrees(46.0511),
+			Degrees(lon),
+			Meters(295.5),
+			Meters(10.0),
+		)
+		.unwrap(),
+		from,
+		received: Timestamp(1_715_675_403),
+	}
+}
+
+/// Write a document with the given Pings
+fn document(format: ExportFormat, pings: &[ReceivedPing<'_>]) -> String {
+	let mut out = String::new();
+	format.write_document(&mut out, pings).unwrap();
+	out
+}
+
+#[test]
+fn gpx() {
+	assert_eq!(
+		document(ExportFormat::Gpx, &[
+			ping("42", 14.5051),
+			ping("<&\"'>", 180.0)
+		]),
+		r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Pinger" xmlns="http://www.topografix.com/GPX/1/1" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd">
+<wpt lat="46.0511" lon="14.5051"><ele>295.5</ele><time>2024-05-14T08:30:01Z</time><name>Ping from 42</name><desc>Position error: 10 m, received at 2024-05-14T08:30:03Z</desc><type>Ping</type></wpt>
+<wpt lat="46.0511" lon="-180"><ele>295.5</ele><time>2024-05-14T08:30:01Z</time><name>Ping from &lt;&amp;&quot;&apos;&gt;</name><desc>Position error: 10 m, received at 2024-05-14T08:30:03Z</desc><type>Ping</type></wpt>
+</gpx>
+"#
+	);
+}
+
+#[test]
+fn geojson() {
+	let json = document(ExportFormat::GeoJson, &[
+		ping("42", 14.5051),
+		ping("\"\\\n\u{1}", -14.5),
+	]);
+	let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+
+	assert_eq!(
+		json,
+		serde_json::json!({
+			"type": "FeatureCollection",
+			"features": [
+				{
+					"type": "Feature",
+					"geometry": { "type": "Point", "coordinates": [14.5051, 46.0511] },
+					"properties": {
+						"from": "42",
+						"time": "2024-05-14T08:30:01Z",
+						"received": "2024-05-14T08:30:03Z",
+						"alt": 295.5,
+						"err": 10,
+					},
+				},
+				{
+					"type": "Feature",
+					"geometry": { "type": "Point", "coordinates": [-14.5, 46.0511] },
+					"properties": {
+						"from": "\"\\\n\u{1}",
+						"time": "2024-05-14T08:30:01Z",
+						"received": "2024-05-14T08:30:03Z",
+						"alt": 295.5,
+						"err": 10,
+					},
+				},
+			],
+		})
+	);
+
+	let empty = document(ExportFormat::GeoJson, &[]);
+	assert_eq!(
+		serde_json::from_str::<serde_json::Value>(&empty).unwrap(),
+		serde_json::json!({ "type": "FeatureCollection", "features": [] })
+	);
+}
+
+#[test]
+fn kml() {
+	assert_eq!(
+		document(ExportFormat::Kml, &[ping("a&b", 14.5051)]),
+		r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>
+<name>Pinger</name>
+<Placemark><name>Ping from a&amp;b</name><description>Position error: 10 m, received at 2024-05-14T08:30:03Z</description><TimeStamp><when>2024-05-14T08:30:01Z</when></TimeStamp><ExtendedData><Data name="from"><value>a&amp;b</value></Data><Data name="received"><value>2024-05-14T08:30:03Z</value></Data><Data name="alt"><value>295.5</value></Data><Data name="err"><value>10</value></Data></ExtendedData><Point><altitudeMode>absolute</altitudeMode><coordinates>14.5051,46.0511,295.5</coordinates></Point></Placemark>
+</Document>
+</kml>
+"#
+	);
+}
+
+#[test]
+fn appending() {
+	let pings = [ping("1", 14.0), ping("2", 15.0), ping("3", 16.0)];
+
+	for format in ExportFormat::ALL {
+		// append each Ping by overwriting the footer, like a file would be
+		let mut appended = String::new();
+		for (i, ping) in pings.iter().enumerate() {
+			if i == 0 {
+				appended.push_str(format.header());
+			} else {
+				appended.truncate(appended.len() - format.footer().len());
+			}
+
+			format.write_ping(&mut appended, ping, i == 0).unwrap();
+			appended.push_str(format.footer());
+		}
+
+		assert_eq!(appended, document(format, &pings), "{format}");
+	}
+}
+
+#[test]
+fn timestamps() {
+	for (ts, expected) in [
+		(0, "1970-01-01T00:00:00Z"),
+		(951_782_400, "2000-02-29T00:00:00Z"),
+		(951_868_799, "2000-02-29T23:59:59Z"),
+		(1_709_251_199, "2024-02-29T23:59:59Z"),
+		(4_107_542_400, "2100-03-01T00:00:00Z"),
+		(253_402_300_799, "9999-12-31T23:59:59Z"),
+	] {
+		let mut out = String::new();
+		ExportFormat::GeoJson
+			.write_ping(
+				&mut out,
+				&ReceivedPing {
+					received: Timestamp(ts),
+					..ping("", 0.0)
+				},
+				true,
+			)
+			.unwrap();
+
+		assert!(
+			out.contains(&format!(r#""received":"{expected}""#)),
+			"{ts}: {out}"
+		);
+	}
+}
+
+#[test]
+fn formats() {
+	for format in ExportFormat::ALL {
+		assert_eq!(format.id().parse(), Ok(format));
+		assert_eq!(ExportFormat::from_extension(format.id()), Some(format));
+	}
+
+	assert_eq!("GeoJSON".parse(), Ok(ExportFormat::GeoJson));
+	assert_eq!(
+		ExportFormat::from_extension("JSON"),
+		Some(ExportFormat::GeoJson)
+	);
+	assert_eq!("csv".parse::<ExportFormat>(), Err(UnknownExportFormat));
+	assert_eq!(ExportFormat::from_extension("txt"), None);
+}
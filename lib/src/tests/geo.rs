@@ -0,0 +1,131 @@
+use crate::{
+	geo::{CompassDirection, Coordinates, Distance},
+	*,
+};
+
+/// Create Ping info at the given position with the given position error
+const fn ping_at(lat: f64, lon: f64, err: f32) -> PingInfo {
+	PingInfo {
+		ts: Timestamp(0),
+		lat: Degrees(lat),
+		lon: Degrees(lon),
+		alt: Meters(0.0),
+		err: Meters(err),
+	}
+}
+
+/// Convert degrees, minutes, and seconds to decimal degrees
+fn dms(deg: f64, min: f64, sec: f64) -> f64 {
+	deg.signum() * (deg.abs() + min / 60.0 + sec / 3600.0)
+}
+
+#[test]
+fn vincenty_reference() {
+	// Flinders Peak to Buninyong, from Vincenty's paper and Geoscience Australia
+	let flinders_peak = Coordinates::new(dms(-37.0, 57.0, 3.720_30), dms(144.0, 25.0, 29.524_40));
+	let buninyong = Coordinates::new(dms(-37.0, 39.0, 10.156_10), dms(143.0, 55.0, 35.383_90));
+
+	let distance = flinders_peak.distance_to(buninyong);
+	assert!(
+		(f64::from(distance.0) - 54_972.271).abs() < 0.01,
+		"{distance:?}"
+	);
+
+	let bearing = flinders_peak.bearing_to(buninyong);
+	assert!(
+		(bearing.0 - dms(306.0, 52.0, 5.37)).abs() < 1e-5,
+		"{bearing:?}"
+	);
+}
+
+#[test]
+fn equator() {
+	let a = Coordinates::new(0.0, 0.0);
+	let b = Coordinates::new(0.0, 1.0);
+
+	assert!((a.distance_to(b).0 - 111_319.49).abs() < 0.01);
+	assert!((a.bearing_to(b).0 - 90.0).abs() < 1e-9);
+	assert!((b.bearing_to(a).0 - 270.0).abs() < 1e-9);
+}
+
+#[test]
+fn coincident_and_antipodal() {
+	let a = Coordinates::new(12.3, 45.6);
+	assert!(a.distance_to(a).0.abs() < f32::EPSILON);
+
+	// Vincenty's formula doesn't converge here, so the spherical fallback is used
+	let distance = Coordinates::new(0.0, 0.0).distance_to(Coordinates::new(0.5, 179.7));
+	assert!(
+		(distance.0 - 19_936_000.0).abs() < 100_000.0,
+		"{distance:?}"
+	);
+}
+
+#[test]
+fn compass_directions() {
+	let cases = [
+		(0.0, CompassDirection::N),
+		(22.4, CompassDirection::N),
+		(22.6, CompassDirection::NE),
+		(90.0, CompassDirection::E),
+		(135.0, CompassDirection::SE),
+		(180.0, CompassDirection::S),
+		(225.0, CompassDirection::SW),
+		(270.0, CompassDirection::W),
+		(315.0, CompassDirection::NW),
+		(337.6, CompassDirection::N),
+		(359.9, CompassDirection::N),
+		(-45.0, CompassDirection::NW),
+		(405.0, CompassDirection::NE),
+		(-400.0, CompassDirection::NW),
+		(-720.0, CompassDirection::N),
+		(-0.1, CompassDirection::N),
+		(1170.0, CompassDirection::E),
+		(1e10, CompassDirection::W),
+		(f64::NAN, CompassDirection::N),
+		(f64::INFINITY, CompassDirection::N),
+	];
+
+	for (bearing, direction) in cases {
+		assert_eq!(
+			CompassDirection::from_bearing(Degrees(bearing)),
+			direction,
+			"{bearing}"
+		);
+	}
+}
+
+#[test]
+fn combined_error() {
+	let a = ping_at(0.0, 0.0, 3.0);
+	let b = ping_at(0.0, 0.0, 4.0);
+
+	assert!((a.combined_error(&b).0 - 5.0).abs() < f32::EPSILON);
+}
+
+#[test]
+fn relative_display() {
+	let me = ping_at(46.0, 14.5, 15.0);
+	let them = ping_at(46.008, 14.512, 20.0);
+
+	assert_eq!(them.relative_to(&me).to_string(), "1.3 km NE (±25 m)");
+	assert_eq!(me.relative_to(&them).to_string(), "1.3 km SW (±25 m)");
+}
+
+#[test]
+fn distance_display() {
+	let cases = [
+		(0.0, "0 m"),
+		(25.4, "25 m"),
+		(999.4, "999 m"),
+		(999.6, "1.0 km"),
+		(1_340.0, "1.3 km"),
+		(9_949.0, "9.9 km"),
+		(9_951.0, "10 km"),
+		(42_195.0, "42 km"),
+	];
+
+	for (meters, formatted) in cases {
+		assert_eq!(Distance(Meters(meters)).to_string(), formatted);
+	}
+}
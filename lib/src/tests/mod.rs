@@ -0,0 +1,11 @@
+#![cfg(test)]
+
+mod coords;
+mod crypto;
+mod export;
+mod geo;
+mod pake;
+mod privacy;
+mod properties;
+mod replay;
+mod sas;
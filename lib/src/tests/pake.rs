@@ -0,0 +1,72 @@
+#![cfg(feature = "std")]
+
+use super::crypto::PING_INFO;
+use crate::{
+	pake::{PakeMessage, PakeSecret, normalize_code},
+	*,
+};
+
+/// Perform a key exchange with a code on each side, returning both parties'
+/// shared keys
+fn exchange(alices_code: &str, bobs_code: &str) -> (SharedKey, SharedKey) {
+	let alice = PakeSecret::new(alices_code);
+	let bob = PakeSecret::new(bobs_code);
+	let alices_message = alice.message();
+	let bobs_message = bob.message();
+
+	(
+		alice.finish(&bobs_message).unwrap(),
+		bob.finish(&alices_message).unwrap(),
+	)
+}
+
+#[test]
+fn same_code() {
+	let (alices_key, bobs_key) = exchange("4242", "42 42");
+	assert_eq!(alices_key.to_bytes(), bobs_key.to_bytes());
+
+	let encrypted = PING_INFO.encrypt(&alices_key).unwrap();
+	assert_eq!(PingInfo::decrypt(encrypted, &bobs_key), Ok(PING_INFO));
+
+	// every exchange has a different key, even with the same code
+	let (other_key, _) = exchange("4242", "4242");
+	assert_ne!(alices_key.to_bytes(), other_key.to_bytes());
+}
+
+#[test]
+fn different_code() {
+	let (alices_key, bobs_key) = exchange("4242", "4243");
+	assert_ne!(alices_key.to_bytes(), bobs_key.to_bytes());
+
+	let encrypted = PING_INFO.encrypt(&alices_key).unwrap();
+	assert_eq!(
+		PingInfo::decrypt(encrypted, &bobs_key),
+		Err(Error::Authentication)
+	);
+}
+
+#[test]
+fn bad_messages() {
+	// a message reflected back to its sender
+	let alice = PakeSecret::new("4242");
+	let message = alice.message();
+	assert_eq!(
+		alice.finish(&message).map(|_| ()),
+		Err(Error::BadPakeMessage)
+	);
+
+	// not a point on the curve
+	let alice = PakeSecret::new("4242");
+	let not_a_point = PakeMessage::from_bytes([2; 32]);
+	assert_eq!(
+		alice.finish(&not_a_point).map(|_| ()),
+		Err(Error::BadPakeMessage)
+	);
+}
+
+#[test]
+fn codes() {
+	assert_eq!(normalize_code(" 12 34\n"), "1234");
+	assert_eq!(normalize_code("Blue-Moon 7"), "bluemoon7");
+	assert_eq!(normalize_code("ÄB"), "äb");
+}
@@ -0,0 +1,230 @@
+use crate::{
+	privacy::{Altitude, Horizontal, Precision},
+	*,
+};
+
+/// Create Ping info at the given position with the given altitude and
+/// position error
+const fn ping_at(lat: f64, lon: f64, alt: f32, err: f32) -> PingInfo {
+	PingInfo {
+		ts: Timestamp(1_700_000_000),
+		lat: Degrees(lat),
+		lon: Degrees(lon),
+		alt: Meters(alt),
+		err: Meters(err),
+	}
+}
+
+/// Get the precision with the `horizontal` part, keeping the altitude
+const fn horizontal(horizontal: Horizontal) -> Precision {
+	Precision {
+		horizontal,
+		altitude: Altitude::Keep,
+	}
+}
+
+#[test]
+fn exact() {
+	let info = ping_at(46.056_95, 14.505_75, 295.0, 12.0);
+	assert_eq!(Precision::default().apply(info), Ok(info));
+}
+
+#[test]
+fn grid() {
+	let precision = horizontal(Horizontal::Grid(Meters(1000.0)));
+
+	for info in [
+		ping_at(46.056_95, 14.505_75, 295.0, 12.0),
+		ping_at(-33.856_78, 151.215_29, 4.0, 0.0),
+		ping_at(89.999, -179.999, 0.0, 5.0),
+		ping_at(-90.0, 180.0, -20.0, 5.0),
+		ping_at(0.0, 0.0, 8848.0, 100.0),
+	] {
+		let snapped = precision.apply(info).unwrap();
+
+		// the true position is still within the position error
+		assert!(
+			snapped.distance_to(&info).0 <= snapped.err().0 - info.err().0,
+			"{info:?} -> {snapped:?}"
+		);
+		assert!(snapped.err().0 <= info.err().0 + 1000.0, "{snapped:?}");
+		assert_eq!(snapped.ts(), info.ts());
+		assert_eq!(snapped.alt(), info.alt());
+
+		// the center of a cell is in the same cell
+		let resnapped = precision.apply(snapped).unwrap();
+		assert_eq!(resnapped.coordinates(), snapped.coordinates());
+	}
+}
+
+#[test]
+fn grid_cells() {
+	let precision = horizontal(Horizontal::Grid(Meters(5000.0)));
+	let snap = |lat, lon| {
+		precision
+			.apply(ping_at(lat, lon, 0.0, 0.0))
+			.unwrap()
+			.coordinates()
+	};
+
+	// nearby positions are in the same cell, and far away ones aren't
+	assert_eq!(snap(46.056_95, 14.505_75), snap(46.057_5, 14.506));
+	assert_ne!(snap(46.056_95, 14.505_75), snap(46.2, 14.505_75));
+	assert_ne!(snap(46.056_95, 14.505_75), snap(46.056_95, 14.7));
+
+	// the antimeridian doesn't split cells
+	assert_eq!(snap(10.0, 180.0), snap(10.0, -180.0));
+}
+
+#[test]
+fn blur() {
+	let info = ping_at(46.056_95, 14.505_75, 295.0, 12.0);
+	let precision = horizontal(Horizontal::Blur(Meters(500.0)));
+
+	let blurred = (0..100)
+		.map(|_| precision.apply(info).unwrap())
+		.collect::<Vec<_>>();
+
+	for ping in &blurred {
+		assert!(ping.distance_to(&info).0 <= 500.0, "{ping:?}");
+		assert_eq!(ping.err(), Meters(512.0));
+		assert_eq!(ping.alt(), info.alt());
+	}
+
+	assert!(blurred.windows(2).any(|w| w[0] != w[1]));
+}
+
+#[test]
+fn blur_poles() {
+	for info in [
+		ping_at(90.0, 0.0, 0.0, 0.0),
+		ping_at(-89.9999, 180.0, 0.0, 0.0),
+	] {
+		let blurred = horizontal(Horizontal::Blur(Meters(10_000.0)))
+			.apply(info)
+			.unwrap();
+		assert!(blurred.distance_to(&info).0 <= 10_000.0, "{blurred:?}");
+	}
+}
+
+#[test]
+fn altitude() {
+	let info = ping_at(46.056_95, 14.505_75, 1234.0, 12.0);
+	let altitude = |altitude| {
+		Precision {
+			horizontal: Horizontal::Grid(Meters(10_000.0)),
+			altitude,
+		}
+		.apply(info)
+		.unwrap()
+		.alt()
+	};
+
+	// the altitude is independent of the grid cell size
+	assert_eq!(altitude(Altitude::Keep), Meters(1234.0));
+	assert_eq!(altitude(Altitude::Coarsen(Meters(100.0))), Meters(1200.0));
+	assert_eq!(altitude(Altitude::Coarsen(Meters(5000.0))), Meters(0.0));
+	assert_eq!(altitude(Altitude::Omit), Meters(0.0));
+
+	// and can also be changed without changing the position
+	let omitted = Precision {
+		horizontal: Horizontal::Exact,
+		altitude: Altitude::Omit,
+	}
+	.apply(info)
+	.unwrap();
+	assert_eq!(omitted.coordinates(), info.coordinates());
+	assert_eq!(omitted.err(), info.err());
+	assert_eq!(omitted.alt(), Meters(0.0));
+}
+
+#[test]
+fn parse() {
+	assert_eq!("exact".parse(), Ok(Precision::default()));
+	assert_eq!(
+		"grid:1000".parse(),
+		Ok(horizontal(Horizontal::Grid(Meters(1000.0))))
+	);
+	assert_eq!(
+		" Blur: 250.5 ".parse(),
+		Ok(horizontal(Horizontal::Blur(Meters(250.5))))
+	);
+	assert_eq!(
+		"grid:1000, Altitude:Coarsen:100".parse(),
+		Ok(Precision {
+			horizontal: Horizontal::Grid(Meters(1000.0)),
+			altitude: Altitude::Coarsen(Meters(100.0)),
+		})
+	);
+	assert_eq!(
+		"altitude:omit".parse(),
+		Ok(Precision {
+			horizontal: Horizontal::Exact,
+			altitude: Altitude::Omit,
+		})
+	);
+	assert_eq!("exact,altitude:keep".parse(), Ok(Precision::default()));
+
+	for invalid in [
+		"",
+		"grid",
+		"grid:",
+		"grid:0",
+		"blur:-5",
+		"blur:NaN",
+		"blur:inf",
+		"grid:30000000",
+		"snap:100",
+		"grid:1000,",
+		"grid:1000,blur:500",
+		"altitude:omit,altitude:keep",
+		"altitude:coarsen:0",
+		"altitude:coarsen",
+		"altitude:100",
+		"exact,altitude",
+	] {
+		assert_eq!(
+			invalid.parse::<Precision>(),
+			Err(InvalidPrecision),
+			"{invalid}"
+		);
+	}
+
+	for precision in [
+		Precision::default(),
+		horizontal(Horizontal::Grid(Meters(1000.0))),
+		horizontal(Horizontal::Blur(Meters(0.5))),
+		Precision {
+			horizontal: Horizontal::Exact,
+			altitude: Altitude::Coarsen(Meters(10.0)),
+		},
+		Precision {
+			horizontal: Horizontal::Blur(Meters(200.0)),
+			altitude: Altitude::Omit,
+		},
+	] {
+		assert_eq!(precision.to_string().parse(), Ok(precision));
+	}
+}
+
+#[test]
+fn invalid() {
+	let info = ping_at(0.0, 0.0, 0.0, 0.0);
+
+	assert_eq!(
+		horizontal(Horizontal::Grid(Meters(0.0))).apply(info),
+		Err(InvalidPrecision)
+	);
+	assert_eq!(
+		horizontal(Horizontal::Blur(Meters(f32::NAN))).apply(info),
+		Err(InvalidPrecision)
+	);
+	assert_eq!(
+		Precision {
+			horizontal: Horizontal::Exact,
+			altitude: Altitude::Coarsen(Meters(-1.0)),
+		}
+		.apply(info),
+		Err(InvalidPrecision)
+	);
+}
@@ -0,0 +1,135 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::{collection::vec, num, prelude::*};
+
+use super::crypto::key_pair;
+use crate::*;
+
+/// Any `f64` bit pattern, including NaNs, infinities, subnormals, and zeros
+fn any_f64() -> impl Strategy<Value = f64> {
+	prop_oneof![num::f64::ANY, any::<u64>().prop_map(f64::from_bits)]
+}
+
+/// Any `f32` bit pattern, including NaNs, infinities, subnormals, and zeros
+fn any_f32() -> impl Strategy<Value = f32> {
+	prop_oneof![num::f32::ANY, any::<u32>().prop_map(f32::from_bits)]
+}
+
+/// Any valid Ping info
+fn ping_info() -> impl Strategy<Value = PingInfo> {
+	(any::<u64>(), any_f64(), any_f64(), any_f32(), any_f32()).prop_filter_map(
+		"invalid Ping info",
+		|(ts, lat, lon, alt, err)| {
+			PingInfo::new(
+				Timestamp(ts),
+				Degrees(lat),
+				Degrees(lon),
+				Meters(alt),
+				Meters(err),
+			)
+			.ok()
+		},
+	)
+}
+
+proptest! {
+	#[test]
+	fn encode_round_trip(
+		ts in any::<u64>(),
+		lat in any_f64(),
+		lon in any_f64(),
+		alt in any_f32(),
+		err in any_f32(),
+	) {
+		let valid = (-90.0..=90.0).contains(&lat)
+			&& (-180.0..=180.0).contains(&lon)
+			&& alt.is_finite()
+			&& err.is_finite()
+			&& err >= 0.0;
+		let info = PingInfo::new(
+			Timestamp(ts),
+			Degrees(lat),
+			Degrees(lon),
+			Meters(alt),
+			Meters(err),
+		);
+		prop_assert_eq!(info.is_ok(), valid);
+
+		if let Ok(info) = info {
+			// the exact bits are kept, e.g. for negative zero and subnormals
+			let encoded = info.encode();
+			let expected = [
+				&ts.to_be_bytes()[..],
+				&lat.to_be_bytes(),
+				&lon.to_be_bytes(),
+				&alt.to_be_bytes(),
+				&err.to_be_bytes(),
+			]
+			.concat();
+			prop_assert_eq!(&encoded[..], &expected[..]);
+			prop_assert_eq!(PingInfo::decode(encoded)?.encode(), encoded);
+		}
+	}
+
+	#[test]
+	fn decode_round_trip(bytes in any::<[u8; 32]>()) {
+		match PingInfo::decode(bytes) {
+			Ok(info) => prop_assert_eq!(info.encode(), bytes),
+			Err(e) => prop_assert!(matches!(e, Error::InvalidValue(_)), "{e:?}"),
+		}
+	}
+
+	#[test]
+	fn encrypt_round_trip(info in ping_info()) {
+		let (alices_key, bobs_key) = key_pair();
+		let encrypted = info.encrypt(&alices_key)?;
+
+		prop_assert_eq!(PingInfo::decrypt(encrypted, &bobs_key)?.encode(), info.encode());
+	}
+
+	#[test]
+	fn tampering(info in ping_info(), byte in 0..64usize, bit in 0..8u8) {
+		let (alices_key, bobs_key) = key_pair();
+		let mut encrypted = info.encrypt(&alices_key)?;
+		encrypted.0[byte] ^= 1 << bit;
+
+		let expected = if byte < 4 { Error::BadMagic } else { Error::Authentication };
+		prop_assert_eq!(PingInfo::decrypt(encrypted, &bobs_key), Err(expected));
+	}
+
+	#[test]
+	fn base64_round_trip(bytes in vec(any::<u8>(), 64)) {
+		let encrypted = EncryptedPingInfo::try_from(&bytes[..])?;
+		let json = serde_json::to_string(&encrypted).unwrap();
+
+		prop_assert_eq!(serde_json::from_str::<EncryptedPingInfo>(&json).unwrap().0, encrypted.0);
+		prop_assert_eq!(EncryptedPingInfo::from_base64(json.trim_matches('"'))?.0, encrypted.0);
+	}
+
+	#[test]
+	fn base64_errors(str in "[A-Za-z0-9_+/=-]{0,100}") {
+		match EncryptedPingInfo::from_base64(&str) {
+			Ok(encrypted) => {
+				prop_assert_eq!(str.len(), 86);
+				prop_assert_eq!(serde_json::to_string(&encrypted).unwrap(), format!("\"{str}\""));
+			}
+			Err(e) => prop_assert!(matches!(e, Error::BadLength | Error::BadEncoding), "{e:?}"),
+		}
+	}
+
+	#[cfg(feature = "java-ffi")]
+	#[test]
+	fn java_public_key(bytes in any::<[u8; 32]>(), str in "[A-Za-z0-9_+/=-]{0,50}") {
+		use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+		let mut encoded = [0u8; 43];
+		URL_SAFE_NO_PAD.encode_slice(bytes, &mut encoded).unwrap();
+		let encoded = str::from_utf8(&encoded).unwrap();
+		prop_assert_eq!(java_ffi::decode_public_key(encoded)?.to_bytes(), bytes);
+
+		match java_ffi::decode_public_key(&str) {
+			Ok(_) => prop_assert_eq!(str.len(), 43),
+			Err(e) => prop_assert!(matches!(e, Error::BadLength | Error::BadEncoding), "{e:?}"),
+		}
+	}
+}
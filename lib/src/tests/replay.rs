@@ -0,0 +1,127 @@
+use core::time::Duration;
+
+use super::crypto::key_pair;
+use crate::{
+	replay::{Freshness, ReplayGuard},
+	*,
+};
+
+/// The current time in the tests
+const NOW: Timestamp = Timestamp(1_700_000_000);
+
+/// Create Ping info with the given timestamp
+fn ping_at(ts: u64) -> PingInfo {
+	PingInfo::new(
+		Timestamp(ts),
+		Degrees(46.05),
+		Degrees(14.5),
+		Meters(295.0),
+		Meters(10.0),
+	)
+	.unwrap()
+}
+
+#[test]
+fn freshness() {
+	let freshness = Freshness {
+		max_age: Duration::from_mins(1),
+		max_future_skew: Duration::from_secs(5),
+	};
+
+	assert_eq!(freshness.check(NOW, NOW), Ok(()));
+	assert_eq!(freshness.check(Timestamp(NOW.0 - 60), NOW), Ok(()));
+	assert_eq!(freshness.check(Timestamp(NOW.0 + 5), NOW), Ok(()));
+	assert_eq!(
+		freshness.check(Timestamp(NOW.0 - 61), NOW),
+		Err(Error::Stale(Duration::from_secs(61)))
+	);
+	assert_eq!(
+		freshness.check(Timestamp(NOW.0 + 6), NOW),
+		Err(Error::FromFuture(Duration::from_secs(6)))
+	);
+	assert_eq!(
+		freshness.check(Timestamp(0), NOW),
+		Err(Error::Stale(Duration::from_secs(NOW.0)))
+	);
+}
+
+#[test]
+fn replayed() {
+	let (alices_key, bobs_key) = key_pair();
+	let mut guard = ReplayGuard::default();
+
+	let first = ping_at(NOW.0).encrypt(&alices_key).unwrap();
+	let second = ping_at(NOW.0).encrypt(&alices_key).unwrap();
+
+	assert_eq!(guard.decrypt(first, &bobs_key, NOW), Ok(ping_at(NOW.0)));
+	assert_eq!(guard.decrypt(first, &bobs_key, NOW), Err(Error::Replayed));
+	assert_eq!(guard.decrypt(second, &bobs_key, NOW), Ok(ping_at(NOW.0)));
+	assert_eq!(guard.decrypt(second, &bobs_key, NOW), Err(Error::Replayed));
+
+	// a replayed Ping is reported as replayed even once it's stale
+	let later = Timestamp(NOW.0 + 3600);
+	assert_eq!(guard.decrypt(first, &bobs_key, later), Err(Error::Replayed));
+}
+
+#[test]
+fn stale_and_future() {
+	let (alices_key, bobs_key) = key_pair();
+	let mut guard = ReplayGuard::new(Freshness {
+		max_age: Duration::from_mins(1),
+		max_future_skew: Duration::from_secs(5),
+	});
+
+	let stale = ping_at(NOW.0 - 120).encrypt(&alices_key).unwrap();
+	assert_eq!(
+		guard.decrypt(stale, &bobs_key, NOW),
+		Err(Error::Stale(Duration::from_mins(2)))
+	);
+	assert_eq!(guard.decrypt(stale, &bobs_key, NOW), Err(Error::Replayed));
+
+	let future = ping_at(NOW.0 + 30).encrypt(&alices_key).unwrap();
+	assert_eq!(
+		guard.decrypt(future, &bobs_key, NOW),
+		Err(Error::FromFuture(Duration::from_secs(30)))
+	);
+}
+
+#[test]
+fn unauthenticated_not_remembered() {
+	let (alices_key, bobs_key) = key_pair();
+	let (eves_key, _) = key_pair();
+	let mut guard = ReplayGuard::default();
+
+	let encrypted = ping_at(NOW.0).encrypt(&alices_key).unwrap();
+	let mut forged = encrypted;
+	forged.0[20] ^= 1;
+
+	assert_eq!(
+		guard.decrypt(encrypted, &eves_key, NOW),
+		Err(Error::Authentication)
+	);
+	assert_eq!(
+		guard.decrypt(forged, &bobs_key, NOW),
+		Err(Error::Authentication)
+	);
+	assert_eq!(guard.decrypt(encrypted, &bobs_key, NOW), Ok(ping_at(NOW.0)));
+}
+
+#[test]
+fn capacity() {
+	let (alices_key, bobs_key) = key_pair();
+	let mut guard = ReplayGuard::default();
+
+	let pings =
+		[(); ReplayGuard::CAPACITY + 1].map(|()| ping_at(NOW.0).encrypt(&alices_key).unwrap());
+
+	for ping in pings {
+		assert!(guard.decrypt(ping, &bobs_key, NOW).is_ok());
+	}
+
+	// the oldest nonce was forgotten, but the most recent ones are remembered
+	assert!(guard.decrypt(pings[0], &bobs_key, NOW).is_ok());
+	assert_eq!(
+		guard.decrypt(pings[ReplayGuard::CAPACITY], &bobs_key, NOW),
+		Err(Error::Replayed)
+	);
+}
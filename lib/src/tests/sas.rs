@@ -0,0 +1,68 @@
+use crate::{sas::SafetyNumber, *};
+
+#[test]
+fn same_exchange() {
+	let alices_secret = EphemeralSecret::random();
+	let bobs_secret = EphemeralSecret::random();
+	let alices_public_key = PublicKey::from(&alices_secret);
+	let bobs_public_key = PublicKey::from(&bobs_secret);
+	let alices_key = alices_secret.diffie_hellman(&bobs_public_key).into();
+	let bobs_key = bobs_secret.diffie_hellman(&alices_public_key).into();
+
+	let alices_number = SafetyNumber::new(
+		&alices_key,
+		alices_public_key.to_bytes(),
+		bobs_public_key.to_bytes(),
+	);
+	let bobs_number = SafetyNumber::new(
+		&bobs_key,
+		bobs_public_key.to_bytes(),
+		alices_public_key.to_bytes(),
+	);
+
+	assert_eq!(alices_number, bobs_number);
+	assert!(alices_number.value() < 1_000_000_000_000);
+}
+
+#[test]
+fn intercepted_exchange() {
+	// the server replaces both public keys with its own
+	let alices_secret = EphemeralSecret::random();
+	let bobs_secret = EphemeralSecret::random();
+	let servers_secret = EphemeralSecret::random();
+	let alices_public_key = PublicKey::from(&alices_secret);
+	let bobs_public_key = PublicKey::from(&bobs_secret);
+	let servers_public_key = PublicKey::from(&servers_secret);
+
+	let alices_number = SafetyNumber::new(
+		&alices_secret.diffie_hellman(&servers_public_key).into(),
+		alices_public_key.to_bytes(),
+		servers_public_key.to_bytes(),
+	);
+	let bobs_number = SafetyNumber::new(
+		&bobs_secret.diffie_hellman(&servers_public_key).into(),
+		bobs_public_key.to_bytes(),
+		servers_public_key.to_bytes(),
+	);
+
+	assert_ne!(alices_number, bobs_number);
+
+	// everything is part of the safety number
+	let key = SharedKey::from_bytes([1; 32]);
+	let number = SafetyNumber::new(&key, [2; 32], [3; 32]);
+	assert_ne!(
+		number,
+		SafetyNumber::new(&SharedKey::from_bytes([4; 32]), [2; 32], [3; 32])
+	);
+	assert_ne!(number, SafetyNumber::new(&key, [4; 32], [3; 32]));
+	assert_ne!(number, SafetyNumber::new(&key, [2; 32], [4; 32]));
+}
+
+#[test]
+fn known_value() {
+	// safety numbers have to be the same on all platforms
+	let number = SafetyNumber::new(&SharedKey::from_bytes([1; 32]), [3; 32], [2; 32]);
+
+	assert_eq!(number.value(), 63_113_271_589);
+	assert_eq!(number.to_string(), "0631 1327 1589");
+}
@@ -0,0 +1,219 @@
+//! WebAssembly bindings for this library
+//!
+//! The bindings are generated using `wasm-bindgen` and are intended for use by
+//! the web client.
+//! Randomness (for key generation and encryption nonces) comes from the
+//! browser's `crypto.getRandomValues`.
+
+use core::str;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+	Degrees, EncryptedPingInfo, EphemeralSecret, Meters, PingInfo, PublicKey, SharedKey, Timestamp,
+};
+
+/// **`class KeyExchange`**
+///
+/// One side of an x25519 key exchange, with a random ephemeral secret key
+///
+/// The ephemeral secret key never leaves WebAssembly memory, and is consumed by
+/// the first (and only) call to `diffieHellman`.
+#[wasm_bindgen(js_name = KeyExchange)]
+pub struct WasmKeyExchange {
+	/// The ephemeral secret key, `None` if it was already used
+	secret: Option<EphemeralSecret>,
+	/// The public key corresponding to the secret key
+	public_key: PublicKey,
+}
+
+#[wasm_bindgen(js_class = KeyExchange)]
+impl WasmKeyExchange {
+	/// **`new KeyExchange()`**
+	///
+	/// Generate a random ephemeral secret key for the key exchange
+	#[wasm_bindgen(constructor)]
+	#[must_use]
+	#[expect(
+		clippy::new_without_default,
+		reason = "a random key pair is not a sensible default"
+	)]
+	pub fn new() -> Self {
+		let secret = EphemeralSecret::random();
+		let public_key = PublicKey::from(&secret);
+
+		Self {
+			secret: Some(secret),
+			public_key,
+		}
+	}
+
+	/// **`KeyExchange.publicKey: string`**
+	///
+	/// Our public key as a base64 string
+	#[wasm_bindgen(getter = publicKey)]
+	#[must_use]
+	#[expect(
+		clippy::missing_panics_doc,
+		reason = "the possibly-panicking expect is encoding 32 bytes into a 43-byte buffer, and \
+		          therefore can't panic"
+	)]
+	pub fn public_key(&self) -> String {
+		let mut buf = [0u8; 43];
+		let n = URL_SAFE_NO_PAD
+			.encode_slice(self.public_key.as_bytes(), &mut buf)
+			.expect("a 32-byte key always fits into 43 base64 characters");
+
+		String::from_utf8_lossy(&buf[..n]).into_owned()
+	}
+
+	/// **`KeyExchange.diffieHellman(theirPublicKey: string): SharedKey`**
+	///
+	/// Perform the key exchange with the other party's (base64-encoded) public
+	/// key
+	///
+	/// # Errors
+	/// If the public key is invalid or this key exchange was already performed,
+	/// an error is thrown
+	#[wasm_bindgen(js_name = diffieHellman)]
+	pub fn diffie_hellman(&mut self, their_public_key: &str) -> Result<WasmSharedKey, JsError> {
+		let mut buf = [0u8; 32];
+		let n = URL_SAFE_NO_PAD
+			.decode_slice(their_public_key, &mut buf)
+			.map_err(|_| JsError::new("the public key is not valid base64"))?;
+		let public_key = PublicKey::from(<[u8; 32]>::try_from(&buf[..n])?);
+
+		let secret = self
+			.secret
+			.take()
+			.ok_or_else(|| JsError::new("the key exchange was already performed"))?;
+
+		Ok(WasmSharedKey(secret.diffie_hellman(&public_key).into()))
+	}
+}
+
+/// **`class SharedKey`**
+///
+/// The shared symmetric encryption key resulting from a key exchange
+///
+/// The key never leaves WebAssembly memory.
+#[wasm_bindgen(js_name = SharedKey)]
+pub struct WasmSharedKey(SharedKey);
+
+#[wasm_bindgen(js_class = SharedKey)]
+impl WasmSharedKey {
+	/// **`SharedKey.toString(): string`**
+	///
+	/// Get an opaque placeholder, so that the key isn't leaked by logging it
+	#[wasm_bindgen(js_name = toString)]
+	#[must_use]
+	#[expect(
+		clippy::inherent_to_string,
+		reason = "this is `SharedKey.toString` in JavaScript, `Display` would be misleading for \
+		          secret key material in Rust"
+	)]
+	pub fn to_string(&self) -> String {
+		"SharedKey(...)".to_string()
+	}
+}
+
+/// **`class PingInfo`**
+///
+/// Information about a Ping
+#[wasm_bindgen(js_name = PingInfo)]
+#[derive(Clone, Copy)]
+pub struct WasmPingInfo(PingInfo);
+
+#[wasm_bindgen(js_class = PingInfo)]
+#[expect(
+	clippy::missing_const_for_fn,
+	reason = "wasm-bindgen does not support const functions"
+)]
+impl WasmPingInfo {
+	/// **`new PingInfo(ts: bigint, lat: number, lon: number, alt: number, err:
+	/// number)`**
+	///
+	/// Create new Ping info from a unix timestamp (in seconds), latitude and
+	/// longitude (in degrees), altitude (in meters above mean sea level), and
+	/// position error (in meters)
+	///
+	/// # Errors
+	/// If any of the values are invalid (e.g. a latitude outside of `-90..=90`
+	/// or a negative position error), an error is thrown
+	#[wasm_bindgen(constructor)]
+	pub fn new(ts: u64, lat: f64, lon: f64, alt: f32, err: f32) -> Result<Self, JsError> {
+		Ok(Self(PingInfo::new(
+			Timestamp(ts),
+			Degrees(lat),
+			Degrees(lon),
+			Meters(alt),
+			Meters(err),
+		)?))
+	}
+
+	/// **`PingInfo.ts: bigint`**
+	#[wasm_bindgen(getter)]
+	#[must_use]
+	pub fn ts(&self) -> u64 {
+		self.0.ts().0
+	}
+
+	/// **`PingInfo.lat: number`**
+	#[wasm_bindgen(getter)]
+	#[must_use]
+	pub fn lat(&self) -> f64 {
+		self.0.lat().0
+	}
+
+	/// **`PingInfo.lon: number`**
+	#[wasm_bindgen(getter)]
+	#[must_use]
+	pub fn lon(&self) -> f64 {
+		self.0.lon().0
+	}
+
+	/// **`PingInfo.alt: number`**
+	#[wasm_bindgen(getter)]
+	#[must_use]
+	pub fn alt(&self) -> f32 {
+		self.0.alt().0
+	}
+
+	/// **`PingInfo.err: number`**
+	#[wasm_bindgen(getter)]
+	#[must_use]
+	pub fn err(&self) -> f32 {
+		self.0.err().0
+	}
+
+	/// **`PingInfo.encrypt(key: SharedKey): string`**
+	///
+	/// Encrypt and base64-encode this Ping info using the given shared key
+	///
+	/// # Errors
+	/// If encryption fails, an error is thrown
+	pub fn encrypt(&self, key: &WasmSharedKey) -> Result<String, JsError> {
+		let encrypted = self.0.encrypt(&key.0)?;
+
+		let mut buf = [0u8; 86];
+		let n = URL_SAFE_NO_PAD
+			.encode_slice(encrypted.0, &mut buf)
+			.map_err(|_| JsError::new("failed to base64-encode"))?;
+		Ok(str::from_utf8(&buf[..n])?.to_string())
+	}
+
+	/// **`PingInfo.decrypt(str: string, key: SharedKey): PingInfo`**
+	///
+	/// Decrypt the given (base64-encoded) encrypted Ping info using the given
+	/// shared key
+	///
+	/// # Errors
+	/// If the encrypted Ping info is invalid or decryption fails, an error is
+	/// thrown
+	pub fn decrypt(str: &str, key: &WasmSharedKey) -> Result<Self, JsError> {
+		let info = PingInfo::decrypt(EncryptedPingInfo::from_base64(str)?, &key.0)?;
+
+		Ok(Self(info))
+	}
+}
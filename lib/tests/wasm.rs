@@ -0,0 +1,62 @@
+//! Tests for the WebAssembly bindings, run with `wasm-bindgen-test-runner`
+//! (`cargo test --target wasm32-unknown-unknown --features wasm`)
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use pinger::wasm::{WasmKeyExchange as KeyExchange, WasmPingInfo as PingInfo};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn key_exchange() {
+	let mut alice = KeyExchange::new();
+	let mut bob = KeyExchange::new();
+
+	assert_eq!(alice.public_key().len(), 43);
+	assert_ne!(alice.public_key(), bob.public_key());
+
+	let alices_key = alice.diffie_hellman(&bob.public_key()).unwrap();
+	let bobs_key = bob.diffie_hellman(&alice.public_key()).unwrap();
+
+	// both sides have the same key if what one encrypts the other can decrypt
+	let info = PingInfo::new(0, 1.0, 2.0, 3.0, 4.0).unwrap();
+	let decrypted = PingInfo::decrypt(&info.encrypt(&alices_key).unwrap(), &bobs_key).unwrap();
+	assert_eq!(decrypted.lat().to_bits(), 1.0f64.to_bits());
+	assert_eq!(alices_key.to_string(), "SharedKey(...)");
+	assert!(alice.diffie_hellman(&bob.public_key()).is_err());
+	assert!(KeyExchange::new().diffie_hellman("not a key").is_err());
+}
+
+#[wasm_bindgen_test]
+fn encrypt_decrypt() {
+	let mut alice = KeyExchange::new();
+	let mut bob = KeyExchange::new();
+	let mut eve = KeyExchange::new();
+
+	let alices_key = alice.diffie_hellman(&bob.public_key()).unwrap();
+	let bobs_key = bob.diffie_hellman(&alice.public_key()).unwrap();
+	let eves_key = eve.diffie_hellman(&alice.public_key()).unwrap();
+
+	let info = PingInfo::new(0x0012_3456_7890, 1.2, 3.4, 5.6, 7.8).unwrap();
+	let encrypted = info.encrypt(&alices_key).unwrap();
+
+	assert_eq!(encrypted.len(), 86);
+	assert_ne!(encrypted, info.encrypt(&alices_key).unwrap());
+
+	let decrypted = PingInfo::decrypt(&encrypted, &bobs_key).unwrap();
+
+	assert_eq!(decrypted.ts(), 0x0012_3456_7890);
+	assert_eq!(decrypted.lat().to_bits(), 1.2f64.to_bits());
+	assert_eq!(decrypted.lon().to_bits(), 3.4f64.to_bits());
+	assert_eq!(decrypted.alt().to_bits(), 5.6f32.to_bits());
+	assert_eq!(decrypted.err().to_bits(), 7.8f32.to_bits());
+
+	assert!(PingInfo::decrypt(&encrypted, &eves_key).is_err());
+	assert!(PingInfo::decrypt(&encrypted[..43], &bobs_key).is_err());
+}
+
+#[wasm_bindgen_test]
+fn invalid_ping_info() {
+	assert!(PingInfo::new(0, 91.0, 0.0, 0.0, 0.0).is_err());
+	assert!(PingInfo::new(0, 0.0, f64::NAN, 0.0, 0.0).is_err());
+	assert!(PingInfo::new(0, 0.0, 0.0, 0.0, -1.0).is_err());
+}
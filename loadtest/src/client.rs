@@ -0,0 +1,316 @@
+//! A simulated Pinger client, which sends Pings when told to and accepts all
+//! incoming Ping requests
+
+use std::{collections::HashMap, future, sync::Arc, time::Duration};
+
+use futures_util::{SinkExt, StreamExt};
+use pinger::{Degrees, EphemeralSecret, Meters, PingInfo, SharedKey, Timestamp};
+use rand::Rng;
+use tokio::{
+	net::TcpStream,
+	select,
+	sync::{mpsc, watch},
+	time::{self, Instant},
+};
+use tokio_tungstenite::{
+	Connector, MaybeTlsStream, WebSocketStream, connect_async_tls_with_config, tungstenite::Message,
+};
+
+use crate::{
+	messages::{
+		ClientClientMessage, ClientDownMessage, ClientUpMessage, Id, PublicKey, ServerClientMessage,
+	},
+	stats::Stats,
+};
+
+/// A websocket connection to the server
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A client connected to the server
+pub struct Client {
+	/// The websocket connection
+	ws: Ws,
+	/// The state of the client's Ping exchanges
+	pub exchanges: Exchanges,
+}
+
+impl Client {
+	/// Connect to the server at `url`, giving up after `timeout`
+	///
+	/// # Errors
+	/// Returns why the connection couldn't be opened, or why the client's ID
+	/// wasn't received
+	pub async fn connect(
+		url: &str,
+		connector: Option<Connector>,
+		timeout: Duration,
+		stats: Arc<Stats>,
+	) -> Result<Self, String> {
+		let start = Instant::now();
+
+		let connecting = async {
+			let (mut ws, _) = connect_async_tls_with_config(url, None, false, connector)
+				.await
+				.map_err(|e| e.to_string())?;
+
+			while let Some(msg) = ws.next().await {
+				let Message::Text(text) = msg.map_err(|e| e.to_string())? else {
+					continue;
+				};
+
+				return match serde_json::from_str(&text) {
+					Ok(ClientDownMessage::FromServer {
+						msg: ServerClientMessage::Connected { id: Id(id) },
+					}) => Ok((ws, id)),
+					_ => Err(format!("unexpected message `{text}`")),
+				};
+			}
+
+			Err("connection closed before receiving an ID".to_string())
+		};
+
+		let (ws, id) = time::timeout(timeout, connecting)
+			.await
+			.map_err(|_| "timed out".to_string())??;
+
+		stats.connect_latency.record(start.elapsed());
+		Stats::count(&stats.connected);
+
+		Ok(Self {
+			ws,
+			exchanges: Exchanges::new(id, timeout, stats),
+		})
+	}
+
+	/// Run the client, starting a Ping exchange with each ID received on
+	/// `commands`, until `stop` changes
+	pub async fn run(mut self, mut commands: mpsc::Receiver<u16>, mut stop: watch::Receiver<bool>) {
+		loop {
+			let reply = select! {
+				msg = self.ws.next() => match msg {
+					Some(Ok(Message::Text(text))) => {
+						if let Ok(msg) = serde_json::from_str(&text) {
+							self.exchanges.handle(msg, Instant::now())
+						} else {
+							Stats::count(&self.exchanges.stats.errors);
+							None
+						}
+					}
+					Some(Ok(Message::Close(_)) | Err(_)) | None => {
+						Stats::count(&self.exchanges.stats.disconnected);
+						return;
+					}
+					Some(Ok(_)) => None,
+				},
+				Some(to) = commands.recv() => self.exchanges.start(to, Instant::now()),
+				() = sleep_until(self.exchanges.deadline()) => {
+					self.exchanges.time_out();
+					None
+				},
+				_ = stop.changed() => {
+					let _ = self.ws.close(None).await;
+					return;
+				}
+			};
+
+			if let Some(reply) = reply {
+				let json = serde_json::to_string(&reply).expect("failed to serialize message");
+
+				if self.ws.send(Message::text(json)).await.is_err() {
+					Stats::count(&self.exchanges.stats.disconnected);
+					return;
+				}
+			}
+		}
+	}
+}
+
+/// An outgoing Ping exchange
+struct Outgoing {
+	/// The ID the Ping is sent to
+	to: u16,
+	/// The key for the key agreement, until the request is accepted
+	secret: Option<EphemeralSecret>,
+	/// When the Ping request was sent
+	started: Instant,
+}
+
+/// The state of a client's Ping exchanges
+pub struct Exchanges {
+	/// The client's ID
+	pub id: u16,
+	/// The outgoing exchange, if any
+	outgoing: Option<Outgoing>,
+	/// The shared keys of accepted incoming requests, and when they were
+	/// accepted
+	incoming: HashMap<u16, (SharedKey, Instant)>,
+	/// How long an exchange may take
+	timeout: Duration,
+	/// Where to count what happens
+	stats: Arc<Stats>,
+}
+
+impl Exchanges {
+	/// Create the state for the client with `id`
+	pub fn new(id: u16, timeout: Duration, stats: Arc<Stats>) -> Self {
+		Self {
+			id,
+			outgoing: None,
+			incoming: HashMap::new(),
+			timeout,
+			stats,
+		}
+	}
+
+	/// Start a Ping exchange with `to` at `now`, unless one is already going
+	/// on, and get the Ping request to send
+	pub fn start(&mut self, to: u16, now: Instant) -> Option<ClientUpMessage> {
+		if self.outgoing.is_some() {
+			Stats::count(&self.stats.skipped);
+			return None;
+		}
+
+		let secret = EphemeralSecret::random();
+		let key = PublicKey(pinger::PublicKey::from(&secret));
+
+		self.outgoing = Some(Outgoing {
+			to,
+			secret: Some(secret),
+			started: now,
+		});
+		Stats::count(&self.stats.started);
+
+		Some(ClientUpMessage {
+			to: Id(to),
+			msg: ClientClientMessage::PingRequest { key, pake: false },
+		})
+	}
+
+	/// Get when the outgoing exchange times out, if there is one
+	pub fn deadline(&self) -> Option<Instant> {
+		self.outgoing
+			.as_ref()
+			.map(|outgoing| outgoing.started + self.timeout)
+	}
+
+	/// Give up on the outgoing exchange
+	pub fn time_out(&mut self) {
+		if self.outgoing.take().is_some() {
+			Stats::count(&self.stats.timed_out);
+		}
+	}
+
+	/// Handle a message received at `now`, and get the reply to send, if any
+	pub fn handle(&mut self, msg: ClientDownMessage, now: Instant) -> Option<ClientUpMessage> {
+		let (from, msg) = match msg {
+			ClientDownMessage::FromClient {
+				from: Id(from),
+				msg,
+			} => (from, msg),
+			ClientDownMessage::FromServer { msg } => {
+				match msg {
+					ServerClientMessage::NoSuchId { id: Id(id) } => {
+						Stats::count(&self.stats.no_such_id);
+
+						if self.outgoing.as_ref().is_some_and(|out| out.to == id) {
+							self.outgoing = None;
+						}
+					}
+					ServerClientMessage::Error { .. } => Stats::count(&self.stats.errors),
+					ServerClientMessage::Connected { .. } | ServerClientMessage::Notice { .. } => {}
+				}
+
+				return None;
+			}
+		};
+
+		match msg {
+			// the simulated clients don't use short codes
+			ClientClientMessage::PingRequest { pake: true, .. } => Some(ClientUpMessage {
+				to: Id(from),
+				msg: ClientClientMessage::RejectPing,
+			}),
+			ClientClientMessage::PingRequest { key, .. } => {
+				let timeout = self.timeout;
+				self.incoming
+					.retain(|_, (_, accepted)| now.duration_since(*accepted) < timeout);
+
+				let secret = EphemeralSecret::random();
+				let reply = PublicKey(pinger::PublicKey::from(&secret));
+				self.incoming
+					.insert(from, (secret.diffie_hellman(&key.0).into(), now));
+
+				Some(ClientUpMessage {
+					to: Id(from),
+					msg: ClientClientMessage::AcceptPing {
+						key: reply,
+						pake: false,
+					},
+				})
+			}
+			ClientClientMessage::AcceptPing { pake: true, .. } => None,
+			ClientClientMessage::AcceptPing { key, .. } => {
+				let outgoing = self.outgoing.as_mut().filter(|out| out.to == from)?;
+				let shared = outgoing.secret.take()?.diffie_hellman(&key.0);
+				let info = random_ping_info().encrypt(shared).ok()?;
+
+				Some(ClientUpMessage {
+					to: Id(from),
+					msg: ClientClientMessage::Ping { info },
+				})
+			}
+			ClientClientMessage::RejectPing => {
+				if self.outgoing.as_ref().is_some_and(|out| out.to == from) {
+					self.outgoing = None;
+					Stats::count(&self.stats.rejected);
+				}
+
+				None
+			}
+			ClientClientMessage::Ping { info } => {
+				let (key, _) = self.incoming.remove(&from)?;
+
+				if PingInfo::decrypt(info, key).is_err() {
+					Stats::count(&self.stats.invalid);
+					return None;
+				}
+
+				Some(ClientUpMessage {
+					to: Id(from),
+					msg: ClientClientMessage::PingAck,
+				})
+			}
+			ClientClientMessage::PingAck => {
+				let outgoing = self.outgoing.take_if(|out| out.to == from)?;
+				self.stats
+					.exchange_latency
+					.record(now.duration_since(outgoing.started));
+				Stats::count(&self.stats.completed);
+
+				None
+			}
+		}
+	}
+}
+
+/// Get Ping info for a random location at the current time
+fn random_ping_info() -> PingInfo {
+	let mut rng = rand::rng();
+
+	PingInfo::new(
+		Timestamp::now(),
+		Degrees(rng.random_range(-90.0..=90.0)),
+		Degrees(rng.random_range(-180.0..180.0)),
+		Meters(rng.random_range(0.0..1000.0)),
+		Meters(rng.random_range(1.0..100.0)),
+	)
+	.expect("random Ping info is invalid")
+}
+
+/// Wait until `deadline`, or forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => time::sleep_until(deadline).await,
+		None => future::pending().await,
+	}
+}
@@ -0,0 +1,290 @@
+//! A load generator for the Pinger backend
+//!
+//! Run with `./executable-name [SERVER]`, where `[SERVER]` is the websocket
+//! URI of the Pinger API (`ws://localhost:8000/api` by default, for a local
+//! backend).
+//!
+//! First, `--clients` connections are opened (at up to `--connect-rate` per
+//! second). Then, for `--duration` seconds, `--rate` Ping exchanges per second
+//! are started between random pairs of clients, each going through the full
+//! request, acceptation, Ping, and acknowledgement, with real key agreement and
+//! encryption. Finally, the latencies of connecting and of the exchanges are
+//! reported along with any failures, `no_such_id` messages, and `error`
+//! messages.
+//!
+//! Every connection needs a file descriptor, so the open file limit (e.g.
+//! `ulimit -n`) may need to be raised for thousands of clients, both here and
+//! for the server.
+
+use std::{
+	path::PathBuf,
+	process::ExitCode,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rand::Rng;
+use rustls::{
+	ClientConfig, RootCertStore,
+	pki_types::{CertificateDer, pem::PemObject},
+};
+use tokio::{
+	sync::{mpsc, watch},
+	task::JoinSet,
+	time,
+};
+use tokio_tungstenite::Connector;
+
+use crate::{client::Client, stats::Stats};
+
+mod client;
+#[path = "../../backend/src/messages.rs"]
+mod messages;
+#[path = "../../backend/src/serde_support.rs"]
+mod serde_support;
+mod stats;
+mod tests;
+
+/// How often progress is shown while Pings are being sent
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The highest connection or Ping exchange rate per second, so that the time
+/// between them is at least a microsecond
+const MAX_RATE: u32 = 1_000_000;
+
+/// A load generator for the Pinger backend
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+	/// The websocket URI of the Pinger API
+	#[arg(default_value = "ws://localhost:8000/api")]
+	server: String,
+	/// The number of clients to simulate
+	#[arg(short, long, default_value_t = 100)]
+	clients: usize,
+	/// The number of connections opened per second
+	#[arg(long, value_name = "CONNECTIONS", default_value_t = 200)]
+	connect_rate: u32,
+	/// The number of Ping exchanges started per second
+	#[arg(short, long, value_name = "EXCHANGES", default_value_t = 10)]
+	rate: u32,
+	/// The number of seconds to send Pings for
+	#[arg(short, long, value_name = "SECS", default_value_t = 30)]
+	duration: u64,
+	/// The number of seconds to wait for a connection or an exchange before
+	/// giving up
+	#[arg(short, long, value_name = "SECS", default_value_t = 10)]
+	timeout: u64,
+	/// Trust the PEM CA certificates in this file (for `wss://`), in addition
+	/// to the system's
+	#[arg(long, value_name = "FILE")]
+	tls_ca: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+	let args = Args::parse();
+
+	if args.clients < 2
+		|| !(1..=MAX_RATE).contains(&args.connect_rate)
+		|| !(1..=MAX_RATE).contains(&args.rate)
+	{
+		eprintln!(
+			"There must be at least 2 clients, and the rates must be between 1 and {MAX_RATE}"
+		);
+		return ExitCode::FAILURE;
+	}
+
+	let connector = match tls_connector(args.tls_ca.as_ref()) {
+		Ok(connector) => connector,
+		Err(e) => {
+			eprintln!("Invalid TLS settings: {e}");
+			return ExitCode::FAILURE;
+		}
+	};
+
+	let stats = Arc::new(Stats::default());
+	let timeout = Duration::from_secs(args.timeout);
+	let (stop_sender, stop) = watch::channel(false);
+
+	println!("Connecting {} clients to {}", args.clients, args.server);
+	let start = Instant::now();
+	let mut connecting = JoinSet::new();
+	let mut interval = time::interval(Duration::from_secs(1) / args.connect_rate);
+
+	for _ in 0..args.clients {
+		interval.tick().await;
+
+		let url = args.server.clone();
+		let connector = connector.clone();
+		let stats = Arc::clone(&stats);
+		connecting.spawn(async move { Client::connect(&url, connector, timeout, stats).await });
+	}
+
+	let mut clients = Vec::new();
+	let mut running = JoinSet::new();
+	let mut last_error = None;
+
+	while let Some(res) = connecting.join_next().await {
+		match res.expect("connecting panicked") {
+			Ok(client) => {
+				let (commands, receiver) = mpsc::channel(1);
+				clients.push((client.exchanges.id, commands));
+				running.spawn(client.run(receiver, stop.clone()));
+			}
+			Err(e) => {
+				Stats::count(&stats.connect_failed);
+				last_error = Some(e);
+			}
+		}
+	}
+
+	println!(
+		"Connected {} clients in {:.1}s",
+		clients.len(),
+		start.elapsed().as_secs_f64()
+	);
+
+	if let Some(e) = last_error {
+		println!("Couldn't connect some clients, e.g.: {e}");
+	}
+
+	if clients.len() < 2 {
+		eprintln!("Not enough clients connected to send Pings");
+		return ExitCode::FAILURE;
+	}
+
+	println!(
+		"Starting {} Ping exchanges per second for {}s",
+		args.rate, args.duration
+	);
+	let start = Instant::now();
+	let end = start + Duration::from_secs(args.duration);
+	let mut interval = time::interval(Duration::from_secs(1) / args.rate);
+	let mut progress = start + PROGRESS_INTERVAL;
+
+	while Instant::now() < end {
+		interval.tick().await;
+
+		// clients which disconnected can't take part anymore
+		clients.retain(|(_, commands)| !commands.is_closed());
+		let Some([(_, from), (to, _)]) = pick_pair(&clients) else {
+			eprintln!("All clients disconnected");
+			break;
+		};
+
+		if from.try_send(to).is_err() {
+			Stats::count(&stats.skipped);
+		}
+
+		if Instant::now() >= progress {
+			progress += PROGRESS_INTERVAL;
+			println!(
+				"{:>4}s: {} completed, {} timed out, {} no_such_id, {} errors, {} disconnected",
+				start.elapsed().as_secs(),
+				Stats::get(&stats.completed),
+				Stats::get(&stats.timed_out),
+				Stats::get(&stats.no_such_id),
+				Stats::get(&stats.errors),
+				Stats::get(&stats.disconnected),
+			);
+		}
+	}
+
+	let elapsed = start.elapsed();
+
+	// let the last exchanges finish
+	drop(clients);
+	time::sleep(timeout.min(Duration::from_secs(1) + stats.exchange_latency.percentiles().max))
+		.await;
+	let _ = stop_sender.send(true);
+	running.join_all().await;
+
+	report(&stats, elapsed);
+	ExitCode::SUCCESS
+}
+
+/// Pick two different random clients
+fn pick_pair<T: Clone>(clients: &[(u16, T)]) -> Option<[(u16, T); 2]> {
+	if clients.len() < 2 {
+		return None;
+	}
+
+	let mut rng = rand::rng();
+	let from = rng.random_range(0..clients.len());
+	let to = loop {
+		let to = rng.random_range(0..clients.len());
+
+		if to != from {
+			break to;
+		}
+	};
+
+	Some([clients[from].clone(), clients[to].clone()])
+}
+
+/// Print the results of the load test, which sent Pings for `elapsed`
+fn report(stats: &Stats, elapsed: Duration) {
+	let completed = Stats::get(&stats.completed);
+
+	println!();
+	println!(
+		"Connections: {} opened, {} failed, {} lost",
+		Stats::get(&stats.connected),
+		Stats::get(&stats.connect_failed),
+		Stats::get(&stats.disconnected)
+	);
+	println!("Connect latency: {}", stats.connect_latency.percentiles());
+	println!(
+		"Exchanges: {} started, {completed} completed, {} timed out, {} rejected, {} skipped \
+		 (client busy), {} invalid Pings",
+		Stats::get(&stats.started),
+		Stats::get(&stats.timed_out),
+		Stats::get(&stats.rejected),
+		Stats::get(&stats.skipped),
+		Stats::get(&stats.invalid)
+	);
+	println!("Exchange latency: {}", stats.exchange_latency.percentiles());
+	#[expect(clippy::cast_precision_loss, reason = "the count is far below 2^52")]
+	let throughput = completed as f64 / elapsed.as_secs_f64();
+	println!("Throughput: {throughput:.1} exchanges/s");
+	println!(
+		"Server messages: {} no_such_id, {} error",
+		Stats::get(&stats.no_such_id),
+		Stats::get(&stats.errors)
+	);
+}
+
+/// Get the TLS connector for `wss://` servers, trusting the system's root
+/// certificates and those in `ca`
+fn tls_connector(ca: Option<&PathBuf>) -> Result<Option<Connector>, String> {
+	let mut roots = RootCertStore::empty();
+	roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+
+	if let Some(ca) = ca {
+		let certs = CertificateDer::pem_file_iter(ca)
+			.and_then(Iterator::collect::<Result<Vec<_>, _>>)
+			.map_err(|e| format!("can't load certificates from {}: {e}", ca.display()))?;
+
+		for cert in certs {
+			roots
+				.add(cert)
+				.map_err(|e| format!("invalid certificate in {}: {e}", ca.display()))?;
+		}
+	}
+
+	if roots.is_empty() {
+		// plain `ws://` still works
+		return Ok(None);
+	}
+
+	let config =
+		ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+			.with_safe_default_protocol_versions()
+			.map_err(|e| e.to_string())?
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+
+	Ok(Some(Connector::Rustls(Arc::new(config))))
+}
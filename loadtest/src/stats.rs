@@ -0,0 +1,137 @@
+//! Counting what happens during a load test
+
+use std::{
+	fmt::{Display, Formatter, Result as FmtResult},
+	sync::{
+		Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::Duration,
+};
+
+/// Counters and latencies shared by all simulated clients
+#[derive(Debug, Default)]
+pub struct Stats {
+	/// Connections which received their ID
+	pub connected: AtomicU64,
+	/// Connections which couldn't be opened
+	pub connect_failed: AtomicU64,
+	/// Connections which were closed before the end of the test
+	pub disconnected: AtomicU64,
+	/// Ping exchanges which were started
+	pub started: AtomicU64,
+	/// Ping exchanges which were acknowledged
+	pub completed: AtomicU64,
+	/// Ping exchanges which weren't acknowledged in time
+	pub timed_out: AtomicU64,
+	/// Ping exchanges which weren't started because the client was busy
+	pub skipped: AtomicU64,
+	/// Ping requests which were rejected
+	pub rejected: AtomicU64,
+	/// Pings which couldn't be decrypted
+	pub invalid: AtomicU64,
+	/// `no_such_id` messages from the server
+	pub no_such_id: AtomicU64,
+	/// `error` messages from the server
+	pub errors: AtomicU64,
+	/// The time from opening each connection to receiving its ID
+	pub connect_latency: Latencies,
+	/// The time from sending each Ping request to receiving its
+	/// acknowledgement
+	pub exchange_latency: Latencies,
+}
+
+impl Stats {
+	/// Add one to `counter`
+	pub fn count(counter: &AtomicU64) {
+		counter.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Get the value of `counter`
+	pub fn get(counter: &AtomicU64) -> u64 {
+		counter.load(Ordering::Relaxed)
+	}
+}
+
+/// A collection of latency samples
+#[derive(Debug, Default)]
+pub struct Latencies(Mutex<Vec<Duration>>);
+
+impl Latencies {
+	/// Add a sample
+	pub fn record(&self, latency: Duration) {
+		self.0.lock().expect("lock poisoned").push(latency);
+	}
+
+	/// Get the percentiles of the samples so far
+	pub fn percentiles(&self) -> Percentiles {
+		let mut samples = self.0.lock().expect("lock poisoned").clone();
+		samples.sort_unstable();
+		Percentiles::of_sorted(&samples)
+	}
+}
+
+/// Percentiles of latency samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percentiles {
+	/// The number of samples
+	pub count: usize,
+	/// The median
+	pub p50: Duration,
+	/// The 90th percentile
+	pub p90: Duration,
+	/// The 99th percentile
+	pub p99: Duration,
+	/// The largest sample
+	pub max: Duration,
+}
+
+impl Percentiles {
+	/// Get the percentiles of sorted samples
+	pub fn of_sorted(samples: &[Duration]) -> Self {
+		Self {
+			count: samples.len(),
+			p50: percentile(samples, 500),
+			p90: percentile(samples, 900),
+			p99: percentile(samples, 990),
+			max: samples.last().copied().unwrap_or_default(),
+		}
+	}
+}
+
+impl Display for Percentiles {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		if self.count == 0 {
+			return f.write_str("no samples");
+		}
+
+		write!(
+			f,
+			"p50 {}, p90 {}, p99 {}, max {} ({} samples)",
+			Ms(self.p50),
+			Ms(self.p90),
+			Ms(self.p99),
+			Ms(self.max),
+			self.count
+		)
+	}
+}
+
+/// Get the nearest-rank percentile (in permille) of sorted samples
+fn percentile(samples: &[Duration], permille: usize) -> Duration {
+	if samples.is_empty() {
+		return Duration::ZERO;
+	}
+
+	let rank = (permille * samples.len()).div_ceil(1000).max(1);
+	samples[rank - 1]
+}
+
+/// A duration shown in milliseconds
+struct Ms(Duration);
+
+impl Display for Ms {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "{:.1}ms", self.0.as_secs_f64() * 1000.0)
+	}
+}
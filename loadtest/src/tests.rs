@@ -0,0 +1,113 @@
+#![cfg(test)]
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+	client::Exchanges,
+	messages::{ClientClientMessage, ClientDownMessage, ClientUpMessage, Id},
+	stats::{Percentiles, Stats},
+};
+
+/// Deliver `msg` from the client with the ID `from` as the server would
+fn deliver(from: u16, msg: &ClientUpMessage) -> ClientDownMessage {
+	let json = serde_json::to_string(msg).unwrap();
+	let json = json.replacen(
+		&format!(r#""to":{}"#, msg.to),
+		&format!(r#""from":{from}"#),
+		1,
+	);
+
+	serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn percentiles() {
+	let samples = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+	let percentiles = Percentiles::of_sorted(&samples);
+
+	assert_eq!(percentiles.count, 100);
+	assert_eq!(percentiles.p50, Duration::from_millis(50));
+	assert_eq!(percentiles.p90, Duration::from_millis(90));
+	assert_eq!(percentiles.p99, Duration::from_millis(99));
+	assert_eq!(percentiles.max, Duration::from_millis(100));
+
+	let one = Percentiles::of_sorted(&[Duration::from_millis(7)]);
+	assert_eq!(one.p50, Duration::from_millis(7));
+	assert_eq!(one.p99, Duration::from_millis(7));
+
+	assert_eq!(Percentiles::of_sorted(&[]).to_string(), "no samples");
+}
+
+#[test]
+fn exchange() {
+	let stats = Arc::new(Stats::default());
+	let timeout = Duration::from_secs(10);
+	let mut alice = Exchanges::new(123, timeout, Arc::clone(&stats));
+	let mut bob = Exchanges::new(42, timeout, Arc::clone(&stats));
+	let start = Instant::now();
+
+	// request, acceptation, Ping, and acknowledgement
+	let request = alice.start(42, start).unwrap();
+	assert!(matches!(
+		request.msg,
+		ClientClientMessage::PingRequest { .. }
+	));
+	assert!(alice.start(42, start).is_none());
+	assert_eq!(alice.deadline(), Some(start + timeout));
+
+	let msg = deliver(123, &request);
+	let accept = bob.handle(msg, start).unwrap();
+	assert!(matches!(accept.msg, ClientClientMessage::AcceptPing { .. }));
+
+	let msg = deliver(42, &accept);
+	let ping = alice.handle(msg, start).unwrap();
+	assert!(matches!(ping.msg, ClientClientMessage::Ping { .. }));
+
+	let msg = deliver(123, &ping);
+	let ack = bob.handle(msg, start).unwrap();
+	assert!(matches!(ack.msg, ClientClientMessage::PingAck));
+
+	let msg = deliver(42, &ack);
+	assert!(
+		alice
+			.handle(msg, start + Duration::from_millis(25))
+			.is_none()
+	);
+	assert_eq!(alice.deadline(), None);
+
+	assert_eq!(Stats::get(&stats.started), 1);
+	assert_eq!(Stats::get(&stats.completed), 1);
+	assert_eq!(Stats::get(&stats.skipped), 1);
+	assert_eq!(
+		stats.exchange_latency.percentiles().max,
+		Duration::from_millis(25)
+	);
+
+	// unknown IDs end the exchange
+	alice.start(7, start).unwrap();
+	let no_such_id = serde_json::from_str(r#"{"msg":"no_such_id","id":7}"#).unwrap();
+	assert!(alice.handle(no_such_id, start).is_none());
+	assert_eq!(alice.deadline(), None);
+	assert_eq!(Stats::get(&stats.no_such_id), 1);
+
+	// Pings without an accepted request are ignored
+	let ping = deliver(9, &ClientUpMessage {
+		to: Id(42),
+		msg: ClientClientMessage::PingAck,
+	});
+	assert!(bob.handle(ping, start).is_none());
+
+	alice.start(42, start).unwrap();
+	alice.time_out();
+	assert_eq!(Stats::get(&stats.timed_out), 1);
+
+	// requests authenticated with short codes are rejected
+	let mut request = alice.start(42, start).unwrap();
+	if let ClientClientMessage::PingRequest { pake, .. } = &mut request.msg {
+		*pake = true;
+	}
+	let reject = bob.handle(deliver(123, &request), start).unwrap();
+	assert!(matches!(reject.msg, ClientClientMessage::RejectPing));
+}